@@ -1,27 +1,216 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
-use crate::llm::provider::{LLMProvider, Message, MessageRole};
+use crate::agents::knowledge::{self, KNOWLEDGE_EXCERPT_CHARS, KNOWLEDGE_TOP_K};
+use crate::llm::provider::{
+    estimate_tokens, normalize_finish_reason, LLMProvider, LLMResponse, Message, MessageRole,
+    ToolChoice,
+};
+use crate::llm::rate_limit::RateLimitInfo;
 use crate::models::agent::Agent;
+use crate::models::knowledge::KnowledgeDoc;
 use crate::tools::definition::{ToolDefinition, ToolTrace};
 use crate::tools::executor::ToolExecutor;
+use crate::tools::format::format_result;
+use crate::tools::summarize::{self, SummarizeLimits};
 
 #[derive(Clone)]
 pub struct AgentInstance {
     pub id: String,
     pub name: String,
     pub system_prompt: String,
+    pub speaking_priority: i32,
     pub temperature: f64,
     pub max_tokens: u32,
     pub max_tool_iterations: u32,
+    /// When set, the system prompt is cut to this many characters before sending.
+    pub system_prompt_limit: Option<usize>,
+    /// Re-prompt once when a tool-using turn ignores every tool result.
+    pub require_citations: bool,
+    /// Tool the model must call on the first iteration of each turn.
+    pub forced_tool: Option<String>,
+    /// Opinions longer than this many characters are cut and marked `[truncated]`.
+    pub max_opinion_chars: Option<usize>,
+    /// Documents from the agent's knowledge base, searched for each turn's topic.
+    pub knowledge: Vec<KnowledgeDoc>,
+    /// Fixed instructions added to every prompt; see `PromptInstructions`.
+    pub instructions: PromptInstructions,
+    /// The agent's avatar, passed through on its opinion events.
+    pub avatar: Option<String>,
+    /// Send turns without tools through `chat_stream`; only set when the
+    /// provider supports streaming.
+    pub stream: bool,
     llm: std::sync::Arc<dyn LLMProvider>,
     opinions: Vec<String>,
 }
 
+/// The instruction text wrapped around each turn's context, from
+/// `mode_config.instructions`: `collaboration` (the `[DONE]` convention),
+/// `tools` (shown when tools are offered) and one optional entry per phase,
+/// e.g. `initial` or `response`. Phase templates may use `{{topic}}`.
+/// Missing or empty entries keep the defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptInstructions {
+    pub collaboration: String,
+    pub tools: String,
+    pub phases: HashMap<String, String>,
+    /// `mode_config.output_language`: when set, every turn tells the agent to
+    /// answer only in this language, whatever language the prompts are in.
+    pub output_language: Option<String>,
+}
+
+const COLLABORATION_INSTRUCTION: &str =
+    "协作提示：如果你认为当前讨论已经充分完成，请在回复末尾另起一行写上 [DONE]";
+const TOOLS_INSTRUCTION: &str = "你可以在需要时调用工具来读取/搜索/修改工作目录下的文件。";
+/// Default for the `response` phase; the `@名字` it asks for is read back by
+/// `addressed_peer`.
+const RESPONSE_INSTRUCTION: &str = "如果你主要在回应某位专家的观点，请在回复开头写上 @专家名。";
+
+impl Default for PromptInstructions {
+    fn default() -> Self {
+        Self {
+            collaboration: COLLABORATION_INSTRUCTION.to_string(),
+            tools: TOOLS_INSTRUCTION.to_string(),
+            phases: HashMap::new(),
+            output_language: None,
+        }
+    }
+}
+
+impl PromptInstructions {
+    pub fn from_mode_config(mode_config: &serde_json::Value) -> Self {
+        let mut out = Self {
+            output_language: mode_config
+                .get("output_language")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+            ..Self::default()
+        };
+        let Some(config) = mode_config.get("instructions").and_then(|v| v.as_object()) else {
+            return out;
+        };
+        for (key, value) in config {
+            let Some(text) = value.as_str().map(str::trim).filter(|s| !s.is_empty()) else {
+                continue;
+            };
+            match key.as_str() {
+                "collaboration" => out.collaboration = text.to_string(),
+                "tools" => out.tools = text.to_string(),
+                phase => {
+                    out.phases.insert(phase.to_string(), text.to_string());
+                }
+            }
+        }
+        out
+    }
+
+    fn for_phase(&self, phase: &str, topic: &str) -> Option<String> {
+        self.phases
+            .get(phase)
+            .map(|template| template.replace("{{topic}}", topic))
+            .or_else(|| (phase == "response").then(|| RESPONSE_INSTRUCTION.to_string()))
+    }
+
+    fn language_directive(&self) -> Option<String> {
+        self.output_language.as_ref().map(|language| {
+            format!("Respond only in {language}, regardless of the language used in these instructions or by other participants.")
+        })
+    }
+}
+
+/// The `metadata` of every agent reply, and so of every opinion message in
+/// all three modes. Consumers may rely on these fields; anything else found
+/// in a message's metadata (reply sequence, appearance) is added later.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageMetadata {
+    /// Model that produced the reply, as reported by the provider.
+    pub model: String,
+    /// `stop`, `length`, `tool_calls`, `content_filter` or `other`; see
+    /// `normalize_finish_reason`.
+    pub finish_reason: Option<String>,
+    /// The provider's own stop reason.
+    pub raw_finish_reason: Option<String>,
+    /// Summed over every request behind the reply, tool iterations included.
+    pub tokens: MessageTokens,
+    /// Tools called while producing the reply, in call order.
+    pub tool_calls: Vec<ToolCallMetadata>,
+    /// Hidden reasoning tokens, when the provider reported any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<u32>,
+    /// Prompt-cache usage, when the provider reported any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached: Option<CachedTokens>,
+    /// Limits from the last response's headers, when the provider sent any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitInfo>,
+    /// Whether `max_opinion_chars` cut the reply, and its length before.
+    pub truncated: bool,
+    pub original_chars: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageTokens {
+    pub input: u32,
+    pub output: u32,
+    pub estimated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallMetadata {
+    pub id: String,
+    pub name: String,
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CachedTokens {
+    pub read: u32,
+    pub write: u32,
+}
+
+impl MessageMetadata {
+    /// Fold one provider response into the totals; its model and stop reason
+    /// replace earlier ones.
+    fn record(&mut self, resp: &LLMResponse) {
+        self.add_tokens(resp.usage.input_tokens, resp.usage.output_tokens);
+        self.tokens.estimated |= resp.usage.estimated;
+        if resp.usage.reasoning_tokens > 0 {
+            let reasoning = self.reasoning.get_or_insert(0);
+            *reasoning = reasoning.saturating_add(resp.usage.reasoning_tokens);
+        }
+        if resp.usage.cache_read_tokens > 0 || resp.usage.cache_write_tokens > 0 {
+            let cached = self.cached.get_or_insert_with(CachedTokens::default);
+            cached.read = cached.read.saturating_add(resp.usage.cache_read_tokens);
+            cached.write = cached.write.saturating_add(resp.usage.cache_write_tokens);
+        }
+        if !resp.model.trim().is_empty() {
+            self.model = resp.model.clone();
+        }
+        if resp.rate_limit.is_some() {
+            self.rate_limit = resp.rate_limit.clone();
+        }
+        self.finish_reason = resp
+            .finish_reason
+            .as_deref()
+            .map(|raw| normalize_finish_reason(raw).to_string());
+        self.raw_finish_reason = resp.finish_reason.clone();
+    }
+
+    fn add_tokens(&mut self, input: u32, output: u32) {
+        self.tokens.input = self.tokens.input.saturating_add(input);
+        self.tokens.output = self.tokens.output.saturating_add(output);
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentResponse {
     pub content: String,
     #[serde(default = "default_true")]
     pub wants_to_continue: bool,
+    /// Agent id of the peer this reply addresses with `@名字`, if any.
     #[serde(default)]
     pub responding_to: Option<String>,
     #[serde(default)]
@@ -30,26 +219,30 @@ pub struct AgentResponse {
 
 impl AgentResponse {
     /// Extract the `(input_tokens, output_tokens, estimated)` triple from
-    /// `metadata`, defaulting to `(0, 0, false)` when a field is absent or the
-    /// wrong type. Centralizes the token-accounting boilerplate that every
-    /// orchestration mode (roundtable / debate / pipeline) needs after a turn.
+    /// `metadata.tokens`, defaulting to `(0, 0, false)` when a field is absent
+    /// or the wrong type. Centralizes the token-accounting boilerplate that
+    /// every orchestration mode (roundtable / debate / pipeline) needs after a
+    /// turn.
     pub fn token_counts(&self) -> (u32, u32, bool) {
-        let input_tokens = self
-            .metadata
-            .get("input_tokens")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(0) as u32;
-        let output_tokens = self
-            .metadata
-            .get("output_tokens")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(0) as u32;
-        let estimated = self
-            .metadata
-            .get("tokens_estimated")
+        let tokens = &self.metadata["tokens"];
+        let count = |key: &str| {
+            tokens
+                .get(key)
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0)
+                .min(u64::from(u32::MAX)) as u32
+        };
+        let estimated = tokens
+            .get("estimated")
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
-        (input_tokens, output_tokens, estimated)
+        (count("input"), count("output"), estimated)
+    }
+
+    /// Prompt-cache `(read, write)` tokens from `metadata.cached`, billed
+    /// apart from `token_counts`.
+    pub fn cached_tokens(&self) -> (u32, u32) {
+        crate::models::execution::cached_tokens(&self.metadata)
     }
 }
 
@@ -59,14 +252,79 @@ impl AgentInstance {
             id: agent.id.clone(),
             name: agent.name.clone(),
             system_prompt: agent.system_prompt.clone(),
+            speaking_priority: agent.speaking_priority,
             temperature: agent.temperature,
             max_tokens: agent.max_tokens,
             max_tool_iterations: agent.max_tool_iterations.unwrap_or(10).clamp(1, 50),
+            system_prompt_limit: None,
+            require_citations: false,
+            forced_tool: None,
+            max_opinion_chars: None,
+            knowledge: Vec::new(),
+            instructions: PromptInstructions::default(),
+            avatar: agent
+                .avatar
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+            stream: false,
             llm,
             opinions: Vec::new(),
         }
     }
 
+    /// Cut the system prompt to `limit` characters before sending it, warning
+    /// once here if that will shorten it.
+    pub fn limit_system_prompt(&mut self, limit: Option<usize>) {
+        if let Some(limit) = limit {
+            let len = self.system_prompt.chars().count();
+            if len > limit {
+                eprintln!(
+                    "agent '{}': system_prompt truncated from {len} to {limit} characters",
+                    self.name
+                );
+            }
+        }
+        self.system_prompt_limit = limit;
+    }
+
+    /// `avatar` (null when the agent has none) and a color derived from the
+    /// agent id, so the UI can tell speakers apart the same way every run.
+    pub fn appearance(&self) -> serde_json::Value {
+        serde_json::json!({ "avatar": self.avatar, "color": agent_color(&self.id) })
+    }
+
+    /// Apply a team member's `config_override` on top of the agent's own settings.
+    /// Recognized keys are `temperature`, `max_tokens`, `max_tool_iterations`,
+    /// `require_citations`, `forced_tool` and `max_opinion_chars`; values are clamped to sane ranges and anything else is ignored.
+    pub fn apply_config_override(&mut self, overrides: &serde_json::Value) {
+        if let Some(t) = overrides.get("temperature").and_then(|v| v.as_f64()) {
+            if t.is_finite() {
+                self.temperature = t.clamp(0.0, 2.0);
+            }
+        }
+        if let Some(n) = overrides.get("max_tokens").and_then(|v| v.as_u64()) {
+            self.max_tokens = n.clamp(1, 200_000) as u32;
+        }
+        if let Some(n) = overrides
+            .get("max_tool_iterations")
+            .and_then(|v| v.as_u64())
+        {
+            self.max_tool_iterations = n.clamp(1, 50) as u32;
+        }
+        if let Some(b) = overrides.get("require_citations").and_then(|v| v.as_bool()) {
+            self.require_citations = b;
+        }
+        if let Some(n) = overrides.get("max_opinion_chars").and_then(|v| v.as_u64()) {
+            self.max_opinion_chars = Some(n.clamp(MIN_OPINION_CHARS as u64, 1_000_000) as usize);
+        }
+        if let Some(name) = overrides.get("forced_tool").and_then(|v| v.as_str()) {
+            let name = name.trim();
+            self.forced_tool = (!name.is_empty()).then(|| name.to_string());
+        }
+    }
+
     fn build_context_message(
         &self,
         discussion_summary: &str,
@@ -78,17 +336,28 @@ impl AgentInstance {
             parts.push(format!("## 讨论摘要\n{discussion_summary}"));
         }
 
+        let docs = knowledge::retrieve(&self.knowledge, current_topic, KNOWLEDGE_TOP_K);
+        if !docs.is_empty() {
+            let sections: Vec<String> = docs
+                .iter()
+                .map(|doc| {
+                    let title = doc.path.as_deref().unwrap_or(&doc.id);
+                    let excerpt: String =
+                        doc.content.chars().take(KNOWLEDGE_EXCERPT_CHARS).collect();
+                    format!("### {title}\n{excerpt}")
+                })
+                .collect();
+            parts.push(format!("## 知识库\n{}", sections.join("\n\n")));
+        }
+
         if !recent_opinions.is_empty() {
-            let mut lines = Vec::new();
-            for op in recent_opinions {
-                let agent_name = op
-                    .get("agent_name")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("unknown");
-                let content = op.get("content").and_then(|v| v.as_str()).unwrap_or("");
-                lines.push(format!("- **{agent_name}**: {content}"));
-            }
-            parts.push(format!("## 其他专家的观点\n{}", lines.join("\n")));
+            // The opinions themselves are sent as separate, named messages.
+            let speakers: Vec<&str> = recent_opinions.iter().map(opinion_speaker).collect();
+            parts.push(format!(
+                "## 其他专家的观点\n上面 {} 条消息依次是以下专家的观点（不是你说的）：{}",
+                speakers.len(),
+                speakers.join("、")
+            ));
         }
 
         if !self.opinions.is_empty() {
@@ -104,58 +373,68 @@ impl AgentInstance {
     }
 
     fn system_message(&self) -> Message {
+        let prompt = match self.system_prompt_limit {
+            Some(limit) => self.system_prompt.chars().take(limit).collect(),
+            None => self.system_prompt.clone(),
+        };
         Message {
             role: MessageRole::System,
-            content: Some(self.system_prompt.clone()),
+            content: Some(prompt),
             name: None,
             tool_call_id: None,
             tool_calls: None,
+            images: Vec::new(),
         }
     }
 
-    #[allow(dead_code)]
-    pub async fn generate_opinion(
-        &mut self,
+    /// The opening conversation for a turn: system prompt, collaboration hint,
+    /// optional tool hint and the discussion context.
+    fn build_messages(
+        &self,
         topic: &str,
         discussion_summary: &str,
         recent_opinions: &[serde_json::Value],
+        tools_enabled: bool,
         phase: &str,
-    ) -> Result<AgentResponse, crate::error::AppError> {
-        let (resp, _traces) = self
-            .generate_opinion_with_tools(
-                topic,
-                discussion_summary,
-                recent_opinions,
-                phase,
-                &[],
-                None,
-            )
-            .await?;
-        Ok(resp)
-    }
-
-    pub async fn generate_opinion_with_tools(
-        &mut self,
-        topic: &str,
-        discussion_summary: &str,
-        recent_opinions: &[serde_json::Value],
-        _phase: &str,
-        tools: &[ToolDefinition],
-        executor: Option<&ToolExecutor>,
-    ) -> Result<(AgentResponse, Vec<ToolTrace>), crate::error::AppError> {
+    ) -> Vec<Message> {
         let mut messages = vec![self.system_message()];
 
         // 添加协作机制提示（[DONE] 标记）
         messages.push(Message {
             role: MessageRole::System,
-            content: Some(
-                "协作提示：如果你认为当前讨论已经充分完成，请在回复末尾另起一行写上 [DONE]"
-                    .to_string(),
-            ),
+            content: Some(self.instructions.collaboration.clone()),
             name: None,
             tool_call_id: None,
             tool_calls: None,
+            images: Vec::new(),
         });
+        let directives = [
+            self.instructions.for_phase(phase, topic),
+            self.instructions.language_directive(),
+        ];
+        for instruction in directives.into_iter().flatten() {
+            messages.push(Message {
+                role: MessageRole::System,
+                content: Some(instruction),
+                name: None,
+                tool_call_id: None,
+                tool_calls: None,
+                images: Vec::new(),
+            });
+        }
+
+        for op in recent_opinions {
+            let speaker = opinion_speaker(op);
+            let content = op.get("content").and_then(|v| v.as_str()).unwrap_or("");
+            messages.push(Message {
+                role: MessageRole::User,
+                content: Some(format!("【{speaker}】的观点：\n{content}")),
+                name: Some(speaker.to_string()),
+                tool_call_id: None,
+                tool_calls: None,
+                images: Vec::new(),
+            });
+        }
 
         let context = self.build_context_message(discussion_summary, recent_opinions, topic);
         messages.push(Message {
@@ -164,36 +443,128 @@ impl AgentInstance {
             name: None,
             tool_call_id: None,
             tool_calls: None,
+            images: Vec::new(),
         });
 
-        let tools_enabled = executor.is_some() && !tools.is_empty();
         if tools_enabled {
             messages.insert(
                 2,
                 Message {
                     role: MessageRole::System,
-                    content: Some(
-                        "你可以在需要时调用工具来读取/搜索/修改工作目录下的文件。".to_string(),
-                    ),
+                    content: Some(self.instructions.tools.clone()),
                     name: None,
                     tool_call_id: None,
                     tool_calls: None,
+                    images: Vec::new(),
                 },
             );
         }
 
+        messages
+    }
+
+    /// Rough prompt size for an `initial` turn, used to preview costs before any request is made.
+    pub fn estimate_prompt_tokens(
+        &self,
+        topic: &str,
+        discussion_summary: &str,
+        recent_opinions: &[serde_json::Value],
+        tools_enabled: bool,
+    ) -> u32 {
+        self.build_messages(
+            topic,
+            discussion_summary,
+            recent_opinions,
+            tools_enabled,
+            "initial",
+        )
+        .iter()
+        .filter_map(|m| m.content.as_deref())
+        .map(estimate_tokens)
+        .fold(0u32, |acc, n| acc.saturating_add(n))
+    }
+
+    pub fn provider_name(&self) -> &'static str {
+        self.llm.provider_name()
+    }
+
+    pub fn model_id(&self) -> &str {
+        self.llm.model_id()
+    }
+
+    pub fn supports_streaming(&self) -> bool {
+        self.llm.supports_streaming()
+    }
+
+    pub async fn generate_opinion(
+        &mut self,
+        topic: &str,
+        discussion_summary: &str,
+        recent_opinions: &[serde_json::Value],
+        phase: &str,
+    ) -> Result<AgentResponse, crate::error::AppError> {
+        let (resp, _traces) = self
+            .generate_opinion_with_tools(
+                topic,
+                discussion_summary,
+                recent_opinions,
+                phase,
+                &[],
+                None,
+            )
+            .await?;
+        Ok(resp)
+    }
+
+    pub async fn generate_opinion_with_tools(
+        &mut self,
+        topic: &str,
+        discussion_summary: &str,
+        recent_opinions: &[serde_json::Value],
+        phase: &str,
+        tools: &[ToolDefinition],
+        executor: Option<&ToolExecutor>,
+    ) -> Result<(AgentResponse, Vec<ToolTrace>), crate::error::AppError> {
+        let tools_enabled = executor.is_some() && !tools.is_empty();
+        let mut messages = self.build_messages(
+            topic,
+            discussion_summary,
+            recent_opinions,
+            tools_enabled,
+            phase,
+        );
+
         let mut traces: Vec<ToolTrace> = Vec::new();
-        let mut total_input_tokens: u32 = 0;
-        let mut total_output_tokens: u32 = 0;
-        let mut tokens_estimated: bool = false;
+        let mut metadata = MessageMetadata {
+            model: self.llm.model_id().to_string(),
+            ..Default::default()
+        };
+
+        // A forced tool only applies to the first iteration, and only when it is offered.
+        let mut tool_choice = match &self.forced_tool {
+            Some(name) if tools.iter().any(|t| &t.name == name) => ToolChoice::Tool(name.clone()),
+            _ => ToolChoice::Auto,
+        };
 
         let max_iters: usize = self.max_tool_iterations.clamp(1, 50) as usize;
         let mut final_text = String::new();
         let mut last_text = String::new();
+        let mut answered = false;
         for _ in 0..max_iters {
             let resp = if tools_enabled {
+                let choice = std::mem::take(&mut tool_choice);
                 self.llm
-                    .chat_with_tools(messages.clone(), tools, self.temperature, self.max_tokens)
+                    .chat_with_tools(
+                        messages.clone(),
+                        tools,
+                        &choice,
+                        self.temperature,
+                        self.max_tokens,
+                    )
+                    .await?
+            } else if self.stream {
+                self.llm
+                    .chat_stream(messages.clone(), self.temperature, self.max_tokens)
                     .await?
             } else {
                 self.llm
@@ -201,13 +572,21 @@ impl AgentInstance {
                     .await?
             };
 
-            total_input_tokens = total_input_tokens.saturating_add(resp.usage.input_tokens);
-            total_output_tokens = total_output_tokens.saturating_add(resp.usage.output_tokens);
-            tokens_estimated = tokens_estimated || resp.usage.estimated;
+            metadata.record(&resp);
             last_text = resp.content.clone();
 
+            let stop = metadata.finish_reason.as_deref();
             if resp.tool_calls.is_empty() || !tools_enabled {
+                // `tool_use` / `tool_calls` promises pending tools; ending the
+                // turn on it would silently drop the work the model asked for.
+                if tools_enabled && stop == Some("tool_calls") && resp.content.trim().is_empty() {
+                    return Err(crate::error::AppError::Message(format!(
+                        "{} stopped to call a tool but sent no tool call",
+                        self.llm.provider_name()
+                    )));
+                }
                 final_text = resp.content;
+                answered = true;
                 break;
             }
 
@@ -218,58 +597,288 @@ impl AgentInstance {
                 name: None,
                 tool_call_id: None,
                 tool_calls: Some(tool_calls.clone()),
+                images: Vec::new(),
             });
 
+            // Output cut off at max_tokens may have truncated the arguments,
+            // so answer each call with an error instead of running it.
+            if stop == Some("length") {
+                for call in tool_calls {
+                    let tool_payload = serde_json::json!({
+                        "ok": false,
+                        "name": call.name,
+                        "output": null,
+                        "error": TRUNCATED_TOOL_CALL
+                    });
+                    messages.push(Message {
+                        role: MessageRole::Tool,
+                        content: Some(tool_payload.to_string()),
+                        name: None,
+                        tool_call_id: Some(call.id),
+                        tool_calls: None,
+                        images: Vec::new(),
+                    });
+                }
+                continue;
+            }
+
+            // One Tool message per call, in call order: OpenAI-style APIs
+            // take them as is, and the Anthropic provider folds them into a
+            // single user message of `tool_result` blocks when converting.
             for call in tool_calls {
                 let Some(executor) = executor else { break };
-                let result = executor.execute(call.clone()).await;
+                let result = if call.name == "summarize_file" {
+                    let (result, input, output) = summarize::execute(
+                        self.llm.as_ref(),
+                        executor,
+                        call.clone(),
+                        &SummarizeLimits::default(),
+                    )
+                    .await;
+                    metadata.add_tokens(input, output);
+                    result
+                } else {
+                    executor.execute(call.clone()).await
+                };
                 traces.push(ToolTrace {
                     call: call.clone(),
                     result: result.clone(),
                 });
 
-                let tool_payload = serde_json::json!({
-                    "ok": result.ok,
-                    "name": result.name,
-                    "output": result.output,
-                    "error": result.error
+                // The trace above keeps the full JSON; the model may get a
+                // compact rendering instead.
+                let compact = result
+                    .ok
+                    .then(|| format_result(&result.name, &result.output))
+                    .flatten();
+                let tool_content = compact.unwrap_or_else(|| {
+                    let tool_payload = serde_json::json!({
+                        "ok": result.ok,
+                        "name": result.name,
+                        "output": result.output,
+                        "error": result.error
+                    });
+                    serde_json::to_string(&tool_payload)
+                        .unwrap_or_else(|_| tool_payload.to_string())
                 });
-                let tool_content = serde_json::to_string(&tool_payload)
-                    .unwrap_or_else(|_| tool_payload.to_string());
                 messages.push(Message {
                     role: MessageRole::Tool,
                     content: Some(tool_content),
                     name: None,
                     tool_call_id: Some(result.tool_call_id.clone()),
                     tool_calls: None,
+                    images: Vec::new(),
                 });
             }
         }
 
+        // Still calling tools at the iteration cap: ask once more, with tools
+        // ruled out, for an answer built from what the tools returned so far.
+        // The request still goes through `chat_with_tools`, since plain
+        // `chat` can't carry the tool turns already in the conversation.
+        if !answered {
+            messages.push(Message {
+                role: MessageRole::User,
+                content: Some(FINAL_ANSWER_NUDGE.to_string()),
+                name: None,
+                tool_call_id: None,
+                tool_calls: None,
+                images: Vec::new(),
+            });
+            let resp = self
+                .llm
+                .chat_with_tools(
+                    messages.clone(),
+                    tools,
+                    &ToolChoice::None,
+                    self.temperature,
+                    self.max_tokens,
+                )
+                .await?;
+            metadata.record(&resp);
+            final_text = resp.content;
+        }
+
         if final_text.trim().is_empty() {
             final_text = last_text;
         }
 
+        if self.require_citations && !traces.is_empty() && !cites_tool_output(&final_text, &traces)
+        {
+            messages.push(Message {
+                role: MessageRole::Assistant,
+                content: Some(final_text.clone()),
+                name: None,
+                tool_call_id: None,
+                tool_calls: None,
+                images: Vec::new(),
+            });
+            messages.push(Message {
+                role: MessageRole::User,
+                content: Some(CITATION_NUDGE.to_string()),
+                name: None,
+                tool_call_id: None,
+                tool_calls: None,
+                images: Vec::new(),
+            });
+            let resp = self
+                .llm
+                .chat_with_tools(
+                    messages,
+                    tools,
+                    &ToolChoice::None,
+                    self.temperature,
+                    self.max_tokens,
+                )
+                .await?;
+            metadata.record(&resp);
+            if !resp.content.trim().is_empty() {
+                final_text = resp.content;
+            }
+        }
+
         let content = final_text.trim().to_string();
-        self.opinions.push(content.clone());
+        // Check for [DONE] before truncation can cut it off the end.
         let wants_to_continue = should_continue(&content);
+        let responding_to = addressed_peer(&content, recent_opinions, &self.id);
+        let original_chars = content.chars().count();
+        let (content, truncated) = truncate_opinion(content, self.max_opinion_chars);
+        self.opinions.push(content.clone());
 
         Ok((
             AgentResponse {
                 content,
                 wants_to_continue,
-                responding_to: None,
-                metadata: serde_json::json!({
-                    "input_tokens": total_input_tokens,
-                    "output_tokens": total_output_tokens,
-                    "tokens_estimated": tokens_estimated
-                }),
+                responding_to,
+                metadata: serde_json::to_value(MessageMetadata {
+                    tool_calls: traces
+                        .iter()
+                        .map(|trace| ToolCallMetadata {
+                            id: trace.call.id.clone(),
+                            name: trace.call.name.clone(),
+                            ok: trace.result.ok,
+                        })
+                        .collect(),
+                    truncated,
+                    original_chars,
+                    ..metadata
+                })
+                .unwrap_or_default(),
             },
             traces,
         ))
     }
 }
 
+const AGENT_COLORS: [&str; 10] = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4", "#f032e6", "#9a6324",
+    "#469990", "#808000",
+];
+
+/// Pick a palette color from an FNV-1a hash of `id`; unlike `DefaultHasher`,
+/// the result never changes between builds.
+fn agent_color(id: &str) -> &'static str {
+    let hash = id.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    AGENT_COLORS[(hash % AGENT_COLORS.len() as u64) as usize]
+}
+
+/// Who wrote a recent-opinion entry, as shown to the other agents.
+fn opinion_speaker(op: &serde_json::Value) -> &str {
+    op.get("agent_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown")
+}
+
+/// Smallest `max_opinion_chars` an override may set.
+const MIN_OPINION_CHARS: usize = 200;
+const TRUNCATION_MARKER: &str = "\n\n[truncated]";
+
+/// Cut `content` to `max` characters plus a `[truncated]` marker; returns
+/// whether anything was removed.
+fn truncate_opinion(content: String, max: Option<usize>) -> (String, bool) {
+    match max {
+        Some(max) if content.chars().count() > max => {
+            let mut cut: String = content.chars().take(max).collect();
+            cut.push_str(TRUNCATION_MARKER);
+            (cut, true)
+        }
+        _ => (content, false),
+    }
+}
+
+/// Tool result sent back for calls whose arguments hit the output limit.
+const TRUNCATED_TOOL_CALL: &str =
+    "The response hit max_tokens before this tool call was complete, so it was not run. Retry with shorter arguments.";
+
+const FINAL_ANSWER_NUDGE: &str =
+    "工具调用次数已用完。请不要再调用工具，根据上面已获得的工具结果，直接给出你的最终回答。";
+
+const CITATION_NUDGE: &str = "你的回答没有引用任何工具返回的内容。请基于上面的工具结果重新作答，并明确引用相关的文件路径或代码片段。";
+
+/// Whether `content` mentions at least one file path or snippet line returned
+/// by the tools. Short lines are ignored so that braces or keywords don't count.
+fn cites_tool_output(content: &str, traces: &[ToolTrace]) -> bool {
+    fn collect(value: &serde_json::Value, refs: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, v) in map {
+                    match (key.as_str(), v.as_str()) {
+                        ("path" | "old_path" | "new_path", Some(path)) => {
+                            refs.push(path.to_string());
+                            if let Some(name) = std::path::Path::new(path).file_name() {
+                                refs.push(name.to_string_lossy().to_string());
+                            }
+                        }
+                        ("content" | "snippet", Some(text)) => refs.extend(
+                            text.lines()
+                                .map(str::trim)
+                                .filter(|l| l.chars().count() >= 16)
+                                .map(str::to_string),
+                        ),
+                        _ => collect(v, refs),
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|v| collect(v, refs)),
+            _ => {}
+        }
+    }
+
+    let mut refs = Vec::new();
+    for trace in traces {
+        collect(&trace.call.arguments, &mut refs);
+        collect(&trace.result.output, &mut refs);
+    }
+    refs.iter()
+        .filter(|r| !r.trim().is_empty())
+        .any(|r| content.contains(r.as_str()))
+}
+
+/// The peer in `recent_opinions` that `content` addresses first as `@名字`,
+/// by agent id; never `own_id`. Longer names win at the same position, so
+/// `@Ann Lee` isn't read as `@Ann`.
+fn addressed_peer(
+    content: &str,
+    recent_opinions: &[serde_json::Value],
+    own_id: &str,
+) -> Option<String> {
+    recent_opinions
+        .iter()
+        .filter_map(|op| {
+            let id = op.get("agent_id")?.as_str()?;
+            let name = op.get("agent_name")?.as_str()?;
+            if id == own_id || name.is_empty() {
+                return None;
+            }
+            let at = content.find(&format!("@{name}"))?;
+            Some((at, std::cmp::Reverse(name.len()), id))
+        })
+        .min()
+        .map(|(_, _, id)| id.to_string())
+}
+
 fn should_continue(content: &str) -> bool {
     // 检测 [DONE] 标记
     !content.contains("[DONE]")
@@ -282,6 +891,15 @@ fn default_true() -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::mock::{self, MockProvider};
+
+    fn agent() -> Agent {
+        mock::agent("a1", "Alice")
+    }
+
+    fn instance(agent: &Agent) -> AgentInstance {
+        AgentInstance::from_agent(agent, MockProvider::new(""))
+    }
 
     fn resp_with(metadata: serde_json::Value) -> AgentResponse {
         AgentResponse {
@@ -295,9 +913,7 @@ mod tests {
     #[test]
     fn token_counts_reads_all_fields() {
         let resp = resp_with(serde_json::json!({
-            "input_tokens": 12,
-            "output_tokens": 34,
-            "tokens_estimated": true
+            "tokens": { "input": 12, "output": 34, "estimated": true }
         }));
         assert_eq!(resp.token_counts(), (12, 34, true));
     }
@@ -314,15 +930,630 @@ mod tests {
         );
     }
 
+    #[test]
+    fn token_counts_saturate_instead_of_wrapping() {
+        let resp = resp_with(serde_json::json!({
+            "tokens": { "input": 5_000_000_000u64, "output": u64::from(u32::MAX) + 1 }
+        }));
+        assert_eq!(resp.token_counts(), (u32::MAX, u32::MAX, false));
+    }
+
     #[test]
     fn token_counts_handles_partial_metadata() {
-        let resp = resp_with(serde_json::json!({ "input_tokens": 7 }));
+        let resp = resp_with(serde_json::json!({ "tokens": { "input": 7 } }));
         assert_eq!(resp.token_counts(), (7, 0, false));
     }
 
+    #[test]
+    fn config_override_changes_instance_not_agent() {
+        let agent = agent();
+        let mut inst = instance(&agent);
+        inst.apply_config_override(&serde_json::json!({
+            "temperature": 0.1,
+            "max_tokens": 512,
+            "max_tool_iterations": 3
+        }));
+
+        assert_eq!(inst.temperature, 0.1);
+        assert_eq!(inst.max_tokens, 512);
+        assert_eq!(inst.max_tool_iterations, 3);
+        assert_eq!(agent.temperature, 0.7);
+        assert_eq!(agent.max_tokens, 2048);
+    }
+
+    #[test]
+    fn config_override_clamps_out_of_range_values() {
+        let mut inst = instance(&agent());
+        inst.apply_config_override(&serde_json::json!({
+            "temperature": 9.5,
+            "max_tokens": 0,
+            "max_tool_iterations": 1000
+        }));
+
+        assert_eq!(inst.temperature, 2.0);
+        assert_eq!(inst.max_tokens, 1);
+        assert_eq!(inst.max_tool_iterations, 50);
+    }
+
+    #[test]
+    fn config_override_ignores_missing_and_mistyped_keys() {
+        let mut inst = instance(&agent());
+        inst.apply_config_override(&serde_json::json!({ "temperature": "hot" }));
+        inst.apply_config_override(&serde_json::Value::Null);
+
+        assert_eq!(inst.temperature, 0.7);
+        assert_eq!(inst.max_tokens, 2048);
+        assert_eq!(inst.max_tool_iterations, 10);
+    }
+
+    #[test]
+    fn system_message_truncates_head_when_limited() {
+        let mut inst = instance(&agent());
+        inst.system_prompt = "abcdefghij".to_string();
+        inst.limit_system_prompt(Some(4));
+        assert_eq!(inst.system_message().content.as_deref(), Some("abcd"));
+
+        inst.limit_system_prompt(None);
+        assert_eq!(inst.system_message().content.as_deref(), Some("abcdefghij"));
+    }
+
+    #[test]
+    fn system_message_keeps_prompt_within_limit() {
+        let mut inst = instance(&agent());
+        inst.limit_system_prompt(Some(1_000));
+        assert_eq!(
+            inst.system_message().content.as_deref(),
+            Some("You are Alice.")
+        );
+    }
+
+    #[test]
+    fn replies_address_the_first_mentioned_peer() {
+        let peers = vec![
+            serde_json::json!({ "agent_id": "a", "agent_name": "Ann", "content": "x" }),
+            serde_json::json!({ "agent_id": "al", "agent_name": "Ann Lee", "content": "y" }),
+            serde_json::json!({ "agent_id": "b", "agent_name": "Bob", "content": "z" }),
+        ];
+        let addressed = |content: &str| addressed_peer(content, &peers, "b");
+        assert_eq!(addressed("@Bob and @Ann: no").as_deref(), Some("a"));
+        assert_eq!(addressed("@Ann Lee is right").as_deref(), Some("al"));
+        assert_eq!(addressed("Agreed with everyone"), None);
+        assert_eq!(addressed("@Bob, myself"), None);
+    }
+
     #[test]
     fn should_continue_flips_on_done_marker() {
         assert!(should_continue("still thinking"));
         assert!(!should_continue("final answer\n[DONE]"));
     }
+
+    fn read_notes_script(answer: &str) -> Vec<crate::llm::provider::LLMResponse> {
+        let call = crate::tools::definition::ToolCall {
+            id: "call-1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::json!({ "path": "notes.md" }),
+        };
+        vec![
+            mock::response("", vec![call]),
+            mock::response(answer, Vec::new()),
+        ]
+    }
+
+    async fn run_with_tools(
+        provider: std::sync::Arc<MockProvider>,
+        require_citations: bool,
+    ) -> AgentResponse {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("notes.md"),
+            "the cache is rebuilt nightly\n",
+        )
+        .unwrap();
+        let executor = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        let mut inst = AgentInstance::from_agent(&agent(), provider);
+        inst.require_citations = require_citations;
+        let tools = executor.definitions();
+        let (resp, traces) = inst
+            .generate_opinion_with_tools("topic", "", &[], "initial", &tools, Some(&executor))
+            .await
+            .unwrap();
+        assert_eq!(traces.len(), 1);
+        resp
+    }
+
+    #[tokio::test]
+    async fn ignoring_tool_output_triggers_one_reprompt() {
+        let provider = MockProvider::scripted(
+            read_notes_script("I think it is fine."),
+            "Per notes.md, the cache is rebuilt nightly.",
+        );
+        let resp = run_with_tools(provider.clone(), true).await;
+
+        assert_eq!(provider.calls().len(), 3);
+        assert!(provider.user_content(2).contains(CITATION_NUDGE));
+        assert_eq!(provider.tool_choices().last(), Some(&ToolChoice::None));
+        assert_eq!(resp.content, "Per notes.md, the cache is rebuilt nightly.");
+        assert_eq!(resp.token_counts(), (30, 15, false));
+    }
+
+    #[tokio::test]
+    async fn citing_tool_output_is_accepted() {
+        let provider = MockProvider::scripted(read_notes_script("See notes.md."), "unused");
+        let resp = run_with_tools(provider.clone(), true).await;
+
+        assert_eq!(provider.calls().len(), 2);
+        assert_eq!(resp.content, "See notes.md.");
+    }
+
+    #[tokio::test]
+    async fn citation_check_is_off_by_default() {
+        let provider = MockProvider::scripted(read_notes_script("I think it is fine."), "unused");
+        let resp = run_with_tools(provider.clone(), false).await;
+
+        assert_eq!(provider.calls().len(), 2);
+        assert_eq!(resp.content, "I think it is fine.");
+    }
+
+    fn with_stop(
+        mut resp: crate::llm::provider::LLMResponse,
+        raw: &str,
+    ) -> crate::llm::provider::LLMResponse {
+        resp.finish_reason = Some(raw.to_string());
+        resp
+    }
+
+    #[tokio::test]
+    async fn metadata_totals_every_request_and_lists_tool_calls() {
+        let mut script = read_notes_script("See notes.md.");
+        script[0].usage.reasoning_tokens = 3;
+        script[1].usage.cache_read_tokens = 8;
+        script[1].model = "mock-model-2024".to_string();
+        script[1].rate_limit = Some(RateLimitInfo {
+            requests_limit: Some(100),
+            requests_remaining: Some(5),
+            ..Default::default()
+        });
+        let provider = MockProvider::scripted(script, "unused");
+        let resp = run_with_tools(provider, false).await;
+
+        let metadata: MessageMetadata = serde_json::from_value(resp.metadata.clone()).unwrap();
+        assert_eq!(metadata.model, "mock-model-2024");
+        assert_eq!(
+            metadata.tokens,
+            MessageTokens {
+                input: 20,
+                output: 10,
+                estimated: false
+            }
+        );
+        assert_eq!(metadata.tool_calls.len(), 1);
+        assert_eq!(metadata.tool_calls[0].name, "read_file");
+        assert!(metadata.tool_calls[0].ok);
+        assert_eq!(metadata.reasoning, Some(3));
+        assert_eq!(metadata.cached, Some(CachedTokens { read: 8, write: 0 }));
+        assert_eq!(
+            metadata.rate_limit.and_then(|r| r.requests_remaining),
+            Some(5)
+        );
+        assert_eq!(resp.token_counts(), (20, 10, false));
+    }
+
+    #[tokio::test]
+    async fn tool_use_stop_continues_the_loop_until_end_turn() {
+        let script = read_notes_script("See notes.md.");
+        let provider = MockProvider::scripted(
+            vec![
+                with_stop(script[0].clone(), "tool_use"),
+                with_stop(script[1].clone(), "end_turn"),
+            ],
+            "unused",
+        );
+        let resp = run_with_tools(provider.clone(), false).await;
+
+        assert_eq!(provider.calls().len(), 2);
+        assert_eq!(resp.content, "See notes.md.");
+        assert_eq!(resp.metadata["finish_reason"], "stop");
+        assert_eq!(resp.metadata["raw_finish_reason"], "end_turn");
+    }
+
+    #[tokio::test]
+    async fn search_results_reach_the_model_compactly_but_trace_keeps_json() {
+        let call = crate::tools::definition::ToolCall {
+            id: "call-1".to_string(),
+            name: "search_content".to_string(),
+            arguments: serde_json::json!({ "pattern": "nightly" }),
+        };
+        let provider = MockProvider::scripted(
+            vec![
+                mock::response("", vec![call]),
+                mock::response("See notes.md.", Vec::new()),
+            ],
+            "unused",
+        );
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.md"), "intro\nrebuilt nightly\n").unwrap();
+        let executor = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        let mut inst = AgentInstance::from_agent(&agent(), provider.clone());
+        let tools = executor.definitions();
+        let (_, traces) = inst
+            .generate_opinion_with_tools("topic", "", &[], "initial", &tools, Some(&executor))
+            .await
+            .unwrap();
+
+        let tool_reply = provider.calls()[1].last().unwrap().clone();
+        assert_eq!(
+            tool_reply.content.as_deref(),
+            Some("notes.md:2: rebuilt nightly")
+        );
+        let stored = &traces[0].result.output;
+        assert_eq!(stored[0]["path"], "notes.md");
+        assert_eq!(stored[0]["line"], 2);
+        assert!(stored[0]["column"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn parallel_tool_calls_get_one_tool_message_each_in_call_order() {
+        let call = |id: &str, path: &str| crate::tools::definition::ToolCall {
+            id: id.to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::json!({ "path": path }),
+        };
+        let provider = MockProvider::scripted(
+            vec![
+                mock::response("", vec![call("call-1", "a.md"), call("call-2", "b.md")]),
+                mock::response("See a.md and b.md.", Vec::new()),
+            ],
+            "unused",
+        );
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.md"), "alpha\n").unwrap();
+        std::fs::write(dir.path().join("b.md"), "beta\n").unwrap();
+        let executor = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        let mut inst = AgentInstance::from_agent(&agent(), provider.clone());
+        let tools = executor.definitions();
+        let (_, traces) = inst
+            .generate_opinion_with_tools("topic", "", &[], "initial", &tools, Some(&executor))
+            .await
+            .unwrap();
+        assert_eq!(traces.len(), 2);
+
+        let followup = provider.calls()[1].clone();
+        let tail = &followup[followup.len() - 3..];
+        assert_eq!(tail[0].tool_calls.as_ref().unwrap().len(), 2);
+        let ids = tail[1..]
+            .iter()
+            .map(|m| {
+                assert!(matches!(m.role, MessageRole::Tool));
+                m.tool_call_id.clone().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["call-1", "call-2"]);
+
+        // OpenAI-style APIs get the results as separate `tool` messages.
+        let converted = tail
+            .iter()
+            .cloned()
+            .map(crate::llm::openai_compatible::to_openai_message)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let roles = converted
+            .iter()
+            .map(|m| m["role"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(roles, vec!["assistant", "tool", "tool"]);
+    }
+
+    #[tokio::test]
+    async fn tool_calls_cut_off_at_max_tokens_are_not_run() {
+        let script = read_notes_script("unused");
+        let provider = MockProvider::scripted(
+            vec![
+                with_stop(script[0].clone(), "max_tokens"),
+                with_stop(script[1].clone(), "end_turn"),
+            ],
+            "unused",
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let executor = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        let mut inst = AgentInstance::from_agent(&agent(), provider.clone());
+        let tools = executor.definitions();
+        let (_, traces) = inst
+            .generate_opinion_with_tools("topic", "", &[], "initial", &tools, Some(&executor))
+            .await
+            .unwrap();
+
+        assert!(traces.is_empty());
+        let retry = provider.calls()[1].clone();
+        let tool_reply = retry.last().unwrap();
+        assert_eq!(tool_reply.tool_call_id.as_deref(), Some("call-1"));
+        assert!(tool_reply
+            .content
+            .as_deref()
+            .unwrap()
+            .contains("max_tokens"));
+    }
+
+    #[tokio::test]
+    async fn hitting_the_tool_cap_forces_a_final_answer() {
+        let call = crate::tools::definition::ToolCall {
+            id: "call-1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::json!({ "path": "notes.md" }),
+        };
+        let looping = vec![mock::response("", vec![call]); 3];
+        let provider = MockProvider::scripted(looping, "The cache is rebuilt nightly.");
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.md"), "rebuilt nightly\n").unwrap();
+        let executor = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        let mut inst = AgentInstance::from_agent(&agent(), provider.clone());
+        inst.max_tool_iterations = 3;
+        let tools = executor.definitions();
+        let (resp, traces) = inst
+            .generate_opinion_with_tools("topic", "", &[], "initial", &tools, Some(&executor))
+            .await
+            .unwrap();
+
+        assert_eq!(traces.len(), 3);
+        assert_eq!(provider.calls().len(), 4);
+        assert_eq!(
+            provider.tool_choices(),
+            vec![
+                ToolChoice::Auto,
+                ToolChoice::Auto,
+                ToolChoice::Auto,
+                ToolChoice::None
+            ]
+        );
+        assert!(provider.user_content(3).contains(FINAL_ANSWER_NUDGE));
+        assert_eq!(resp.content, "The cache is rebuilt nightly.");
+        assert_eq!(resp.token_counts(), (40, 20, false));
+    }
+
+    #[tokio::test]
+    async fn tool_use_stop_without_a_tool_call_is_an_error() {
+        let provider = MockProvider::scripted(
+            vec![with_stop(mock::response("", Vec::new()), "tool_use")],
+            "unused",
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let executor = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        let mut inst = AgentInstance::from_agent(&agent(), provider);
+        let tools = executor.definitions();
+        let err = inst
+            .generate_opinion_with_tools("topic", "", &[], "initial", &tools, Some(&executor))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no tool call"));
+    }
+
+    fn system_text(call: &[Message]) -> String {
+        call.iter()
+            .filter(|m| matches!(m.role, MessageRole::System))
+            .filter_map(|m| m.content.clone())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[tokio::test]
+    async fn custom_instruction_is_used_for_the_response_phase() {
+        let provider = MockProvider::new("ok");
+        let mut inst = AgentInstance::from_agent(&agent(), provider.clone());
+        inst.instructions = PromptInstructions::from_mode_config(&serde_json::json!({
+            "instructions": {
+                "collaboration": "End with [DONE] when the discussion is finished.",
+                "response": "Reply to the other experts on: {{topic}}",
+                "initial": "  "
+            }
+        }));
+
+        inst.generate_opinion("caching", "", &[], "initial")
+            .await
+            .unwrap();
+        inst.generate_opinion("caching", "", &[], "response")
+            .await
+            .unwrap();
+
+        let calls = provider.calls();
+        let initial = system_text(&calls[0]);
+        assert!(initial.contains("End with [DONE]"));
+        assert!(!initial.contains("协作提示"));
+        assert!(!initial.contains("Reply to the other experts"));
+        assert!(system_text(&calls[1]).contains("Reply to the other experts on: caching"));
+    }
+
+    #[test]
+    fn default_instructions_are_unchanged() {
+        let defaults = PromptInstructions::from_mode_config(&serde_json::json!({}));
+        assert_eq!(defaults, PromptInstructions::default());
+        assert!(defaults.collaboration.contains("[DONE]"));
+        assert!(defaults.phases.is_empty());
+    }
+
+    #[tokio::test]
+    async fn output_language_directive_is_added_only_when_set() {
+        let provider = MockProvider::new("ok");
+        let mut inst = AgentInstance::from_agent(&agent(), provider.clone());
+        inst.generate_opinion("caching", "", &[], "initial")
+            .await
+            .unwrap();
+
+        inst.instructions = PromptInstructions::from_mode_config(&serde_json::json!({
+            "output_language": " English "
+        }));
+        inst.generate_opinion("caching", "", &[], "response")
+            .await
+            .unwrap();
+
+        let calls = provider.calls();
+        assert!(!system_text(&calls[0]).contains("Respond only in"));
+        assert!(system_text(&calls[1]).contains("Respond only in English,"));
+    }
+
+    #[tokio::test]
+    async fn peer_opinions_are_named_messages_on_the_openai_path() {
+        let provider = MockProvider::new("ok");
+        let mut inst = AgentInstance::from_agent(&agent(), provider.clone());
+        let peers = vec![
+            serde_json::json!({ "agent_name": "Bob", "content": "Use a queue." }),
+            serde_json::json!({ "agent_name": "Carol Ann", "content": "Use a cron job." }),
+        ];
+        inst.generate_opinion("topic", "", &peers, "response")
+            .await
+            .unwrap();
+
+        let wire: Vec<serde_json::Value> = provider.calls()[0]
+            .iter()
+            .cloned()
+            .map(|m| crate::llm::openai_compatible::to_openai_message(m).unwrap())
+            .collect();
+        let named: Vec<(&str, &str)> = wire
+            .iter()
+            .filter_map(|m| Some((m["name"].as_str()?, m["content"].as_str()?)))
+            .collect();
+        assert_eq!(named.len(), 2);
+        assert_eq!(named[0].0, "Bob");
+        assert!(named[0].1.contains("Use a queue."));
+        assert_eq!(named[1].0, "Carol_Ann");
+        assert!(named[1].1.contains("【Carol Ann】"));
+
+        let context = wire.last().unwrap();
+        assert_eq!(context["role"], "user");
+        assert!(context.get("name").is_none());
+        assert!(context["content"]
+            .as_str()
+            .unwrap()
+            .contains("Bob、Carol Ann"));
+    }
+
+    #[test]
+    fn knowledge_base_docs_matching_the_topic_are_injected() {
+        let now = chrono::Utc::now();
+        let doc = |id: &str, path: &str, content: &str| KnowledgeDoc {
+            id: id.to_string(),
+            kb_id: "kb1".to_string(),
+            path: Some(path.to_string()),
+            content: content.to_string(),
+            created_at: now,
+            updated_at: now,
+        };
+        let mut inst = instance(&agent());
+        inst.knowledge = vec![
+            doc(
+                "d1",
+                "deploy.md",
+                "Blue-green deployment keeps the old fleet warm.",
+            ),
+            doc("d2", "recipes.md", "Knead the dough for ten minutes."),
+        ];
+
+        let context = inst.build_context_message("", &[], "How should we plan the deployment?");
+        assert!(context.contains("## 知识库"));
+        assert!(context.contains("### deploy.md"));
+        assert!(context.contains("old fleet warm"));
+        assert!(!context.contains("dough"));
+
+        let unrelated = inst.build_context_message("", &[], "量子计算");
+        assert!(!unrelated.contains("## 知识库"));
+    }
+
+    #[test]
+    fn context_has_no_knowledge_section_without_a_knowledge_base() {
+        let context = instance(&agent()).build_context_message("", &[], "deployment plan");
+        assert!(!context.contains("## 知识库"));
+    }
+
+    #[test]
+    fn config_override_enables_citations() {
+        let mut inst = instance(&agent());
+        assert!(!inst.require_citations);
+        inst.apply_config_override(&serde_json::json!({ "require_citations": true }));
+        assert!(inst.require_citations);
+    }
+
+    #[tokio::test]
+    async fn long_opinions_are_truncated_but_fully_counted() {
+        let long = format!("{} [DONE]", "x".repeat(500));
+        let provider = MockProvider::new(&long);
+        let mut inst = AgentInstance::from_agent(&agent(), provider);
+        inst.apply_config_override(&serde_json::json!({ "max_opinion_chars": 250 }));
+
+        let (resp, _) = inst
+            .generate_opinion_with_tools("topic", "", &[], "initial", &[], None)
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.content,
+            format!("{}{TRUNCATION_MARKER}", "x".repeat(250))
+        );
+        assert!(!resp.wants_to_continue);
+        assert_eq!(resp.metadata["truncated"], true);
+        assert_eq!(resp.metadata["original_chars"], 507);
+        assert_eq!(resp.token_counts(), (10, 5, false));
+        assert_eq!(inst.opinions, vec![resp.content.clone()]);
+    }
+
+    #[tokio::test]
+    async fn opinions_within_the_limit_are_untouched() {
+        let mut inst = AgentInstance::from_agent(&agent(), MockProvider::new("short answer"));
+        inst.max_opinion_chars = Some(250);
+
+        let (resp, _) = inst
+            .generate_opinion_with_tools("topic", "", &[], "initial", &[], None)
+            .await
+            .unwrap();
+        assert_eq!(resp.content, "short answer");
+        assert_eq!(resp.metadata["truncated"], false);
+        assert_eq!(
+            truncate_opinion("abc".to_string(), None),
+            ("abc".to_string(), false)
+        );
+    }
+
+    #[tokio::test]
+    async fn forced_tool_applies_to_first_iteration_only() {
+        let provider = MockProvider::scripted(read_notes_script("See notes.md."), "unused");
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.md"), "notes").unwrap();
+        let executor = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        let mut inst = AgentInstance::from_agent(&agent(), provider.clone());
+        inst.apply_config_override(&serde_json::json!({ "forced_tool": "read_file" }));
+
+        inst.generate_opinion_with_tools(
+            "topic",
+            "",
+            &[],
+            "initial",
+            &executor.definitions(),
+            Some(&executor),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            provider.tool_choices(),
+            vec![ToolChoice::Tool("read_file".to_string()), ToolChoice::Auto]
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_forced_tool_falls_back_to_auto() {
+        let provider = MockProvider::new("done");
+        let dir = tempfile::tempdir().unwrap();
+        let executor = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        let mut inst = AgentInstance::from_agent(&agent(), provider.clone());
+        inst.forced_tool = Some("summarize_workspace".to_string());
+
+        inst.generate_opinion_with_tools(
+            "topic",
+            "",
+            &[],
+            "initial",
+            &executor.definitions(),
+            Some(&executor),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(provider.tool_choices(), vec![ToolChoice::Auto]);
+    }
 }
@@ -0,0 +1,120 @@
+//! Keyword retrieval over an agent's local knowledge base.
+
+use std::collections::HashSet;
+
+use crate::models::knowledge::KnowledgeDoc;
+
+/// Documents injected into a turn's context at most.
+pub const KNOWLEDGE_TOP_K: usize = 3;
+/// Characters of each retrieved document included in the context.
+pub const KNOWLEDGE_EXCERPT_CHARS: usize = 1200;
+
+/// Common English words that would match nearly every document.
+const STOPWORDS: &[&str] = &[
+    "an", "and", "are", "as", "at", "be", "by", "can", "do", "does", "for", "from", "how", "in",
+    "is", "it", "of", "on", "or", "our", "should", "that", "the", "this", "to", "we", "what",
+    "when", "which", "who", "why", "will", "with", "you",
+];
+
+/// The `top_k` documents sharing the most distinct terms with `query`, best
+/// first; documents matching nothing are never returned.
+pub fn retrieve<'a>(docs: &'a [KnowledgeDoc], query: &str, top_k: usize) -> Vec<&'a KnowledgeDoc> {
+    let terms = terms(query);
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let mut scored = docs
+        .iter()
+        .filter_map(|doc| {
+            let text = format!(
+                "{}\n{}",
+                doc.path.as_deref().unwrap_or_default(),
+                doc.content
+            )
+            .to_lowercase();
+            let score = terms.iter().filter(|t| text.contains(t.as_str())).count();
+            (score > 0).then_some((score, doc))
+        })
+        .collect::<Vec<_>>();
+    // Stable sort keeps insertion order among equally relevant docs.
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().take(top_k).map(|(_, doc)| doc).collect()
+}
+
+/// Lowercased search terms: alphanumeric words of two or more characters
+/// (minus `STOPWORDS`), and overlapping character pairs within CJK runs (which have no spaces).
+fn terms(query: &str) -> HashSet<String> {
+    let chars = query.to_lowercase().chars().collect::<Vec<_>>();
+    let is_word = |c: char| (c.is_alphanumeric() || c == '_') && !is_cjk(c);
+    let mut out = HashSet::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        if is_cjk(chars[i]) {
+            while i < chars.len() && is_cjk(chars[i]) {
+                i += 1;
+            }
+            let run = &chars[start..i];
+            if run.len() == 1 {
+                out.insert(run[0].to_string());
+            }
+            out.extend(run.windows(2).map(|w| w.iter().collect::<String>()));
+        } else if is_word(chars[i]) {
+            while i < chars.len() && is_word(chars[i]) {
+                i += 1;
+            }
+            let word = chars[start..i].iter().collect::<String>();
+            if i - start >= 2 && !STOPWORDS.contains(&word.as_str()) {
+                out.insert(word);
+            }
+        } else {
+            i += 1;
+        }
+    }
+    out
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(id: &str, content: &str) -> KnowledgeDoc {
+        let now = chrono::Utc::now();
+        KnowledgeDoc {
+            id: id.to_string(),
+            kb_id: "kb".to_string(),
+            path: None,
+            content: content.to_string(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn terms_split_words_and_cjk_pairs() {
+        let terms = terms("Rust 数据库迁移 a the");
+        assert!(terms.contains("rust"));
+        assert!(terms.contains("数据"));
+        assert!(terms.contains("据库"));
+        assert!(terms.contains("迁移"));
+        assert!(!terms.contains("a"));
+        assert!(!terms.contains("the"));
+    }
+
+    #[test]
+    fn ranks_by_distinct_matching_terms() {
+        let docs = vec![
+            doc("cooking", "How to bake bread"),
+            doc("partial", "SQLite basics"),
+            doc("best", "SQLite migration guide: 数据库迁移步骤"),
+        ];
+        let hits = retrieve(&docs, "SQLite 数据库迁移 migration", 2);
+        let ids = hits.iter().map(|d| d.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["best", "partial"]);
+        assert!(retrieve(&docs, "quantum", 3).is_empty());
+    }
+}
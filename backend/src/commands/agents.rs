@@ -3,13 +3,17 @@ use tauri::State;
 use uuid::Uuid;
 
 use crate::error::AppError;
-use crate::models::agent::{Agent, AgentCreate, AgentListItem, AgentUpdate};
-use crate::models::common::{PaginatedResponse, SuccessResponse};
+use crate::models::agent::{
+    system_prompt_length_warning, Agent, AgentCreate, AgentListItem, AgentUpdate, SavedAgent,
+};
+use crate::models::common::{check_revision, Ownership, PaginatedResponse, SuccessResponse};
 use crate::state::AppState;
+use crate::store::sqlite::SqliteStore;
 
 const LOCAL_USER_ID: &str = "local";
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn list_agents(
     state: State<AppState>,
     page: Option<usize>,
@@ -18,12 +22,14 @@ pub fn list_agents(
     tags: Option<String>,
     is_template: Option<bool>,
     collaboration_style: Option<String>,
+    ownership: Option<Ownership>,
 ) -> Result<PaginatedResponse<AgentListItem>, AppError> {
     let page = page.unwrap_or(1).max(1);
     let page_size = page_size.unwrap_or(20).clamp(1, 100);
 
     let mut agents = state.store.agents_list()?;
-    agents.retain(|a| a.user_id == LOCAL_USER_ID || a.is_public);
+    let ownership = ownership.unwrap_or_default();
+    agents.retain(|a| ownership.allows(a.user_id == LOCAL_USER_ID, a.is_public));
 
     if let Some(search) = search {
         let needle = search.to_lowercase();
@@ -109,7 +115,7 @@ pub fn get_agent(state: State<AppState>, id: String) -> Result<Agent, AppError>
 }
 
 #[tauri::command]
-pub fn create_agent(state: State<AppState>, agent: AgentCreate) -> Result<Agent, AppError> {
+pub fn create_agent(state: State<AppState>, agent: AgentCreate) -> Result<SavedAgent, AppError> {
     let now = Utc::now();
     let record = Agent {
         id: Uuid::new_v4().to_string(),
@@ -131,6 +137,7 @@ pub fn create_agent(state: State<AppState>, agent: AgentCreate) -> Result<Agent,
         speaking_priority: agent.speaking_priority,
         interaction_rules: agent.interaction_rules,
         version: 1,
+        revision: 0,
         is_template: agent.is_template,
         is_public: agent.is_public,
         parent_id: None,
@@ -141,7 +148,7 @@ pub fn create_agent(state: State<AppState>, agent: AgentCreate) -> Result<Agent,
         updated_at: now,
     };
     state.store.agents_upsert(&record)?;
-    Ok(record)
+    with_prompt_warning(&state.store, record)
 }
 
 #[tauri::command]
@@ -149,12 +156,44 @@ pub fn update_agent(
     state: State<AppState>,
     id: String,
     update: AgentUpdate,
+    expected_revision: Option<u32>,
+) -> Result<SavedAgent, AppError> {
+    let agent = apply_agent_update(&state.store, &id, update, expected_revision)?;
+    with_prompt_warning(&state.store, agent)
+}
+
+/// Pair a just-saved agent with the warning for an over-long system prompt,
+/// so the editor can show it.
+fn with_prompt_warning(store: &SqliteStore, agent: Agent) -> Result<SavedAgent, AppError> {
+    let max_chars = store.settings_get()?.max_system_prompt_chars();
+    let warning = system_prompt_length_warning(&agent.system_prompt, max_chars);
+    Ok(SavedAgent { agent, warning })
+}
+
+/// Apply `update` to agent `id` atomically, rejecting it if the agent's
+/// `revision` no longer matches `expected_revision`. The revision moves on
+/// every edit; the version only when the update changes how the agent
+/// behaves, so autosaved cosmetic edits leave it alone.
+fn apply_agent_update(
+    store: &SqliteStore,
+    id: &str,
+    update: AgentUpdate,
+    expected_revision: Option<u32>,
 ) -> Result<Agent, AppError> {
-    let mut existing = state
-        .store
-        .agents_get(&id)?
-        .ok_or_else(|| AppError::Message(format!("Agent {id} not found")))?;
+    store.agents_update(id, |existing| {
+        check_revision("Agent", id, existing.revision, expected_revision)?;
+        let before = existing.clone();
+        apply_fields(existing, update);
+        if !existing.behaves_like(&before) {
+            existing.version = existing.version.saturating_add(1);
+        }
+        existing.revision = existing.revision.saturating_add(1);
+        existing.updated_at = Utc::now();
+        Ok(())
+    })
+}
 
+fn apply_fields(existing: &mut Agent, update: AgentUpdate) {
     if let Some(v) = update.name {
         existing.name = v;
     }
@@ -206,11 +245,6 @@ pub fn update_agent(
     if let Some(v) = update.is_public {
         existing.is_public = v;
     }
-
-    existing.version = existing.version.saturating_add(1);
-    existing.updated_at = Utc::now();
-    state.store.agents_upsert(&existing)?;
-    Ok(existing)
 }
 
 #[tauri::command]
@@ -232,7 +266,16 @@ pub fn duplicate_agent(
         .store
         .agents_get(&id)?
         .ok_or_else(|| AppError::Message(format!("Agent {id} not found")))?;
+    copy_agent(&state.store, original, new_name)
+}
 
+/// Store a user-owned, non-template copy of `original`, named `new_name` or
+/// "<name> (副本)".
+pub(crate) fn copy_agent(
+    store: &SqliteStore,
+    original: Agent,
+    new_name: Option<String>,
+) -> Result<Agent, AppError> {
     let now = Utc::now();
     let record = Agent {
         id: Uuid::new_v4().to_string(),
@@ -254,6 +297,7 @@ pub fn duplicate_agent(
         speaking_priority: original.speaking_priority,
         interaction_rules: original.interaction_rules.clone(),
         version: 1,
+        revision: 0,
         is_template: false,
         is_public: false,
         parent_id: Some(original.id),
@@ -264,6 +308,132 @@ pub fn duplicate_agent(
         updated_at: now,
     };
 
-    state.store.agents_upsert(&record)?;
+    store.agents_upsert(&record)?;
     Ok(record)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_revisions_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        store
+            .agents_upsert(&crate::llm::mock::agent("a1", "Alice"))
+            .unwrap();
+        let read = store.agents_get("a1").unwrap().unwrap().revision;
+
+        let reprompt = |prompt: &str| AgentUpdate {
+            system_prompt: Some(prompt.to_string()),
+            ..Default::default()
+        };
+        let updated = apply_agent_update(&store, "a1", reprompt("Be brief."), Some(read)).unwrap();
+        assert_eq!(updated.revision, read + 1);
+
+        // A second tab still holding the old revision loses, and writes nothing.
+        let err = apply_agent_update(&store, "a1", reprompt("Be verbose."), Some(read))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Conflict"));
+        let stored = store.agents_get("a1").unwrap().unwrap();
+        assert_eq!(stored.system_prompt, "Be brief.");
+        assert_eq!(stored.revision, read + 1);
+
+        // Without an expected revision the write is unconditional.
+        assert_eq!(
+            apply_agent_update(&store, "a1", reprompt("Be kind."), None)
+                .unwrap()
+                .revision,
+            read + 2
+        );
+    }
+
+    #[test]
+    fn long_prompts_come_back_with_a_warning() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        store
+            .settings_set(&crate::models::settings::AppSettings {
+                max_system_prompt_chars: Some(10),
+                ..Default::default()
+            })
+            .unwrap();
+        let mut agent = crate::llm::mock::agent("a1", "Alice");
+        agent.system_prompt = "Be brief.".to_string();
+
+        let saved = with_prompt_warning(&store, agent.clone()).unwrap();
+        assert_eq!(saved.warning, None);
+        assert!(serde_json::to_value(&saved)
+            .unwrap()
+            .get("warning")
+            .is_none());
+
+        agent.system_prompt = "Be brief, always.".to_string();
+        let saved = with_prompt_warning(&store, agent).unwrap();
+        assert_eq!(
+            saved.warning.as_deref(),
+            Some("system_prompt is 17 characters, exceeding the recommended maximum of 10")
+        );
+        let json = serde_json::to_value(&saved).unwrap();
+        assert_eq!(json["id"], "a1");
+        assert!(json["warning"].is_string());
+    }
+
+    #[test]
+    fn concurrent_cosmetic_edits_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        store
+            .agents_upsert(&crate::llm::mock::agent("a1", "Alice"))
+            .unwrap();
+        let read = store.agents_get("a1").unwrap().unwrap();
+        let rename = |name: &str| AgentUpdate {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+
+        let first = apply_agent_update(&store, "a1", rename("Ann"), Some(read.revision)).unwrap();
+        assert_eq!(first.version, read.version);
+        let err = apply_agent_update(&store, "a1", rename("Anna"), Some(read.revision))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Conflict"), "{err}");
+        assert_eq!(store.agents_get("a1").unwrap().unwrap().name, "Ann");
+    }
+
+    #[test]
+    fn only_behavioral_changes_bump_the_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        store
+            .agents_upsert(&crate::llm::mock::agent("a1", "Alice"))
+            .unwrap();
+        let read = store.agents_get("a1").unwrap().unwrap();
+
+        let cosmetic = AgentUpdate {
+            name: Some("Ann".to_string()),
+            avatar: Some("🦉".to_string()),
+            ..Default::default()
+        };
+        let updated = apply_agent_update(&store, "a1", cosmetic, None).unwrap();
+        assert_eq!(updated.avatar.as_deref(), Some("🦉"));
+        assert_eq!(updated.version, read.version);
+
+        // Re-saving the same prompt is not a change either.
+        let same_prompt = AgentUpdate {
+            system_prompt: Some(read.system_prompt.clone()),
+            ..Default::default()
+        };
+        let updated = apply_agent_update(&store, "a1", same_prompt, None).unwrap();
+        assert_eq!(updated.version, read.version);
+
+        let new_prompt = AgentUpdate {
+            system_prompt: Some("Answer in one line.".to_string()),
+            ..Default::default()
+        };
+        let updated = apply_agent_update(&store, "a1", new_prompt, None).unwrap();
+        assert_eq!(updated.version, read.version + 1);
+    }
+}
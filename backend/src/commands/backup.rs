@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use tauri::State;
+
+use crate::error::AppError;
+use crate::state::AppState;
+use crate::store::backup::{Backup, BackupSummary, ImportMode};
+
+/// Write every agent, team, model config, execution and message to `path`.
+/// API keys are redacted unless `include_secrets` is set.
+#[tauri::command]
+pub fn export_all(
+    state: State<AppState>,
+    path: String,
+    include_secrets: Option<bool>,
+) -> Result<BackupSummary, AppError> {
+    let mut backup = state.store.export_backup()?;
+    if !include_secrets.unwrap_or(false) {
+        backup.redact_secrets();
+    }
+    backup.write_to(&PathBuf::from(path))?;
+    Ok(backup.summary())
+}
+
+/// Restore a file written by `export_all`, keeping its ids.
+#[tauri::command]
+pub fn import_all(
+    state: State<AppState>,
+    path: String,
+    mode: ImportMode,
+) -> Result<BackupSummary, AppError> {
+    let backup = Backup::read_from(&PathBuf::from(path))?;
+    state.store.import_backup(&backup, mode)?;
+    Ok(backup.summary())
+}
@@ -0,0 +1,281 @@
+use std::future::Future;
+use std::time::Instant;
+
+use tauri::{State, Window};
+
+use crate::commands::executions::{catch_panic, insert_execution, mark_failed, run_execution};
+use crate::error::AppError;
+use crate::models::execution::{BenchmarkResult, ExecutionCreate};
+use crate::models::llm::ExecutionLLMConfig;
+use crate::state::AppState;
+use crate::store::sqlite::SqliteStore;
+
+/// Run `input` through the team once per model in `model_ids`, each as its
+/// own execution on the normal path, and compare their outputs, tokens, cost
+/// and latency. `llm` supplies provider and key; it falls back to the app
+/// settings default. Runs go one at a time so latencies are comparable.
+#[tauri::command]
+pub async fn benchmark_team(
+    window: Window,
+    state: State<'_, AppState>,
+    team_id: String,
+    input: String,
+    model_ids: Vec<String>,
+    llm: Option<ExecutionLLMConfig>,
+) -> Result<Vec<BenchmarkResult>, AppError> {
+    let store = state.store.clone();
+    run_benchmark(&store, &team_id, &input, &model_ids, llm, |execution_id| {
+        let window = window.clone();
+        let store = state.store.clone();
+        let locks = state.workspace_locks.clone();
+        let metrics = state.metrics.clone();
+        let task = state.execution_tasks.track(&execution_id);
+        async move {
+            let _task = task;
+            catch_panic(run_execution(
+                window,
+                store,
+                locks,
+                metrics,
+                execution_id,
+                None,
+                None,
+            ))
+            .await
+        }
+    })
+    .await
+}
+
+async fn run_benchmark<F, Fut>(
+    store: &SqliteStore,
+    team_id: &str,
+    input: &str,
+    model_ids: &[String],
+    llm: Option<ExecutionLLMConfig>,
+    mut run: F,
+) -> Result<Vec<BenchmarkResult>, AppError>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<(), AppError>>,
+{
+    let mut models: Vec<&str> = Vec::new();
+    for model_id in model_ids.iter().map(|m| m.trim()).filter(|m| !m.is_empty()) {
+        if !models.contains(&model_id) {
+            models.push(model_id);
+        }
+    }
+    if models.is_empty() {
+        return Err(AppError::Message(
+            "At least one model id is required".to_string(),
+        ));
+    }
+    if input.trim().is_empty() {
+        return Err(AppError::Message("Benchmark input is required".to_string()));
+    }
+    let team = store
+        .teams_get(team_id)?
+        .ok_or_else(|| AppError::Message(format!("Team {team_id} not found")))?;
+    let llm = llm
+        .or(store.settings_get()?.default_llm)
+        .ok_or_else(|| AppError::Message("No LLM configured".to_string()))?;
+    let mut agent_models = Vec::new();
+    for member in team.active_members() {
+        if let Some(model) = store
+            .agents_get(&member.agent_id)?
+            .and_then(|agent| agent.model_id)
+            .filter(|m| !m.trim().is_empty())
+        {
+            agent_models.push(model.trim().to_string());
+        }
+    }
+
+    let mut results = Vec::new();
+    for model_id in models {
+        let execution = insert_execution(
+            store,
+            ExecutionCreate {
+                team_id: team_id.to_string(),
+                input: input.to_string(),
+                title: Some(format!("基准测试：{model_id}")),
+                budget: None,
+                llm: Some(benchmark_llm(&llm, model_id, &agent_models)),
+                workspace_path: None,
+                workspace_roots: Default::default(),
+                tool_policy: Default::default(),
+                stream: Some(false),
+                seed_from_execution_id: None,
+            },
+        )?;
+        let started = Instant::now();
+        if let Err(err) = run(execution.id.clone()).await {
+            mark_failed(store, &execution.id, &err);
+        }
+        let latency_ms = started.elapsed().as_millis().min(u128::from(u64::MAX)) as u64;
+        let record = store
+            .executions_get(&execution.id)?
+            .ok_or_else(|| AppError::Message(format!("Execution {} not found", execution.id)))?;
+        results.push(BenchmarkResult {
+            model_id: model_id.to_string(),
+            execution_id: record.id,
+            status: record.status,
+            final_output: record.final_output,
+            error_message: record.error_message,
+            tokens_used: record.tokens_used,
+            cost: record.cost,
+            latency_ms,
+        });
+    }
+    Ok(results)
+}
+
+/// `llm` with every agent pointed at `model_id`: the bundle's own config for
+/// that id if it has one, otherwise the default config renamed. Agents that
+/// name a model of their own are mapped onto it too, so the whole team runs
+/// on the model under test.
+fn benchmark_llm(
+    llm: &ExecutionLLMConfig,
+    model_id: &str,
+    agent_models: &[String],
+) -> ExecutionLLMConfig {
+    let config = llm.models.get(model_id).cloned().unwrap_or_else(|| {
+        let mut config = llm.default.clone();
+        config.model_id = model_id.to_string();
+        config
+    });
+    ExecutionLLMConfig {
+        default: config.clone(),
+        models: agent_models
+            .iter()
+            .map(|model| (model.clone(), config.clone()))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use chrono::Utc;
+
+    use crate::commands::executions::EventLog;
+    use crate::llm::mock::{self, MockProvider};
+    use crate::metrics::Metrics;
+    use crate::models::team::Team;
+    use crate::tools::lock::WorkspaceLocks;
+
+    fn store_with_team() -> (tempfile::TempDir, SqliteStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut agent = mock::agent("a1", "Alice");
+        agent.model_id = Some("gpt-4o".to_string());
+        store.agents_upsert(&agent).unwrap();
+        let team: Team = serde_json::from_value(serde_json::json!({
+            "id": "team",
+            "user_id": "local",
+            "name": "Team",
+            "description": null,
+            "icon": null,
+            "collaboration_mode": "roundtable",
+            "coordinator_id": null,
+            "is_template": false,
+            "is_public": false,
+            "usage_count": 0,
+            "rating": 0.0,
+            "rating_count": 0,
+            "members": [{
+                "id": "m1",
+                "agent_id": "a1",
+                "role_override": null,
+                "priority_override": null,
+                "position": 0,
+                "is_active": true,
+                "created_at": Utc::now(),
+                "updated_at": Utc::now()
+            }],
+            "created_at": Utc::now(),
+            "updated_at": Utc::now()
+        }))
+        .unwrap();
+        store.teams_upsert(&team).unwrap();
+        (dir, store)
+    }
+
+    fn llm() -> ExecutionLLMConfig {
+        serde_json::from_value(serde_json::json!({
+            "default": {
+                "model_id": "default-model",
+                "api_key": "k",
+                "input_price_per_1k": 1.0,
+                "output_price_per_1k": 2.0
+            }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn each_model_runs_in_its_own_execution() {
+        let (_dir, store) = store_with_team();
+        let store = Arc::new(store);
+        let locks = Arc::new(WorkspaceLocks::default());
+        let metrics = Arc::new(Metrics::default());
+        let a = MockProvider::new("A says ship it");
+        let b = MockProvider::new("B says wait a week");
+        mock::register("bench-model-a", a.clone());
+        mock::register("bench-model-b", b.clone());
+
+        // The normal run path, with events recorded instead of sent to a window.
+        let events = EventLog::default();
+        let results = run_benchmark(
+            &store,
+            "team",
+            "Should we ship?",
+            &["bench-model-a".to_string(), "bench-model-b".to_string()],
+            Some(llm()),
+            |execution_id| {
+                run_execution(
+                    events.clone(),
+                    store.clone(),
+                    locks.clone(),
+                    metrics.clone(),
+                    execution_id,
+                    None,
+                    None,
+                )
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results.len(), 2);
+        let requests = a.calls().len();
+        assert!(requests > 0);
+        assert_eq!(b.calls().len(), requests);
+        for (result, model, reply) in [
+            (&results[0], "bench-model-a", "A says ship it"),
+            (&results[1], "bench-model-b", "B says wait a week"),
+        ] {
+            assert_eq!(result.model_id, model);
+            assert_eq!(result.status, "completed", "{:?}", result.error_message);
+            assert!(result.final_output.as_deref().unwrap().contains(reply));
+            // Every mock reply reports 10 input and 5 output tokens, priced
+            // at 1.0 and 2.0 per 1k.
+            assert_eq!(result.tokens_used, 15 * requests as u32);
+            assert!((result.cost - 0.02 * requests as f64).abs() < 1e-9);
+            let stored = store.executions_get(&result.execution_id).unwrap().unwrap();
+            assert_eq!(stored.llm.unwrap().default.model_id, model);
+        }
+        assert_ne!(results[0].execution_id, results[1].execution_id);
+        assert_eq!(
+            events.of_type("status").last().unwrap()["status"],
+            "completed"
+        );
+
+        assert!(
+            run_benchmark(&store, "team", "x", &[], Some(llm()), |_| async { Ok(()) })
+                .await
+                .is_err()
+        );
+    }
+}
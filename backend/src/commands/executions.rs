@@ -1,29 +1,54 @@
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+
 use chrono::Utc;
+use futures::FutureExt;
 use serde::Serialize;
 use serde_json::Value;
 use tauri::{Emitter, State, Window};
 use uuid::Uuid;
 
-use crate::agents::instance::AgentInstance;
+use crate::agents::instance::{AgentInstance, PromptInstructions};
+use crate::commands::settings::pricing_table;
 use crate::error::AppError;
-use crate::llm::factory::{provider_from_runtime_config, resolve_runtime_config_for_agent};
+use crate::llm::concurrency::AdaptiveConcurrency;
+use crate::llm::factory::{
+    provider_from_runtime_config, resolve_runtime_config_for_agent, with_concurrency, with_metrics,
+};
+use crate::llm::rate_limit::RateLimitInfo;
+use crate::metrics::Metrics;
 use crate::models::common::{PaginatedResponse, SuccessResponse};
 use crate::models::execution::{
-    ExecutionCreate, ExecutionListItem, ExecutionMessage, ExecutionRecord, ExecutionResponse,
+    progress, sort_messages, ActiveExecution, CostBreakdown, CostEstimate, ExecutionCreate,
+    ExecutionListItem, ExecutionMessage, ExecutionRecord, ExecutionResponse, ExecutionSummary,
+    UNKNOWN_COLLABORATION_MODE,
 };
+use crate::models::settings::AppSettings;
 use crate::models::team::Team;
-use crate::orchestration::debate::run_debate;
-use crate::orchestration::pipeline::run_pipeline;
-use crate::orchestration::roundtable::run_roundtable;
-use crate::orchestration::state::OrchestrationState;
+use crate::orchestration::convergence::StopOnStable;
+use crate::orchestration::critic::{run_critic, CriticConfig};
+use crate::orchestration::debate::{run_debate, verdict_prompt, DebateFormat};
+use crate::orchestration::dedupe::dedupe_threshold;
+use crate::orchestration::error_events::{app_error_event, error_event, ErrorSeverity};
+use crate::orchestration::output::OutputFormat;
+use crate::orchestration::pipeline::{run_pipeline, StageErrorPolicy};
+use crate::orchestration::plan::build_plan;
+use crate::orchestration::roundtable::{run_roundtable, summary_prompt, InitialOrder};
+use crate::orchestration::state::{Opinion, OrchestrationState, MAX_RETAINED_OPINIONS};
+use crate::orchestration::status::can_transition;
+use crate::sanitize::{redact_credentials, Redaction, Sanitizer};
 use crate::state::AppState;
 use crate::tools::executor::ToolExecutor;
+use crate::tools::lock::WorkspaceLocks;
+use crate::tools::workspace_diff;
 
 const LOCAL_USER_ID: &str = "local";
 const EVENT_NAME: &str = "execution-event";
+/// Minimum gap between live token/cost writes while a round is running.
+const LIVE_USAGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
 
 #[derive(Debug, Clone, Serialize)]
-struct ExecutionEventPayload {
+pub(crate) struct ExecutionEventPayload {
     execution_id: String,
     event_type: String,
     data: Value,
@@ -31,6 +56,46 @@ struct ExecutionEventPayload {
     sequence: u64,
 }
 
+/// Where a run's events go: the app window, or a recorder in tests, so a
+/// whole run can be driven without one.
+pub(crate) trait EventSink: Clone + Send + Sync + 'static {
+    fn send(&self, payload: ExecutionEventPayload);
+}
+
+impl EventSink for Window {
+    fn send(&self, payload: ExecutionEventPayload) {
+        let _ = self.emit(EVENT_NAME, payload);
+    }
+}
+
+/// Keeps every event it is sent as `(event_type, data)`.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct EventLog(std::sync::Arc<std::sync::Mutex<Vec<(String, Value)>>>);
+
+#[cfg(test)]
+impl EventLog {
+    pub(crate) fn of_type(&self, event_type: &str) -> Vec<Value> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(kind, _)| kind == event_type)
+            .map(|(_, data)| data.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl EventSink for EventLog {
+    fn send(&self, payload: ExecutionEventPayload) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((payload.event_type, payload.data));
+    }
+}
+
 #[tauri::command]
 pub fn list_executions(
     state: State<AppState>,
@@ -38,6 +103,8 @@ pub fn list_executions(
     page_size: Option<usize>,
     team_id: Option<String>,
     status_filter: Option<String>,
+    used_tools: Option<bool>,
+    search: Option<String>,
 ) -> Result<PaginatedResponse<ExecutionListItem>, AppError> {
     let page = page.unwrap_or(1).max(1);
     let page_size = page_size.unwrap_or(20).clamp(1, 100);
@@ -51,6 +118,13 @@ pub fn list_executions(
     if let Some(status_filter) = status_filter {
         executions.retain(|e| e.status == status_filter);
     }
+    if let Some(used_tools) = used_tools {
+        executions.retain(|e| e.used_tools() == used_tools);
+    }
+    if let Some(search) = search {
+        let needle = search.to_lowercase();
+        executions.retain(|e| matches_search(e, &needle));
+    }
 
     executions.sort_by_key(|e| std::cmp::Reverse(e.created_at));
 
@@ -68,21 +142,7 @@ pub fn list_executions(
         &executions[start..end]
     };
 
-    let items = slice
-        .iter()
-        .map(|e| ExecutionListItem {
-            id: e.id.clone(),
-            team_id: e.team_id.clone(),
-            title: e.title.clone(),
-            status: e.status.clone(),
-            current_round: e.current_round,
-            tokens_used: e.tokens_used,
-            cost: e.cost,
-            started_at: e.started_at,
-            completed_at: e.completed_at,
-            created_at: e.created_at,
-        })
-        .collect();
+    let items = slice.iter().map(ExecutionListItem::from).collect();
 
     Ok(PaginatedResponse {
         items,
@@ -93,6 +153,51 @@ pub fn list_executions(
     })
 }
 
+/// Whether `needle` (already lowercased) occurs in the execution's title or
+/// initial input.
+fn matches_search(execution: &ExecutionRecord, needle: &str) -> bool {
+    execution
+        .title
+        .as_ref()
+        .is_some_and(|t| t.to_lowercase().contains(needle))
+        || execution.initial_input.to_lowercase().contains(needle)
+}
+
+/// Executions currently `running`, longest-running first.
+#[tauri::command]
+pub fn list_active_executions(state: State<AppState>) -> Result<Vec<ActiveExecution>, AppError> {
+    active_executions(&state.store)
+}
+
+fn active_executions(
+    store: &crate::store::sqlite::SqliteStore,
+) -> Result<Vec<ActiveExecution>, AppError> {
+    let now = Utc::now();
+    let mut active = store
+        .executions_list()?
+        .into_iter()
+        .filter(|e| e.user_id == LOCAL_USER_ID && e.status == "running")
+        .map(|e| {
+            let (phase, round) = progress(&e.shared_state);
+            let elapsed_ms = e
+                .started_at
+                .map(|at| (now - at).num_milliseconds().max(0) as u64)
+                .unwrap_or(0);
+            ActiveExecution {
+                id: e.id,
+                team_id: e.team_id,
+                title: e.title,
+                phase,
+                round,
+                started_at: e.started_at,
+                elapsed_ms,
+            }
+        })
+        .collect::<Vec<_>>();
+    active.sort_by_key(|e| std::cmp::Reverse(e.elapsed_ms));
+    Ok(active)
+}
+
 #[tauri::command]
 pub fn get_execution(state: State<AppState>, id: String) -> Result<ExecutionResponse, AppError> {
     let record = state
@@ -100,11 +205,76 @@ pub fn get_execution(state: State<AppState>, id: String) -> Result<ExecutionResp
         .executions_get(&id)?
         .ok_or_else(|| AppError::Message(format!("Execution {id} not found")))?;
     let mut messages = state.store.execution_messages_list(&id)?;
-    messages.sort_by_key(|m| m.sequence);
+    sort_messages(&mut messages);
+    resolve_replies(&mut messages);
     let total = messages.len();
     let start = total.saturating_sub(50);
     let recent = messages[start..].to_vec();
-    Ok(ExecutionResponse::from_record(record, recent))
+    execution_response(&state.store, record, recent)
+}
+
+#[tauri::command]
+pub fn get_execution_summary(
+    state: State<AppState>,
+    execution_id: String,
+) -> Result<ExecutionSummary, AppError> {
+    execution_summary(&state.store, &execution_id)
+}
+
+fn execution_summary(
+    store: &crate::store::sqlite::SqliteStore,
+    id: &str,
+) -> Result<ExecutionSummary, AppError> {
+    let record = store
+        .executions_get(id)?
+        .ok_or_else(|| AppError::Message(format!("Execution {id} not found")))?;
+    Ok(ExecutionSummary {
+        execution: ExecutionListItem::from(&record),
+        final_output: record.final_output,
+        error_message: record.error_message,
+        counts: store.execution_message_counts(id)?,
+    })
+}
+
+/// Point each reply at the message it answers: a `responding_to` id found
+/// in `messages` gets its sequence as `metadata.responding_to_sequence`; one
+/// that matches nothing is cleared so the UI never threads to a missing row.
+fn resolve_replies(messages: &mut [ExecutionMessage]) {
+    let sequences = messages
+        .iter()
+        .map(|m| (m.id.clone(), m.sequence))
+        .collect::<std::collections::HashMap<_, _>>();
+    for message in messages.iter_mut() {
+        let Some(target) = message.responding_to.as_deref() else {
+            continue;
+        };
+        match sequences.get(target) {
+            Some(&sequence) if target != message.id => {
+                if !message.metadata.is_object() {
+                    message.metadata = serde_json::json!({});
+                }
+                message.metadata["responding_to_sequence"] = serde_json::json!(sequence);
+            }
+            _ => message.responding_to = None,
+        }
+    }
+}
+
+/// Build the response for `record`, resolving the team's current mode.
+fn execution_response(
+    store: &crate::store::sqlite::SqliteStore,
+    record: ExecutionRecord,
+    recent_messages: Vec<ExecutionMessage>,
+) -> Result<ExecutionResponse, AppError> {
+    let mode = store
+        .teams_get(&record.team_id)?
+        .map(|team| team.collaboration_mode)
+        .unwrap_or_else(|| UNKNOWN_COLLABORATION_MODE.to_string());
+    Ok(ExecutionResponse::from_record(
+        record,
+        mode,
+        recent_messages,
+    ))
 }
 
 #[tauri::command]
@@ -112,35 +282,132 @@ pub fn create_execution(
     state: State<AppState>,
     execution: ExecutionCreate,
 ) -> Result<ExecutionResponse, AppError> {
+    let record = insert_execution(&state.store, execution)?;
+    execution_response(&state.store, record, Vec::new())
+}
+
+/// Store a new pending execution, filling omitted fields from the app
+/// settings.
+pub(crate) fn insert_execution(
+    store: &crate::store::sqlite::SqliteStore,
+    mut execution: ExecutionCreate,
+) -> Result<ExecutionRecord, AppError> {
+    let settings = store.settings_get()?;
+    settings.apply_to_execution(&mut execution);
+    check_workspaces(
+        &settings,
+        execution.workspace_path.as_deref(),
+        &execution.workspace_roots,
+    )?;
+    let budget = execution.budget.unwrap_or_default().validated()?;
+    if let Some(source_id) = &execution.seed_from_execution_id {
+        execution.input = seeded_input(store, source_id, &execution.input)?;
+    }
+    let topic = store
+        .teams_get(&execution.team_id)?
+        .map(|team| team.apply_input_template(&execution.input))
+        .unwrap_or_else(|| execution.input.clone());
+    let shared_state = if topic.trim().is_empty() {
+        serde_json::json!({})
+    } else {
+        serde_json::json!({ "topic": topic })
+    };
+
     let now = Utc::now();
     let record = ExecutionRecord {
         id: Uuid::new_v4().to_string(),
         user_id: LOCAL_USER_ID.to_string(),
         team_id: execution.team_id,
-        title: execution.title,
+        title: execution_title(execution.title, &execution.input),
         initial_input: execution.input,
         llm: execution.llm,
         status: "pending".to_string(),
         current_stage: None,
         current_round: 0,
-        shared_state: serde_json::json!({}),
+        shared_state,
         agent_states: serde_json::json!({}),
         final_output: None,
         structured_output: None,
         tokens_used: 0,
-        tokens_budget: execution.budget.max_tokens,
+        tokens_budget: budget.max_tokens,
         cost: 0.0,
-        cost_budget: execution.budget.max_cost,
+        cost_budget: budget.max_cost,
+        estimate: None,
+        eta_ms: None,
         started_at: None,
         completed_at: None,
         error_message: None,
         retry_count: 0,
+        tool_calls: 0,
         workspace_path: execution.workspace_path,
+        workspace_roots: execution.workspace_roots,
+        workspace_baseline: None,
+        tool_policy: execution.tool_policy,
+        stream: execution.stream,
         created_at: now,
         updated_at: now,
     };
-    state.store.executions_upsert(&record)?;
-    Ok(ExecutionResponse::from_record(record, Vec::new()))
+    store.executions_upsert(&record)?;
+    Ok(record)
+}
+
+/// Input for an execution chained onto `source_id`: the source's
+/// `final_output`, or failing that its last round of opinions, followed by
+/// any extra `input` the caller gave.
+fn seeded_input(
+    store: &crate::store::sqlite::SqliteStore,
+    source_id: &str,
+    input: &str,
+) -> Result<String, AppError> {
+    let source = store
+        .executions_get(source_id)?
+        .ok_or_else(|| AppError::Message(format!("Execution {source_id} not found")))?;
+    let output = match source.final_output.filter(|o| !o.trim().is_empty()) {
+        Some(output) => output,
+        None => {
+            let opinions = store
+                .execution_messages_list(source_id)?
+                .iter()
+                .filter_map(Opinion::from_message)
+                .collect::<Vec<_>>();
+            let last_round = opinions.iter().map(|op| op.round).max();
+            opinions
+                .iter()
+                .filter(|op| Some(op.round) == last_round)
+                .map(|op| format!("**{}**: {}", op.agent_name, op.content.trim()))
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        }
+    };
+    if output.trim().is_empty() {
+        return Err(AppError::Message(format!(
+            "Execution {source_id} has no output to seed from"
+        )));
+    }
+    Ok(if input.trim().is_empty() {
+        output
+    } else {
+        format!("{output}\n\n{input}")
+    })
+}
+
+/// Longest title derived from an execution's input, in characters.
+const DERIVED_TITLE_CHARS: usize = 40;
+
+/// The given title, or when it's missing or blank, the start of `input`
+/// collapsed onto one line.
+fn execution_title(title: Option<String>, input: &str) -> Option<String> {
+    if let Some(title) = title.filter(|t| !t.trim().is_empty()) {
+        return Some(title);
+    }
+    let line = input.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.is_empty() {
+        return None;
+    }
+    match line.char_indices().nth(DERIVED_TITLE_CHARS) {
+        Some((end, _)) => Some(format!("{}…", line[..end].trim_end())),
+        None => Some(line),
+    }
 }
 
 #[tauri::command]
@@ -159,58 +426,565 @@ pub fn control_execution(
     action: String,
     params: Option<Value>,
 ) -> Result<SuccessResponse, AppError> {
-    let mut execution = state
-        .store
-        .executions_get(&id)?
-        .ok_or_else(|| AppError::Message(format!("Execution {id} not found")))?;
-
     let params = params.unwrap_or_else(|| serde_json::json!({}));
-    let status = execution.status.clone();
-
-    if action == "pause" && status == "running" {
-        execution.status = "paused".to_string();
-    } else if action == "resume" && status == "paused" {
-        execution.status = "running".to_string();
-    } else if action == "stop" && (status == "running" || status == "paused") {
-        execution.status = "completed".to_string();
-        execution.completed_at = Some(Utc::now());
-    } else if action == "extend_budget" {
-        let add_tokens = params
-            .get("tokens")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(50_000) as u32;
-        let add_cost = params.get("cost").and_then(|v| v.as_f64()).unwrap_or(5.0);
-        execution.tokens_budget = execution.tokens_budget.saturating_add(add_tokens);
-        execution.cost_budget += add_cost;
-    } else {
-        return Err(AppError::Message(
-            "Invalid action or execution state".to_string(),
-        ));
+    apply_control(&state.store, &id, &action, &params)?;
+    if action == "stop" {
+        // The stopped record is already saved, and the task's own final save
+        // skips a stopped record; cancelling just ends the work sooner.
+        state.execution_tasks.cancel(&id);
     }
 
-    execution.updated_at = Utc::now();
-    state.store.executions_upsert(&execution)?;
-
     Ok(SuccessResponse {
         success: true,
         message: format!("Action '{action}' executed successfully"),
     })
 }
 
+/// Apply a control `action` to the stored record in one compare-and-set, so
+/// token and cost totals written by a running round are kept.
+fn apply_control(
+    store: &crate::store::sqlite::SqliteStore,
+    id: &str,
+    action: &str,
+    params: &Value,
+) -> Result<ExecutionRecord, AppError> {
+    if !matches!(action, "pause" | "resume" | "stop" | "extend_budget") {
+        return Err(AppError::Message(format!("Unknown action '{action}'")));
+    }
+    store.executions_update(id, |execution| {
+        let next = can_transition(&execution.status, action)?;
+        if action == "stop" {
+            if execution.status == "running" {
+                // Best-effort: a failure here must not block stopping.
+                if let Err(e) = fill_partial_output(store, execution) {
+                    eprintln!("execution {id}: no partial output on stop: {e}");
+                }
+            }
+            execution.current_stage = None;
+            execution.eta_ms = None;
+            execution.completed_at = Some(Utc::now());
+        } else if action == "extend_budget" {
+            let add_tokens = params
+                .get("tokens")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(50_000) as u32;
+            let add_cost = params.get("cost").and_then(|v| v.as_f64()).unwrap_or(5.0);
+            execution.tokens_budget = execution.tokens_budget.saturating_add(add_tokens);
+            execution.cost_budget += add_cost;
+        }
+        execution.status = next.to_string();
+        execution.updated_at = Utc::now();
+        Ok(())
+    })
+}
+
+/// Give a run stopped mid-way a `final_output` built from the opinions
+/// persisted so far. Leaves the record untouched when there are none.
+fn fill_partial_output(
+    store: &crate::store::sqlite::SqliteStore,
+    execution: &mut ExecutionRecord,
+) -> Result<(), AppError> {
+    let state = OrchestrationState::from_shared_state(&execution.shared_state);
+    let opinions = store
+        .execution_messages_list(&execution.id)?
+        .iter()
+        .filter_map(Opinion::from_message)
+        .collect::<Vec<_>>();
+    if let Some(output) = state.partial_output(&opinions) {
+        let format = store
+            .teams_get(&execution.team_id)?
+            .map(|team| OutputFormat::from_rules(&team.output_rules))
+            .unwrap_or_default();
+        let structured = state.structured_output(&opinions);
+        execution.final_output = Some(format.render(&output, &structured));
+        execution.structured_output = Some(structured);
+    }
+    Ok(())
+}
+
+/// Scrub a pasted secret (or anything else) from an execution's stored history:
+/// applies a regex replacement to every message's content and metadata (which
+/// holds tool traces) and to the record's state and outputs. Returns how many
+/// messages changed.
+#[tauri::command]
+pub fn redact_execution(
+    state: State<AppState>,
+    execution_id: String,
+    pattern: String,
+    replacement: String,
+) -> Result<u32, AppError> {
+    let redaction = Redaction::new(&pattern, &replacement)?;
+    redact_history(&state.store, &execution_id, &redaction)
+}
+
+/// A running round holds its own copy of the state and messages and would
+/// write the unredacted text back, so redaction waits for it to stop.
+fn redact_history(
+    store: &crate::store::sqlite::SqliteStore,
+    execution_id: &str,
+    redaction: &Redaction,
+) -> Result<u32, AppError> {
+    let ensure_idle = |execution: &ExecutionRecord| {
+        if execution.status != "running" {
+            return Ok(());
+        }
+        Err(AppError::Message(format!(
+            "Execution {} is running; stop it or wait for it to finish before redacting",
+            execution.id
+        )))
+    };
+    let execution = store
+        .executions_get(execution_id)?
+        .ok_or_else(|| AppError::Message(format!("Execution {execution_id} not found")))?;
+    ensure_idle(&execution)?;
+
+    let mut changed = 0u32;
+    for mut message in store.execution_messages_list(execution_id)? {
+        let content = redaction.redact_text(&message.content);
+        let metadata = redaction.redact_value(&mut message.metadata);
+        if content.is_none() && !metadata {
+            continue;
+        }
+        if let Some(content) = content {
+            message.content = content;
+        }
+        message.updated_at = Utc::now();
+        store.execution_messages_upsert(execution_id, &message)?;
+        changed += 1;
+    }
+
+    store.executions_update(execution_id, |execution| {
+        ensure_idle(execution)?;
+        let mut record_changed = redaction.redact_value(&mut execution.shared_state);
+        record_changed |= redaction.redact_value(&mut execution.agent_states);
+        for text in [
+            Some(&mut execution.initial_input),
+            execution.final_output.as_mut(),
+            execution.title.as_mut(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if let Some(out) = redaction.redact_text(text) {
+                *text = out;
+                record_changed = true;
+            }
+        }
+        if let Some(structured) = execution.structured_output.as_mut() {
+            record_changed |= redaction.redact_value(structured);
+        }
+        if record_changed {
+            execution.updated_at = Utc::now();
+        }
+        Ok(())
+    })?;
+    Ok(changed)
+}
+
 #[tauri::command]
 pub fn set_execution_workspace(
     state: State<AppState>,
     id: String,
     workspace_path: Option<String>,
+    workspace_roots: Option<std::collections::BTreeMap<String, String>>,
 ) -> Result<ExecutionResponse, AppError> {
-    let mut execution = state
-        .store
-        .executions_get(&id)?
+    let execution = update_workspace(&state.store, &id, workspace_path, workspace_roots)?;
+    execution_response(&state.store, execution, Vec::new())
+}
+
+/// A running round built its tool executor from the old workspace and would
+/// keep using it, so the workspace can only change between rounds.
+fn update_workspace(
+    store: &crate::store::sqlite::SqliteStore,
+    id: &str,
+    workspace_path: Option<String>,
+    workspace_roots: Option<std::collections::BTreeMap<String, String>>,
+) -> Result<ExecutionRecord, AppError> {
+    check_workspaces(
+        &store.settings_get()?,
+        workspace_path.as_deref(),
+        workspace_roots.as_ref().unwrap_or(&Default::default()),
+    )?;
+    // Checked inside the update so a round starting meanwhile can't slip
+    // between the check and the write.
+    store.executions_update(id, |execution| {
+        if execution.status == "running" {
+            return Err(AppError::Message(
+                "Cannot change the workspace while the execution is running; stop or pause it first"
+                    .to_string(),
+            ));
+        }
+        if execution.workspace_path != workspace_path {
+            // The next round snapshots the new workspace afresh.
+            execution.workspace_baseline = None;
+        }
+        execution.workspace_path = workspace_path;
+        if let Some(roots) = workspace_roots {
+            execution.workspace_roots = roots;
+        }
+        execution.updated_at = Utc::now();
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn export_execution(
+    state: State<AppState>,
+    id: String,
+    sanitize: Option<bool>,
+) -> Result<Value, AppError> {
+    export_value(&state.store, &id, sanitize.unwrap_or(false))
+}
+
+/// The record and its messages as one JSON document. Provider keys are
+/// always redacted; `sanitize` additionally scrubs paths and secrets from
+/// the content.
+fn export_value(
+    store: &crate::store::sqlite::SqliteStore,
+    id: &str,
+    sanitize: bool,
+) -> Result<Value, AppError> {
+    let record = store
+        .executions_get(id)?
+        .ok_or_else(|| AppError::Message(format!("Execution {id} not found")))?;
+    let mut messages = store.execution_messages_list(id)?;
+    sort_messages(&mut messages);
+
+    let workspace_path = record.workspace_path.clone();
+    let mut out = serde_json::json!({
+        "execution": record,
+        "messages": messages,
+    });
+    if sanitize {
+        Sanitizer::new(workspace_path.as_deref()).sanitize_value(&mut out);
+    } else {
+        redact_credentials(&mut out);
+    }
+    Ok(out)
+}
+
+/// Remove messages left behind by executions that no longer exist; returns
+/// how many were deleted.
+#[tauri::command]
+pub fn prune_orphan_messages(state: State<AppState>) -> Result<usize, AppError> {
+    state.store.prune_orphan_messages()
+}
+
+/// Per-agent, per-round and per-phase cost of a completed execution, priced
+/// from its stored message token counts.
+#[tauri::command]
+pub fn get_execution_cost_breakdown(
+    state: State<AppState>,
+    id: String,
+) -> Result<CostBreakdown, AppError> {
+    cost_breakdown(&state.store, &id)
+}
+
+fn cost_breakdown(
+    store: &crate::store::sqlite::SqliteStore,
+    id: &str,
+) -> Result<CostBreakdown, AppError> {
+    let record = store
+        .executions_get(id)?
         .ok_or_else(|| AppError::Message(format!("Execution {id} not found")))?;
-    execution.workspace_path = workspace_path;
-    execution.updated_at = Utc::now();
-    state.store.executions_upsert(&execution)?;
-    Ok(ExecutionResponse::from_record(execution, Vec::new()))
+    if record.status != "completed" {
+        return Err(AppError::Message(format!(
+            "Execution {id} is {}; cost breakdowns are only available once it completes",
+            record.status
+        )));
+    }
+    let table = pricing_table(store)?;
+    let pricing = record
+        .llm
+        .as_ref()
+        .map(|llm| llm.default.resolve_pricing(&table))
+        .unwrap_or_default();
+    let mut messages = store.execution_messages_list(id)?;
+    sort_messages(&mut messages);
+    Ok(CostBreakdown::from_messages(id, pricing, &messages))
+}
+
+/// Phase of the message holding a non-debate execution's regenerated summary.
+const SUMMARY_PHASE: &str = "summary";
+
+/// Re-run only the closing step of a completed execution over its stored
+/// opinions: the judge's verdict for a debate, a summary for other modes.
+#[tauri::command]
+pub async fn regenerate_summary(
+    window: Window,
+    state: State<'_, AppState>,
+    execution_id: String,
+) -> Result<ExecutionResponse, AppError> {
+    let store = state.store.clone();
+    let execution = store
+        .executions_get(&execution_id)?
+        .ok_or_else(|| AppError::Message(format!("Execution {execution_id} not found")))?;
+    let team = store
+        .teams_get(&execution.team_id)?
+        .ok_or_else(|| AppError::Message("Team not found".to_string()))?;
+    let llm = execution
+        .llm
+        .clone()
+        .ok_or_else(|| AppError::Message("No LLM configured".to_string()))?;
+    let messages = store.execution_messages_list(&execution_id)?;
+    let roster = roster(&store, &team)?;
+    let coordinator = resolve_coordinator(&team, &roster)?;
+    let summarizer_id = summarizer_id(&team, coordinator, &messages, &roster)
+        .ok_or_else(|| AppError::Message("No agents in team".to_string()))?;
+    // Only the summarizer runs, so only its model config has to be valid.
+    let mut summarizer = build_agent_instances(
+        &store,
+        &team,
+        &llm,
+        Some(&summarizer_id),
+        Some(&state.metrics),
+    )
+    .await?
+    .pop()
+    .ok_or_else(|| AppError::Message("No agents in team".to_string()))?;
+
+    let (execution, message) = resummarize(&store, execution, &team, &mut summarizer).await?;
+    let mut seq = 0;
+    emit_event(
+        &window,
+        &execution_id,
+        "summary_regenerated",
+        serde_json::json!({
+            "agent_name": message.sender_name,
+            "content": message.content,
+            "phase": message.phase,
+            "message_id": message.id,
+            "message_sequence": message.sequence
+        }),
+        message.sender_id.clone(),
+        &mut seq,
+    );
+    execution_response(&store, execution, Vec::new())
+}
+
+/// `(agent id, speaking priority)` of the active members whose agents still
+/// exist, in team order: who `build_agent_instances` would build, without
+/// setting up their providers.
+fn roster(
+    store: &crate::store::sqlite::SqliteStore,
+    team: &Team,
+) -> Result<Vec<(String, i32)>, AppError> {
+    let mut roster = Vec::new();
+    for member in team.active_members() {
+        if let Some(agent) = store.agents_get(&member.agent_id)? {
+            let priority = member.priority_override.unwrap_or(agent.speaking_priority);
+            roster.push((agent.id, priority));
+        }
+    }
+    Ok(roster)
+}
+
+/// The team's coordinator among `roster`. A `coordinator_id` whose agent was
+/// deleted or deactivated falls back to the highest-priority agent, unless
+/// `mode_config.strict_coordinator` asks for an error instead.
+fn resolve_coordinator(team: &Team, roster: &[(String, i32)]) -> Result<Option<String>, AppError> {
+    let Some(coordinator) = &team.coordinator_id else {
+        return Ok(None);
+    };
+    if roster.iter().any(|(id, _)| id == coordinator) {
+        return Ok(Some(coordinator.clone()));
+    }
+    if team.strict_coordinator() {
+        let inactive = team
+            .members
+            .iter()
+            .any(|m| &m.agent_id == coordinator && !m.is_active);
+        let problem = if inactive {
+            "is not an active member of the team"
+        } else {
+            "no longer exists"
+        };
+        return Err(AppError::Message(format!(
+            "Coordinator agent {coordinator} of team {} {problem}; choose another coordinator or clear coordinator_id",
+            team.name
+        )));
+    }
+    // `max_by_key` keeps the last of equals; prefer the earliest position.
+    let fallback = roster
+        .iter()
+        .rev()
+        .max_by_key(|(_, priority)| *priority)
+        .map(|(id, _)| id.clone());
+    Ok(fallback)
+}
+
+/// Who writes the regenerated summary among `roster`: a debate's judge
+/// (whoever gave the last verdict, else the last member, as `run_debate`
+/// picks it), otherwise the team's summary agent, `coordinator` or first member.
+fn summarizer_id(
+    team: &Team,
+    coordinator: Option<String>,
+    messages: &[ExecutionMessage],
+    roster: &[(String, i32)],
+) -> Option<String> {
+    let is_member = |id: &String| roster.iter().any(|(member, _)| member == id);
+    let previous = |phase: &str| {
+        messages
+            .iter()
+            .rev()
+            .find(|m| m.phase == phase)
+            .and_then(|m| m.sender_id.clone())
+    };
+    let candidates = if team.collaboration_mode == "debate" {
+        vec![
+            previous("judge_verdict"),
+            roster.last().map(|(id, _)| id.clone()),
+        ]
+    } else {
+        vec![
+            team.output_rules.summary_agent_id.clone(),
+            coordinator,
+            previous(SUMMARY_PHASE),
+            roster.first().map(|(id, _)| id.clone()),
+        ]
+    };
+    candidates.into_iter().flatten().find(is_member)
+}
+
+/// Generate a new verdict or summary with `summarizer`, then replace the
+/// stored one and the `final_output` built from it.
+async fn resummarize(
+    store: &crate::store::sqlite::SqliteStore,
+    execution: ExecutionRecord,
+    team: &Team,
+    summarizer: &mut AgentInstance,
+) -> Result<(ExecutionRecord, ExecutionMessage), AppError> {
+    let ensure_completed = |execution: &ExecutionRecord| {
+        if execution.status == "completed" {
+            return Ok(());
+        }
+        Err(AppError::Message(format!(
+            "Execution {} is {}; only completed executions can regenerate their summary",
+            execution.id, execution.status
+        )))
+    };
+    ensure_completed(&execution)?;
+
+    let is_debate = team.collaboration_mode == "debate";
+    let phase = if is_debate {
+        "judge_verdict"
+    } else {
+        SUMMARY_PHASE
+    };
+    let mut history = store.execution_messages_list(&execution.id)?;
+    let opinions = history
+        .iter()
+        .filter(|m| m.phase != phase)
+        .filter_map(Opinion::from_message)
+        .collect::<Vec<_>>();
+    if opinions.is_empty() {
+        return Err(AppError::Message(format!(
+            "Execution {} has no opinions to summarize",
+            execution.id
+        )));
+    }
+
+    let mut state = OrchestrationState::from_shared_state(&execution.shared_state);
+    let prompt = if is_debate {
+        verdict_prompt(&state.topic, &opinions)
+    } else {
+        summary_prompt(&state.topic, &opinions)
+    };
+    let resp = summarizer
+        .generate_opinion(&prompt, "", &[], "initial")
+        .await?;
+    let (input_tokens, output_tokens, tokens_estimated) = resp.token_counts();
+
+    let now = Utc::now();
+    // Only the last round's summary is replaced; earlier rounds keep theirs.
+    let round = state.round;
+    let previous = history
+        .iter()
+        .rposition(|m| m.phase == phase && m.round == round);
+    let base = match previous {
+        Some(idx) => history[idx].clone(),
+        None => ExecutionMessage {
+            id: Uuid::new_v4().to_string(),
+            sequence: store.execution_messages_next_sequence(&execution.id)?,
+            round: state.round,
+            phase: phase.to_string(),
+            // Kept out of the opinion history: only a debate's verdict is one.
+            sender_type: if is_debate { "agent" } else { "system" }.to_string(),
+            sender_id: None,
+            sender_name: None,
+            content: String::new(),
+            content_type: "text".to_string(),
+            responding_to: None,
+            target_agent_id: None,
+            wants_to_continue: false,
+            input_tokens: 0,
+            output_tokens: 0,
+            tokens_estimated: false,
+            metadata: serde_json::json!({}),
+            created_at: now,
+            updated_at: now,
+        },
+    };
+    let message = ExecutionMessage {
+        sender_id: Some(summarizer.id.clone()),
+        sender_name: Some(summarizer.name.clone()),
+        content: resp.content.clone(),
+        input_tokens,
+        output_tokens,
+        tokens_estimated,
+        metadata: resp.metadata.clone(),
+        updated_at: now,
+        ..base
+    };
+    match previous {
+        Some(idx) => history[idx] = message.clone(),
+        None => history.push(message.clone()),
+    }
+
+    state.summary = resp.content.clone();
+    if is_debate {
+        if let Some(verdict) = state
+            .opinions
+            .iter_mut()
+            .rev()
+            .find(|o| o.phase == phase && o.round == round)
+        {
+            verdict.agent_id = summarizer.id.clone();
+            verdict.agent_name = summarizer.name.clone();
+            verdict.content = resp.content.clone();
+        }
+    }
+    let table = pricing_table(store)?;
+    let cost = execution.llm.as_ref().map_or(0.0, |llm| {
+        message.cost(&llm.default.resolve_pricing(&table))
+    });
+    let tokens = message.billed_tokens();
+    state.tokens_used = state.tokens_used.saturating_add(tokens);
+    state.cost += cost;
+
+    let opinions = history
+        .iter()
+        .filter_map(Opinion::from_message)
+        .collect::<Vec<_>>();
+    let structured = state.structured_output(&opinions);
+    let final_output = state
+        .final_output(&opinions)
+        .map(|output| OutputFormat::from_rules(&team.output_rules).render(&output, &structured));
+    let shared_state = state.to_shared_state();
+
+    // A follow-up may have started while the summary was generated.
+    let execution = store.executions_update(&execution.id, |execution| {
+        ensure_completed(execution)?;
+        execution.final_output = final_output.clone();
+        execution.structured_output = Some(structured.clone());
+        execution.shared_state = shared_state.clone();
+        execution.tokens_used = execution.tokens_used.saturating_add(tokens);
+        execution.cost += cost;
+        execution.updated_at = Utc::now();
+        Ok(())
+    })?;
+    store.execution_messages_upsert(&execution.id, &message)?;
+    Ok((execution, message))
 }
 
 #[tauri::command]
@@ -218,38 +992,61 @@ pub fn start_execution(
     window: Window,
     state: State<AppState>,
     execution_id: String,
+    dry_run: Option<bool>,
 ) -> Result<(), AppError> {
     let store = state.store.clone();
+    let locks = state.workspace_locks.clone();
+    let metrics = state.metrics.clone();
     let window = window.clone();
 
-    tauri::async_runtime::spawn(async move {
-        if let Err(err) = run_execution(
+    if dry_run.unwrap_or(false) {
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) =
+                plan_execution(window.clone(), store.clone(), execution_id.clone()).await
+            {
+                let mut seq = 0;
+                emit_event(
+                    &window,
+                    &execution_id,
+                    "error",
+                    error_event_data(&err),
+                    None,
+                    &mut seq,
+                );
+            }
+        });
+        return Ok(());
+    }
+
+    let task = state.execution_tasks.track(&execution_id);
+    let token = task.token();
+    let id = execution_id.clone();
+    let handle = tauri::async_runtime::spawn(async move {
+        let _task = task;
+        let run = run_execution(
             window.clone(),
             store.clone(),
+            locks,
+            metrics,
             execution_id.clone(),
             None,
             None,
-        )
-        .await
-        {
-            let message = err.to_string();
-            if let Ok(Some(mut execution)) = store.executions_get(&execution_id) {
-                execution.status = "failed".to_string();
-                execution.error_message = Some(message.clone());
-                execution.updated_at = Utc::now();
-                let _ = store.executions_upsert(&execution);
-            }
+        );
+        if let Err(err) = run_guarded(&store, &execution_id, run).await {
             let mut seq = 0;
             emit_event(
                 &window,
                 &execution_id,
                 "error",
-                serde_json::json!({ "message": message }),
+                error_event_data(&err),
                 None,
                 &mut seq,
             );
         }
     });
+    state
+        .execution_tasks
+        .set_abort(&id, token, handle.inner().abort_handle());
 
     Ok(())
 }
@@ -263,55 +1060,118 @@ pub fn followup_execution(
     target_agent_id: Option<String>,
 ) -> Result<(), AppError> {
     let store = state.store.clone();
+    let locks = state.workspace_locks.clone();
+    let metrics = state.metrics.clone();
     let window = window.clone();
 
-    tauri::async_runtime::spawn(async move {
-        if let Err(err) = run_execution(
+    // Claim the execution before spawning so a second follow-up sent while this
+    // one is in flight is rejected instead of running a concurrent round.
+    claim_status(&store, &execution_id, "followup")?;
+
+    let task = state.execution_tasks.track(&execution_id);
+    let token = task.token();
+    let id = execution_id.clone();
+    let handle = tauri::async_runtime::spawn(async move {
+        let _task = task;
+        let run = run_execution(
             window.clone(),
             store.clone(),
+            locks,
+            metrics,
             execution_id.clone(),
             Some(input),
             target_agent_id,
-        )
-        .await
-        {
-            let message = err.to_string();
-            if let Ok(Some(mut execution)) = store.executions_get(&execution_id) {
-                execution.status = "failed".to_string();
-                execution.error_message = Some(message.clone());
-                execution.updated_at = Utc::now();
-                let _ = store.executions_upsert(&execution);
-            }
+        );
+        if let Err(err) = run_guarded(&store, &execution_id, run).await {
             let mut seq = 0;
             emit_event(
                 &window,
                 &execution_id,
                 "error",
-                serde_json::json!({ "message": message }),
+                error_event_data(&err),
                 None,
                 &mut seq,
             );
         }
     });
+    state
+        .execution_tasks
+        .set_abort(&id, token, handle.inner().abort_handle());
 
     Ok(())
 }
 
-async fn run_execution(
-    window: Window,
-    store: std::sync::Arc<crate::store::sqlite::SqliteStore>,
-    execution_id: String,
-    followup_input: Option<String>,
-    target_agent_id: Option<String>,
+/// Await a spawned run, recording an error, or a panic anywhere inside it,
+/// as a failed execution instead of leaving it `running`.
+async fn run_guarded(
+    store: &crate::store::sqlite::SqliteStore,
+    execution_id: &str,
+    run: impl std::future::Future<Output = Result<(), AppError>>,
 ) -> Result<(), AppError> {
-    let mut event_seq: u64 = 0;
+    let result = catch_panic(run).await;
+    if let Err(err) = &result {
+        mark_failed(store, execution_id, err);
+    }
+    result
+}
+
+/// `run`'s result, with a panic turned into an error carrying its message.
+pub(crate) async fn catch_panic(
+    run: impl std::future::Future<Output = Result<(), AppError>>,
+) -> Result<(), AppError> {
+    match AssertUnwindSafe(run).catch_unwind().await {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(AppError::Message(format!("Execution panicked: {message}")))
+        }
+    }
+}
+
+/// Record a run that ended in `err` as failed, best effort.
+pub(crate) fn mark_failed(
+    store: &crate::store::sqlite::SqliteStore,
+    execution_id: &str,
+    err: &AppError,
+) {
+    let _ = store.executions_update(execution_id, |execution| {
+        if let Ok(next) = can_transition(&execution.status, "fail") {
+            execution.status = next.to_string();
+        }
+        execution.current_stage = None;
+        execution.eta_ms = None;
+        execution.error_message = Some(err.to_string());
+        execution.updated_at = Utc::now();
+        Ok(())
+    });
+}
+
+pub(crate) async fn run_execution(
+    window: impl EventSink,
+    store: std::sync::Arc<crate::store::sqlite::SqliteStore>,
+    locks: std::sync::Arc<WorkspaceLocks>,
+    metrics: std::sync::Arc<Metrics>,
+    execution_id: String,
+    followup_input: Option<String>,
+    target_agent_id: Option<String>,
+) -> Result<(), AppError> {
+    let mut event_seq: u64 = 0;
 
     let Some(mut execution) = store.executions_get(&execution_id)? else {
         emit_event(
             &window,
             &execution_id,
             "error",
-            serde_json::json!({"message": "Execution not found"}),
+            error_event(
+                ErrorSeverity::Fatal,
+                "not_found",
+                "Execution not found",
+                None,
+            ),
             None,
             &mut event_seq,
         );
@@ -324,21 +1184,7 @@ async fn run_execution(
     }
 
     if let Some(input) = &followup_input {
-        if execution.status != "paused" && execution.status != "completed" {
-            emit_event(
-                &window,
-                &execution_id,
-                "error",
-                serde_json::json!({"message": "Invalid execution state for follow-up"}),
-                None,
-                &mut event_seq,
-            );
-            return Ok(());
-        }
-        // Continue with follow-up.
-        execution.status = "running".to_string();
-        execution.updated_at = Utc::now();
-        store.executions_upsert(&execution)?;
+        // `followup_execution` already moved the execution to `running`.
         emit_event(
             &window,
             &execution_id,
@@ -351,6 +1197,8 @@ async fn run_execution(
         run_round(
             window,
             store,
+            &locks,
+            &metrics,
             execution,
             input.clone(),
             target_agent_id,
@@ -363,7 +1211,7 @@ async fn run_execution(
     // Start execution
     let initial = execution.initial_input.trim().to_string();
     if initial.is_empty() {
-        execution.status = "paused".to_string();
+        execution.status = can_transition(&execution.status, "await_input")?.to_string();
         execution.updated_at = Utc::now();
         store.executions_upsert(&execution)?;
         emit_event(
@@ -380,9 +1228,28 @@ async fn run_execution(
         return Ok(());
     }
 
-    execution.status = "running".to_string();
+    // A concurrent start may have claimed the execution since it was loaded;
+    // that run goes on, so this one reports why it stopped without failing it.
+    let mut execution = match claim_status(&store, &execution_id, "start") {
+        Ok(execution) => execution,
+        Err(err) => {
+            emit_event(
+                &window,
+                &execution_id,
+                "error",
+                app_error_event(
+                    ErrorSeverity::Warning,
+                    &err,
+                    format!("Execution could not be started: {err}"),
+                    None,
+                ),
+                None,
+                &mut event_seq,
+            );
+            return Ok(());
+        }
+    };
     execution.started_at = Some(Utc::now());
-    execution.updated_at = Utc::now();
     store.executions_upsert(&execution)?;
 
     emit_event(
@@ -394,13 +1261,110 @@ async fn run_execution(
         &mut event_seq,
     );
 
-    run_round(window, store, execution, initial, None, &mut event_seq).await?;
+    // The effective topic is the raw input wrapped in the team's input template.
+    let topic = store
+        .teams_get(&execution.team_id)?
+        .map(|team| team.apply_input_template(&initial))
+        .unwrap_or(initial);
+
+    run_round(
+        window,
+        store,
+        &locks,
+        &metrics,
+        execution,
+        topic,
+        None,
+        &mut event_seq,
+    )
+    .await?;
     Ok(())
 }
 
-async fn run_round(
+/// Resolve the agents, models and prompt sizes a start would use and emit them
+/// as a `plan` event. No provider is called and the execution is left untouched.
+async fn plan_execution(
     window: Window,
     store: std::sync::Arc<crate::store::sqlite::SqliteStore>,
+    execution_id: String,
+) -> Result<(), AppError> {
+    let mut event_seq: u64 = 0;
+    let execution = store
+        .executions_get(&execution_id)?
+        .ok_or_else(|| AppError::Message(format!("Execution {execution_id} not found")))?;
+    let team = store
+        .teams_get(&execution.team_id)?
+        .ok_or_else(|| AppError::Message("Team not found".to_string()))?;
+    let llm = execution
+        .llm
+        .clone()
+        .ok_or_else(|| AppError::Message("No LLM configured".to_string()))?;
+
+    let state = OrchestrationState::from_shared_state(&execution.shared_state);
+    let topic = team.apply_input_template(execution.initial_input.trim());
+    warm_up(&store, &team, &llm, None)?;
+    let agents = build_agent_instances(&store, &team, &llm, None, None).await?;
+    let tools_enabled = execution
+        .workspace_path
+        .as_deref()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .is_some_and(|path| workspace_executor(path, &execution.workspace_roots).is_ok());
+
+    let mut plan = build_plan(
+        &agents,
+        &team.collaboration_mode,
+        &topic,
+        &state.summary,
+        &state.context_opinions_json(agents.len()),
+        tools_enabled,
+    );
+    // Kept on the record so the run's actual usage can be compared with it.
+    let tokens = plan["estimated_prompt_tokens"]
+        .as_u64()
+        .unwrap_or(0)
+        .min(u64::from(u32::MAX)) as u32;
+    let estimate = CostEstimate {
+        tokens,
+        cost: llm
+            .default
+            .resolve_pricing(&pricing_table(&store)?)
+            .cost(tokens, 0),
+        round: state.round + 1,
+        ..CostEstimate::default()
+    };
+    plan["estimated_cost"] = serde_json::json!(estimate.cost);
+    store.executions_update(&execution_id, |execution| {
+        execution.estimate = Some(estimate);
+        Ok(())
+    })?;
+    emit_event(&window, &execution_id, "plan", plan, None, &mut event_seq);
+    Ok(())
+}
+
+/// Stream every agent whose provider supports it unless the execution opted
+/// out; when it asked for streaming explicitly, returns the `(id, name)` of
+/// agents that fall back to plain requests.
+fn enable_streaming(
+    agents: &mut [AgentInstance],
+    requested: Option<bool>,
+) -> Vec<(String, String)> {
+    let mut unsupported = Vec::new();
+    for agent in agents.iter_mut() {
+        agent.stream = requested.unwrap_or(true) && agent.supports_streaming();
+        if requested == Some(true) && !agent.stream {
+            unsupported.push((agent.id.clone(), agent.name.clone()));
+        }
+    }
+    unsupported
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_round(
+    window: impl EventSink,
+    store: std::sync::Arc<crate::store::sqlite::SqliteStore>,
+    locks: &WorkspaceLocks,
+    metrics: &std::sync::Arc<Metrics>,
     mut execution: ExecutionRecord,
     topic: String,
     target_agent_id: Option<String>,
@@ -416,33 +1380,86 @@ async fn run_round(
             &window,
             &execution_id,
             "error",
-            serde_json::json!({"message": "No LLM configured. Please set it in the UI (API配置) and start a new execution."}),
+            error_event(
+                ErrorSeverity::Fatal,
+                "no_llm_configured",
+                "No LLM configured. Please set it in the UI (API配置) and start a new execution.",
+                None,
+            ),
             None,
             event_seq,
         );
-        execution.status = "failed".to_string();
+        execution.status = can_transition(&execution.status, "fail")?.to_string();
         execution.error_message = Some("No LLM configured".to_string());
         execution.updated_at = Utc::now();
         store.executions_upsert(&execution)?;
         return Ok(());
     };
 
-    let mut state: OrchestrationState =
-        serde_json::from_value(execution.shared_state.clone()).unwrap_or_default();
+    let mut state = OrchestrationState::from_shared_state(&execution.shared_state);
+    // Usage before this round, so the round's own share can be compared
+    // with a `plan_execution` estimate.
+    let round_start = (state.tokens_used, execution.cost);
+    let stop_on_stable = StopOnStable::from_mode_config(&team.mode_config);
     if state.topic.trim().is_empty() {
         state.topic = topic.clone();
     }
     state.start_new_round();
     state.topic = topic.clone();
+    state.context_limit = team
+        .context_window()
+        .or(store.settings_get()?.followup_context_limit);
+    let pricing = llm.default.resolve_pricing(&pricing_table(&store)?);
+    state.pricing = pricing;
+    state.dedupe_threshold = dedupe_threshold(&team.mode_config);
     let round_num = state.round;
 
-    let agents = build_agent_instances(&store, &team, &llm, target_agent_id.as_deref()).await?;
+    warm_up(&store, &team, &llm, target_agent_id.as_deref())?;
+    let mut agents = build_agent_instances(
+        &store,
+        &team,
+        &llm,
+        target_agent_id.as_deref(),
+        Some(metrics),
+    )
+    .await?;
+    let fallbacks = enable_streaming(&mut agents, execution.stream);
+    // One notice per execution: follow-up rounds fall back the same way.
+    if execution.current_round == 0 && !fallbacks.is_empty() {
+        let names = fallbacks
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .collect::<Vec<_>>()
+            .join("、");
+        emit_event(
+            &window,
+            &execution_id,
+            "error",
+            error_event(
+                ErrorSeverity::Warning,
+                "streaming_unsupported",
+                format!("{names} 的模型不支持流式输出，已改为普通请求"),
+                None,
+            ),
+            None,
+            event_seq,
+        );
+    }
+
+    let appearances = agents
+        .iter()
+        .map(|agent| (agent.id.clone(), agent.appearance()))
+        .collect::<std::collections::HashMap<_, _>>();
 
     let mut msg_seq = store.execution_messages_next_sequence(&execution_id)?;
+    let ordered = team.ordered_messages();
+    let message_lock = store.execution_message_lock(&execution_id);
+    let lock_messages = || ordered.then(|| message_lock.lock().unwrap_or_else(|e| e.into_inner()));
 
     // persist + emit user message first
     let now = Utc::now();
-    let user_message = ExecutionMessage {
+    let order_guard = lock_messages();
+    let mut user_message = ExecutionMessage {
         id: Uuid::new_v4().to_string(),
         sequence: msg_seq,
         round: state.round,
@@ -462,7 +1479,7 @@ async fn run_round(
         created_at: now,
         updated_at: now,
     };
-    store.execution_messages_upsert(&execution_id, &user_message)?;
+    persist_message(&store, &execution_id, &mut user_message, ordered)?;
     emit_event(
         &window,
         &execution_id,
@@ -477,11 +1494,29 @@ async fn run_round(
         None,
         event_seq,
     );
-    msg_seq += 1;
+    drop(order_guard);
+    msg_seq = user_message.sequence + 1;
 
+    let mut usage = LiveUsage::new(&execution, pricing, LIVE_USAGE_INTERVAL);
+    // Latest opinion message per agent, so a reply's `target_agent_id` can be
+    // turned into the `responding_to` message id.
+    let mut latest_opinions = std::collections::HashMap::<String, String>::new();
     let mut emit =
         |event_type: &str, mut data: Value, agent_id: Option<String>| -> Result<(), AppError> {
+            if event_type == "checkpoint" {
+                // Kept for resuming the run; the UI has no use for it.
+                store.executions_update(&execution_id, |execution| {
+                    execution.shared_state = data;
+                    Ok(())
+                })?;
+                return Ok(());
+            }
+            // Held until the event is emitted below.
+            let _order_guard = lock_messages();
             if event_type == "opinion" {
+                if let Some(appearance) = agent_id.as_ref().and_then(|id| appearances.get(id)) {
+                    add_appearance(&mut data, appearance);
+                }
                 let agent_name = data
                     .get("agent_name")
                     .and_then(|v| v.as_str())
@@ -502,7 +1537,8 @@ async fn run_round(
                     .and_then(|v| v.as_u64())
                     .or_else(|| {
                         data.get("metadata")
-                            .and_then(|m| m.get("input_tokens"))
+                            .and_then(|m| m.get("tokens"))
+                            .and_then(|t| t.get("input"))
                             .and_then(|v| v.as_u64())
                     })
                     .unwrap_or(0)
@@ -512,7 +1548,8 @@ async fn run_round(
                     .and_then(|v| v.as_u64())
                     .or_else(|| {
                         data.get("metadata")
-                            .and_then(|m| m.get("output_tokens"))
+                            .and_then(|m| m.get("tokens"))
+                            .and_then(|t| t.get("output"))
                             .and_then(|v| v.as_u64())
                     })
                     .unwrap_or(0)
@@ -522,11 +1559,25 @@ async fn run_round(
                     .and_then(|v| v.as_bool())
                     .or_else(|| {
                         data.get("metadata")
-                            .and_then(|m| m.get("tokens_estimated"))
+                            .and_then(|m| m.get("tokens"))
+                            .and_then(|t| t.get("estimated"))
                             .and_then(|v| v.as_bool())
                     })
                     .unwrap_or(false);
-                let message = ExecutionMessage {
+                let target_agent_id = data
+                    .get("target_agent_id")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string());
+                let responding_to = data
+                    .get("responding_to")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+                    .or_else(|| {
+                        target_agent_id
+                            .as_ref()
+                            .and_then(|id| latest_opinions.get(id).cloned())
+                    });
+                let mut message = ExecutionMessage {
                     id: Uuid::new_v4().to_string(),
                     sequence: msg_seq,
                     round,
@@ -540,14 +1591,8 @@ async fn run_round(
                     sender_name: Some(agent_name.to_string()),
                     content: content.to_string(),
                     content_type: "text".to_string(),
-                    responding_to: data
-                        .get("responding_to")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string()),
-                    target_agent_id: data
-                        .get("target_agent_id")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string()),
+                    responding_to,
+                    target_agent_id,
                     wants_to_continue: data
                         .get("wants_to_continue")
                         .and_then(|v| v.as_bool())
@@ -562,15 +1607,29 @@ async fn run_round(
                     created_at: now,
                     updated_at: now,
                 };
-                store.execution_messages_upsert(&execution_id, &message)?;
+                persist_message(&store, &execution_id, &mut message, ordered)?;
+                usage.add(&message);
+                if let Some(id) = &agent_id {
+                    latest_opinions.insert(id.clone(), message.id.clone());
+                }
                 if let Some(obj) = data.as_object_mut() {
+                    obj.insert(
+                        "responding_to".to_string(),
+                        serde_json::json!(message.responding_to),
+                    );
+                    let finish_reason = message
+                        .metadata
+                        .get("finish_reason")
+                        .cloned()
+                        .unwrap_or(Value::Null);
+                    obj.entry("finish_reason").or_insert(finish_reason);
                     obj.insert("message_id".to_string(), Value::String(message.id.clone()));
                     obj.insert(
                         "message_sequence".to_string(),
                         serde_json::json!(message.sequence),
                     );
                 }
-                msg_seq += 1;
+                msg_seq = message.sequence + 1;
             } else if event_type == "tool_call" || event_type == "tool_result" {
                 let tool_name = data
                     .get("tool_name")
@@ -587,7 +1646,7 @@ async fn run_round(
                     .unwrap_or(event_type)
                     .to_string();
                 let now = Utc::now();
-                let message = ExecutionMessage {
+                let mut message = ExecutionMessage {
                     id: Uuid::new_v4().to_string(),
                     sequence: msg_seq,
                     round,
@@ -607,7 +1666,13 @@ async fn run_round(
                     created_at: now,
                     updated_at: now,
                 };
-                store.execution_messages_upsert(&execution_id, &message)?;
+                persist_message(&store, &execution_id, &mut message, ordered)?;
+                // Calls the tool_policy refused never ran, so don't count them.
+                if event_type == "tool_result"
+                    && data.get("denied").and_then(|v| v.as_bool()) != Some(true)
+                {
+                    usage.add_tool_call();
+                }
                 if let Some(obj) = data.as_object_mut() {
                     obj.insert("message_id".to_string(), Value::String(message.id.clone()));
                     obj.insert(
@@ -615,17 +1680,39 @@ async fn run_round(
                         serde_json::json!(message.sequence),
                     );
                 }
-                msg_seq += 1;
+                msg_seq = message.sequence + 1;
+            } else if event_type == "stage_error"
+                && data.get("policy").and_then(|v| v.as_str()) == Some("retry")
+            {
+                metrics.record_stage_retry();
+            } else if event_type == "progress" {
+                let eta_ms = data.get("eta_ms").and_then(|v| v.as_u64());
+                usage.flush(&store, eta_ms)?;
+            } else if event_type == "status" {
+                persist_current_stage(&store, &execution_id, &data)?;
             }
 
+            let rate_limit = (event_type == "opinion")
+                .then(|| rate_limit_warning(&data))
+                .flatten();
             emit_event(
                 &window,
                 &execution_id,
                 event_type,
                 data,
-                agent_id,
+                agent_id.clone(),
                 event_seq,
             );
+            if let Some(warning) = rate_limit {
+                emit_event(
+                    &window,
+                    &execution_id,
+                    "rate_limit",
+                    warning,
+                    agent_id,
+                    event_seq,
+                );
+            }
             Ok(())
         };
 
@@ -637,10 +1724,18 @@ async fn run_round(
         .map(|s| s.trim())
         .filter(|s| !s.is_empty())
     {
-        match ToolExecutor::new(std::path::PathBuf::from(path)) {
+        match workspace_executor(path, &execution.workspace_roots) {
             Ok(exec) => {
+                if execution.workspace_baseline.is_none() {
+                    execution.workspace_baseline =
+                        record_workspace_baseline(&store, &execution_id, path)?;
+                }
+                let exec = exec
+                    .with_commands(team.allow_commands())
+                    .with_strict_path_case(team.strict_path_case())
+                    .with_policy(execution.tool_policy.clone());
                 tool_defs = exec.definitions();
-                tool_executor = Some(exec);
+                tool_executor = Some(exec.with_locks(locks).with_metrics(metrics.clone()));
             }
             Err(e) => {
                 emit(
@@ -657,50 +1752,91 @@ async fn run_round(
     }
 
     // Choose orchestrator
-    match team.collaboration_mode.as_str() {
+    let first_opinion = state.opinions.len();
+    let mut agents = match team.collaboration_mode.as_str() {
         "pipeline" => {
             state.phase = crate::orchestration::state::OrchestrationPhase::Sequential;
-            let _ = run_pipeline(
+            run_pipeline(
                 agents,
                 &mut state,
                 &mut emit,
+                StageErrorPolicy::from_mode_config(&team.mode_config),
                 tool_defs.as_slice(),
                 tool_executor.clone(),
             )
-            .await?;
+            .await?
         }
         "debate" => {
-            let _ = run_debate(
+            run_debate(
                 agents,
                 &mut state,
                 &mut emit,
                 3,
+                DebateFormat::from_mode_config(&team.mode_config),
                 tool_defs.as_slice(),
                 tool_executor.clone(),
             )
-            .await?;
+            .await?
         }
         _ => {
-            let _ = run_roundtable(
+            run_roundtable(
                 agents,
                 &mut state,
                 &mut emit,
                 true,
+                InitialOrder::from_mode_config(&team.mode_config),
                 tool_defs.as_slice(),
                 tool_executor.clone(),
             )
-            .await?;
+            .await?
         }
+    };
+    if let Some(critic) = CriticConfig::from_mode_config(&team.mode_config) {
+        run_critic(&mut agents, &critic, first_opinion, &mut state, &mut emit).await?;
     }
 
-    // Save execution state
-    execution.status = "completed".to_string();
-    execution.completed_at = Some(Utc::now());
-    execution.current_round = state.round;
-    execution.tokens_used = state.tokens_used;
-    execution.shared_state = serde_json::to_value(&state).unwrap_or_else(|_| serde_json::json!({}));
-    execution.updated_at = Utc::now();
-    store.executions_upsert(&execution)?;
+    let history = if state.pruned_opinions > 0 {
+        store.execution_messages_list(&execution_id)?
+    } else {
+        Vec::new()
+    };
+    let opinions = state.full_opinions(&history);
+    state.converged = stop_on_stable.and_then(|stable| stable.converged(&opinions, state.round));
+    if let (Some(stable), Some(similarity)) = (stop_on_stable, state.converged) {
+        emit_event(
+            &window,
+            &execution_id,
+            "converged",
+            stable.event(state.round, similarity),
+            None,
+            event_seq,
+        );
+    }
+    let structured = state.structured_output(&opinions);
+    let final_output = state
+        .final_output(&opinions)
+        .map(|output| OutputFormat::from_rules(&team.output_rules).render(&output, &structured));
+    state.prune_opinions(MAX_RETAINED_OPINIONS);
+
+    let completed = complete_round(&store, &execution_id, |execution| {
+        execution.current_round = state.round;
+        execution.tokens_used = state.tokens_used;
+        execution.cost = usage.cost;
+        execution.tool_calls = usage.tool_calls;
+        if let Some(estimate) = execution.estimate.as_mut() {
+            estimate.record_round(
+                state.round,
+                state.tokens_used.saturating_sub(round_start.0),
+                usage.cost - round_start.1,
+            );
+        }
+        execution.final_output = final_output;
+        execution.structured_output = Some(structured);
+        execution.shared_state = state.to_shared_state();
+    })?;
+    if !completed {
+        return Ok(());
+    }
 
     emit_event(
         &window,
@@ -714,34 +1850,159 @@ async fn run_round(
     Ok(())
 }
 
+/// Snapshot the workspace before its first round runs, as the baseline
+/// `get_workspace_diff` compares against, and return the stored baseline.
+/// A workspace that can't be read is left without one; tool calls will
+/// report the problem.
+fn record_workspace_baseline(
+    store: &crate::store::sqlite::SqliteStore,
+    execution_id: &str,
+    path: &str,
+) -> Result<Option<workspace_diff::WorkspaceSnapshot>, AppError> {
+    let Ok(snapshot) =
+        workspace_diff::snapshot(Path::new(path), workspace_diff::MAX_SNAPSHOT_FILES)
+    else {
+        return Ok(None);
+    };
+    let stored = store.executions_update(execution_id, |execution| {
+        execution.workspace_baseline.get_or_insert(snapshot);
+        Ok(())
+    })?;
+    Ok(stored.workspace_baseline)
+}
+
+/// Save a finished round as `completed` with `apply`'s changes, unless a stop
+/// or failure landed during the round: that record and any partial output
+/// stand. Returns whether the round was saved.
+fn complete_round(
+    store: &crate::store::sqlite::SqliteStore,
+    execution_id: &str,
+    apply: impl FnOnce(&mut ExecutionRecord),
+) -> Result<bool, AppError> {
+    let mut saved = false;
+    store.executions_update(execution_id, |execution| {
+        // A paused run still completes; a stopped or failed one can't.
+        let Ok(next) = can_transition(&execution.status, "complete") else {
+            return Ok(());
+        };
+        execution.status = next.to_string();
+        execution.current_stage = None;
+        execution.eta_ms = None;
+        execution.completed_at = Some(Utc::now());
+        apply(execution);
+        execution.updated_at = Utc::now();
+        saved = true;
+        Ok(())
+    })?;
+    Ok(saved)
+}
+
+/// Resolve every active member's model config and construct its provider
+/// before the round starts, reporting all problems at once instead of failing
+/// on the first (or partway through a round).
+fn warm_up(
+    store: &crate::store::sqlite::SqliteStore,
+    team: &Team,
+    llm: &crate::models::llm::ExecutionLLMConfig,
+    target_agent_id: Option<&str>,
+) -> Result<(), AppError> {
+    let mut problems = Vec::new();
+    for member in team.active_members() {
+        if target_agent_id.is_some_and(|target| member.agent_id != target) {
+            continue;
+        }
+        let Some(agent) = store.agents_get(&member.agent_id)? else {
+            continue;
+        };
+        let checked = resolve_runtime_config_for_agent(agent.model_id.as_deref(), llm)
+            .and_then(|cfg| provider_from_runtime_config(&cfg));
+        if let Err(e) = checked {
+            problems.push(format!("- {}（{}）: {e}", agent.name, agent.id));
+        }
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(AppError::Message(format!(
+        "{} 个 Agent 的模型配置有问题，请修正后再开始：\n{}",
+        problems.len(),
+        problems.join("\n")
+    )))
+}
+
+/// Apply `AppSettings::allowed_workspace_roots` to an execution's workspace
+/// and every extra root it mounts.
+pub(crate) fn check_workspaces(
+    settings: &AppSettings,
+    path: Option<&str>,
+    roots: &std::collections::BTreeMap<String, String>,
+) -> Result<(), AppError> {
+    path.into_iter()
+        .chain(roots.values().map(String::as_str))
+        .filter(|p| !p.trim().is_empty())
+        .try_for_each(|p| settings.check_workspace(p))
+}
+
+/// The tool executor for an execution's workspace and any extra named roots.
+fn workspace_executor(
+    path: &str,
+    roots: &std::collections::BTreeMap<String, String>,
+) -> Result<ToolExecutor, AppError> {
+    let mut exec = ToolExecutor::new(std::path::PathBuf::from(path))?;
+    for (name, root) in roots {
+        exec = exec.with_root(name, std::path::PathBuf::from(root))?;
+    }
+    Ok(exec)
+}
+
 async fn build_agent_instances(
     store: &crate::store::sqlite::SqliteStore,
     team: &Team,
     llm: &crate::models::llm::ExecutionLLMConfig,
     target_agent_id: Option<&str>,
+    metrics: Option<&std::sync::Arc<Metrics>>,
 ) -> Result<Vec<AgentInstance>, AppError> {
-    let mut members = team.members.clone();
-    members.sort_by_key(|m| m.position);
-    let agent_ids = members
-        .iter()
-        .filter(|m| m.is_active)
-        .map(|m| m.agent_id.clone())
-        .collect::<Vec<_>>();
-
+    // One limit for the whole round, so a 429 seen by any agent slows them all.
+    let mut concurrency = AdaptiveConcurrency::from_mode_config(&team.mode_config);
+    if let Some(metrics) = metrics {
+        concurrency = concurrency.with_metrics(metrics.clone());
+    }
+    let concurrency = std::sync::Arc::new(concurrency);
+    let system_prompt_limit = store.settings_get()?.system_prompt_limit();
     let mut instances = Vec::new();
-    for agent_id in agent_ids {
+    for member in team.active_members() {
         if let Some(target) = target_agent_id {
-            if agent_id != target {
+            if member.agent_id != target {
                 continue;
             }
         }
-        let Some(agent) = store.agents_get(&agent_id)? else {
+        let Some(agent) = store.agents_get(&member.agent_id)? else {
             continue;
         };
 
         let cfg = resolve_runtime_config_for_agent(agent.model_id.as_deref(), llm)?;
-        let provider = provider_from_runtime_config(&cfg)?;
-        instances.push(AgentInstance::from_agent(&agent, provider));
+        let mut provider = provider_from_runtime_config(&cfg)?;
+        if let Some(metrics) = metrics {
+            provider = with_metrics(provider, metrics.clone());
+        }
+        let provider = with_concurrency(provider, concurrency.clone());
+        let mut instance = AgentInstance::from_agent(&agent, provider);
+        instance.limit_system_prompt(system_prompt_limit);
+        if let Some(kb_id) = agent
+            .knowledge_base_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        {
+            instance.knowledge = store.knowledge_docs_list(kb_id)?;
+        }
+        instance.max_opinion_chars = team.max_opinion_chars();
+        instance.instructions = PromptInstructions::from_mode_config(&team.mode_config);
+        instance.apply_config_override(&member.config_override);
+        if let Some(priority) = member.priority_override {
+            instance.speaking_priority = priority;
+        }
+        instances.push(instance);
     }
 
     if instances.is_empty() {
@@ -751,8 +2012,171 @@ async fn build_agent_instances(
     Ok(instances)
 }
 
+/// Running token/cost/tool-call totals and ETA for an execution, written back to its
+/// record at most once per `interval` so polling clients see progress mid-round.
+struct LiveUsage {
+    execution_id: String,
+    tokens_used: u32,
+    cost: f64,
+    tool_calls: u32,
+    pricing: crate::models::llm::Pricing,
+    interval: std::time::Duration,
+    last_flush: Option<std::time::Instant>,
+}
+
+impl LiveUsage {
+    fn new(
+        execution: &ExecutionRecord,
+        pricing: crate::models::llm::Pricing,
+        interval: std::time::Duration,
+    ) -> Self {
+        Self {
+            execution_id: execution.id.clone(),
+            tokens_used: execution.tokens_used,
+            cost: execution.cost,
+            tool_calls: execution.tool_calls,
+            pricing,
+            interval,
+            last_flush: None,
+        }
+    }
+
+    fn add(&mut self, message: &ExecutionMessage) {
+        self.tokens_used = self.tokens_used.saturating_add(message.billed_tokens());
+        self.cost += message.cost(&self.pricing);
+    }
+
+    fn add_tool_call(&mut self) {
+        self.tool_calls = self.tool_calls.saturating_add(1);
+    }
+
+    /// Write the running totals and ETA, unless the last write was too recent.
+    fn flush(
+        &mut self,
+        store: &crate::store::sqlite::SqliteStore,
+        eta_ms: Option<u64>,
+    ) -> Result<(), AppError> {
+        if self
+            .last_flush
+            .is_some_and(|at| at.elapsed() < self.interval)
+        {
+            return Ok(());
+        }
+        self.last_flush = Some(std::time::Instant::now());
+        let (tokens_used, cost, tool_calls) = (self.tokens_used, self.cost, self.tool_calls);
+        store.executions_update(&self.execution_id, |execution| {
+            execution.tokens_used = tokens_used;
+            execution.cost = cost;
+            execution.tool_calls = tool_calls;
+            execution.eta_ms = eta_ms;
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
+/// A `rate_limit` event for an opinion whose provider reported less than
+/// `LOW_RATE_LIMIT_FRACTION` of its request or token budget left.
+fn rate_limit_warning(opinion: &Value) -> Option<Value> {
+    let info: RateLimitInfo =
+        serde_json::from_value(opinion.get("metadata")?.get("rate_limit")?.clone()).ok()?;
+    if !info.is_low() {
+        return None;
+    }
+    let agent_name = opinion
+        .get("agent_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("agent");
+    let mut data = serde_json::to_value(&info).ok()?;
+    data["message"] = Value::String(format!(
+        "{agent_name} 使用的模型接近速率限制，剩余额度约 {:.0}%",
+        info.remaining_fraction().unwrap_or(0.0) * 100.0
+    ));
+    data["remaining_fraction"] = serde_json::json!(info.remaining_fraction());
+    data["model"] = opinion["metadata"]["model"].clone();
+    Some(data)
+}
+
+/// Copy an agent's `avatar` and `color` onto its opinion event, both at the
+/// top level and in the `metadata` persisted with the message.
+fn add_appearance(data: &mut Value, appearance: &Value) {
+    let Some(obj) = data.as_object_mut() else {
+        return;
+    };
+    let metadata = obj
+        .entry("metadata")
+        .or_insert_with(|| serde_json::json!({}));
+    if !metadata.is_object() {
+        *metadata = serde_json::json!({});
+    }
+    for key in ["avatar", "color"] {
+        metadata[key] = appearance[key].clone();
+    }
+    for key in ["avatar", "color"] {
+        obj.insert(key.to_string(), appearance[key].clone());
+    }
+}
+
+/// Save a message written during a round. With `ordered`, the caller holds
+/// the execution's message lock and the store allocates the sequence, so
+/// writers outside this round can't take the same one.
+fn persist_message(
+    store: &crate::store::sqlite::SqliteStore,
+    execution_id: &str,
+    message: &mut ExecutionMessage,
+    ordered: bool,
+) -> Result<(), AppError> {
+    if ordered {
+        store.execution_messages_append(execution_id, message)
+    } else {
+        store.execution_messages_upsert(execution_id, message)
+    }
+}
+
+/// Store the pipeline stage a `status` event announces (or clears with
+/// `null`) as the execution's `current_stage`, for clients that poll.
+fn persist_current_stage(
+    store: &crate::store::sqlite::SqliteStore,
+    execution_id: &str,
+    data: &Value,
+) -> Result<(), AppError> {
+    let Some(stage) = data.get("current_stage") else {
+        return Ok(());
+    };
+    let stage = stage.as_str().map(str::to_string);
+    store.executions_update(execution_id, |execution| {
+        execution.current_stage = stage.clone();
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Atomically apply `action` to an execution's status. Fails without writing
+/// if the current status doesn't allow it, e.g. a follow-up while one is running.
+fn claim_status(
+    store: &crate::store::sqlite::SqliteStore,
+    execution_id: &str,
+    action: &str,
+) -> Result<ExecutionRecord, AppError> {
+    store.executions_update(execution_id, |execution| {
+        if action == "followup" && execution.status == "running" {
+            return Err(AppError::Message(
+                "A follow-up is already in progress for this execution".to_string(),
+            ));
+        }
+        execution.status = can_transition(&execution.status, action)?.to_string();
+        execution.updated_at = Utc::now();
+        Ok(())
+    })
+}
+
+/// Payload for the `error` event sent when a run stops on `err`.
+fn error_event_data(err: &AppError) -> Value {
+    app_error_event(ErrorSeverity::Fatal, err, err.to_string(), None)
+}
+
 fn emit_event(
-    window: &Window,
+    window: &impl EventSink,
     execution_id: &str,
     event_type: &str,
     data: Value,
@@ -767,5 +2191,1395 @@ fn emit_event(
         agent_id,
         sequence: *seq,
     };
-    let _ = window.emit(EVENT_NAME, payload);
+    window.send(payload);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestration::state::OrchestrationPhase;
+    use crate::store::sqlite::SqliteStore;
+
+    fn record(id: &str, status: &str) -> ExecutionRecord {
+        let now = Utc::now();
+        ExecutionRecord {
+            id: id.to_string(),
+            user_id: LOCAL_USER_ID.to_string(),
+            team_id: "team".to_string(),
+            title: None,
+            initial_input: "topic".to_string(),
+            llm: None,
+            status: status.to_string(),
+            current_stage: None,
+            current_round: 1,
+            shared_state: serde_json::json!({}),
+            agent_states: serde_json::json!({}),
+            final_output: None,
+            structured_output: None,
+            tokens_used: 0,
+            tokens_budget: 1_000,
+            cost: 0.0,
+            cost_budget: 1.0,
+            estimate: None,
+            eta_ms: None,
+            started_at: None,
+            completed_at: None,
+            error_message: None,
+            retry_count: 0,
+            tool_calls: 0,
+            workspace_path: None,
+            workspace_roots: Default::default(),
+            workspace_baseline: None,
+            tool_policy: Default::default(),
+            stream: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn team() -> Team {
+        serde_json::from_value(serde_json::json!({
+            "id": "team",
+            "user_id": LOCAL_USER_ID,
+            "name": "Team",
+            "description": null,
+            "icon": null,
+            "collaboration_mode": "roundtable",
+            "coordinator_id": null,
+            "is_template": false,
+            "is_public": false,
+            "usage_count": 0,
+            "rating": 0.0,
+            "rating_count": 0,
+            "created_at": Utc::now(),
+            "updated_at": Utc::now()
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn response_reflects_current_team_mode_and_survives_team_deletion() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut team = team();
+        store.teams_upsert(&team).unwrap();
+
+        let response = execution_response(&store, record("e1", "completed"), Vec::new()).unwrap();
+        assert_eq!(response.collaboration_mode, "roundtable");
+
+        team.collaboration_mode = "debate".to_string();
+        store.teams_upsert(&team).unwrap();
+        let response = execution_response(&store, record("e1", "completed"), Vec::new()).unwrap();
+        assert_eq!(response.collaboration_mode, "debate");
+
+        store.teams_delete("team").unwrap();
+        let response = execution_response(&store, record("e1", "completed"), Vec::new()).unwrap();
+        assert_eq!(response.collaboration_mode, UNKNOWN_COLLABORATION_MODE);
+    }
+
+    #[test]
+    fn omitted_title_is_derived_from_the_input() {
+        assert_eq!(
+            execution_title(None, "  Pick a\n  database\tfor us "),
+            Some("Pick a database for us".to_string())
+        );
+        let long = "为".repeat(DERIVED_TITLE_CHARS + 5);
+        let derived = execution_title(Some("  ".to_string()), &long).unwrap();
+        assert_eq!(derived, format!("{}…", "为".repeat(DERIVED_TITLE_CHARS)));
+        assert_eq!(execution_title(None, " \n "), None);
+
+        assert_eq!(
+            execution_title(Some("Mine".to_string()), "Pick a database"),
+            Some("Mine".to_string())
+        );
+    }
+
+    #[test]
+    fn response_compares_the_stored_estimate_with_actual_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut execution = record("e1", "pending");
+        let mut estimate = CostEstimate {
+            tokens: 2_000,
+            cost: 0.02,
+            round: 2,
+            ..CostEstimate::default()
+        };
+        execution.estimate = Some(estimate);
+
+        let response = execution_response(&store, execution.clone(), Vec::new()).unwrap();
+        assert_eq!(response.estimate_variance_pct, None);
+
+        // Round 1 isn't the estimated round; round 2's share is compared,
+        // not the execution's running totals.
+        estimate.record_round(1, 4_000, 0.04);
+        assert_eq!(estimate.variance_pct(), None);
+        estimate.record_round(2, 3_000, 0.025);
+        execution.status = "completed".to_string();
+        execution.tokens_used = 7_000;
+        execution.cost = 0.065;
+        execution.estimate = Some(estimate);
+        let response = execution_response(&store, execution.clone(), Vec::new()).unwrap();
+        assert_eq!(response.estimate.unwrap().tokens, 2_000);
+        assert!((response.estimate_variance_pct.unwrap() - 25.0).abs() < 1e-9);
+
+        // Unpriced estimates fall back to comparing tokens.
+        estimate.cost = 0.0;
+        execution.estimate = Some(estimate);
+        let response = execution_response(&store, execution, Vec::new()).unwrap();
+        assert!((response.estimate_variance_pct.unwrap() - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn response_surfaces_phase_and_round_from_shared_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+
+        let response = execution_response(&store, record("e1", "pending"), Vec::new()).unwrap();
+        assert_eq!(
+            (response.phase.as_str(), response.round),
+            ("initializing", 0)
+        );
+
+        let mut state = OrchestrationState {
+            topic: "Pick a database".to_string(),
+            phase: OrchestrationPhase::Responding,
+            ..Default::default()
+        };
+        state.start_new_round();
+        state.start_new_round();
+        let mut running = record("e1", "running");
+        running.shared_state = state.to_shared_state();
+        let response = execution_response(&store, running, Vec::new()).unwrap();
+        assert_eq!((response.phase.as_str(), response.round), ("responding", 2));
+    }
+
+    #[test]
+    fn only_one_of_two_simultaneous_followups_proceeds() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = std::sync::Arc::new(SqliteStore::open_at(dir.path().join("app.db")).unwrap());
+        store.executions_upsert(&record("e1", "completed")).unwrap();
+
+        let barrier = std::sync::Arc::new(std::sync::Barrier::new(2));
+        let handles = (0..2)
+            .map(|_| {
+                let store = store.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    claim_status(&store, "e1", "followup")
+                })
+            })
+            .collect::<Vec<_>>();
+        let results = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+        let err = results.into_iter().find_map(Result::err).unwrap();
+        assert!(err.to_string().contains("already in progress"));
+        assert_eq!(
+            store.executions_get("e1").unwrap().unwrap().status,
+            "running"
+        );
+    }
+
+    #[test]
+    fn ordered_writers_persist_messages_in_emission_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = std::sync::Arc::new(SqliteStore::open_at(dir.path().join("app.db")).unwrap());
+        let emitted = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let handles = (0..8)
+            .map(|i| {
+                let store = store.clone();
+                let emitted = emitted.clone();
+                std::thread::spawn(move || {
+                    let lock = store.execution_message_lock("e1");
+                    let _guard = lock.lock().unwrap();
+                    // Every writer starts from the same stale sequence.
+                    let mut message = agent_message(1, "A", "hi");
+                    message.id = format!("m{i}");
+                    persist_message(&store, "e1", &mut message, true).unwrap();
+                    emitted.lock().unwrap().push(message.id);
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let persisted = store.execution_messages_list("e1").unwrap();
+        let ids = persisted.iter().map(|m| m.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids, *emitted.lock().unwrap());
+        let sequences = persisted.iter().map(|m| m.sequence).collect::<Vec<_>>();
+        assert_eq!(sequences, (1..=8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn active_list_tracks_running_executions() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        store
+            .executions_upsert(&record("done", "completed"))
+            .unwrap();
+        store
+            .executions_upsert(&record("queued", "pending"))
+            .unwrap();
+        let mut older = record("older", "running");
+        older.started_at = Some(Utc::now() - chrono::Duration::seconds(30));
+        older.shared_state = serde_json::json!({ "phase": "responding", "round": 2 });
+        store.executions_upsert(&older).unwrap();
+        let mut newer = record("newer", "running");
+        newer.started_at = Some(Utc::now());
+        store.executions_upsert(&newer).unwrap();
+
+        let active = active_executions(&store).unwrap();
+        let ids = active.iter().map(|e| e.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["older", "newer"]);
+        assert_eq!(
+            (active[0].phase.as_str(), active[0].round),
+            ("responding", 2)
+        );
+        assert!(active[0].elapsed_ms >= 30_000);
+        assert_eq!(active[1].phase, "initializing");
+
+        store
+            .executions_update("older", |e| {
+                e.status = "completed".to_string();
+                Ok(())
+            })
+            .unwrap();
+        let active = active_executions(&store).unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].id, "newer");
+    }
+
+    #[test]
+    fn rejected_claim_leaves_record_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        store.executions_upsert(&record("e1", "failed")).unwrap();
+
+        assert!(claim_status(&store, "e1", "followup").is_err());
+        assert_eq!(
+            store.executions_get("e1").unwrap().unwrap().status,
+            "failed"
+        );
+        assert!(claim_status(&store, "missing", "followup").is_err());
+    }
+
+    #[test]
+    fn exports_never_contain_provider_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut execution = record("e1", "completed");
+        let key = |secret: &str| {
+            let mut config = priced_llm();
+            config.api_key = secret.to_string();
+            config
+        };
+        execution.llm = Some(crate::models::llm::ExecutionLLMConfig {
+            default: key("sk-default-secret"),
+            models: [("gpt-4o".to_string(), key("sk-model-secret"))]
+                .into_iter()
+                .collect(),
+        });
+        store.executions_upsert(&execution).unwrap();
+
+        for sanitize in [false, true] {
+            let out = export_value(&store, "e1", sanitize).unwrap();
+            let text = out.to_string();
+            assert!(!text.contains("secret"), "{text}");
+            assert_eq!(out["execution"]["llm"]["default"]["api_key"], "<redacted>");
+            assert_eq!(out["execution"]["llm"]["default"]["model_id"], "m");
+        }
+    }
+
+    fn priced_llm() -> crate::models::llm::LLMRuntimeConfig {
+        serde_json::from_value(serde_json::json!({
+            "model_id": "m",
+            "api_key": "k",
+            "input_price_per_1k": 1.0,
+            "output_price_per_1k": 2.0
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn live_usage_persists_completed_opinions_mid_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let execution = record("e1", "running");
+        store.executions_upsert(&execution).unwrap();
+
+        let mut usage = LiveUsage::new(
+            &execution,
+            priced_llm().pricing(),
+            std::time::Duration::ZERO,
+        );
+        let mut message = agent_message(1, "A", "hi");
+        (message.input_tokens, message.output_tokens) = (100, 50);
+        usage.add(&message);
+        usage.flush(&store, None).unwrap();
+        let persisted = store.executions_get("e1").unwrap().unwrap();
+        assert_eq!(persisted.tokens_used, 150);
+        assert!((persisted.cost - 0.2).abs() < 1e-9);
+        assert_eq!(persisted.status, "running");
+        assert_eq!(persisted.eta_ms, None);
+
+        (message.input_tokens, message.output_tokens) = (10, 10);
+        message.metadata = serde_json::json!({ "cached": { "read": 1000, "write": 0 } });
+        usage.add(&message);
+        usage.flush(&store, Some(4_000)).unwrap();
+        let persisted = store.executions_get("e1").unwrap().unwrap();
+        // Cache reads count toward the budget and bill at the input price.
+        assert_eq!(persisted.tokens_used, 1_170);
+        assert!((persisted.cost - 1.23).abs() < 1e-9);
+        assert_eq!(persisted.eta_ms, Some(4_000));
+    }
+
+    #[test]
+    fn search_matches_title_or_input_case_insensitively() {
+        let mut titled = record("e1", "completed");
+        titled.title = Some("Database Migration".to_string());
+        let mut described = record("e2", "completed");
+        described.initial_input = "Should we adopt SQLite for caching?".to_string();
+        let other = record("e3", "completed");
+
+        let found = |needle: &str| {
+            [&titled, &described, &other]
+                .into_iter()
+                .filter(|e| matches_search(e, &needle.to_lowercase()))
+                .map(|e| e.id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(found("migration"), ["e1"]);
+        assert_eq!(found("SQLITE"), ["e2"]);
+        assert!(found("kubernetes").is_empty());
+    }
+
+    #[test]
+    fn tool_calls_mark_the_execution_as_using_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        for id in ["tools", "plain"] {
+            store.executions_upsert(&record(id, "running")).unwrap();
+        }
+
+        let mut with_tools = LiveUsage::new(
+            &record("tools", "running"),
+            priced_llm().pricing(),
+            std::time::Duration::ZERO,
+        );
+        let message = agent_message(1, "A", "hi");
+        with_tools.add(&message);
+        with_tools.add_tool_call();
+        with_tools.add_tool_call();
+        with_tools.flush(&store, None).unwrap();
+        let mut without_tools = LiveUsage::new(
+            &record("plain", "running"),
+            priced_llm().pricing(),
+            std::time::Duration::ZERO,
+        );
+        without_tools.add(&message);
+        without_tools.flush(&store, None).unwrap();
+
+        let tools = store.executions_get("tools").unwrap().unwrap();
+        assert_eq!(tools.tool_calls, 2);
+        let response = ExecutionResponse::from_record(tools, "roundtable".to_string(), Vec::new());
+        assert!(response.used_tools);
+        assert_eq!(response.tool_calls, 2);
+        let plain = store.executions_get("plain").unwrap().unwrap();
+        assert!(!plain.used_tools());
+        assert_eq!(plain.tool_calls, 0);
+    }
+
+    #[tokio::test]
+    async fn policy_denied_tool_calls_are_not_counted() {
+        use crate::llm::mock::{self, MockProvider};
+        use crate::tools::definition::ToolCall;
+
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::write(workspace.path().join("notes.txt"), "hello").unwrap();
+        let store = std::sync::Arc::new(SqliteStore::open_at(dir.path().join("app.db")).unwrap());
+        store.agents_upsert(&mock::agent("a1", "Alice")).unwrap();
+        let mut team = serde_json::to_value(team()).unwrap();
+        team["members"] = serde_json::json!([{
+            "id": "m1",
+            "agent_id": "a1",
+            "role_override": null,
+            "priority_override": null,
+            "position": 0,
+            "is_active": true,
+            "created_at": Utc::now(),
+            "updated_at": Utc::now()
+        }]);
+        store
+            .teams_upsert(&serde_json::from_value(team).unwrap())
+            .unwrap();
+
+        // The default policy allows reads but not writes.
+        let call = |id: &str, name: &str, arguments: Value| ToolCall {
+            id: id.to_string(),
+            name: name.to_string(),
+            arguments,
+        };
+        let provider = MockProvider::scripted(
+            vec![mock::response(
+                "",
+                vec![
+                    call(
+                        "c1",
+                        "read_file",
+                        serde_json::json!({ "path": "notes.txt" }),
+                    ),
+                    call(
+                        "c2",
+                        "write_file",
+                        serde_json::json!({ "path": "out.txt", "content": "x" }),
+                    ),
+                ],
+            )],
+            "done",
+        );
+        mock::register("tool-count-model", provider);
+        let mut execution = record("e1", "pending");
+        execution.llm = Some(
+            serde_json::from_value(serde_json::json!({
+                "default": { "model_id": "tool-count-model", "api_key": "k" }
+            }))
+            .unwrap(),
+        );
+        execution.workspace_path = Some(workspace.path().to_string_lossy().to_string());
+        store.executions_upsert(&execution).unwrap();
+
+        let events = EventLog::default();
+        run_execution(
+            events.clone(),
+            store.clone(),
+            std::sync::Arc::new(WorkspaceLocks::default()),
+            std::sync::Arc::new(Metrics::default()),
+            "e1".to_string(),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let results = events.of_type("tool_result");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1]["denied"], true);
+        assert!(!workspace.path().join("out.txt").exists());
+        let stored = store.executions_get("e1").unwrap().unwrap();
+        assert_eq!(stored.status, "completed");
+        assert_eq!(stored.tool_calls, 1);
+    }
+
+    #[tokio::test]
+    async fn workspace_diff_reports_an_agents_rename_against_the_first_rounds_baseline() {
+        use crate::llm::mock::{self, MockProvider};
+        use crate::tools::definition::ToolCall;
+
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::write(workspace.path().join("draft.md"), "# Plan").unwrap();
+        std::fs::write(workspace.path().join("todo.txt"), "ship").unwrap();
+        let store = std::sync::Arc::new(SqliteStore::open_at(dir.path().join("app.db")).unwrap());
+        store.agents_upsert(&mock::agent("a1", "Alice")).unwrap();
+        let mut team = serde_json::to_value(team()).unwrap();
+        team["members"] = serde_json::json!([{
+            "id": "m1",
+            "agent_id": "a1",
+            "role_override": null,
+            "priority_override": null,
+            "position": 0,
+            "is_active": true,
+            "created_at": Utc::now(),
+            "updated_at": Utc::now()
+        }]);
+        store
+            .teams_upsert(&serde_json::from_value(team).unwrap())
+            .unwrap();
+
+        let provider = MockProvider::scripted(
+            vec![mock::response(
+                "",
+                vec![
+                    ToolCall {
+                        id: "c1".to_string(),
+                        name: "rename_file".to_string(),
+                        arguments: serde_json::json!({
+                            "old_path": "draft.md",
+                            "new_path": "plan.md"
+                        }),
+                    },
+                    ToolCall {
+                        id: "c2".to_string(),
+                        name: "write_file".to_string(),
+                        arguments: serde_json::json!({ "path": "todo.txt", "content": "wait" }),
+                    },
+                ],
+            )],
+            "done",
+        );
+        mock::register("workspace-diff-model", provider);
+        let mut execution = record("e1", "pending");
+        execution.llm = Some(
+            serde_json::from_value(serde_json::json!({
+                "default": { "model_id": "workspace-diff-model", "api_key": "k" }
+            }))
+            .unwrap(),
+        );
+        execution.workspace_path = Some(workspace.path().to_string_lossy().to_string());
+        execution.tool_policy = crate::tools::policy::ToolPolicy::unrestricted();
+        store.executions_upsert(&execution).unwrap();
+
+        run_execution(
+            EventLog::default(),
+            store.clone(),
+            std::sync::Arc::new(WorkspaceLocks::default()),
+            std::sync::Arc::new(Metrics::default()),
+            "e1".to_string(),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let baseline = store
+            .executions_get("e1")
+            .unwrap()
+            .unwrap()
+            .workspace_baseline
+            .unwrap();
+        assert_eq!(
+            baseline.keys().collect::<Vec<_>>(),
+            vec!["draft.md", "todo.txt"]
+        );
+        let changes = crate::commands::fs::workspace_changes(&store, "e1").unwrap();
+        let summary = changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.old_path.as_deref(), c.status.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("plan.md", Some("draft.md"), "renamed"),
+                ("todo.txt", None, "modified"),
+            ]
+        );
+
+        // Pointing the execution elsewhere drops the old baseline.
+        let elsewhere = tempfile::tempdir().unwrap();
+        let updated = update_workspace(
+            &store,
+            "e1",
+            Some(elsewhere.path().to_string_lossy().to_string()),
+            None,
+        )
+        .unwrap();
+        assert!(updated.workspace_baseline.is_none());
+    }
+
+    #[test]
+    fn responses_report_whether_an_llm_is_configured() {
+        let bare = record("bare", "pending");
+        assert!(!ExecutionListItem::from(&bare).llm_configured);
+        let response = ExecutionResponse::from_record(bare, "roundtable".to_string(), Vec::new());
+        assert!(!response.llm_configured);
+
+        let mut configured = record("configured", "pending");
+        configured.llm = Some(crate::models::llm::ExecutionLLMConfig {
+            default: priced_llm(),
+            models: Default::default(),
+        });
+        assert!(ExecutionListItem::from(&configured).llm_configured);
+        let response =
+            ExecutionResponse::from_record(configured, "roundtable".to_string(), Vec::new());
+        assert!(response.llm_configured);
+    }
+
+    #[test]
+    fn cost_breakdown_matches_the_charged_cost() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut execution = record("e1", "running");
+        execution.llm = Some(crate::models::llm::ExecutionLLMConfig {
+            default: priced_llm(),
+            models: Default::default(),
+        });
+        store.executions_upsert(&execution).unwrap();
+        assert!(cost_breakdown(&store, "e1")
+            .unwrap_err()
+            .to_string()
+            .contains("only available once it completes"));
+
+        let mut usage = LiveUsage::new(
+            &execution,
+            priced_llm().pricing(),
+            std::time::Duration::ZERO,
+        );
+        for (seq, sender, tokens) in [(1, "A", 300), (2, "B", 120), (3, "A", 40)] {
+            let mut message = agent_message(seq, sender, "hi");
+            (message.input_tokens, message.output_tokens) = (tokens, tokens / 2);
+            message.tokens_estimated = sender == "B";
+            if seq == 3 {
+                message.metadata = serde_json::json!({ "cached": { "read": 60, "write": 20 } });
+            }
+            store.execution_messages_upsert("e1", &message).unwrap();
+            usage.add(&message);
+        }
+        usage.flush(&store, None).unwrap();
+        let mut completed = store.executions_get("e1").unwrap().unwrap();
+        completed.status = "completed".to_string();
+        store.executions_upsert(&completed).unwrap();
+
+        let breakdown = cost_breakdown(&store, "e1").unwrap();
+        assert!((breakdown.total.cost - completed.cost).abs() < 1e-9);
+        let agents = breakdown
+            .by_agent
+            .iter()
+            .map(|l| (l.key.as_str(), l.exact_tokens, l.estimated_tokens))
+            .collect::<Vec<_>>();
+        assert_eq!(agents, [("a", 590, 0), ("b", 0, 180)]);
+    }
+
+    #[test]
+    fn live_usage_throttles_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let execution = record("e1", "running");
+        store.executions_upsert(&execution).unwrap();
+
+        let mut usage = LiveUsage::new(
+            &execution,
+            priced_llm().pricing(),
+            std::time::Duration::from_secs(3600),
+        );
+        let mut message = agent_message(1, "A", "hi");
+        (message.input_tokens, message.output_tokens) = (100, 0);
+        usage.add(&message);
+        usage.flush(&store, None).unwrap();
+        usage.add(&message);
+        usage.flush(&store, Some(1_000)).unwrap();
+        assert_eq!(
+            store.executions_get("e1").unwrap().unwrap().tokens_used,
+            100
+        );
+        assert_eq!(usage.tokens_used, 200);
+    }
+
+    fn agent_message(seq: i32, sender: &str, content: &str) -> ExecutionMessage {
+        let now = Utc::now();
+        ExecutionMessage {
+            id: format!("m{seq}"),
+            sequence: seq,
+            round: 1,
+            phase: "initial".to_string(),
+            sender_type: "agent".to_string(),
+            sender_id: Some(sender.to_lowercase()),
+            sender_name: Some(sender.to_string()),
+            content: content.to_string(),
+            content_type: "text".to_string(),
+            responding_to: None,
+            target_agent_id: None,
+            wants_to_continue: true,
+            input_tokens: 1,
+            output_tokens: 1,
+            tokens_estimated: false,
+            metadata: serde_json::json!({}),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn summary_counts_messages_without_their_bodies() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut execution = record("e1", "completed");
+        execution.final_output = Some("final answer".to_string());
+        store.executions_upsert(&execution).unwrap();
+        for (seq, round, phase) in [(1, 1, "initial"), (2, 1, "initial"), (3, 2, "response")] {
+            let mut message = agent_message(seq, "A", "secret message body");
+            (message.round, message.phase) = (round, phase.to_string());
+            store.execution_messages_upsert("e1", &message).unwrap();
+        }
+
+        let summary = execution_summary(&store, "e1").unwrap();
+        assert_eq!(summary.counts.message_count, 3);
+        assert_eq!(summary.counts.round_count, 2);
+        assert_eq!(summary.counts.phase_counts["initial"], 2);
+        assert_eq!(summary.counts.phase_counts["response"], 1);
+        assert_eq!(summary.final_output.as_deref(), Some("final answer"));
+        let json = serde_json::to_string(&summary).unwrap();
+        assert!(!json.contains("secret message body"));
+        assert!(!json.contains("recent_messages"));
+
+        let empty = store.execution_message_counts("missing").unwrap();
+        assert_eq!(empty, Default::default());
+    }
+
+    #[test]
+    fn replies_resolve_to_the_sequence_they_answer() {
+        let mut reply = agent_message(3, "Alice", "@Bob SQLite won't scale");
+        reply.phase = "response".to_string();
+        reply.responding_to = Some("m2".to_string());
+        reply.target_agent_id = Some("bob".to_string());
+        let mut dangling = agent_message(4, "Bob", "Fair");
+        dangling.responding_to = Some("deleted".to_string());
+        let mut messages = vec![
+            agent_message(1, "Alice", "Use Postgres"),
+            agent_message(2, "Bob", "Use SQLite"),
+            reply,
+            dangling,
+        ];
+
+        resolve_replies(&mut messages);
+        assert_eq!(messages[2].responding_to.as_deref(), Some("m2"));
+        assert_eq!(messages[2].metadata["responding_to_sequence"], 2);
+        assert_eq!(messages[3].responding_to, None);
+        assert!(messages[3].metadata.get("responding_to_sequence").is_none());
+    }
+
+    #[test]
+    fn stopping_after_a_round_keeps_a_partial_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut execution = record("e1", "running");
+        execution.shared_state = serde_json::json!({ "topic": "Pick a database" });
+        fill_partial_output(&store, &mut execution).unwrap();
+        assert_eq!(execution.final_output, None);
+
+        store
+            .execution_messages_upsert("e1", &agent_message(1, "Ann", "Use SQLite"))
+            .unwrap();
+        store
+            .execution_messages_upsert("e1", &agent_message(2, "Ben", "Use Postgres"))
+            .unwrap();
+        store
+            .execution_messages_upsert("e1", &agent_message(3, "Ann", "SQLite, on reflection"))
+            .unwrap();
+        fill_partial_output(&store, &mut execution).unwrap();
+
+        let output = execution.final_output.unwrap();
+        assert!(output.contains("Pick a database"));
+        assert!(output.contains("**Ann**（第1轮）: SQLite, on reflection"));
+        assert!(output.contains("**Ben**（第1轮）: Use Postgres"));
+        assert!(!output.contains("Use SQLite"));
+        assert!(output.find("Ann").unwrap() < output.find("Ben").unwrap());
+        assert_eq!(
+            execution.structured_output.unwrap()["opinions"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn stop_mid_round_survives_the_rounds_final_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut execution = record("e1", "running");
+        execution.shared_state = serde_json::json!({ "topic": "Pick a database" });
+        execution.current_stage = Some("stage_1".to_string());
+        execution.eta_ms = Some(9_000);
+        store.executions_upsert(&execution).unwrap();
+        store
+            .execution_messages_upsert("e1", &agent_message(1, "Ann", "Use SQLite"))
+            .unwrap();
+        // Usage the round recorded after `execution` was loaded.
+        store
+            .executions_update("e1", |e| {
+                e.tokens_used = 420;
+                Ok(())
+            })
+            .unwrap();
+
+        let stopped = apply_control(&store, "e1", "stop", &serde_json::json!({})).unwrap();
+        assert_eq!(stopped.status, "completed");
+        assert_eq!(stopped.tokens_used, 420);
+        assert_eq!(
+            (stopped.current_stage.clone(), stopped.eta_ms),
+            (None, None)
+        );
+        let partial = stopped.final_output.clone().unwrap();
+        assert!(partial.contains("Use SQLite"));
+
+        let saved = complete_round(&store, "e1", |e| {
+            e.final_output = Some("full verdict".to_string());
+        })
+        .unwrap();
+        assert!(!saved);
+        let stored = store.executions_get("e1").unwrap().unwrap();
+        assert_eq!(stored.final_output, Some(partial));
+        assert_eq!(stored.completed_at, stopped.completed_at);
+
+        let mut running = record("e2", "running");
+        running.eta_ms = Some(3_000);
+        store.executions_upsert(&running).unwrap();
+        assert!(complete_round(&store, "e2", |_| {}).unwrap());
+        let completed = store.executions_get("e2").unwrap().unwrap();
+        assert_eq!(
+            (completed.status.as_str(), completed.eta_ms),
+            ("completed", None)
+        );
+        assert!(apply_control(&store, "e2", "rewind", &serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn seeding_pulls_the_prior_output_into_the_new_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        assert!(seeded_input(&store, "missing", "").is_err());
+
+        let mut source = record("e1", "completed");
+        store.executions_upsert(&source).unwrap();
+        let err = seeded_input(&store, "e1", "").unwrap_err().to_string();
+        assert!(err.contains("no output"));
+
+        // Without a final_output, the last round's opinions stand in.
+        let mut later = agent_message(3, "Ann", "SQLite, on reflection");
+        later.round = 2;
+        for message in [agent_message(1, "Ann", "Use SQLite"), later] {
+            store.execution_messages_upsert("e1", &message).unwrap();
+        }
+        assert_eq!(
+            seeded_input(&store, "e1", "").unwrap(),
+            "**Ann**: SQLite, on reflection"
+        );
+
+        source.final_output = Some("## 讨论结果\nUse SQLite".to_string());
+        store.executions_upsert(&source).unwrap();
+        assert_eq!(
+            seeded_input(&store, "e1", "Now plan the migration").unwrap(),
+            "## 讨论结果\nUse SQLite\n\nNow plan the migration"
+        );
+    }
+
+    fn phase_message(seq: i32, sender: &str, phase: &str, content: &str) -> ExecutionMessage {
+        ExecutionMessage {
+            phase: phase.to_string(),
+            ..agent_message(seq, sender, content)
+        }
+    }
+
+    fn summarizer(provider: std::sync::Arc<crate::llm::mock::MockProvider>) -> AgentInstance {
+        AgentInstance::from_agent(&crate::llm::mock::agent("judge", "Judge"), provider)
+    }
+
+    fn ranked(id: &str, priority: i32) -> (String, i32) {
+        (id.to_string(), priority)
+    }
+
+    #[tokio::test]
+    async fn stream_flag_selects_streaming_and_falls_back_when_unsupported() {
+        use crate::llm::mock::{agent, MockProvider};
+
+        let streaming = MockProvider::streaming("streamed");
+        let plain = MockProvider::new("plain");
+        let mut agents = vec![
+            AgentInstance::from_agent(&agent("s", "Streamer"), streaming.clone()),
+            AgentInstance::from_agent(&agent("p", "Plain"), plain.clone()),
+        ];
+
+        let fallbacks = enable_streaming(&mut agents, Some(true));
+        assert_eq!(fallbacks, vec![("p".to_string(), "Plain".to_string())]);
+        for agent in agents.iter_mut() {
+            agent
+                .generate_opinion("topic", "", &[], "initial")
+                .await
+                .unwrap();
+        }
+        assert_eq!(streaming.stream_calls(), 1);
+        assert_eq!(plain.stream_calls(), 0);
+        assert_eq!(plain.calls().len(), 1);
+
+        // Left unset, streaming is used where it works and the rest fall
+        // back without a warning.
+        assert!(enable_streaming(&mut agents, None).is_empty());
+        assert!(agents[0].stream && !agents[1].stream);
+
+        assert!(enable_streaming(&mut agents, Some(false)).is_empty());
+        agents[0]
+            .generate_opinion("topic", "", &[], "initial")
+            .await
+            .unwrap();
+        assert_eq!(streaming.stream_calls(), 1, "opted out");
+        assert_eq!(streaming.calls().len(), 2);
+    }
+
+    #[test]
+    fn existing_coordinator_is_kept() {
+        let mut team = team();
+        team.coordinator_id = Some("a".to_string());
+        let agents = [ranked("a", 0), ranked("b", 5)];
+        assert_eq!(
+            resolve_coordinator(&team, &agents).unwrap().as_deref(),
+            Some("a")
+        );
+        team.coordinator_id = None;
+        assert_eq!(resolve_coordinator(&team, &agents).unwrap(), None);
+    }
+
+    #[test]
+    fn deleted_coordinator_falls_back_to_highest_priority_agent() {
+        let mut team = team();
+        team.coordinator_id = Some("gone".to_string());
+        let agents = [ranked("a", 1), ranked("b", 5), ranked("c", 5)];
+        assert_eq!(
+            resolve_coordinator(&team, &agents).unwrap().as_deref(),
+            Some("b")
+        );
+        assert_eq!(
+            summarizer_id(&team, Some("b".to_string()), &[], &agents).as_deref(),
+            Some("b")
+        );
+    }
+
+    #[test]
+    fn deleted_coordinator_is_an_error_when_strict() {
+        let mut team = team();
+        team.coordinator_id = Some("gone".to_string());
+        team.mode_config = serde_json::json!({ "strict_coordinator": true });
+        let err = resolve_coordinator(&team, &[ranked("a", 1)])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("gone") && err.contains("coordinator_id"));
+        assert!(err.contains("no longer exists"));
+
+        team.members.push(
+            serde_json::from_value(serde_json::json!({
+                "id": "m-gone",
+                "agent_id": "gone",
+                "role_override": null,
+                "priority_override": null,
+                "position": 0,
+                "is_active": false,
+                "created_at": Utc::now(),
+                "updated_at": Utc::now()
+            }))
+            .unwrap(),
+        );
+        let err = resolve_coordinator(&team, &[ranked("a", 1)])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("not an active member"), "{err}");
+    }
+
+    #[test]
+    fn opinion_events_carry_the_agents_avatar_and_color() {
+        let provider = crate::llm::mock::MockProvider::new("ok");
+        let mut agent = crate::llm::mock::agent("a1", "Alice");
+        agent.avatar = Some("🦊".to_string());
+        let with_avatar = AgentInstance::from_agent(&agent, provider.clone());
+        let without_avatar = summarizer(provider);
+
+        let mut data = serde_json::json!({
+            "agent_name": "Alice",
+            "content": "Use Postgres",
+            "metadata": { "finish_reason": "stop" }
+        });
+        add_appearance(&mut data, &with_avatar.appearance());
+        assert_eq!(data["avatar"], "🦊");
+        assert_eq!(data["metadata"]["avatar"], "🦊");
+        assert_eq!(data["metadata"]["finish_reason"], "stop");
+        assert_eq!(data["color"], with_avatar.appearance()["color"]);
+        assert!(data["color"].as_str().unwrap().starts_with('#'));
+
+        let mut data = serde_json::json!({ "agent_name": "Judge", "content": "ok" });
+        add_appearance(&mut data, &without_avatar.appearance());
+        assert!(data["avatar"].is_null());
+        assert!(data["metadata"]["color"].is_string());
+    }
+
+    #[tokio::test]
+    async fn pipeline_stages_are_persisted_as_current_stage() {
+        use crate::llm::mock::{self, MockProvider};
+        use crate::orchestration::pipeline::{run_pipeline, StageErrorPolicy};
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        store.executions_upsert(&record("e1", "running")).unwrap();
+        let agents = ["Drafter", "Reviewer"]
+            .iter()
+            .map(|name| {
+                AgentInstance::from_agent(&mock::agent(name, name), MockProvider::new("done"))
+            })
+            .collect();
+        let mut state = OrchestrationState {
+            topic: "topic".to_string(),
+            round: 1,
+            ..Default::default()
+        };
+
+        let mut seen = Vec::new();
+        let mut emit = |event: &str, data: Value, _: Option<String>| {
+            if event == "status" {
+                persist_current_stage(&store, "e1", &data)?;
+                seen.push(store.executions_get("e1")?.unwrap().current_stage);
+            }
+            Ok(())
+        };
+        run_pipeline(
+            agents,
+            &mut state,
+            &mut emit,
+            StageErrorPolicy::Abort,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            seen,
+            vec![
+                None,
+                Some("stage_1: Drafter".to_string()),
+                Some("stage_2: Reviewer".to_string()),
+                None,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn regenerating_a_debate_replaces_the_verdict() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut team = team();
+        team.collaboration_mode = "debate".to_string();
+        let mut execution = record("e1", "running");
+        execution.shared_state = serde_json::json!({ "topic": "Rust or Go?", "round": 1 });
+        store.executions_upsert(&execution).unwrap();
+        for message in [
+            phase_message(1, "Pro", "pro_opening", "Rust is safer"),
+            phase_message(2, "Con", "con_opening", "Go is simpler"),
+            phase_message(3, "Judge", "judge_verdict", "Old verdict"),
+        ] {
+            store.execution_messages_upsert("e1", &message).unwrap();
+        }
+
+        let provider = crate::llm::mock::MockProvider::new("New verdict");
+        let err = resummarize(
+            &store,
+            execution.clone(),
+            &team,
+            &mut summarizer(provider.clone()),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("only completed executions"));
+
+        execution.status = "completed".to_string();
+        store.executions_upsert(&execution).unwrap();
+        let (updated, message) =
+            resummarize(&store, execution, &team, &mut summarizer(provider.clone()))
+                .await
+                .unwrap();
+
+        let prompt = provider.user_content(0);
+        assert!(prompt.contains("Rust is safer") && prompt.contains("Go is simpler"));
+        assert!(!prompt.contains("Old verdict"));
+        assert_eq!(message.id, "m3");
+        let messages = store.execution_messages_list("e1").unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[2].content, "New verdict");
+        let output = updated.final_output.unwrap();
+        assert!(output.contains("New verdict") && !output.contains("Old verdict"));
+        assert_eq!(updated.tokens_used, 15);
+    }
+
+    #[tokio::test]
+    async fn regenerating_a_roundtable_replaces_its_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let team = team();
+        let mut execution = record("e1", "completed");
+        execution.shared_state = serde_json::json!({ "topic": "Pick a database", "round": 1 });
+        store.executions_upsert(&execution).unwrap();
+        store
+            .execution_messages_upsert("e1", &agent_message(1, "Ann", "Use SQLite"))
+            .unwrap();
+        store
+            .execution_messages_upsert("e1", &agent_message(2, "Ben", "Use Postgres"))
+            .unwrap();
+
+        let first = crate::llm::mock::MockProvider::new("First summary");
+        let (execution, _) = resummarize(&store, execution, &team, &mut summarizer(first))
+            .await
+            .unwrap();
+        let second = crate::llm::mock::MockProvider::new("Second summary");
+        let (execution, message) = resummarize(&store, execution, &team, &mut summarizer(second))
+            .await
+            .unwrap();
+
+        assert_eq!(message.phase, SUMMARY_PHASE);
+        assert_eq!(message.sequence, 3);
+        let messages = store.execution_messages_list("e1").unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[2].content, "Second summary");
+        let output = execution.final_output.unwrap();
+        assert!(output.contains("### 摘要\nSecond summary"));
+        assert!(output.contains("**Ann**（第1轮）: Use SQLite"));
+        assert_eq!(
+            execution.structured_output.unwrap()["summary"],
+            "Second summary"
+        );
+    }
+
+    #[tokio::test]
+    async fn regenerating_after_a_follow_up_replaces_only_the_last_rounds_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut execution = record("e1", "completed");
+        execution.shared_state = serde_json::json!({ "topic": "Pick a database", "round": 2 });
+        store.executions_upsert(&execution).unwrap();
+        for (seq, round, sender, phase, content) in [
+            (1, 1, "Ann", "initial", "Use SQLite"),
+            (2, 1, "Judge", SUMMARY_PHASE, "Round one summary"),
+            (3, 2, "Ben", "initial", "Use Postgres"),
+            (4, 2, "Judge", SUMMARY_PHASE, "Round two summary"),
+        ] {
+            let mut message = phase_message(seq, sender, phase, content);
+            message.round = round;
+            store.execution_messages_upsert("e1", &message).unwrap();
+        }
+
+        let provider = crate::llm::mock::MockProvider::new("New summary");
+        let (_, message) = resummarize(&store, execution, &team(), &mut summarizer(provider))
+            .await
+            .unwrap();
+
+        assert_eq!((message.id.as_str(), message.round), ("m4", 2));
+        let messages = store.execution_messages_list("e1").unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[1].content, "Round one summary");
+        assert_eq!(messages[3].content, "New summary");
+    }
+
+    #[test]
+    fn redaction_scrubs_a_token_across_messages_and_tool_traces() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let token = "sk-live-0123456789abcdef";
+        let mut execution = record("e1", "completed");
+        execution.initial_input = format!("Why does {token} fail?");
+        execution.shared_state = serde_json::json!({ "topic": format!("Why does {token} fail?") });
+        store.executions_upsert(&execution).unwrap();
+
+        store
+            .execution_messages_upsert(
+                "e1",
+                &agent_message(1, "Ann", &format!("Rotate {token} now")),
+            )
+            .unwrap();
+        store
+            .execution_messages_upsert("e1", &agent_message(2, "Ben", "Agreed"))
+            .unwrap();
+        let mut tool = agent_message(3, "Ann", "read_file ok");
+        tool.sender_type = "system".to_string();
+        tool.metadata = serde_json::json!({ "tool_name": "read_file", "output": { "content": format!("KEY={token}") } });
+        store.execution_messages_upsert("e1", &tool).unwrap();
+
+        let redaction = Redaction::new(r"sk-live-[0-9a-f]+", "[REDACTED]").unwrap();
+        assert_eq!(redact_history(&store, "e1", &redaction).unwrap(), 2);
+
+        let messages = store.execution_messages_list("e1").unwrap();
+        let stored = serde_json::to_string(&messages).unwrap();
+        assert!(!stored.contains(token));
+        assert_eq!(messages[0].content, "Rotate [REDACTED] now");
+        assert_eq!(messages[2].metadata["output"]["content"], "KEY=[REDACTED]");
+        let execution = store.executions_get("e1").unwrap().unwrap();
+        assert_eq!(execution.initial_input, "Why does [REDACTED] fail?");
+        assert!(!execution.shared_state.to_string().contains(token));
+
+        // Nothing left to change the second time.
+        assert_eq!(redact_history(&store, "e1", &redaction).unwrap(), 0);
+    }
+
+    #[test]
+    fn redaction_waits_for_a_running_execution() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut execution = record("e1", "running");
+        execution.shared_state = serde_json::json!({ "topic": "token sk-live-1" });
+        store.executions_upsert(&execution).unwrap();
+        store
+            .execution_messages_upsert("e1", &agent_message(1, "Ann", "sk-live-1"))
+            .unwrap();
+
+        let redaction = Redaction::new(r"sk-live-\d+", "[REDACTED]").unwrap();
+        let err = redact_history(&store, "e1", &redaction).unwrap_err();
+        assert!(err.to_string().contains("is running"));
+        let messages = store.execution_messages_list("e1").unwrap();
+        assert_eq!(messages[0].content, "sk-live-1");
+
+        execution.status = "paused".to_string();
+        store.executions_upsert(&execution).unwrap();
+        assert_eq!(redact_history(&store, "e1", &redaction).unwrap(), 1);
+        let paused = store.executions_get("e1").unwrap().unwrap();
+        assert_eq!(paused.shared_state["topic"], "token [REDACTED]");
+    }
+
+    #[test]
+    fn workspace_cannot_change_while_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut execution = record("e1", "running");
+        execution.workspace_path = Some("/old".to_string());
+        store.executions_upsert(&execution).unwrap();
+
+        let err = update_workspace(&store, "e1", Some("/new".to_string()), None).unwrap_err();
+        assert!(err.to_string().contains("while the execution is running"));
+        assert_eq!(
+            store
+                .executions_get("e1")
+                .unwrap()
+                .unwrap()
+                .workspace_path
+                .as_deref(),
+            Some("/old")
+        );
+
+        for status in ["paused", "pending"] {
+            execution.status = status.to_string();
+            store.executions_upsert(&execution).unwrap();
+            let updated = update_workspace(&store, "e1", Some("/new".to_string()), None).unwrap();
+            assert_eq!(updated.workspace_path.as_deref(), Some("/new"));
+        }
+    }
+
+    #[test]
+    fn workspace_must_be_under_an_allowed_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let allowed = dir.path().join("projects");
+        std::fs::create_dir_all(allowed.join("app")).unwrap();
+        store
+            .settings_set(&AppSettings {
+                allowed_workspace_roots: vec![allowed.to_string_lossy().to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+        store.executions_upsert(&record("e1", "paused")).unwrap();
+
+        let outside = dir.path().to_string_lossy().to_string();
+        assert!(update_workspace(&store, "e1", Some(outside.clone()), None).is_err());
+        let roots = [("docs".to_string(), outside)].into_iter().collect();
+        let inside = allowed.join("app").to_string_lossy().to_string();
+        assert!(update_workspace(&store, "e1", Some(inside.clone()), Some(roots)).is_err());
+
+        let updated = update_workspace(&store, "e1", Some(inside.clone()), None).unwrap();
+        assert_eq!(updated.workspace_path, Some(inside));
+    }
+
+    #[test]
+    fn warm_up_reports_every_misconfigured_agent() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut team = team();
+        for (id, name, model) in [
+            ("good", "Good", None),
+            ("stale", "Stale", Some("mc_deleted")),
+            ("keyless", "Keyless", Some("no-key")),
+        ] {
+            let mut agent = crate::llm::mock::agent(id, name);
+            agent.model_id = model.map(str::to_string);
+            store.agents_upsert(&agent).unwrap();
+            team.members.push(
+                serde_json::from_value(serde_json::json!({
+                    "id": format!("m-{id}"),
+                    "agent_id": id,
+                    "role_override": null,
+                    "priority_override": null,
+                    "position": team.members.len(),
+                    "is_active": true,
+                    "created_at": Utc::now(),
+                    "updated_at": Utc::now()
+                }))
+                .unwrap(),
+            );
+        }
+        let llm: crate::models::llm::ExecutionLLMConfig =
+            serde_json::from_value(serde_json::json!({
+                "default": { "model_id": "gpt", "api_key": "sk-test" },
+                "models": { "no-key": { "model_id": "gpt", "api_key": " " } }
+            }))
+            .unwrap();
+
+        let err = warm_up(&store, &team, &llm, None).unwrap_err().to_string();
+        assert!(err.starts_with("2 个 Agent"), "{err}");
+        assert!(
+            err.contains("Stale（stale）: Agent model_id 'mc_deleted'"),
+            "{err}"
+        );
+        assert!(
+            err.contains("Keyless（keyless）: Model config is missing api_key"),
+            "{err}"
+        );
+        assert!(!err.contains("Good"));
+
+        assert!(warm_up(&store, &team, &llm, Some("good")).is_ok());
+    }
+
+    #[test]
+    fn low_rate_limit_on_an_opinion_becomes_a_warning() {
+        let opinion = |remaining: u64| {
+            serde_json::json!({
+                "agent_name": "Alice",
+                "content": "ok",
+                "metadata": {
+                    "model": "gpt-4o",
+                    "rate_limit": {
+                        "tokens_limit": 10_000,
+                        "tokens_remaining": remaining,
+                        "tokens_reset": "30s"
+                    }
+                }
+            })
+        };
+
+        let warning = rate_limit_warning(&opinion(500)).unwrap();
+        assert_eq!(warning["tokens_remaining"], 500);
+        assert_eq!(warning["tokens_reset"], "30s");
+        assert_eq!(warning["model"], "gpt-4o");
+        assert!(warning["message"].as_str().unwrap().contains("Alice"));
+        assert!(rate_limit_warning(&opinion(5_000)).is_none());
+        assert!(rate_limit_warning(&serde_json::json!({"metadata": {}})).is_none());
+    }
+
+    #[tokio::test]
+    async fn a_panicking_run_leaves_the_execution_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let mut execution = record("e1", "running");
+        execution.current_stage = Some("stage_2".to_string());
+        execution.eta_ms = Some(5_000);
+        store.executions_upsert(&execution).unwrap();
+
+        let err = run_guarded(&store, "e1", async {
+            let data = serde_json::json!({});
+            data.get("metadata").expect("metadata is set");
+            Ok(())
+        })
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("metadata is set"));
+        let stored = store.executions_get("e1").unwrap().unwrap();
+        assert_eq!(stored.status, "failed");
+        assert_eq!((stored.current_stage.clone(), stored.eta_ms), (None, None));
+        assert!(stored.error_message.unwrap().contains("panicked"));
+
+        store.executions_upsert(&record("e2", "running")).unwrap();
+        assert!(run_guarded(&store, "e2", async { Ok(()) }).await.is_ok());
+        assert_eq!(
+            store.executions_get("e2").unwrap().unwrap().status,
+            "running"
+        );
+    }
 }
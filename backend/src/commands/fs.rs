@@ -5,6 +5,8 @@ use tauri::State;
 
 use crate::error::AppError;
 use crate::state::AppState;
+use crate::store::sqlite::SqliteStore;
+use crate::tools::workspace_diff::{self, FileChange};
 
 #[derive(Debug, Clone, Serialize)]
 pub struct FileEntry {
@@ -88,6 +90,11 @@ pub fn read_file(
         .map_err(|e| AppError::Message(format!("Invalid path: {e}")))?;
     ensure_within_root(&root, &full)?;
 
+    let meta = std::fs::metadata(&full).map_err(|e| AppError::Message(e.to_string()))?;
+    if !meta.is_file() {
+        return Err(AppError::Message("Path is not a regular file".to_string()));
+    }
+
     std::fs::read_to_string(&full).map_err(|e| AppError::Message(e.to_string()))
 }
 
@@ -127,6 +134,43 @@ pub fn write_file(
     Ok(())
 }
 
+/// Files changed in the execution's workspace since its first round started.
+/// A rename made with `rename_file`, or one that kept the file's content,
+/// is one `renamed` change rather than a delete and an add.
+#[tauri::command]
+pub fn get_workspace_diff(
+    state: State<AppState>,
+    execution_id: String,
+) -> Result<Vec<FileChange>, AppError> {
+    workspace_changes(&state.store, &execution_id)
+}
+
+pub(crate) fn workspace_changes(
+    store: &SqliteStore,
+    execution_id: &str,
+) -> Result<Vec<FileChange>, AppError> {
+    let execution = store
+        .executions_get(execution_id)?
+        .ok_or_else(|| AppError::Message(format!("Execution {execution_id} not found")))?;
+    let root = execution
+        .workspace_path
+        .as_deref()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| AppError::Message("Execution workspace_path is not set".to_string()))?;
+    let baseline = execution.workspace_baseline.as_ref().ok_or_else(|| {
+        AppError::Message(
+            "Execution has no workspace baseline yet; it is taken when the first round starts"
+                .to_string(),
+        )
+    })?;
+
+    let current = workspace_diff::snapshot(Path::new(root), workspace_diff::MAX_SNAPSHOT_FILES)?;
+    let messages = store.execution_messages_list(execution_id)?;
+    let renames = workspace_diff::renames_from_events(messages.iter().map(|m| &m.metadata));
+    Ok(workspace_diff::diff_snapshots(baseline, &current, &renames))
+}
+
 fn workspace_root(state: &State<AppState>, execution_id: &str) -> Result<PathBuf, AppError> {
     let execution = state
         .store
@@ -0,0 +1,84 @@
+use chrono::Utc;
+use tauri::State;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::common::SuccessResponse;
+use crate::models::knowledge::{KnowledgeDoc, KnowledgeDocCreate, KnowledgeDocUpdate};
+use crate::state::AppState;
+
+#[tauri::command]
+pub fn list_knowledge_docs(
+    state: State<AppState>,
+    kb_id: String,
+) -> Result<Vec<KnowledgeDoc>, AppError> {
+    state.store.knowledge_docs_list(&kb_id)
+}
+
+#[tauri::command]
+pub fn get_knowledge_doc(state: State<AppState>, id: String) -> Result<KnowledgeDoc, AppError> {
+    state
+        .store
+        .knowledge_docs_get(&id)?
+        .ok_or_else(|| AppError::Message(format!("Knowledge doc {id} not found")))
+}
+
+#[tauri::command]
+pub fn create_knowledge_doc(
+    state: State<AppState>,
+    doc: KnowledgeDocCreate,
+) -> Result<KnowledgeDoc, AppError> {
+    let kb_id = doc.kb_id.trim();
+    if kb_id.is_empty() {
+        return Err(AppError::Message("kb_id is required".to_string()));
+    }
+    let now = Utc::now();
+    let record = KnowledgeDoc {
+        id: Uuid::new_v4().to_string(),
+        kb_id: kb_id.to_string(),
+        path: doc.path,
+        content: doc.content,
+        created_at: now,
+        updated_at: now,
+    };
+    state.store.knowledge_docs_upsert(&record)?;
+    Ok(record)
+}
+
+#[tauri::command]
+pub fn update_knowledge_doc(
+    state: State<AppState>,
+    id: String,
+    update: KnowledgeDocUpdate,
+) -> Result<KnowledgeDoc, AppError> {
+    let mut existing = state
+        .store
+        .knowledge_docs_get(&id)?
+        .ok_or_else(|| AppError::Message(format!("Knowledge doc {id} not found")))?;
+
+    if let Some(v) = update.kb_id.filter(|v| !v.trim().is_empty()) {
+        existing.kb_id = v.trim().to_string();
+    }
+    if let Some(v) = update.path {
+        existing.path = Some(v);
+    }
+    if let Some(v) = update.content {
+        existing.content = v;
+    }
+
+    existing.updated_at = Utc::now();
+    state.store.knowledge_docs_upsert(&existing)?;
+    Ok(existing)
+}
+
+#[tauri::command]
+pub fn delete_knowledge_doc(
+    state: State<AppState>,
+    id: String,
+) -> Result<SuccessResponse, AppError> {
+    state.store.knowledge_docs_delete(&id)?;
+    Ok(SuccessResponse {
+        success: true,
+        message: "Knowledge doc deleted successfully".to_string(),
+    })
+}
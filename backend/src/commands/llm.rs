@@ -1,9 +1,11 @@
+use std::time::{Duration, Instant};
+
 use serde::Serialize;
 
 use crate::error::AppError;
 use crate::llm::factory::provider_from_runtime_config;
 use crate::llm::openai_compatible::normalize_openai_compatible_base_url;
-use crate::llm::provider::{Message, MessageRole};
+use crate::llm::provider::{LLMProvider, LLMResponse, Message, MessageRole};
 use crate::models::llm::{LLMRuntimeConfig, ProviderKind};
 
 #[derive(Debug, Clone, Serialize)]
@@ -12,6 +14,28 @@ pub struct TestLLMResponse {
     pub response_preview: String,
     pub tokens_used: u32,
     pub resolved_base_url: Option<String>,
+    /// Wall-clock time for the whole test completion.
+    pub latency_ms: u64,
+    /// Always `None` for now: `LLMProvider::chat_stream` hands back only the
+    /// finished reply, so there is no first-token moment to time.
+    pub time_to_first_token_ms: Option<u64>,
+    /// Output tokens divided by total latency.
+    pub tokens_per_second: Option<f64>,
+}
+
+/// Run one completion and measure how long it took.
+async fn timed_chat(
+    provider: &dyn LLMProvider,
+    messages: Vec<Message>,
+) -> Result<(LLMResponse, Duration), AppError> {
+    let started = Instant::now();
+    let resp = provider.chat(messages, 0.2, 64).await?;
+    Ok((resp, started.elapsed()))
+}
+
+fn tokens_per_second(output_tokens: u32, elapsed: Duration) -> Option<f64> {
+    let secs = elapsed.as_secs_f64();
+    (output_tokens > 0 && secs > 0.0).then(|| f64::from(output_tokens) / secs)
 }
 
 #[tauri::command]
@@ -33,9 +57,10 @@ pub async fn test_llm(
         name: None,
         tool_call_id: None,
         tool_calls: None,
+        images: Vec::new(),
     }];
 
-    let resp = provider.chat(messages, 0.2, 64).await?;
+    let (resp, elapsed) = timed_chat(provider.as_ref(), messages).await?;
     let preview = if resp.content.len() > 100 {
         format!("{}...", &resp.content[..100])
     } else {
@@ -50,5 +75,53 @@ pub async fn test_llm(
             .input_tokens
             .saturating_add(resp.usage.output_tokens),
         resolved_base_url,
+        latency_ms: elapsed.as_millis().min(u128::from(u64::MAX)) as u64,
+        time_to_first_token_ms: None,
+        tokens_per_second: tokens_per_second(resp.usage.output_tokens, elapsed),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::MockProvider;
+
+    #[test]
+    fn tokens_per_second_divides_output_tokens_by_elapsed() {
+        let tps = tokens_per_second(50, Duration::from_millis(500)).unwrap();
+        assert!((tps - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tokens_per_second_needs_tokens_and_time() {
+        assert_eq!(tokens_per_second(0, Duration::from_secs(1)), None);
+        assert_eq!(tokens_per_second(10, Duration::ZERO), None);
+    }
+
+    #[tokio::test]
+    async fn test_llm_reports_the_providers_latency() {
+        let provider = MockProvider::slow(Duration::from_millis(50), "pong");
+        crate::llm::mock::register("latency-test-model", provider.clone());
+        let config: LLMRuntimeConfig = serde_json::from_value(serde_json::json!({
+            "model_id": "latency-test-model",
+            "api_key": "k"
+        }))
+        .unwrap();
+
+        let started = Instant::now();
+        let result = test_llm(config, "ping".to_string()).await.unwrap();
+        let wall_ms = started.elapsed().as_millis() as u64;
+
+        assert_eq!(provider.calls().len(), 1);
+        assert_eq!(result.response_preview, "pong");
+        assert!(
+            (50..=wall_ms).contains(&result.latency_ms),
+            "{} ms",
+            result.latency_ms
+        );
+        // The mock reports 5 output tokens, so at most 5 / 0.05 s.
+        let tps = result.tokens_per_second.unwrap();
+        assert!(tps > 0.0 && tps <= 100.0, "{tps}");
+        assert_eq!(result.tokens_used, 15);
+    }
+}
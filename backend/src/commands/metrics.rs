@@ -0,0 +1,10 @@
+use tauri::State;
+
+use crate::error::AppError;
+use crate::metrics::MetricsSnapshot;
+use crate::state::AppState;
+
+#[tauri::command]
+pub fn get_metrics(state: State<AppState>) -> Result<MetricsSnapshot, AppError> {
+    Ok(state.metrics.snapshot())
+}
@@ -1,5 +1,12 @@
 pub mod agents;
+pub mod backup;
+pub mod benchmark;
 pub mod executions;
 pub mod fs;
+pub mod knowledge;
 pub mod llm;
+pub mod metrics;
+pub mod settings;
 pub mod teams;
+pub mod templates;
+pub mod tools;
@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+
+use tauri::State;
+
+use crate::error::AppError;
+use crate::models::llm::{Pricing, PricingTable};
+use crate::models::settings::AppSettings;
+use crate::state::AppState;
+use crate::store::sqlite::SqliteStore;
+
+#[tauri::command]
+pub fn get_settings(state: State<AppState>) -> Result<AppSettings, AppError> {
+    state.store.settings_get()
+}
+
+#[tauri::command]
+pub fn set_settings(
+    state: State<AppState>,
+    settings: AppSettings,
+) -> Result<AppSettings, AppError> {
+    state.store.settings_set(&settings)?;
+    Ok(settings)
+}
+
+/// The built-in pricing table with the user's overrides applied.
+pub(crate) fn pricing_table(store: &SqliteStore) -> Result<PricingTable, AppError> {
+    Ok(PricingTable::with_overrides(&store.model_pricing_get()?))
+}
+
+#[tauri::command]
+pub fn list_model_pricing(state: State<AppState>) -> Result<BTreeMap<String, Pricing>, AppError> {
+    Ok(pricing_table(&state.store)?.models().clone())
+}
+
+/// Set the table price for `model_id`, replacing any built-in entry.
+#[tauri::command]
+pub fn set_model_pricing(
+    state: State<AppState>,
+    model_id: String,
+    pricing: Pricing,
+) -> Result<BTreeMap<String, Pricing>, AppError> {
+    let model_id = model_id.trim().to_lowercase();
+    if model_id.is_empty() {
+        return Err(AppError::Message("Model id is required".to_string()));
+    }
+    if pricing.input_per_1k < 0.0 || pricing.output_per_1k < 0.0 {
+        return Err(AppError::Message("Prices cannot be negative".to_string()));
+    }
+    let mut overrides = state.store.model_pricing_get()?;
+    overrides.insert(model_id, pricing);
+    state.store.model_pricing_set(&overrides)?;
+    Ok(pricing_table(&state.store)?.models().clone())
+}
+
+/// Drop the user's price for `model_id`, restoring the built-in one if any.
+#[tauri::command]
+pub fn reset_model_pricing(
+    state: State<AppState>,
+    model_id: String,
+) -> Result<BTreeMap<String, Pricing>, AppError> {
+    let mut overrides = state.store.model_pricing_get()?;
+    overrides.remove(&model_id.trim().to_lowercase());
+    state.store.model_pricing_set(&overrides)?;
+    Ok(pricing_table(&state.store)?.models().clone())
+}
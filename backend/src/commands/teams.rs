@@ -4,11 +4,14 @@ use tauri::State;
 use uuid::Uuid;
 
 use crate::error::AppError;
-use crate::models::common::{PaginatedResponse, SuccessResponse};
+use crate::models::agent::system_prompt_length_warning;
+use crate::models::common::{check_revision, Ownership, PaginatedResponse, SuccessResponse};
 use crate::models::team::{
-    Team, TeamCreate, TeamListItem, TeamMember, TeamMemberCreate, TeamUpdate,
+    normalize_collaboration_mode, IssueSeverity, Team, TeamCreate, TeamDetail, TeamIssue,
+    TeamListItem, TeamMember, TeamMemberCreate, TeamReadiness, TeamUpdate,
 };
 use crate::state::AppState;
+use crate::store::sqlite::SqliteStore;
 
 const LOCAL_USER_ID: &str = "local";
 
@@ -20,12 +23,14 @@ pub fn list_teams(
     search: Option<String>,
     collaboration_mode: Option<String>,
     is_template: Option<bool>,
+    ownership: Option<Ownership>,
 ) -> Result<PaginatedResponse<TeamListItem>, AppError> {
     let page = page.unwrap_or(1).max(1);
     let page_size = page_size.unwrap_or(20).clamp(1, 100);
 
     let mut teams = state.store.teams_list()?;
-    teams.retain(|t| t.user_id == LOCAL_USER_ID || t.is_public);
+    let ownership = ownership.unwrap_or_default();
+    teams.retain(|t| ownership.allows(t.user_id == LOCAL_USER_ID, t.is_public));
 
     if let Some(search) = search {
         let needle = search.to_lowercase();
@@ -90,17 +95,98 @@ pub fn list_teams(
 }
 
 #[tauri::command]
-pub fn get_team(state: State<AppState>, id: String) -> Result<Team, AppError> {
-    state
+pub fn get_team(state: State<AppState>, id: String) -> Result<TeamDetail, AppError> {
+    let team = state
         .store
         .teams_get(&id)?
-        .ok_or_else(|| AppError::Message(format!("Team {id} not found")))
+        .ok_or_else(|| AppError::Message(format!("Team {id} not found")))?;
+    let readiness = TeamReadiness::from_issues(validate_team(&state.store, &team)?);
+    Ok(TeamDetail { team, readiness })
+}
+
+/// Checks whether `team` can run as configured. Blockers are problems that
+/// make an execution fail outright; warnings still run but likely poorly.
+fn validate_team(store: &SqliteStore, team: &Team) -> Result<Vec<TeamIssue>, AppError> {
+    let mut issues = Vec::new();
+    let warn = |message: String| TeamIssue {
+        severity: IssueSeverity::Warning,
+        message,
+    };
+    let block = |message: String| TeamIssue {
+        severity: IssueSeverity::Blocker,
+        message,
+    };
+
+    let active = team.active_members();
+    if active.is_empty() {
+        issues.push(block("Team has no active members".to_string()));
+        return Ok(issues);
+    }
+
+    let max_prompt_chars = store.settings_get()?.max_system_prompt_chars();
+    let mut existing = Vec::new();
+    for member in &active {
+        let Some(agent) = store.agents_get(&member.agent_id)? else {
+            issues.push(warn(format!(
+                "Member agent {} no longer exists and will be skipped",
+                member.agent_id
+            )));
+            continue;
+        };
+        existing.push(member.agent_id.as_str());
+        if agent.system_prompt.trim().is_empty() {
+            issues.push(warn(format!(
+                "Agent {} has an empty system_prompt",
+                agent.name
+            )));
+        } else if let Some(w) = system_prompt_length_warning(&agent.system_prompt, max_prompt_chars)
+        {
+            issues.push(warn(format!("Agent {}: {w}", agent.name)));
+        }
+    }
+
+    let available = existing.len();
+    if available == 0 {
+        issues.push(block("None of the team's member agents exist".to_string()));
+        return Ok(issues);
+    }
+    match team.collaboration_mode.as_str() {
+        "debate" if available < 3 => issues.push(warn(format!(
+            "Debate mode needs at least 3 agents (pro, con and a judge); this team has {available}"
+        ))),
+        "debate" => {}
+        _ if available < 2 => issues.push(warn(
+            "Only one agent is available, so there is nobody to collaborate with".to_string(),
+        )),
+        _ => {}
+    }
+    if let Some(coordinator) = &team.coordinator_id {
+        if !active.iter().any(|m| &m.agent_id == coordinator) {
+            issues.push(warn(format!(
+                "Coordinator {coordinator} is not an active member of the team"
+            )));
+        } else if !existing.contains(&coordinator.as_str()) {
+            let message = format!("Coordinator agent {coordinator} no longer exists");
+            issues.push(if team.strict_coordinator() {
+                block(format!(
+                    "{message}; choose another coordinator or clear coordinator_id"
+                ))
+            } else {
+                warn(format!(
+                    "{message}; the highest-priority member will coordinate instead"
+                ))
+            });
+        }
+    }
+    Ok(issues)
 }
 
 #[tauri::command]
 pub fn create_team(state: State<AppState>, team: TeamCreate) -> Result<Team, AppError> {
     let now = Utc::now();
-    let members = build_members(team.members, &[], now);
+    let settings = state.store.settings_get()?;
+    let collaboration_mode = normalize_collaboration_mode(&settings.collaboration_mode_for(&team))?;
+    let members = build_members(team.members, &[], settings.max_team_members(), now)?;
 
     let record = Team {
         id: Uuid::new_v4().to_string(),
@@ -108,11 +194,12 @@ pub fn create_team(state: State<AppState>, team: TeamCreate) -> Result<Team, App
         name: team.name,
         description: team.description,
         icon: team.icon,
-        collaboration_mode: team.collaboration_mode,
+        collaboration_mode,
         mode_config: team.mode_config,
         coordinator_id: team.coordinator_id,
         coordination_rules: team.coordination_rules,
         output_rules: team.output_rules,
+        revision: 0,
         is_template: team.is_template,
         is_public: team.is_public,
         usage_count: 0,
@@ -132,47 +219,57 @@ pub fn update_team(
     state: State<AppState>,
     id: String,
     update: TeamUpdate,
+    expected_revision: Option<u32>,
 ) -> Result<Team, AppError> {
-    let mut existing = state
-        .store
-        .teams_get(&id)?
-        .ok_or_else(|| AppError::Message(format!("Team {id} not found")))?;
+    apply_team_update(&state.store, &id, update, expected_revision)
+}
 
-    if let Some(v) = update.name {
-        existing.name = v;
-    }
-    if let Some(v) = update.description {
-        existing.description = Some(v);
-    }
-    if let Some(v) = update.icon {
-        existing.icon = Some(v);
-    }
-    if let Some(v) = update.collaboration_mode {
-        existing.collaboration_mode = v;
-    }
-    if let Some(v) = update.mode_config {
-        existing.mode_config = v;
-    }
-    if let Some(v) = update.coordinator_id {
-        existing.coordinator_id = Some(v);
-    }
-    if let Some(v) = update.coordination_rules {
-        existing.coordination_rules = v;
-    }
-    if let Some(v) = update.output_rules {
-        existing.output_rules = v;
-    }
-    if let Some(v) = update.is_public {
-        existing.is_public = v;
-    }
-    if let Some(members) = update.members {
+/// Apply `update` to team `id` atomically, rejecting it if the team's
+/// `revision` no longer matches `expected_revision`.
+fn apply_team_update(
+    store: &SqliteStore,
+    id: &str,
+    update: TeamUpdate,
+    expected_revision: Option<u32>,
+) -> Result<Team, AppError> {
+    let max_members = store.settings_get()?.max_team_members();
+    store.teams_update(id, |existing| {
+        check_revision("Team", id, existing.revision, expected_revision)?;
+        if let Some(v) = update.name {
+            existing.name = v;
+        }
+        if let Some(v) = update.description {
+            existing.description = Some(v);
+        }
+        if let Some(v) = update.icon {
+            existing.icon = Some(v);
+        }
+        if let Some(v) = update.collaboration_mode {
+            existing.collaboration_mode = normalize_collaboration_mode(&v)?;
+        }
+        if let Some(v) = update.mode_config {
+            existing.mode_config = v;
+        }
+        if let Some(v) = update.coordinator_id {
+            existing.coordinator_id = Some(v);
+        }
+        if let Some(v) = update.coordination_rules {
+            existing.coordination_rules = v;
+        }
+        if let Some(v) = update.output_rules {
+            existing.output_rules = v;
+        }
+        if let Some(v) = update.is_public {
+            existing.is_public = v;
+        }
         let now = Utc::now();
-        existing.members = build_members(members, &existing.members, now);
-    }
+        if let Some(members) = update.members {
+            existing.members = build_members(members, &existing.members, max_members, now)?;
+        }
 
-    existing.updated_at = Utc::now();
-    state.store.teams_upsert(&existing)?;
-    Ok(existing)
+        existing.touch(now);
+        Ok(())
+    })
 }
 
 #[tauri::command]
@@ -194,7 +291,16 @@ pub fn duplicate_team(
         .store
         .teams_get(&id)?
         .ok_or_else(|| AppError::Message(format!("Team {id} not found")))?;
+    copy_team(&state.store, original, new_name)
+}
 
+/// Store a user-owned, non-template copy of `original` with fresh member
+/// rows, named `new_name` or "<name> (副本)".
+pub(crate) fn copy_team(
+    store: &SqliteStore,
+    original: Team,
+    new_name: Option<String>,
+) -> Result<Team, AppError> {
     let now = Utc::now();
     let members = original
         .members
@@ -223,6 +329,7 @@ pub fn duplicate_team(
         coordinator_id: original.coordinator_id.clone(),
         coordination_rules: original.coordination_rules.clone(),
         output_rules: original.output_rules.clone(),
+        revision: 0,
         is_template: false,
         is_public: false,
         usage_count: 0,
@@ -233,7 +340,7 @@ pub fn duplicate_team(
         updated_at: now,
     };
 
-    state.store.teams_upsert(&record)?;
+    store.teams_upsert(&record)?;
     Ok(record)
 }
 
@@ -243,40 +350,48 @@ pub fn add_team_member(
     team_id: String,
     member: TeamMemberCreate,
 ) -> Result<TeamMember, AppError> {
-    let mut team = state
-        .store
-        .teams_get(&team_id)?
-        .ok_or_else(|| AppError::Message(format!("Team {team_id} not found")))?;
+    add_member(&state.store, &team_id, member)
+}
 
+fn add_member(
+    store: &SqliteStore,
+    team_id: &str,
+    member: TeamMemberCreate,
+) -> Result<TeamMember, AppError> {
     // Ensure agent exists.
-    if state.store.agents_get(&member.agent_id)?.is_none() {
+    if store.agents_get(&member.agent_id)?.is_none() {
         return Err(AppError::Message("Agent not found".to_string()));
     }
+    let max_members = store.settings_get()?.max_team_members();
+    let agent_id = member.agent_id.clone();
 
-    let existing = team.members.iter().find(|m| m.agent_id == member.agent_id);
-    if let Some(m) = existing {
-        return Ok(m.clone());
-    }
-
-    let now = Utc::now();
-    let next_pos = team.members.iter().map(|m| m.position).max().unwrap_or(-1) + 1;
-    let record = TeamMember {
-        id: Uuid::new_v4().to_string(),
-        agent_id: member.agent_id,
-        role_override: member.role_override,
-        priority_override: member.priority_override,
-        config_override: member.config_override,
-        position: member.position.unwrap_or(next_pos),
-        is_active: true,
-        created_at: now,
-        updated_at: now,
-    };
+    let team = store.teams_update(team_id, |team| {
+        if team.members.iter().any(|m| m.agent_id == member.agent_id) {
+            return Ok(());
+        }
+        ensure_member_cap(team.members.len() + 1, max_members)?;
 
-    team.members.push(record.clone());
-    team.members.sort_by_key(|m| m.position);
-    team.updated_at = now;
-    state.store.teams_upsert(&team)?;
-    Ok(record)
+        let now = Utc::now();
+        let next_pos = team.members.iter().map(|m| m.position).max().unwrap_or(-1) + 1;
+        team.members.push(TeamMember {
+            id: Uuid::new_v4().to_string(),
+            agent_id: member.agent_id,
+            role_override: member.role_override,
+            priority_override: member.priority_override,
+            config_override: member.config_override,
+            position: member.position.unwrap_or(next_pos),
+            is_active: true,
+            created_at: now,
+            updated_at: now,
+        });
+        team.members.sort_by_key(|m| m.position);
+        team.touch(now);
+        Ok(())
+    })?;
+    team.members
+        .into_iter()
+        .find(|m| m.agent_id == agent_id)
+        .ok_or_else(|| AppError::Message("Member not found".to_string()))
 }
 
 #[tauri::command]
@@ -285,19 +400,15 @@ pub fn remove_team_member(
     team_id: String,
     agent_id: String,
 ) -> Result<SuccessResponse, AppError> {
-    let mut team = state
-        .store
-        .teams_get(&team_id)?
-        .ok_or_else(|| AppError::Message(format!("Team {team_id} not found")))?;
-
-    let before = team.members.len();
-    team.members.retain(|m| m.agent_id != agent_id);
-    if team.members.len() == before {
-        return Err(AppError::Message("Member not found".to_string()));
-    }
-
-    team.updated_at = Utc::now();
-    state.store.teams_upsert(&team)?;
+    state.store.teams_update(&team_id, |team| {
+        let before = team.members.len();
+        team.members.retain(|m| m.agent_id != agent_id);
+        if team.members.len() == before {
+            return Err(AppError::Message("Member not found".to_string()));
+        }
+        team.touch(Utc::now());
+        Ok(())
+    })?;
     Ok(SuccessResponse {
         success: true,
         message: "Member removed successfully".to_string(),
@@ -310,13 +421,66 @@ pub fn reorder_team_members(
     team_id: String,
     agent_ids: Vec<String>,
 ) -> Result<SuccessResponse, AppError> {
-    let mut team = state
-        .store
-        .teams_get(&team_id)?
-        .ok_or_else(|| AppError::Message(format!("Team {team_id} not found")))?;
+    state.store.teams_update(&team_id, |team| {
+        team.members = reorder_members(&team.members, &agent_ids)?;
+        team.touch(Utc::now());
+        Ok(())
+    })?;
+
+    Ok(SuccessResponse {
+        success: true,
+        message: "Members reordered successfully".to_string(),
+    })
+}
+
+#[tauri::command]
+pub fn set_member_position(
+    state: State<AppState>,
+    team_id: String,
+    agent_id: String,
+    new_position: i32,
+) -> Result<SuccessResponse, AppError> {
+    state.store.teams_update(&team_id, |team| {
+        team.members = move_member(&team.members, &agent_id, new_position)?;
+        team.touch(Utc::now());
+        Ok(())
+    })?;
+
+    Ok(SuccessResponse {
+        success: true,
+        message: "Member moved successfully".to_string(),
+    })
+}
+
+/// Move one member to `new_position` (clamped to the team size), shifting the
+/// others to keep positions dense.
+fn move_member(
+    members: &[TeamMember],
+    agent_id: &str,
+    new_position: i32,
+) -> Result<Vec<TeamMember>, AppError> {
+    let mut ordered = members.to_vec();
+    ordered.sort_by_key(|m| m.position);
+    let mut agent_ids = ordered.into_iter().map(|m| m.agent_id).collect::<Vec<_>>();
+    let Some(current) = agent_ids.iter().position(|id| id == agent_id) else {
+        return Err(AppError::Message(format!(
+            "Unknown agent_id in team: {agent_id}"
+        )));
+    };
+    let moved = agent_ids.remove(current);
+    let index = (new_position.max(0) as usize).min(agent_ids.len());
+    agent_ids.insert(index, moved);
+    reorder_members(members, &agent_ids)
+}
 
+/// Assign dense positions: `agent_ids` first in the given order, then any
+/// members not listed, keeping their relative order.
+fn reorder_members(
+    members: &[TeamMember],
+    agent_ids: &[String],
+) -> Result<Vec<TeamMember>, AppError> {
     let mut seen = std::collections::HashSet::new();
-    for id in &agent_ids {
+    for id in agent_ids {
         if !seen.insert(id.clone()) {
             return Err(AppError::Message(
                 "agent_ids contains duplicates".to_string(),
@@ -324,8 +488,7 @@ pub fn reorder_team_members(
         }
     }
 
-    let mut by_agent: std::collections::HashMap<String, TeamMember> = team
-        .members
+    let mut by_agent: std::collections::HashMap<String, TeamMember> = members
         .iter()
         .cloned()
         .map(|m| (m.agent_id.clone(), m))
@@ -341,8 +504,7 @@ pub fn reorder_team_members(
         by_agent.insert(agent_id.clone(), member);
     }
 
-    let mut remaining = team
-        .members
+    let mut remaining = members
         .iter()
         .filter(|m| !agent_ids.contains(&m.agent_id))
         .cloned()
@@ -356,26 +518,36 @@ pub fn reorder_team_members(
 
     let mut members = by_agent.into_values().collect::<Vec<_>>();
     members.sort_by_key(|m| m.position);
-    team.members = members;
-    team.updated_at = Utc::now();
-    state.store.teams_upsert(&team)?;
+    Ok(members)
+}
 
-    Ok(SuccessResponse {
-        success: true,
-        message: "Members reordered successfully".to_string(),
-    })
+/// Reject a team that would end up with more than `max` members.
+fn ensure_member_cap(count: usize, max: usize) -> Result<(), AppError> {
+    if count > max {
+        return Err(AppError::Message(format!(
+            "Teams are limited to {max} members ({count} requested); raise max_team_members in settings to allow more"
+        )));
+    }
+    Ok(())
 }
 
 fn build_members(
     members: Vec<TeamMemberCreate>,
     existing: &[TeamMember],
+    max_members: usize,
     now: chrono::DateTime<Utc>,
-) -> Vec<TeamMember> {
+) -> Result<Vec<TeamMember>, AppError> {
     let existing_by_agent: std::collections::HashMap<String, &TeamMember> =
         existing.iter().map(|m| (m.agent_id.clone(), m)).collect();
 
+    let mut seen = std::collections::HashSet::new();
     let mut out = Vec::new();
     for (idx, m) in members.into_iter().enumerate() {
+        // Keep the first occurrence so the same agent never runs twice per round.
+        if !seen.insert(m.agent_id.clone()) {
+            eprintln!("team members: ignoring duplicate agent_id '{}'", m.agent_id);
+            continue;
+        }
         let pos = m.position.unwrap_or(idx as i32);
         if let Some(prev) = existing_by_agent.get(&m.agent_id) {
             out.push(TeamMember {
@@ -403,8 +575,9 @@ fn build_members(
             });
         }
     }
+    ensure_member_cap(out.len(), max_members)?;
     out.sort_by_key(|m| m.position);
-    out
+    Ok(out)
 }
 
 fn merge_json(a: &Value, b: &Value) -> Value {
@@ -419,3 +592,256 @@ fn merge_json(a: &Value, b: &Value) -> Value {
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::settings::{AppSettings, DEFAULT_MAX_TEAM_MEMBERS};
+    use crate::models::team::{ReadinessStatus, READINESS_WARNING_PENALTY};
+
+    fn member(agent_id: &str, role: Option<&str>) -> TeamMemberCreate {
+        TeamMemberCreate {
+            agent_id: agent_id.to_string(),
+            role_override: role.map(|r| r.to_string()),
+            priority_override: None,
+            config_override: serde_json::json!({}),
+            position: None,
+        }
+    }
+
+    #[test]
+    fn build_members_collapses_duplicates_on_create() {
+        let now = Utc::now();
+        let members = build_members(
+            vec![
+                member("a1", Some("lead")),
+                member("a2", None),
+                member("a1", Some("reviewer")),
+            ],
+            &[],
+            DEFAULT_MAX_TEAM_MEMBERS,
+            now,
+        )
+        .unwrap();
+
+        assert_eq!(members.len(), 2);
+        let a1 = members.iter().find(|m| m.agent_id == "a1").unwrap();
+        assert_eq!(a1.role_override.as_deref(), Some("lead"));
+        assert_eq!(a1.position, 0);
+    }
+
+    #[test]
+    fn build_members_collapses_duplicates_on_update() {
+        let now = Utc::now();
+        let existing =
+            build_members(vec![member("a1", None)], &[], DEFAULT_MAX_TEAM_MEMBERS, now).unwrap();
+        let members = build_members(
+            vec![member("a1", Some("first")), member("a1", Some("second"))],
+            &existing,
+            DEFAULT_MAX_TEAM_MEMBERS,
+            now,
+        )
+        .unwrap();
+
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].id, existing[0].id);
+        assert_eq!(members[0].role_override.as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn build_members_rejects_teams_over_the_cap() {
+        let members = |n: usize| (0..n).map(|i| member(&format!("a{i}"), None)).collect();
+        assert_eq!(
+            build_members(members(3), &[], 3, Utc::now()).unwrap().len(),
+            3
+        );
+        let err = build_members(members(4), &[], 3, Utc::now()).unwrap_err();
+        assert!(err.to_string().contains("limited to 3 members"));
+    }
+
+    fn positions(members: &[TeamMember]) -> Vec<(String, i32)> {
+        members
+            .iter()
+            .map(|m| (m.agent_id.clone(), m.position))
+            .collect()
+    }
+
+    fn team_of(ids: &[&str]) -> Vec<TeamMember> {
+        build_members(
+            ids.iter().map(|id| member(id, None)).collect(),
+            &[],
+            DEFAULT_MAX_TEAM_MEMBERS,
+            Utc::now(),
+        )
+        .unwrap()
+    }
+
+    fn order(members: &[TeamMember]) -> Vec<&str> {
+        members.iter().map(|m| m.agent_id.as_str()).collect()
+    }
+
+    #[test]
+    fn move_member_to_front() {
+        let moved = move_member(&team_of(&["a", "b", "c", "d"]), "c", 0).unwrap();
+        assert_eq!(order(&moved), vec!["c", "a", "b", "d"]);
+        assert_eq!(
+            positions(&moved)
+                .into_iter()
+                .map(|(_, p)| p)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn move_member_to_middle() {
+        let moved = move_member(&team_of(&["a", "b", "c", "d"]), "a", 2).unwrap();
+        assert_eq!(order(&moved), vec!["b", "c", "a", "d"]);
+        assert_eq!(moved[2].position, 2);
+    }
+
+    #[test]
+    fn move_member_to_end_clamps_position() {
+        let moved = move_member(&team_of(&["a", "b", "c"]), "a", 99).unwrap();
+        assert_eq!(
+            positions(&moved),
+            vec![
+                ("b".to_string(), 0),
+                ("c".to_string(), 1),
+                ("a".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn move_member_rejects_unknown_agent() {
+        assert!(move_member(&team_of(&["a"]), "zzz", 0).is_err());
+    }
+
+    fn team_with(mode: &str, ids: &[&str]) -> Team {
+        let now = Utc::now();
+        Team {
+            id: "t1".to_string(),
+            user_id: LOCAL_USER_ID.to_string(),
+            name: "Team".to_string(),
+            description: None,
+            icon: None,
+            collaboration_mode: mode.to_string(),
+            mode_config: serde_json::json!({}),
+            coordinator_id: None,
+            coordination_rules: Default::default(),
+            output_rules: Default::default(),
+            revision: 0,
+            is_template: false,
+            is_public: false,
+            usage_count: 0,
+            rating: 0.0,
+            rating_count: 0,
+            members: team_of(ids),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn store_with_agents(ids: &[&str]) -> (tempfile::TempDir, SqliteStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        for id in ids {
+            store
+                .agents_upsert(&crate::llm::mock::agent(id, id))
+                .unwrap();
+        }
+        (dir, store)
+    }
+
+    #[test]
+    fn adding_members_stops_at_the_configured_cap() {
+        let (_dir, store) = store_with_agents(&["a", "b", "c"]);
+        store
+            .settings_set(&AppSettings {
+                max_team_members: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+        store
+            .teams_upsert(&team_with("roundtable", &["a"]))
+            .unwrap();
+
+        add_member(&store, "t1", member("b", None)).unwrap();
+        // Re-adding an existing member is still fine at the cap.
+        add_member(&store, "t1", member("b", None)).unwrap();
+        let err = add_member(&store, "t1", member("c", None)).unwrap_err();
+        assert!(err.to_string().contains("limited to 2 members"));
+        assert_eq!(store.teams_get("t1").unwrap().unwrap().members.len(), 2);
+    }
+
+    fn readiness(store: &SqliteStore, team: &Team) -> TeamReadiness {
+        TeamReadiness::from_issues(validate_team(store, team).unwrap())
+    }
+
+    #[test]
+    fn team_updates_check_the_expected_revision() {
+        let (_dir, store) = store_with_agents(&["a", "b"]);
+        store
+            .teams_upsert(&team_with("roundtable", &["a"]))
+            .unwrap();
+        let rename = |name: &str| TeamUpdate {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+
+        let updated = apply_team_update(&store, "t1", rename("Core"), Some(0)).unwrap();
+        assert_eq!(updated.revision, 1);
+        assert!(apply_team_update(&store, "t1", rename("Stale"), Some(0)).is_err());
+        assert_eq!(store.teams_get("t1").unwrap().unwrap().name, "Core");
+
+        add_member(&store, "t1", member("b", None)).unwrap();
+        assert_eq!(store.teams_get("t1").unwrap().unwrap().revision, 2);
+    }
+
+    #[test]
+    fn team_with_existing_agents_is_ready() {
+        let (_dir, store) = store_with_agents(&["a", "b", "c"]);
+        let readiness = readiness(&store, &team_with("debate", &["a", "b", "c"]));
+        assert_eq!(readiness.status, ReadinessStatus::Ready);
+        assert_eq!(readiness.score, 100);
+        assert!(readiness.issues.is_empty());
+    }
+
+    #[test]
+    fn missing_agents_and_small_debates_are_warnings() {
+        let (_dir, store) = store_with_agents(&["a", "b"]);
+        let mut team = team_with("debate", &["a", "b", "gone"]);
+        team.coordinator_id = Some("stranger".to_string());
+
+        let readiness = readiness(&store, &team);
+        assert_eq!(readiness.status, ReadinessStatus::Warnings);
+        assert_eq!(readiness.score, 100 - 3 * READINESS_WARNING_PENALTY);
+        let messages: Vec<&str> = readiness
+            .issues
+            .iter()
+            .map(|i| i.message.as_str())
+            .collect();
+        assert!(messages[0].contains("gone"));
+        assert!(messages[1].contains("at least 3 agents"));
+        assert!(messages[2].contains("stranger"));
+    }
+
+    #[test]
+    fn team_without_usable_agents_is_blocked() {
+        let (_dir, store) = store_with_agents(&[]);
+        let empty = readiness(&store, &team_with("roundtable", &[]));
+        assert_eq!(empty.status, ReadinessStatus::Blocked);
+        assert_eq!(empty.score, 0);
+
+        let dangling = readiness(&store, &team_with("roundtable", &["x", "y"]));
+        assert_eq!(dangling.status, ReadinessStatus::Blocked);
+        assert_eq!(
+            dangling.issues.last().unwrap().severity,
+            IssueSeverity::Blocker
+        );
+
+        let json = serde_json::to_value(&dangling).unwrap();
+        assert_eq!(json["status"], "blocked");
+    }
+}
@@ -0,0 +1,121 @@
+use tauri::State;
+
+use crate::commands::agents::copy_agent;
+use crate::commands::teams::copy_team;
+use crate::error::AppError;
+use crate::seed;
+use crate::state::AppState;
+use crate::store::sqlite::SqliteStore;
+
+/// The id the UI should edit for `kind` ("agent" or "team") `id`: a template
+/// is first copied into a user-owned non-template item so the shared template
+/// stays untouched; anything else is edited in place.
+#[tauri::command]
+pub fn clone_for_edit(
+    state: State<AppState>,
+    kind: String,
+    id: String,
+) -> Result<String, AppError> {
+    editable_id(&state.store, &kind, &id)
+}
+
+/// Re-insert default agents and teams that were deleted, or with
+/// `overwrite` also reset edited ones to their shipped versions. Returns how
+/// many records were written.
+#[tauri::command]
+pub fn reseed_defaults(state: State<AppState>, overwrite: bool) -> Result<usize, AppError> {
+    seed::reseed_defaults(&state.store, overwrite)
+}
+
+fn editable_id(store: &SqliteStore, kind: &str, id: &str) -> Result<String, AppError> {
+    match kind {
+        "agent" => {
+            let agent = store
+                .agents_get(id)?
+                .ok_or_else(|| AppError::Message(format!("Agent {id} not found")))?;
+            if !agent.is_template {
+                return Ok(agent.id);
+            }
+            let name = agent.name.clone();
+            Ok(copy_agent(store, agent, Some(name))?.id)
+        }
+        "team" => {
+            let team = store
+                .teams_get(id)?
+                .ok_or_else(|| AppError::Message(format!("Team {id} not found")))?;
+            if !team.is_template {
+                return Ok(team.id);
+            }
+            let name = team.name.clone();
+            Ok(copy_team(store, team, Some(name))?.id)
+        }
+        other => Err(AppError::Message(format!(
+            "Unknown kind {other}; expected agent or team"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> (tempfile::TempDir, SqliteStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn template_agents_are_cloned_before_editing() {
+        let (_dir, store) = store();
+        let mut template = crate::llm::mock::agent("tpl", "Analyst");
+        template.is_template = true;
+        store.agents_upsert(&template).unwrap();
+        store
+            .agents_upsert(&crate::llm::mock::agent("mine", "Mine"))
+            .unwrap();
+
+        let id = editable_id(&store, "agent", "tpl").unwrap();
+        assert_ne!(id, "tpl");
+        let copy = store.agents_get(&id).unwrap().unwrap();
+        assert!(!copy.is_template);
+        assert_eq!(copy.name, "Analyst");
+        assert_eq!(copy.parent_id.as_deref(), Some("tpl"));
+        assert!(store.agents_get("tpl").unwrap().unwrap().is_template);
+
+        assert_eq!(editable_id(&store, "agent", "mine").unwrap(), "mine");
+        assert_eq!(store.agents_list().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn template_teams_are_cloned_before_editing() {
+        let (_dir, store) = store();
+        let mut template: crate::models::team::Team = serde_json::from_value(serde_json::json!({
+            "id": "tpl",
+            "user_id": "system",
+            "name": "Review board",
+            "description": null,
+            "icon": null,
+            "collaboration_mode": "roundtable",
+            "coordinator_id": null,
+            "is_template": true,
+            "is_public": true,
+            "usage_count": 0,
+            "rating": 0.0,
+            "rating_count": 0,
+            "created_at": chrono::Utc::now(),
+            "updated_at": chrono::Utc::now()
+        }))
+        .unwrap();
+        store.teams_upsert(&template).unwrap();
+        template.id = "mine".to_string();
+        template.is_template = false;
+        store.teams_upsert(&template).unwrap();
+
+        let id = editable_id(&store, "team", "tpl").unwrap();
+        assert_ne!(id, "tpl");
+        assert!(!store.teams_get(&id).unwrap().unwrap().is_template);
+        assert_eq!(editable_id(&store, "team", "mine").unwrap(), "mine");
+        assert!(editable_id(&store, "workflow", "tpl").is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use serde::Serialize;
+use serde_json::Value;
+use tauri::State;
+
+use crate::commands::executions::check_workspaces;
+use crate::error::AppError;
+use crate::models::settings::AppSettings;
+use crate::state::AppState;
+use crate::tools::builtin;
+use crate::tools::definition::{ToolCall, ToolDefinition, ToolResult};
+use crate::tools::executor::ToolExecutor;
+use crate::tools::lock::{is_mutating, WorkspaceLocks};
+use crate::tools::policy::{ToolCategory, ToolPolicy};
+
+/// A tool agents can be given, with the schema sent to the model.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolInfo {
+    #[serde(flatten)]
+    pub definition: ToolDefinition,
+    pub category: ToolCategory,
+    pub mutating: bool,
+}
+
+/// Every builtin tool, including the command-backed ones a team has to
+/// enable with `allow_commands`.
+#[tauri::command]
+pub fn list_tools() -> Vec<ToolInfo> {
+    builtin::definitions()
+        .into_iter()
+        .chain(builtin::git::definitions())
+        .map(|definition| ToolInfo {
+            category: ToolCategory::of(&definition.name),
+            mutating: is_mutating(&definition.name),
+            definition,
+        })
+        .collect()
+}
+
+/// Run one tool against `workspace_path` outside any execution, with the
+/// same path checks, limits and workspace locks as tools called by agents.
+/// `tool_policy` defaults to an execution's, read and search only.
+#[tauri::command]
+pub async fn preview_tool(
+    state: State<'_, AppState>,
+    workspace_path: String,
+    tool_name: String,
+    arguments: Value,
+    tool_policy: Option<ToolPolicy>,
+) -> Result<ToolResult, AppError> {
+    preview(
+        &state.workspace_locks,
+        &state.store.settings_get()?,
+        tool_policy.unwrap_or_default(),
+        &workspace_path,
+        &tool_name,
+        arguments,
+    )
+    .await
+}
+
+async fn preview(
+    locks: &WorkspaceLocks,
+    settings: &AppSettings,
+    policy: ToolPolicy,
+    workspace_path: &str,
+    tool_name: &str,
+    arguments: Value,
+) -> Result<ToolResult, AppError> {
+    let workspace_path = workspace_path.trim();
+    if workspace_path.is_empty() {
+        return Err(AppError::Message("workspace_path is required".to_string()));
+    }
+    check_workspaces(settings, Some(workspace_path), &Default::default())?;
+    let executor = ToolExecutor::new(std::path::PathBuf::from(workspace_path))?.with_locks(locks);
+    if !executor.definitions().iter().any(|d| d.name == tool_name) {
+        return Err(AppError::Message(format!("Unknown tool '{tool_name}'")));
+    }
+    policy.check(tool_name)?;
+    Ok(executor
+        .with_policy(policy)
+        .execute(ToolCall {
+            id: "preview".to_string(),
+            name: tool_name.to_string(),
+            arguments,
+        })
+        .await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.md"), "alpha\nbeta\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn lists_every_builtin_tool_with_a_schema() {
+        let tools = list_tools();
+        let names: Vec<&str> = tools.iter().map(|t| t.definition.name.as_str()).collect();
+        for def in builtin::definitions() {
+            assert!(names.contains(&def.name.as_str()), "{} missing", def.name);
+        }
+        for tool in &tools {
+            assert!(
+                !tool.definition.description.is_empty(),
+                "{}",
+                tool.definition.name
+            );
+            assert_eq!(tool.definition.parameters["type"], "object");
+        }
+
+        let write = tools
+            .iter()
+            .find(|t| t.definition.name == "write_file")
+            .unwrap();
+        assert_eq!(
+            (write.category, write.mutating),
+            (ToolCategory::Write, true)
+        );
+        let json = serde_json::to_value(write).unwrap();
+        assert_eq!(json["category"], "write");
+        assert!(json["parameters"]["properties"]["path"].is_object());
+        let git = tools
+            .iter()
+            .find(|t| t.definition.name == "git_status")
+            .unwrap();
+        assert_eq!(git.category, ToolCategory::Exec);
+        assert!(tools.iter().all(|t| t.category != ToolCategory::Network));
+        assert_eq!(ToolCategory::of("fetch_url"), ToolCategory::Network);
+        assert!(!ToolPolicy::default().allows("fetch_url"));
+    }
+
+    #[tokio::test]
+    async fn runs_read_file_and_search_content() {
+        let dir = workspace();
+        let root = dir.path().to_str().unwrap();
+        let locks = WorkspaceLocks::default();
+        let settings = AppSettings::default();
+
+        let read = preview(
+            &locks,
+            &settings,
+            ToolPolicy::default(),
+            root,
+            "read_file",
+            serde_json::json!({ "path": "notes.md" }),
+        )
+        .await
+        .unwrap();
+        assert!(read.ok);
+        assert_eq!(read.output["content"], "alpha\nbeta\n");
+
+        let search = preview(
+            &locks,
+            &settings,
+            ToolPolicy::default(),
+            root,
+            "search_content",
+            serde_json::json!({ "pattern": "bet" }),
+        )
+        .await
+        .unwrap();
+        assert!(search.ok);
+        assert_eq!(search.output[0]["path"], "notes.md");
+        assert_eq!(search.output[0]["line"], 2);
+    }
+
+    #[tokio::test]
+    async fn tool_errors_and_bad_requests_are_reported() {
+        let dir = workspace();
+        let root = dir.path().to_str().unwrap();
+        let locks = WorkspaceLocks::default();
+        let settings = AppSettings::default();
+
+        let escaped = preview(
+            &locks,
+            &settings,
+            ToolPolicy::default(),
+            root,
+            "read_file",
+            serde_json::json!({ "path": "../x" }),
+        )
+        .await
+        .unwrap();
+        assert!(!escaped.ok);
+        assert!(escaped.error.unwrap().contains(".."));
+
+        let err = preview(
+            &locks,
+            &settings,
+            ToolPolicy::default(),
+            root,
+            "rm_rf",
+            serde_json::json!({}),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("Unknown tool"));
+        assert!(preview(
+            &locks,
+            &settings,
+            ToolPolicy::default(),
+            " ",
+            "read_file",
+            serde_json::json!({})
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn workspaces_outside_the_allowed_roots_are_refused() {
+        let dir = workspace();
+        let allowed = tempfile::tempdir().unwrap();
+        let locks = WorkspaceLocks::default();
+        let settings = AppSettings {
+            allowed_workspace_roots: vec![allowed.path().to_string_lossy().to_string()],
+            ..Default::default()
+        };
+
+        let err = preview(
+            &locks,
+            &settings,
+            ToolPolicy::default(),
+            dir.path().to_str().unwrap(),
+            "read_file",
+            serde_json::json!({ "path": "notes.md" }),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("allowed"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn writes_need_a_policy_that_allows_them() {
+        let dir = workspace();
+        let root = dir.path().to_str().unwrap();
+        let locks = WorkspaceLocks::default();
+        let settings = AppSettings::default();
+        let write = || serde_json::json!({ "path": "new.md", "content": "x" });
+
+        let denied = preview(
+            &locks,
+            &settings,
+            ToolPolicy::default(),
+            root,
+            "write_file",
+            write(),
+        )
+        .await
+        .unwrap_err();
+        assert!(denied.to_string().contains("'write' category"), "{denied}");
+        assert!(!dir.path().join("new.md").exists());
+
+        let allowed = preview(
+            &locks,
+            &settings,
+            ToolPolicy::unrestricted(),
+            root,
+            "write_file",
+            write(),
+        )
+        .await
+        .unwrap();
+        assert!(allowed.ok);
+        assert!(dir.path().join("new.md").exists());
+    }
+}
@@ -1,9 +1,23 @@
 use thiserror::Error;
 
+use crate::llm::error::ProviderError;
+
 #[derive(Debug, Error)]
 pub enum AppError {
     #[error("{0}")]
     Message(String),
+    #[error("{0}")]
+    Provider(ProviderError),
+}
+
+impl AppError {
+    /// The structured provider failure, if this error came from an LLM request.
+    pub fn provider_error(&self) -> Option<&ProviderError> {
+        match self {
+            AppError::Provider(e) => Some(e),
+            AppError::Message(_) => None,
+        }
+    }
 }
 
 impl From<anyhow::Error> for AppError {
@@ -2,8 +2,10 @@ pub mod agents;
 pub mod commands;
 pub mod error;
 pub mod llm;
+pub mod metrics;
 pub mod models;
 pub mod orchestration;
+pub mod sanitize;
 pub mod seed;
 pub mod state;
 pub mod store;
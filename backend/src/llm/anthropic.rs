@@ -1,22 +1,39 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, USER_AGENT};
 use serde::Deserialize;
 
 use crate::error::AppError;
+use crate::llm::error::ProviderError;
+use crate::llm::headers::extra_header_map;
 use crate::llm::provider::{
-    estimate_tokens, LLMProvider, LLMResponse, Message, MessageRole, TokenUsage,
+    estimate_tokens, LLMProvider, LLMResponse, Message, MessageRole, TokenUsage, ToolChoice,
 };
+use crate::llm::rate_limit::RateLimitInfo;
+use crate::models::llm::SamplingParams;
 use crate::tools::definition::{ToolCall, ToolDefinition};
 
+/// Output limit used when a request doesn't set one.
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
 #[derive(Clone)]
 pub struct AnthropicProvider {
     client: reqwest::Client,
     model: String,
     base_url: String,
+    thinking_budget: Option<u32>,
+    prompt_cache: bool,
+    sampling: SamplingParams,
 }
 
 impl AnthropicProvider {
-    pub fn new(api_key: String, model: String, base_url: Option<String>) -> Result<Self, AppError> {
+    pub fn new(
+        api_key: String,
+        model: String,
+        base_url: Option<String>,
+        extra_headers: &HashMap<String, String>,
+    ) -> Result<Self, AppError> {
         let base_url = base_url
             .unwrap_or_else(|| "https://api.anthropic.com".to_string())
             .trim_end_matches('/')
@@ -36,6 +53,8 @@ impl AnthropicProvider {
             ),
         );
 
+        headers.extend(extra_header_map(extra_headers)?);
+
         let client = reqwest::Client::builder()
             .default_headers(headers)
             .timeout(std::time::Duration::from_secs(60))
@@ -46,13 +65,113 @@ impl AnthropicProvider {
             client,
             model,
             base_url,
+            thinking_budget: None,
+            prompt_cache: false,
+            sampling: SamplingParams::default(),
         })
     }
 
+    /// Enable extended thinking with a budget derived from `effort`.
+    pub fn with_reasoning_effort(mut self, effort: Option<String>) -> Self {
+        self.thinking_budget = effort.as_deref().and_then(thinking_budget);
+        self
+    }
+
+    /// Send the system prompt as a block marked with an ephemeral
+    /// `cache_control`, so repeated prompts are read from the prompt cache.
+    pub fn with_prompt_cache(mut self, enabled: bool) -> Self {
+        self.prompt_cache = enabled;
+        self
+    }
+
+    /// Send `top_p` and `top_k` when set; the penalties have no Anthropic
+    /// equivalent and are ignored.
+    pub fn with_sampling(mut self, sampling: SamplingParams) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    fn apply_sampling(&self, body: &mut serde_json::Value) {
+        if let Some(top_p) = self.sampling.top_p {
+            body["top_p"] = serde_json::json!(top_p);
+        }
+        if let Some(top_k) = self.sampling.top_k {
+            body["top_k"] = serde_json::json!(top_k);
+        }
+    }
+
+    fn system_value(&self, system: String) -> serde_json::Value {
+        if !self.prompt_cache {
+            return serde_json::Value::String(system);
+        }
+        serde_json::json!([{
+            "type": "text",
+            "text": system,
+            "cache_control": { "type": "ephemeral" }
+        }])
+    }
+
+    /// Add `thinking` to `body`. Thinking needs the default temperature and
+    /// `top_k` and a `max_tokens` above the budget, so the budget is added on top.
+    fn apply_thinking(&self, body: &mut serde_json::Value, max_tokens: u32) {
+        let Some(budget) = self.thinking_budget else {
+            return;
+        };
+        body["thinking"] = serde_json::json!({ "type": "enabled", "budget_tokens": budget });
+        body["max_tokens"] = serde_json::json!(max_tokens.saturating_add(budget));
+        if let Some(obj) = body.as_object_mut() {
+            obj.remove("temperature");
+            obj.remove("top_k");
+        }
+    }
+
     fn endpoint(&self) -> String {
         format!("{}/v1/messages", self.base_url.trim_end_matches('/'))
     }
 
+    fn tools_request_body(
+        &self,
+        messages: Vec<Message>,
+        tools: &[ToolDefinition],
+        tool_choice: &ToolChoice,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> serde_json::Value {
+        let max_tokens = required_max_tokens(max_tokens);
+        let has_tool_calls = messages.iter().any(|m| m.tool_calls.is_some());
+        let (system, converted) = self.convert_messages_with_tools(messages);
+        let tool_defs = tools
+            .iter()
+            .map(|t| {
+                serde_json::json!({
+                    "name": t.name,
+                    "description": t.description,
+                    "input_schema": t.parameters
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "messages": converted,
+            "max_tokens": max_tokens,
+            "temperature": temperature,
+            "tools": tool_defs
+        });
+        if let Some(system) = system {
+            body["system"] = self.system_value(system);
+        }
+        body["tool_choice"] = anthropic_tool_choice(tool_choice);
+        self.apply_sampling(&mut body);
+        // Thinking can't be combined with a forced tool, and replaying a tool
+        // turn would need the thinking blocks we don't keep, so it only
+        // applies to the first request of a turn.
+        if matches!(tool_choice, ToolChoice::Auto) && !has_tool_calls {
+            self.apply_thinking(&mut body, max_tokens);
+        }
+        body
+    }
+
     fn convert_messages(&self, messages: Vec<Message>) -> (Option<String>, Vec<serde_json::Value>) {
         let mut system: Option<String> = None;
         let mut out = Vec::new();
@@ -133,8 +252,80 @@ impl AnthropicProvider {
         } else {
             Some(system_parts.join("\n\n"))
         };
-        (system, out)
+        (system, order_tool_results(out))
+    }
+}
+
+/// Anthropic requires the `tool_result` blocks answering an assistant's
+/// `tool_use` blocks to open the very next message, which must be a user
+/// message. Hoist every result to just after the turn that asked for it and
+/// merge consecutive same-role messages, so user text interleaved into the
+/// history (peer opinions, nudges) can't split the pair. A `tool_use` left
+/// unanswered gets an error result; a result nothing asked for is dropped.
+fn order_tool_results(messages: Vec<serde_json::Value>) -> Vec<serde_json::Value> {
+    let mut results: HashMap<String, serde_json::Value> = HashMap::new();
+    let mut stripped = Vec::new();
+    for mut msg in messages {
+        if msg["role"] == "user" {
+            if let Some(blocks) = msg["content"].as_array_mut() {
+                blocks.retain(|block| {
+                    if block["type"] != "tool_result" {
+                        return true;
+                    }
+                    let id = block["tool_use_id"].as_str().unwrap_or_default();
+                    results.insert(id.to_string(), block.clone());
+                    false
+                });
+                if blocks.is_empty() {
+                    continue;
+                }
+            }
+        }
+        stripped.push(msg);
+    }
+
+    let mut out: Vec<serde_json::Value> = Vec::new();
+    let mut push = |msg: serde_json::Value| match out.last_mut() {
+        Some(last) if last["role"] == msg["role"] => {
+            let blocks = msg["content"].as_array().cloned().unwrap_or_default();
+            if let Some(content) = last["content"].as_array_mut() {
+                content.extend(blocks);
+            }
+        }
+        _ => out.push(msg),
+    };
+    for msg in stripped {
+        let tool_use_ids = msg["content"]
+            .as_array()
+            .map(|blocks| {
+                blocks
+                    .iter()
+                    .filter(|b| b["type"] == "tool_use")
+                    .filter_map(|b| b["id"].as_str().map(str::to_string))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let is_assistant = msg["role"] == "assistant";
+        push(msg);
+        if !is_assistant || tool_use_ids.is_empty() {
+            continue;
+        }
+        let answers = tool_use_ids
+            .into_iter()
+            .map(|id| {
+                results.remove(&id).unwrap_or_else(|| {
+                    serde_json::json!({
+                        "type": "tool_result",
+                        "tool_use_id": id,
+                        "content": "No result was recorded for this tool call.",
+                        "is_error": true
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        push(serde_json::json!({ "role": "user", "content": answers }));
     }
+    out
 }
 
 #[async_trait]
@@ -153,6 +344,7 @@ impl LLMProvider for AnthropicProvider {
         temperature: f64,
         max_tokens: u32,
     ) -> Result<LLMResponse, AppError> {
+        let max_tokens = required_max_tokens(max_tokens);
         let (system, converted) = self.convert_messages(messages);
 
         let mut body = serde_json::json!({
@@ -162,8 +354,10 @@ impl LLMProvider for AnthropicProvider {
             "temperature": temperature
         });
         if let Some(system) = system {
-            body["system"] = serde_json::Value::String(system);
+            body["system"] = self.system_value(system);
         }
+        self.apply_sampling(&mut body);
+        self.apply_thinking(&mut body, max_tokens);
 
         let resp = self
             .client
@@ -176,39 +370,21 @@ impl LLMProvider for AnthropicProvider {
         if !resp.status().is_success() {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_else(|_| "".to_string());
-            return Err(AppError::Message(format!(
-                "Anthropic error: {status} {text}"
+            return Err(AppError::Provider(ProviderError::from_response(
+                self.provider_name(),
+                status.as_u16(),
+                &text,
             )));
         }
 
+        let rate_limit = RateLimitInfo::from_headers(resp.headers());
         let parsed: AnthropicMessageResponse = resp
             .json()
             .await
             .map_err(|e| AppError::Message(e.to_string()))?;
-
-        let mut content = String::new();
-        for block in parsed.content {
-            if block.r#type == "text" {
-                content.push_str(&block.text.unwrap_or_default());
-            }
-        }
-
-        let prompt_tokens = parsed.usage.input_tokens;
-        let completion_tokens = parsed.usage.output_tokens;
-        let estimated = prompt_tokens.is_none() || completion_tokens.is_none();
-        let input_tokens = prompt_tokens.unwrap_or_else(|| estimate_tokens(&body.to_string()));
-        let output_tokens = completion_tokens.unwrap_or_else(|| estimate_tokens(&content));
-
         Ok(LLMResponse {
-            content,
-            usage: TokenUsage {
-                input_tokens,
-                output_tokens,
-                estimated,
-            },
-            model: parsed.model.unwrap_or_else(|| self.model.clone()),
-            finish_reason: parsed.stop_reason,
-            tool_calls: Vec::new(),
+            rate_limit,
+            ..parsed.into_response(&self.model, &body)
         })
     }
 
@@ -216,6 +392,7 @@ impl LLMProvider for AnthropicProvider {
         &self,
         messages: Vec<Message>,
         tools: &[ToolDefinition],
+        tool_choice: &ToolChoice,
         temperature: f64,
         max_tokens: u32,
     ) -> Result<LLMResponse, AppError> {
@@ -223,28 +400,7 @@ impl LLMProvider for AnthropicProvider {
             return self.chat(messages, temperature, max_tokens).await;
         }
 
-        let (system, converted) = self.convert_messages_with_tools(messages);
-        let tool_defs = tools
-            .iter()
-            .map(|t| {
-                serde_json::json!({
-                    "name": t.name,
-                    "description": t.description,
-                    "input_schema": t.parameters
-                })
-            })
-            .collect::<Vec<_>>();
-
-        let mut body = serde_json::json!({
-            "model": self.model,
-            "messages": converted,
-            "max_tokens": max_tokens,
-            "temperature": temperature,
-            "tools": tool_defs
-        });
-        if let Some(system) = system {
-            body["system"] = serde_json::Value::String(system);
-        }
+        let body = self.tools_request_body(messages, tools, tool_choice, temperature, max_tokens);
 
         let resp = self
             .client
@@ -257,19 +413,70 @@ impl LLMProvider for AnthropicProvider {
         if !resp.status().is_success() {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_else(|_| "".to_string());
-            return Err(AppError::Message(format!(
-                "Anthropic error: {status} {text}"
+            return Err(AppError::Provider(ProviderError::from_response(
+                self.provider_name(),
+                status.as_u16(),
+                &text,
             )));
         }
 
+        let rate_limit = RateLimitInfo::from_headers(resp.headers());
         let parsed: AnthropicMessageResponse = resp
             .json()
             .await
             .map_err(|e| AppError::Message(e.to_string()))?;
+        Ok(LLMResponse {
+            rate_limit,
+            ..parsed.into_response(&self.model, &body)
+        })
+    }
+}
+
+/// Extended-thinking budget for a `reasoning_effort`: a named level or an
+/// explicit token count (at least the API minimum of 1024).
+fn thinking_budget(effort: &str) -> Option<u32> {
+    match effort {
+        "low" => Some(1024),
+        "medium" => Some(4096),
+        "high" => Some(16384),
+        other => other.parse::<u32>().ok().map(|n| n.max(1024)),
+    }
+}
+
+/// `max_tokens` to send. The Messages API requires a positive value, unlike
+/// OpenAI where it's optional, so an unset (zero) limit gets a default.
+fn required_max_tokens(max_tokens: u32) -> u32 {
+    if max_tokens == 0 {
+        DEFAULT_MAX_TOKENS
+    } else {
+        max_tokens
+    }
+}
+
+fn anthropic_tool_choice(choice: &ToolChoice) -> serde_json::Value {
+    match choice {
+        ToolChoice::Auto => serde_json::json!({ "type": "auto" }),
+        ToolChoice::Tool(name) => serde_json::json!({ "type": "tool", "name": name }),
+        ToolChoice::None => serde_json::json!({ "type": "none" }),
+    }
+}
 
+#[derive(Debug, Deserialize)]
+struct AnthropicMessageResponse {
+    pub model: Option<String>,
+    pub content: Vec<AnthropicContentBlock>,
+    pub stop_reason: Option<String>,
+    pub usage: AnthropicUsage,
+}
+
+impl AnthropicMessageResponse {
+    /// Collect text and `tool_use` blocks. `stop_reason` is kept raw
+    /// (`end_turn`, `max_tokens`, `tool_use`, `stop_sequence`, ...); callers
+    /// map it with `normalize_finish_reason`.
+    fn into_response(self, fallback_model: &str, request_body: &serde_json::Value) -> LLMResponse {
         let mut content = String::new();
         let mut tool_calls: Vec<ToolCall> = Vec::new();
-        for block in parsed.content {
+        for block in self.content {
             match block.r#type.as_str() {
                 "text" => {
                     if let Some(text) = block.text {
@@ -290,8 +497,8 @@ impl LLMProvider for AnthropicProvider {
             }
         }
 
-        let prompt_tokens = parsed.usage.input_tokens;
-        let completion_tokens = parsed.usage.output_tokens;
+        let prompt_tokens = self.usage.input_tokens;
+        let completion_tokens = self.usage.output_tokens;
         let estimated = prompt_tokens.is_none() || completion_tokens.is_none();
 
         let output_estimate_text = if tool_calls.is_empty() {
@@ -303,29 +510,26 @@ impl LLMProvider for AnthropicProvider {
             )
         };
 
-        Ok(LLMResponse {
+        LLMResponse {
             content,
             usage: TokenUsage {
-                input_tokens: prompt_tokens.unwrap_or_else(|| estimate_tokens(&body.to_string())),
+                input_tokens: prompt_tokens
+                    .unwrap_or_else(|| estimate_tokens(&request_body.to_string())),
                 output_tokens: completion_tokens
                     .unwrap_or_else(|| estimate_tokens(&output_estimate_text)),
                 estimated,
+                reasoning_tokens: 0,
+                cache_read_tokens: self.usage.cache_read_input_tokens.unwrap_or(0),
+                cache_write_tokens: self.usage.cache_creation_input_tokens.unwrap_or(0),
             },
-            model: parsed.model.unwrap_or_else(|| self.model.clone()),
-            finish_reason: parsed.stop_reason,
+            model: self.model.unwrap_or_else(|| fallback_model.to_string()),
+            finish_reason: self.stop_reason,
             tool_calls,
-        })
+            rate_limit: None,
+        }
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct AnthropicMessageResponse {
-    pub model: Option<String>,
-    pub content: Vec<AnthropicContentBlock>,
-    pub stop_reason: Option<String>,
-    pub usage: AnthropicUsage,
-}
-
 #[derive(Debug, Deserialize)]
 struct AnthropicContentBlock {
     pub r#type: String,
@@ -339,4 +543,375 @@ struct AnthropicContentBlock {
 struct AnthropicUsage {
     pub input_tokens: Option<u32>,
     pub output_tokens: Option<u32>,
+    #[serde(default)]
+    pub cache_read_input_tokens: Option<u32>,
+    #[serde(default)]
+    pub cache_creation_input_tokens: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tools() -> Vec<ToolDefinition> {
+        vec![ToolDefinition {
+            name: "read_file".to_string(),
+            description: String::new(),
+            parameters: serde_json::json!({ "type": "object" }),
+        }]
+    }
+
+    #[test]
+    fn forced_tool_is_sent_as_tool_choice() {
+        let provider = AnthropicProvider::new(
+            "key".to_string(),
+            "claude".to_string(),
+            None,
+            &HashMap::new(),
+        )
+        .unwrap();
+        let choice = ToolChoice::Tool("read_file".to_string());
+        let body = provider.tools_request_body(Vec::new(), &tools(), &choice, 0.2, 64);
+        assert_eq!(
+            body["tool_choice"],
+            serde_json::json!({ "type": "tool", "name": "read_file" })
+        );
+    }
+
+    #[test]
+    fn sampling_params_are_sent_when_set() {
+        let provider = AnthropicProvider::new(
+            "key".to_string(),
+            "claude".to_string(),
+            None,
+            &HashMap::new(),
+        )
+        .unwrap();
+        let body = provider.tools_request_body(Vec::new(), &tools(), &ToolChoice::Auto, 0.2, 1000);
+        assert!(body.get("top_p").is_none());
+        assert!(body.get("top_k").is_none());
+
+        let provider = provider.with_sampling(SamplingParams {
+            top_p: Some(0.8),
+            top_k: Some(20),
+            frequency_penalty: Some(1.0),
+            presence_penalty: None,
+        });
+        let body = provider.tools_request_body(Vec::new(), &tools(), &ToolChoice::Auto, 0.2, 1000);
+        assert_eq!(body["top_p"], 0.8);
+        assert_eq!(body["top_k"], 20);
+        assert!(body.get("frequency_penalty").is_none());
+
+        let thinking = provider.with_reasoning_effort(Some("low".to_string()));
+        let body = thinking.tools_request_body(Vec::new(), &tools(), &ToolChoice::Auto, 0.2, 1000);
+        assert_eq!(body["top_p"], 0.8);
+        assert!(body.get("top_k").is_none());
+    }
+
+    #[test]
+    fn reasoning_effort_enables_thinking_on_the_first_request() {
+        let provider = AnthropicProvider::new(
+            "key".to_string(),
+            "claude".to_string(),
+            None,
+            &HashMap::new(),
+        )
+        .unwrap()
+        .with_reasoning_effort(Some("medium".to_string()));
+        let body = provider.tools_request_body(Vec::new(), &tools(), &ToolChoice::Auto, 0.2, 1000);
+        assert_eq!(
+            body["thinking"],
+            serde_json::json!({ "type": "enabled", "budget_tokens": 4096 })
+        );
+        assert_eq!(body["max_tokens"], 5096);
+        assert!(body.get("temperature").is_none());
+
+        let forced = ToolChoice::Tool("read_file".to_string());
+        let body = provider.tools_request_body(Vec::new(), &tools(), &forced, 0.2, 1000);
+        assert!(body.get("thinking").is_none());
+        assert_eq!(body["max_tokens"], 1000);
+
+        assert_eq!(thinking_budget("high"), Some(16384));
+        assert_eq!(thinking_budget("2000"), Some(2000));
+        assert_eq!(thinking_budget("10"), Some(1024));
+        assert_eq!(thinking_budget("none"), None);
+    }
+
+    #[test]
+    fn auto_tool_choice_by_default() {
+        let provider = AnthropicProvider::new(
+            "key".to_string(),
+            "claude".to_string(),
+            None,
+            &HashMap::new(),
+        )
+        .unwrap();
+        let choice = ToolChoice::default();
+        let body = provider.tools_request_body(Vec::new(), &tools(), &choice, 0.2, 64);
+        assert_eq!(body["tool_choice"], serde_json::json!({ "type": "auto" }));
+        let body = provider.tools_request_body(Vec::new(), &tools(), &ToolChoice::None, 0.2, 64);
+        assert_eq!(body["tool_choice"], serde_json::json!({ "type": "none" }));
+    }
+
+    fn parse(stop_reason: &str, content: serde_json::Value) -> LLMResponse {
+        let parsed: AnthropicMessageResponse = serde_json::from_value(serde_json::json!({
+            "model": "claude",
+            "content": content,
+            "stop_reason": stop_reason,
+            "usage": { "input_tokens": 10, "output_tokens": 5 }
+        }))
+        .unwrap();
+        parsed.into_response("claude", &serde_json::json!({}))
+    }
+
+    #[test]
+    fn stop_reasons_map_to_the_common_vocabulary() {
+        use crate::llm::provider::normalize_finish_reason;
+
+        let text = serde_json::json!([{ "type": "text", "text": "hi" }]);
+        for (raw, normalized) in [
+            ("end_turn", "stop"),
+            ("stop_sequence", "stop"),
+            ("max_tokens", "length"),
+            ("tool_use", "tool_calls"),
+        ] {
+            let resp = parse(raw, text.clone());
+            assert_eq!(resp.finish_reason.as_deref(), Some(raw));
+            assert_eq!(normalize_finish_reason(raw), normalized);
+        }
+
+        let resp = parse(
+            "tool_use",
+            serde_json::json!([
+                { "type": "text", "text": "Let me look." },
+                { "type": "tool_use", "id": "tu_1", "name": "read_file", "input": { "path": "a.txt" } }
+            ]),
+        );
+        assert_eq!(resp.content, "Let me look.");
+        assert_eq!(resp.tool_calls.len(), 1);
+        assert_eq!(resp.tool_calls[0].arguments["path"], "a.txt");
+    }
+
+    #[test]
+    fn max_tokens_is_always_sent() {
+        let provider = AnthropicProvider::new(
+            "key".to_string(),
+            "claude".to_string(),
+            None,
+            &HashMap::new(),
+        )
+        .unwrap();
+        let body = provider.tools_request_body(Vec::new(), &tools(), &ToolChoice::Auto, 0.2, 0);
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+    }
+
+    #[test]
+    fn prompt_cache_marks_the_system_block_cacheable() {
+        let provider = |enabled| {
+            AnthropicProvider::new(
+                "key".to_string(),
+                "claude".to_string(),
+                None,
+                &HashMap::new(),
+            )
+            .unwrap()
+            .with_prompt_cache(enabled)
+        };
+        let messages = || {
+            vec![Message {
+                role: MessageRole::System,
+                content: Some("You are a critic.".to_string()),
+                name: None,
+                tool_call_id: None,
+                tool_calls: None,
+                images: Vec::new(),
+            }]
+        };
+
+        let body =
+            provider(true).tools_request_body(messages(), &tools(), &ToolChoice::Auto, 0.2, 64);
+        assert_eq!(
+            body["system"],
+            serde_json::json!([{
+                "type": "text",
+                "text": "You are a critic.",
+                "cache_control": { "type": "ephemeral" }
+            }])
+        );
+        let body =
+            provider(false).tools_request_body(messages(), &tools(), &ToolChoice::Auto, 0.2, 64);
+        assert_eq!(body["system"], "You are a critic.");
+    }
+
+    #[test]
+    fn usage_parses_cached_tokens() {
+        let parsed: AnthropicMessageResponse = serde_json::from_value(serde_json::json!({
+            "content": [{ "type": "text", "text": "ok" }],
+            "stop_reason": "end_turn",
+            "usage": {
+                "input_tokens": 12,
+                "output_tokens": 5,
+                "cache_read_input_tokens": 900,
+                "cache_creation_input_tokens": 40
+            }
+        }))
+        .unwrap();
+        let usage = parsed.into_response("claude", &serde_json::json!({})).usage;
+        assert_eq!((usage.input_tokens, usage.output_tokens), (12, 5));
+        assert_eq!(usage.cache_read_tokens, 900);
+        assert_eq!(usage.cache_write_tokens, 40);
+
+        assert_eq!(
+            parse("end_turn", serde_json::json!([]))
+                .usage
+                .cache_read_tokens,
+            0
+        );
+    }
+
+    fn tool_use_turn(ids: &[&str]) -> Message {
+        Message {
+            role: MessageRole::Assistant,
+            content: None,
+            name: None,
+            tool_call_id: None,
+            tool_calls: Some(
+                ids.iter()
+                    .map(|id| ToolCall {
+                        id: id.to_string(),
+                        name: "read_file".to_string(),
+                        arguments: serde_json::json!({ "path": "a.txt" }),
+                    })
+                    .collect(),
+            ),
+            images: Vec::new(),
+        }
+    }
+
+    fn text(role: MessageRole, content: &str) -> Message {
+        Message {
+            role,
+            content: Some(content.to_string()),
+            name: None,
+            tool_call_id: None,
+            tool_calls: None,
+            images: Vec::new(),
+        }
+    }
+
+    fn tool_result(id: &str) -> Message {
+        Message {
+            tool_call_id: Some(id.to_string()),
+            ..text(MessageRole::Tool, "{\"ok\":true}")
+        }
+    }
+
+    /// Roles alternate, and every `tool_use` turn is followed by a user
+    /// message opening with the matching `tool_result` blocks, in order.
+    fn assert_valid_ordering(messages: &[serde_json::Value]) {
+        for pair in messages.windows(2) {
+            assert_ne!(pair[0]["role"], pair[1]["role"], "roles must alternate");
+        }
+        for (idx, msg) in messages.iter().enumerate() {
+            let uses = msg["content"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|b| b["type"] == "tool_use")
+                .map(|b| b["id"].clone())
+                .collect::<Vec<_>>();
+            if uses.is_empty() {
+                continue;
+            }
+            let next = &messages[idx + 1];
+            assert_eq!(next["role"], "user");
+            let answered = next["content"].as_array().unwrap()[..uses.len()]
+                .iter()
+                .map(|b| {
+                    assert_eq!(b["type"], "tool_result");
+                    b["tool_use_id"].clone()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(answered, uses);
+        }
+    }
+
+    #[test]
+    fn tool_results_directly_follow_their_tool_use_across_iterations() {
+        let provider = AnthropicProvider::new(
+            "key".to_string(),
+            "claude".to_string(),
+            None,
+            &HashMap::new(),
+        )
+        .unwrap();
+        let messages = vec![
+            text(MessageRole::System, "You are A."),
+            text(MessageRole::User, "topic"),
+            tool_use_turn(&["tu_1"]),
+            // A peer opinion slipped in between the call and its result.
+            text(MessageRole::User, "B: I disagree."),
+            tool_result("tu_1"),
+            tool_use_turn(&["tu_2", "tu_3"]),
+            tool_result("tu_3"),
+            tool_result("tu_2"),
+            text(MessageRole::User, "Please cite the files you read."),
+        ];
+        let body = provider.tools_request_body(messages, &tools(), &ToolChoice::Auto, 0.2, 64);
+        let converted = body["messages"].as_array().unwrap();
+        assert_valid_ordering(converted);
+
+        // The interleaved text and the nudge follow the results they displaced.
+        let after_first = converted[2]["content"].as_array().unwrap();
+        assert_eq!(after_first[1]["text"], "B: I disagree.");
+        let last = converted.last().unwrap()["content"].as_array().unwrap();
+        assert_eq!(last.len(), 3);
+        assert_eq!(last[2]["text"], "Please cite the files you read.");
+    }
+
+    #[test]
+    fn parallel_tool_results_share_one_user_message() {
+        let provider = AnthropicProvider::new(
+            "key".to_string(),
+            "claude".to_string(),
+            None,
+            &HashMap::new(),
+        )
+        .unwrap();
+        // The agent loop's layout: one Tool message per call, in call order.
+        let messages = vec![
+            text(MessageRole::User, "topic"),
+            tool_use_turn(&["tu_1", "tu_2", "tu_3"]),
+            tool_result("tu_1"),
+            tool_result("tu_2"),
+            tool_result("tu_3"),
+        ];
+        let body = provider.tools_request_body(messages, &tools(), &ToolChoice::Auto, 0.2, 64);
+        let converted = body["messages"].as_array().unwrap();
+        assert_valid_ordering(converted);
+        assert_eq!(converted.len(), 3);
+        let results = converted[2]["content"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|b| b["type"] == "tool_result"));
+    }
+
+    #[test]
+    fn unanswered_tool_use_gets_an_error_result() {
+        let ordered = order_tool_results(vec![
+            serde_json::json!({ "role": "user", "content": [{ "type": "text", "text": "go" }] }),
+            serde_json::json!({
+                "role": "assistant",
+                "content": [{ "type": "tool_use", "id": "tu_1", "name": "read_file", "input": {} }]
+            }),
+            serde_json::json!({
+                "role": "user",
+                "content": [{ "type": "tool_result", "tool_use_id": "stray", "content": "x" }]
+            }),
+        ]);
+        assert_valid_ordering(&ordered);
+        assert_eq!(ordered.len(), 3);
+        let result = &ordered[2]["content"][0];
+        assert_eq!(result["tool_use_id"], "tu_1");
+        assert_eq!(result["is_error"], true);
+    }
 }
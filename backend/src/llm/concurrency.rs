@@ -0,0 +1,194 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Notify;
+
+use crate::error::AppError;
+use crate::metrics::Metrics;
+
+/// Concurrency an execution starts at when its team sets no `max_concurrency`.
+pub const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Upper bound for a configured `max_concurrency`.
+pub const MAX_CONCURRENCY: usize = 32;
+
+/// AIMD limit on in-flight LLM requests, shared by every agent launched in
+/// an execution round: a rate-limited (429) response halves the limit, and
+/// each success raises it by `1 / limit`, i.e. about one slot per window of
+/// successful requests, back up to the configured maximum.
+#[derive(Debug)]
+pub struct AdaptiveConcurrency {
+    max: usize,
+    state: Mutex<Window>,
+    released: Notify,
+    metrics: Option<Arc<Metrics>>,
+}
+
+#[derive(Debug)]
+struct Window {
+    limit: f64,
+    in_flight: usize,
+}
+
+/// A held slot; dropping it frees the slot for the next waiting request.
+pub struct ConcurrencyPermit<'a> {
+    controller: &'a AdaptiveConcurrency,
+}
+
+impl Drop for ConcurrencyPermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.controller.state.lock().unwrap();
+        state.in_flight = state.in_flight.saturating_sub(1);
+        drop(state);
+        self.controller.released.notify_waiters();
+    }
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(max: usize) -> Self {
+        let max = max.clamp(1, MAX_CONCURRENCY);
+        Self {
+            max,
+            state: Mutex::new(Window {
+                limit: max as f64,
+                in_flight: 0,
+            }),
+            released: Notify::new(),
+            metrics: None,
+        }
+    }
+
+    /// Read `max_concurrency` from a team's `mode_config`.
+    pub fn from_mode_config(mode_config: &serde_json::Value) -> Self {
+        let max = mode_config
+            .get("max_concurrency")
+            .and_then(|v| v.as_u64())
+            .map_or(DEFAULT_MAX_CONCURRENCY, |n| n as usize);
+        Self::new(max)
+    }
+
+    /// Publish the effective limit to `metrics` whenever it changes.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        metrics.set_effective_concurrency(self.current() as u64);
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// The number of requests currently allowed in flight.
+    pub fn current(&self) -> usize {
+        (self.state.lock().unwrap().limit as usize).max(1)
+    }
+
+    /// Wait for a free slot under the current limit.
+    pub async fn acquire(&self) -> ConcurrencyPermit<'_> {
+        loop {
+            let released = self.released.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < (state.limit as usize).max(1) {
+                    state.in_flight += 1;
+                    return ConcurrencyPermit { controller: self };
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Adjust the limit from a request's outcome. Errors other than rate
+    /// limiting leave it unchanged.
+    pub fn record<T>(&self, result: &Result<T, AppError>) {
+        let rate_limited = match result {
+            Ok(_) => false,
+            Err(e) => match e.provider_error() {
+                Some(pe) => pe.http_status == 429 || pe.code == "rate_limited",
+                None => return,
+            },
+        };
+        let (before, after) = {
+            let mut state = self.state.lock().unwrap();
+            let before = (state.limit as usize).max(1);
+            state.limit = if rate_limited {
+                (state.limit / 2.0).max(1.0)
+            } else {
+                (state.limit + 1.0 / state.limit).min(self.max as f64)
+            };
+            (before, (state.limit as usize).max(1))
+        };
+        if after != before {
+            if let Some(metrics) = &self.metrics {
+                metrics.set_effective_concurrency(after as u64);
+            }
+        }
+        // A raised limit may admit requests that are already waiting.
+        if after > before {
+            self.released.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::llm::error::ProviderError;
+
+    fn rate_limited() -> Result<(), AppError> {
+        Err(AppError::Provider(ProviderError::from_response(
+            "openai_compatible",
+            429,
+            r#"{"error":{"message":"slow down","code":"rate_limit_exceeded"}}"#,
+        )))
+    }
+
+    #[test]
+    fn rate_limits_halve_and_successes_recover_the_limit() {
+        let metrics = Arc::new(Metrics::default());
+        let controller = AdaptiveConcurrency::new(8).with_metrics(metrics.clone());
+        assert_eq!(metrics.snapshot().effective_concurrency, 8);
+
+        for expected in [4, 2, 1, 1] {
+            controller.record(&rate_limited());
+            assert_eq!(controller.current(), expected);
+        }
+        assert_eq!(metrics.snapshot().effective_concurrency, 1);
+
+        // Other failures don't count either way.
+        controller.record::<()>(&Err(AppError::Message("boom".to_string())));
+        assert_eq!(controller.current(), 1);
+
+        controller.record(&Ok(()));
+        assert_eq!(controller.current(), 2);
+        for _ in 0..100 {
+            controller.record(&Ok(()));
+        }
+        assert_eq!(controller.current(), 8);
+        assert_eq!(metrics.snapshot().effective_concurrency, 8);
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_for_a_slot_under_the_limit() {
+        let controller = AdaptiveConcurrency::new(2);
+        controller.record(&rate_limited());
+        assert_eq!(controller.current(), 1);
+
+        let held = controller.acquire().await;
+        let blocked = tokio::time::timeout(Duration::from_millis(20), controller.acquire()).await;
+        assert!(blocked.is_err());
+
+        drop(held);
+        let next = tokio::time::timeout(Duration::from_millis(200), controller.acquire()).await;
+        assert!(next.is_ok());
+    }
+
+    #[test]
+    fn max_concurrency_is_read_from_mode_config_and_clamped() {
+        let read = |v| AdaptiveConcurrency::from_mode_config(&v).current();
+        assert_eq!(read(serde_json::json!({})), DEFAULT_MAX_CONCURRENCY);
+        assert_eq!(read(serde_json::json!({ "max_concurrency": 2 })), 2);
+        assert_eq!(read(serde_json::json!({ "max_concurrency": 0 })), 1);
+        assert_eq!(
+            read(serde_json::json!({ "max_concurrency": 1000 })),
+            MAX_CONCURRENCY
+        );
+    }
+}
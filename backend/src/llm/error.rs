@@ -0,0 +1,133 @@
+use serde::Serialize;
+
+/// A failed provider request, normalized so the UI can tell an invalid key
+/// from rate limiting without parsing provider-specific bodies.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProviderError {
+    pub provider: String,
+    pub http_status: u16,
+    /// One of `invalid_api_key`, `permission_denied`, `not_found`,
+    /// `rate_limited`, `quota_exceeded`, `context_length_exceeded`,
+    /// `invalid_request`, `overloaded`, `server_error` or `unknown`.
+    pub code: String,
+    pub message: String,
+    #[serde(skip)]
+    body: String,
+}
+
+impl ProviderError {
+    /// Parse an error response body. OpenAI-style bodies look like
+    /// `{"error": {"message", "type", "code"}}`; Anthropic ones like
+    /// `{"type": "error", "error": {"type", "message"}}`.
+    pub fn from_response(provider: &str, http_status: u16, body: &str) -> Self {
+        let parsed: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+        let error = parsed.get("error").unwrap_or(&serde_json::Value::Null);
+        let field = |key: &str| error.get(key).and_then(|v| v.as_str()).map(str::to_string);
+
+        let raw_codes = [field("code"), field("type")];
+        let code = classify(http_status, raw_codes.iter().flatten().map(String::as_str));
+        let message = field("message")
+            .or_else(|| error.as_str().map(str::to_string))
+            .unwrap_or_else(|| body.trim().to_string());
+
+        Self {
+            provider: provider.to_string(),
+            http_status,
+            code: code.to_string(),
+            message,
+            body: body.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self.provider.as_str() {
+            "openai_compatible" => "OpenAI-compatible",
+            "anthropic" => "Anthropic",
+            other => other,
+        };
+        write!(f, "{label} error: {} {}", self.http_status, self.body)
+    }
+}
+
+fn classify<'a>(http_status: u16, raw_codes: impl Iterator<Item = &'a str>) -> &'static str {
+    for raw in raw_codes {
+        let code = match raw {
+            "invalid_api_key" | "authentication_error" => "invalid_api_key",
+            "permission_error" => "permission_denied",
+            "not_found_error" | "model_not_found" => "not_found",
+            "rate_limit_error" | "rate_limit_exceeded" => "rate_limited",
+            "insufficient_quota" | "billing_error" => "quota_exceeded",
+            "context_length_exceeded" => "context_length_exceeded",
+            "overloaded_error" => "overloaded",
+            "api_error" | "server_error" => "server_error",
+            _ => continue,
+        };
+        return code;
+    }
+    match http_status {
+        401 => "invalid_api_key",
+        403 => "permission_denied",
+        404 => "not_found",
+        429 => "rate_limited",
+        529 => "overloaded",
+        400 | 413 | 422 => "invalid_request",
+        500..=599 => "server_error",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_openai_invalid_key() {
+        let body = r#"{"error":{"message":"Incorrect API key provided: sk-***","type":"invalid_request_error","param":null,"code":"invalid_api_key"}}"#;
+        let err = ProviderError::from_response("openai_compatible", 401, body);
+        assert_eq!(err.provider, "openai_compatible");
+        assert_eq!(err.http_status, 401);
+        assert_eq!(err.code, "invalid_api_key");
+        assert_eq!(err.message, "Incorrect API key provided: sk-***");
+        assert_eq!(
+            err.to_string(),
+            format!("OpenAI-compatible error: 401 {body}")
+        );
+    }
+
+    #[test]
+    fn parses_openai_quota_and_rate_limit() {
+        let quota = r#"{"error":{"message":"You exceeded your current quota","type":"insufficient_quota","code":"insufficient_quota"}}"#;
+        assert_eq!(
+            ProviderError::from_response("openai_compatible", 429, quota).code,
+            "quota_exceeded"
+        );
+        let rate = r#"{"error":{"message":"Rate limit reached","type":"requests","code":"rate_limit_exceeded"}}"#;
+        assert_eq!(
+            ProviderError::from_response("openai_compatible", 429, rate).code,
+            "rate_limited"
+        );
+    }
+
+    #[test]
+    fn parses_anthropic_errors() {
+        let auth = r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#;
+        let err = ProviderError::from_response("anthropic", 401, auth);
+        assert_eq!(err.code, "invalid_api_key");
+        assert_eq!(err.message, "invalid x-api-key");
+
+        let overloaded =
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        let err = ProviderError::from_response("anthropic", 529, overloaded);
+        assert_eq!(err.code, "overloaded");
+        assert!(err.to_string().starts_with("Anthropic error: 529"));
+    }
+
+    #[test]
+    fn falls_back_to_status_and_raw_body() {
+        let err = ProviderError::from_response("openai_compatible", 502, "Bad Gateway");
+        assert_eq!(err.code, "server_error");
+        assert_eq!(err.message, "Bad Gateway");
+    }
+}
@@ -1,9 +1,14 @@
 use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
 
 use crate::error::AppError;
 use crate::llm::anthropic::AnthropicProvider;
+use crate::llm::concurrency::AdaptiveConcurrency;
 use crate::llm::openai_compatible::OpenAICompatibleProvider;
-use crate::llm::provider::LLMProvider;
+use crate::llm::provider::{LLMProvider, LLMResponse, Message, ToolChoice};
+use crate::metrics::Metrics;
 use crate::models::llm::{ExecutionLLMConfig, LLMRuntimeConfig, ProviderKind};
 
 pub fn provider_from_runtime_config(
@@ -15,20 +20,254 @@ pub fn provider_from_runtime_config(
         ));
     }
 
+    #[cfg(test)]
+    if let Some(mock) = crate::llm::mock::registered(&cfg.model_id) {
+        return Ok(Arc::new(CapabilityGate {
+            inner: mock,
+            supports_vision: cfg.supports_vision,
+        }));
+    }
+
     let provider: Arc<dyn LLMProvider> = match &cfg.provider {
-        ProviderKind::OpenaiCompatible => Arc::new(OpenAICompatibleProvider::new(
-            cfg.api_key.clone(),
-            cfg.model_id.clone(),
-            cfg.base_url.clone(),
-        )?),
-        ProviderKind::Anthropic => Arc::new(AnthropicProvider::new(
-            cfg.api_key.clone(),
-            cfg.model_id.clone(),
-            cfg.base_url.clone(),
-        )?),
+        ProviderKind::OpenaiCompatible => Arc::new(
+            OpenAICompatibleProvider::new(
+                cfg.api_key.clone(),
+                cfg.model_id.clone(),
+                cfg.base_url.clone(),
+                &cfg.extra_headers,
+            )?
+            .with_reasoning_effort(cfg.reasoning_effort())
+            .with_sampling(cfg.sampling()),
+        ),
+        ProviderKind::Anthropic => Arc::new(
+            AnthropicProvider::new(
+                cfg.api_key.clone(),
+                cfg.model_id.clone(),
+                cfg.base_url.clone(),
+                &cfg.extra_headers,
+            )?
+            .with_reasoning_effort(cfg.reasoning_effort())
+            .with_prompt_cache(cfg.enable_prompt_cache)
+            .with_sampling(cfg.sampling()),
+        ),
     };
 
-    Ok(provider)
+    Ok(Arc::new(CapabilityGate {
+        inner: provider,
+        supports_vision: cfg.supports_vision,
+    }))
+}
+
+/// Rejects requests the configured model can't serve before they reach the
+/// API, so e.g. images sent to a text-only model fail with a clear message.
+struct CapabilityGate {
+    inner: Arc<dyn LLMProvider>,
+    supports_vision: bool,
+}
+
+impl CapabilityGate {
+    fn check(&self, messages: &[Message]) -> Result<(), AppError> {
+        if !self.supports_vision && messages.iter().any(Message::has_images) {
+            return Err(AppError::Message(format!(
+                "Model '{}' is not configured for image input (supports_vision is false)",
+                self.inner.model_id()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LLMProvider for CapabilityGate {
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    fn model_id(&self) -> &str {
+        self.inner.model_id()
+    }
+
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        self.check(&messages)?;
+        self.inner.chat(messages, temperature, max_tokens).await
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: &[crate::tools::definition::ToolDefinition],
+        tool_choice: &ToolChoice,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        self.check(&messages)?;
+        self.inner
+            .chat_with_tools(messages, tools, tool_choice, temperature, max_tokens)
+            .await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        self.check(&messages)?;
+        self.inner
+            .chat_stream(messages, temperature, max_tokens)
+            .await
+    }
+}
+
+/// Wrap `inner` so every request is counted, with its latency, in `metrics`.
+pub fn with_metrics(inner: Arc<dyn LLMProvider>, metrics: Arc<Metrics>) -> Arc<dyn LLMProvider> {
+    Arc::new(Metered { inner, metrics })
+}
+
+struct Metered {
+    inner: Arc<dyn LLMProvider>,
+    metrics: Arc<Metrics>,
+}
+
+#[async_trait]
+impl LLMProvider for Metered {
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    fn model_id(&self) -> &str {
+        self.inner.model_id()
+    }
+
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        let started = Instant::now();
+        let resp = self.inner.chat(messages, temperature, max_tokens).await;
+        self.metrics.record_llm_request(started.elapsed());
+        resp
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: &[crate::tools::definition::ToolDefinition],
+        tool_choice: &ToolChoice,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        let started = Instant::now();
+        let resp = self
+            .inner
+            .chat_with_tools(messages, tools, tool_choice, temperature, max_tokens)
+            .await;
+        self.metrics.record_llm_request(started.elapsed());
+        resp
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        let started = Instant::now();
+        let resp = self
+            .inner
+            .chat_stream(messages, temperature, max_tokens)
+            .await;
+        self.metrics.record_llm_request(started.elapsed());
+        resp
+    }
+}
+
+/// Wrap `inner` so each request waits for a slot in `controller` and its
+/// outcome adjusts the shared limit.
+pub fn with_concurrency(
+    inner: Arc<dyn LLMProvider>,
+    controller: Arc<AdaptiveConcurrency>,
+) -> Arc<dyn LLMProvider> {
+    Arc::new(Throttled { inner, controller })
+}
+
+struct Throttled {
+    inner: Arc<dyn LLMProvider>,
+    controller: Arc<AdaptiveConcurrency>,
+}
+
+#[async_trait]
+impl LLMProvider for Throttled {
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    fn model_id(&self) -> &str {
+        self.inner.model_id()
+    }
+
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        let _permit = self.controller.acquire().await;
+        let resp = self.inner.chat(messages, temperature, max_tokens).await;
+        self.controller.record(&resp);
+        resp
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: &[crate::tools::definition::ToolDefinition],
+        tool_choice: &ToolChoice,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        let _permit = self.controller.acquire().await;
+        let resp = self
+            .inner
+            .chat_with_tools(messages, tools, tool_choice, temperature, max_tokens)
+            .await;
+        self.controller.record(&resp);
+        resp
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        let _permit = self.controller.acquire().await;
+        let resp = self
+            .inner
+            .chat_stream(messages, temperature, max_tokens)
+            .await;
+        self.controller.record(&resp);
+        resp
+    }
 }
 
 pub fn resolve_runtime_config_for_agent(
@@ -72,3 +311,107 @@ fn looks_like_client_config_id(value: &str) -> bool {
     }
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::MockProvider;
+    use crate::llm::provider::MessageRole;
+
+    fn user(text: &str, images: &[&str]) -> Message {
+        Message {
+            role: MessageRole::User,
+            content: Some(text.to_string()),
+            name: None,
+            tool_call_id: None,
+            tool_calls: None,
+            images: images.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn gate(mock: &Arc<MockProvider>, supports_vision: bool) -> CapabilityGate {
+        CapabilityGate {
+            inner: mock.clone(),
+            supports_vision,
+        }
+    }
+
+    #[tokio::test]
+    async fn text_only_config_rejects_images_before_calling_provider() {
+        let mock = MockProvider::new("ok");
+        let err = gate(&mock, false)
+            .chat(
+                vec![user("describe", &["data:image/png;base64,AAAA"])],
+                0.2,
+                64,
+            )
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("supports_vision"));
+        assert!(mock.calls().is_empty());
+
+        let err = gate(&mock, false)
+            .chat_with_tools(
+                vec![user("describe", &["https://example.com/a.png"])],
+                &[],
+                &ToolChoice::Auto,
+                0.2,
+                64,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("image input"));
+        assert!(mock.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn text_passes_and_vision_config_accepts_images() {
+        let mock = MockProvider::new("ok");
+        gate(&mock, false)
+            .chat(vec![user("hello", &[])], 0.2, 64)
+            .await
+            .unwrap();
+        gate(&mock, true)
+            .chat(
+                vec![user("describe", &["https://example.com/a.png"])],
+                0.2,
+                64,
+            )
+            .await
+            .unwrap();
+        assert_eq!(mock.calls().len(), 2);
+    }
+
+    #[test]
+    fn factory_wraps_providers_in_the_gate() {
+        let cfg: LLMRuntimeConfig = serde_json::from_value(serde_json::json!({
+            "model_id": "gpt-text",
+            "api_key": "sk-test"
+        }))
+        .unwrap();
+        let provider = provider_from_runtime_config(&cfg).unwrap();
+        assert_eq!(provider.model_id(), "gpt-text");
+        assert_eq!(provider.provider_name(), "openai_compatible");
+    }
+
+    #[tokio::test]
+    async fn wrappers_forward_streaming() {
+        let mock = MockProvider::streaming("ok");
+        let metrics = Arc::new(Metrics::default());
+        let wrapped = with_concurrency(
+            with_metrics(Arc::new(gate(&mock, false)), metrics.clone()),
+            Arc::new(AdaptiveConcurrency::new(2)),
+        );
+        assert!(wrapped.supports_streaming());
+        wrapped
+            .chat_stream(vec![user("hi", &[])], 0.2, 64)
+            .await
+            .unwrap();
+        assert_eq!(mock.stream_calls(), 1);
+        assert_eq!(metrics.snapshot().llm_requests, 1);
+
+        let plain = with_metrics(Arc::new(gate(&MockProvider::new("ok"), false)), metrics);
+        assert!(!plain.supports_streaming());
+    }
+}
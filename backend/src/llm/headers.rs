@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::error::AppError;
+
+/// Headers that carry the API key; `extra_headers` may not replace them.
+const PROTECTED: [&str; 2] = ["authorization", "x-api-key"];
+
+/// Validate user-configured `extra_headers` and convert them to a `HeaderMap`.
+pub fn extra_header_map(extra: &HashMap<String, String>) -> Result<HeaderMap, AppError> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in extra {
+        let header = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| AppError::Message(format!("Invalid header name '{name}'")))?;
+        if PROTECTED.contains(&header.as_str()) {
+            return Err(AppError::Message(format!(
+                "Header '{name}' is set from api_key and cannot be overridden"
+            )));
+        }
+        let value = HeaderValue::from_str(value.trim())
+            .map_err(|_| AppError::Message(format!("Invalid value for header '{name}'")))?;
+        headers.insert(header, value);
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extra(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn converts_valid_headers() {
+        let headers = extra_header_map(&extra(&[
+            ("HTTP-Referer", "https://app"),
+            ("X-Title", "Agent Team"),
+        ]))
+        .unwrap();
+        assert_eq!(headers["http-referer"], "https://app");
+        assert_eq!(headers["x-title"], "Agent Team");
+    }
+
+    #[test]
+    fn rejects_invalid_names_and_values() {
+        assert!(extra_header_map(&extra(&[("bad header", "v")])).is_err());
+        assert!(extra_header_map(&extra(&[("X-Ok", "line\nbreak")])).is_err());
+    }
+
+    #[test]
+    fn api_key_headers_are_protected() {
+        assert!(extra_header_map(&extra(&[("Authorization", "Bearer other")])).is_err());
+        assert!(extra_header_map(&extra(&[("X-API-Key", "other")])).is_err());
+    }
+}
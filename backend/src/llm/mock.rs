@@ -0,0 +1,228 @@
+//! Test doubles for exercising agents and orchestration without network access.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::error::AppError;
+use crate::llm::provider::{LLMProvider, LLMResponse, Message, TokenUsage, ToolChoice};
+use crate::models::agent::{Agent, InteractionRules};
+use crate::tools::definition::{ToolCall, ToolDefinition};
+
+/// Replies with a fixed string and records every conversation it is sent.
+/// Scripted responses, if any, are returned first in order; `failing` makes
+/// the first calls error instead; `streaming` makes it support `chat_stream`;
+/// `slow` makes every request take a while.
+pub struct MockProvider {
+    reply: String,
+    streaming: bool,
+    delay: Duration,
+    streamed: Mutex<u32>,
+    failures: Mutex<u32>,
+    script: Mutex<VecDeque<LLMResponse>>,
+    calls: Mutex<Vec<Vec<Message>>>,
+    tool_choices: Mutex<Vec<ToolChoice>>,
+}
+
+impl MockProvider {
+    pub fn new(reply: &str) -> Arc<Self> {
+        Self::scripted(Vec::new(), reply)
+    }
+
+    pub fn scripted(script: Vec<LLMResponse>, reply: &str) -> Arc<Self> {
+        Arc::new(Self::build(script, reply, false))
+    }
+
+    /// A provider that supports streaming and counts `chat_stream` requests.
+    pub fn streaming(reply: &str) -> Arc<Self> {
+        Arc::new(Self::build(Vec::new(), reply, true))
+    }
+
+    fn build(script: Vec<LLMResponse>, reply: &str, streaming: bool) -> Self {
+        Self {
+            reply: reply.to_string(),
+            streaming,
+            delay: Duration::ZERO,
+            streamed: Mutex::new(0),
+            failures: Mutex::new(0),
+            script: Mutex::new(script.into()),
+            calls: Mutex::new(Vec::new()),
+            tool_choices: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Wait `delay` before answering each request.
+    pub fn slow(delay: Duration, reply: &str) -> Arc<Self> {
+        let mut provider = Self::build(Vec::new(), reply, false);
+        provider.delay = delay;
+        Arc::new(provider)
+    }
+
+    /// Fail the next `times` requests, then reply normally.
+    pub fn failing(times: u32, reply: &str) -> Arc<Self> {
+        let provider = Self::new(reply);
+        *provider.failures.lock().unwrap() = times;
+        provider
+    }
+
+    pub fn calls(&self) -> Vec<Vec<Message>> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// How many requests went through `chat_stream`.
+    pub fn stream_calls(&self) -> u32 {
+        *self.streamed.lock().unwrap()
+    }
+
+    /// The `tool_choice` of every `chat_with_tools` request, in order.
+    pub fn tool_choices(&self) -> Vec<ToolChoice> {
+        self.tool_choices.lock().unwrap().clone()
+    }
+
+    /// The concatenated user-role content of the `idx`-th request.
+    pub fn user_content(&self, idx: usize) -> String {
+        self.calls()[idx]
+            .iter()
+            .filter(|m| matches!(m.role, crate::llm::provider::MessageRole::User))
+            .filter_map(|m| m.content.clone())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[async_trait]
+impl LLMProvider for MockProvider {
+    fn provider_name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn model_id(&self) -> &str {
+        "mock-model"
+    }
+
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        _temperature: f64,
+        _max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+        self.calls.lock().unwrap().push(messages);
+        {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(AppError::Message("mock failure".to_string()));
+            }
+        }
+        if let Some(next) = self.script.lock().unwrap().pop_front() {
+            return Ok(next);
+        }
+        Ok(response(&self.reply, Vec::new()))
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: Vec<Message>,
+        _tools: &[ToolDefinition],
+        tool_choice: &ToolChoice,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        self.tool_choices.lock().unwrap().push(tool_choice.clone());
+        self.chat(messages, temperature, max_tokens).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.streaming
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        *self.streamed.lock().unwrap() += 1;
+        self.chat(messages, temperature, max_tokens).await
+    }
+}
+
+/// Providers `provider_from_runtime_config` hands out by model id, so a
+/// full run can go through the normal build path without network access.
+static REGISTERED: Mutex<Vec<(String, Arc<MockProvider>)>> = Mutex::new(Vec::new());
+
+/// Serve every config for `model_id` with `provider`. Tests share the
+/// registry, so each should use model ids of its own.
+pub fn register(model_id: &str, provider: Arc<MockProvider>) {
+    let mut registered = REGISTERED.lock().unwrap();
+    registered.retain(|(id, _)| id != model_id);
+    registered.push((model_id.to_string(), provider));
+}
+
+pub fn registered(model_id: &str) -> Option<Arc<MockProvider>> {
+    REGISTERED
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(id, _)| id == model_id)
+        .map(|(_, provider)| provider.clone())
+}
+
+/// A provider response with fixed token usage.
+pub fn response(content: &str, tool_calls: Vec<ToolCall>) -> LLMResponse {
+    LLMResponse {
+        content: content.to_string(),
+        usage: TokenUsage {
+            input_tokens: 10,
+            output_tokens: 5,
+            estimated: false,
+            reasoning_tokens: 0,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
+        },
+        model: "mock-model".to_string(),
+        finish_reason: Some("stop".to_string()),
+        tool_calls,
+        rate_limit: None,
+    }
+}
+
+/// A stored agent record with the same defaults `AgentCreate` applies.
+pub fn agent(id: &str, name: &str) -> Agent {
+    let now = chrono::Utc::now();
+    Agent {
+        id: id.to_string(),
+        user_id: "local".to_string(),
+        name: name.to_string(),
+        avatar: None,
+        description: None,
+        tags: Vec::new(),
+        system_prompt: format!("You are {name}."),
+        model_id: None,
+        temperature: 0.7,
+        max_tokens: 2048,
+        max_tool_iterations: Some(10),
+        tools: Vec::new(),
+        knowledge_base_id: None,
+        memory_enabled: false,
+        domain: None,
+        collaboration_style: "supportive".to_string(),
+        speaking_priority: 5,
+        interaction_rules: InteractionRules::default(),
+        version: 1,
+        revision: 0,
+        is_template: false,
+        is_public: false,
+        parent_id: None,
+        usage_count: 0,
+        rating: 0.0,
+        rating_count: 0,
+        created_at: now,
+        updated_at: now,
+    }
+}
@@ -1,4 +1,11 @@
 pub mod anthropic;
+pub mod concurrency;
+pub mod error;
 pub mod factory;
+pub mod headers;
+#[cfg(test)]
+pub mod mock;
 pub mod openai_compatible;
 pub mod provider;
+pub mod rate_limit;
+pub mod tool_args;
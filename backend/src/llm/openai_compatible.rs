@@ -1,5 +1,14 @@
+use std::collections::HashMap;
+
 use crate::error::AppError;
-use crate::llm::provider::{estimate_tokens, LLMProvider, LLMResponse, Message, TokenUsage};
+use crate::llm::error::ProviderError;
+use crate::llm::headers::extra_header_map;
+use crate::llm::provider::{
+    estimate_tokens, LLMProvider, LLMResponse, Message, MessageRole, TokenUsage, ToolChoice,
+};
+use crate::llm::rate_limit::RateLimitInfo;
+use crate::llm::tool_args::parse_tool_arguments;
+use crate::models::llm::SamplingParams;
 use crate::tools::definition::{ToolCall, ToolDefinition};
 use async_trait::async_trait;
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
@@ -10,10 +19,17 @@ pub struct OpenAICompatibleProvider {
     client: reqwest::Client,
     model: String,
     base_url: String,
+    reasoning_effort: Option<String>,
+    sampling: SamplingParams,
 }
 
 impl OpenAICompatibleProvider {
-    pub fn new(api_key: String, model: String, base_url: Option<String>) -> Result<Self, AppError> {
+    pub fn new(
+        api_key: String,
+        model: String,
+        base_url: Option<String>,
+        extra_headers: &HashMap<String, String>,
+    ) -> Result<Self, AppError> {
         let base_url = normalize_openai_compatible_base_url(base_url);
         let mut headers = HeaderMap::new();
         headers.insert(
@@ -29,6 +45,8 @@ impl OpenAICompatibleProvider {
             ),
         );
 
+        headers.extend(extra_header_map(extra_headers)?);
+
         let client = reqwest::Client::builder()
             .default_headers(headers)
             .timeout(std::time::Duration::from_secs(60))
@@ -39,12 +57,100 @@ impl OpenAICompatibleProvider {
             client,
             model,
             base_url,
+            reasoning_effort: None,
+            sampling: SamplingParams::default(),
         })
     }
 
+    /// Send `reasoning_effort` (e.g. `low` / `medium` / `high`) with every request.
+    pub fn with_reasoning_effort(mut self, effort: Option<String>) -> Self {
+        self.reasoning_effort = effort;
+        self
+    }
+
+    /// Send `top_p`, `frequency_penalty` and `presence_penalty` when set;
+    /// `top_k` isn't part of this API and is ignored.
+    pub fn with_sampling(mut self, sampling: SamplingParams) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Reasoning models reject `temperature` and take their output limit as
+    /// `max_completion_tokens`, so both are rewritten along with the effort.
+    fn apply_reasoning_effort(&self, body: &mut serde_json::Value) {
+        let Some(effort) = &self.reasoning_effort else {
+            return;
+        };
+        body["reasoning_effort"] = serde_json::Value::String(effort.clone());
+        if let Some(obj) = body.as_object_mut() {
+            obj.remove("temperature");
+            if let Some(max_tokens) = obj.remove("max_tokens") {
+                obj.insert("max_completion_tokens".to_string(), max_tokens);
+            }
+        }
+    }
+
+    fn apply_sampling(&self, body: &mut serde_json::Value) {
+        let SamplingParams {
+            top_p,
+            frequency_penalty,
+            presence_penalty,
+            ..
+        } = self.sampling;
+        for (key, value) in [
+            ("top_p", top_p),
+            ("frequency_penalty", frequency_penalty),
+            ("presence_penalty", presence_penalty),
+        ] {
+            if let Some(value) = value {
+                body[key] = serde_json::json!(value);
+            }
+        }
+    }
+
     fn endpoint(&self) -> String {
         format!("{}/chat/completions", self.base_url.trim_end_matches('/'))
     }
+
+    fn tools_request_body(
+        &self,
+        messages: Vec<Message>,
+        tools: &[ToolDefinition],
+        tool_choice: &ToolChoice,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<serde_json::Value, AppError> {
+        let tool_defs = tools
+            .iter()
+            .map(|t| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": t.name,
+                        "description": t.description,
+                        "parameters": t.parameters
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let openai_messages = messages
+            .into_iter()
+            .map(to_openai_message)
+            .collect::<Result<Vec<_>, AppError>>()?;
+
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "messages": openai_messages,
+            "temperature": temperature,
+            "max_tokens": max_tokens,
+            "tools": tool_defs,
+            "tool_choice": openai_tool_choice(tool_choice)
+        });
+        self.apply_reasoning_effort(&mut body);
+        self.apply_sampling(&mut body);
+        Ok(body)
+    }
 }
 
 #[async_trait]
@@ -63,12 +169,14 @@ impl LLMProvider for OpenAICompatibleProvider {
         temperature: f64,
         max_tokens: u32,
     ) -> Result<LLMResponse, AppError> {
-        let body = serde_json::json!({
+        let mut body = serde_json::json!({
             "model": self.model,
             "messages": messages,
             "temperature": temperature,
             "max_tokens": max_tokens
         });
+        self.apply_reasoning_effort(&mut body);
+        self.apply_sampling(&mut body);
 
         let resp = self
             .client
@@ -81,11 +189,14 @@ impl LLMProvider for OpenAICompatibleProvider {
         if !resp.status().is_success() {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_else(|_| "".to_string());
-            return Err(AppError::Message(format!(
-                "OpenAI-compatible error: {status} {text}"
+            return Err(AppError::Provider(ProviderError::from_response(
+                self.provider_name(),
+                status.as_u16(),
+                &text,
             )));
         }
 
+        let rate_limit = RateLimitInfo::from_headers(resp.headers());
         let parsed: ChatResponse = resp
             .json()
             .await
@@ -109,10 +220,14 @@ impl LLMProvider for OpenAICompatibleProvider {
                     estimate_tokens(&choice.message.content.clone().unwrap_or_default())
                 }),
                 estimated,
+                reasoning_tokens: reasoning_tokens(parsed.usage.as_ref()),
+                cache_read_tokens: 0,
+                cache_write_tokens: 0,
             },
             model: parsed.model.unwrap_or_else(|| self.model.clone()),
             finish_reason: choice.finish_reason.clone(),
             tool_calls: Vec::new(),
+            rate_limit,
         })
     }
 
@@ -120,36 +235,12 @@ impl LLMProvider for OpenAICompatibleProvider {
         &self,
         messages: Vec<Message>,
         tools: &[ToolDefinition],
+        tool_choice: &ToolChoice,
         temperature: f64,
         max_tokens: u32,
     ) -> Result<LLMResponse, AppError> {
-        let tool_defs = tools
-            .iter()
-            .map(|t| {
-                serde_json::json!({
-                    "type": "function",
-                    "function": {
-                        "name": t.name,
-                        "description": t.description,
-                        "parameters": t.parameters
-                    }
-                })
-            })
-            .collect::<Vec<_>>();
-
-        let openai_messages = messages
-            .into_iter()
-            .map(to_openai_message)
-            .collect::<Result<Vec<_>, AppError>>()?;
-
-        let body = serde_json::json!({
-            "model": self.model,
-            "messages": openai_messages,
-            "temperature": temperature,
-            "max_tokens": max_tokens,
-            "tools": tool_defs,
-            "tool_choice": "auto"
-        });
+        let body =
+            self.tools_request_body(messages, tools, tool_choice, temperature, max_tokens)?;
 
         let resp = self
             .client
@@ -162,11 +253,14 @@ impl LLMProvider for OpenAICompatibleProvider {
         if !resp.status().is_success() {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_else(|_| "".to_string());
-            return Err(AppError::Message(format!(
-                "OpenAI-compatible error: {status} {text}"
+            return Err(AppError::Provider(ProviderError::from_response(
+                self.provider_name(),
+                status.as_u16(),
+                &text,
             )));
         }
 
+        let rate_limit = RateLimitInfo::from_headers(resp.headers());
         let parsed: ChatResponse = resp
             .json()
             .await
@@ -184,14 +278,10 @@ impl LLMProvider for OpenAICompatibleProvider {
             .clone()
             .unwrap_or_default()
             .into_iter()
-            .map(|tc| {
-                let args_value = serde_json::from_str(&tc.function.arguments)
-                    .unwrap_or(serde_json::Value::String(tc.function.arguments));
-                ToolCall {
-                    id: tc.id,
-                    name: tc.function.name,
-                    arguments: args_value,
-                }
+            .map(|tc| ToolCall {
+                id: tc.id,
+                name: tc.function.name,
+                arguments: parse_tool_arguments(&tc.function.arguments),
             })
             .collect();
 
@@ -215,10 +305,14 @@ impl LLMProvider for OpenAICompatibleProvider {
                 output_tokens: completion_tokens
                     .unwrap_or_else(|| estimate_tokens(&output_estimate_text)),
                 estimated,
+                reasoning_tokens: reasoning_tokens(parsed.usage.as_ref()),
+                cache_read_tokens: 0,
+                cache_write_tokens: 0,
             },
             model: parsed.model.unwrap_or_else(|| self.model.clone()),
             finish_reason: choice.finish_reason.clone(),
             tool_calls,
+            rate_limit,
         })
     }
 }
@@ -238,14 +332,56 @@ struct ChatChoice {
 
 #[derive(Debug, Deserialize)]
 struct ChatMessage {
+    #[serde(default, deserialize_with = "text_content")]
     pub content: Option<String>,
     pub tool_calls: Option<Vec<OpenAIToolCall>>,
 }
 
+/// `message.content` as a string, or as an array of content parts (sent by
+/// some gateways and newer APIs) whose text parts are concatenated. Non-text
+/// parts are skipped; no text at all reads as `None`.
+fn text_content<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let text = match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(serde_json::Value::String(text)) => Some(text),
+        Some(serde_json::Value::Array(parts)) => {
+            let texts = parts
+                .iter()
+                .filter(|p| {
+                    matches!(
+                        p.get("type").and_then(|t| t.as_str()),
+                        Some("text" | "output_text")
+                    )
+                })
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>();
+            (!texts.is_empty()).then(|| texts.concat())
+        }
+        _ => None,
+    };
+    Ok(text)
+}
+
 #[derive(Debug, Deserialize)]
 struct ChatUsage {
     pub prompt_tokens: Option<u32>,
     pub completion_tokens: Option<u32>,
+    #[serde(default)]
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionTokensDetails {
+    pub reasoning_tokens: Option<u32>,
+}
+
+fn reasoning_tokens(usage: Option<&ChatUsage>) -> u32 {
+    usage
+        .and_then(|u| u.completion_tokens_details.as_ref())
+        .and_then(|d| d.reasoning_tokens)
+        .unwrap_or(0)
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -263,41 +399,69 @@ struct OpenAIFunctionCall {
     pub arguments: String,
 }
 
-fn to_openai_message(msg: Message) -> Result<serde_json::Value, AppError> {
+fn openai_tool_choice(choice: &ToolChoice) -> serde_json::Value {
+    match choice {
+        ToolChoice::Auto => serde_json::json!("auto"),
+        ToolChoice::Tool(name) => serde_json::json!({
+            "type": "function",
+            "function": { "name": name }
+        }),
+        ToolChoice::None => serde_json::json!("none"),
+    }
+}
+
+pub(crate) fn to_openai_message(msg: Message) -> Result<serde_json::Value, AppError> {
     let role = match msg.role {
-        crate::llm::provider::MessageRole::System => "system",
-        crate::llm::provider::MessageRole::User => "user",
-        crate::llm::provider::MessageRole::Assistant => "assistant",
-        crate::llm::provider::MessageRole::Tool => "tool",
+        MessageRole::System => "system",
+        MessageRole::User => "user",
+        MessageRole::Assistant => "assistant",
+        MessageRole::Tool => "tool",
     };
+    let tool_calls = msg.tool_calls.filter(|calls| !calls.is_empty());
+    if tool_calls.is_some() && !matches!(msg.role, MessageRole::Assistant) {
+        return Err(AppError::Message(format!(
+            "Only assistant messages may carry tool_calls, got a {role} message"
+        )));
+    }
 
     let mut out = serde_json::Map::new();
     out.insert(
         "role".to_string(),
         serde_json::Value::String(role.to_string()),
     );
-    out.insert(
-        "content".to_string(),
-        msg.content
-            .map(serde_json::Value::String)
-            .unwrap_or(serde_json::Value::Null),
-    );
+    let content = match (&msg.role, msg.content) {
+        (_, Some(content)) => serde_json::Value::String(content),
+        // Tool results must be strings; several providers reject a null here.
+        (MessageRole::Tool, None) => serde_json::Value::String(String::new()),
+        (_, None) => serde_json::Value::Null,
+    };
+    out.insert("content".to_string(), content);
 
-    if let Some(name) = msg.name {
+    if let Some(name) = msg.name.as_deref().and_then(participant_name) {
         out.insert("name".to_string(), serde_json::Value::String(name));
     }
 
-    if let Some(tool_call_id) = msg.tool_call_id {
+    if matches!(msg.role, MessageRole::Tool) {
+        let tool_call_id = msg
+            .tool_call_id
+            .filter(|id| !id.trim().is_empty())
+            .ok_or_else(|| AppError::Message("Tool message is missing tool_call_id".to_string()))?;
         out.insert(
             "tool_call_id".to_string(),
             serde_json::Value::String(tool_call_id),
         );
     }
 
-    if let Some(tool_calls) = msg.tool_calls {
+    if let Some(tool_calls) = tool_calls {
         let mapped = tool_calls
             .into_iter()
             .map(|tc| {
+                if tc.id.trim().is_empty() || tc.name.trim().is_empty() {
+                    return Err(AppError::Message(format!(
+                        "Assistant tool call must have an id and a name (id: {:?}, name: {:?})",
+                        tc.id, tc.name
+                    )));
+                }
                 let args = serde_json::to_string(&tc.arguments)
                     .map_err(|e| AppError::Message(e.to_string()))?;
                 Ok(serde_json::json!({
@@ -315,35 +479,387 @@ fn to_openai_message(msg: Message) -> Result<serde_json::Value, AppError> {
 
 pub fn normalize_openai_compatible_base_url(base_url: Option<String>) -> String {
     let default_url = "https://api.openai.com/v1".to_string();
-    let Some(mut base) = base_url else {
+    let Some(base) = base_url else {
         return default_url;
     };
-    base = base.trim().to_string();
+    let base = base.trim();
     if base.is_empty() {
         return default_url;
     }
 
-    // Users sometimes paste full endpoint.
-    let trimmed = base.trim_end_matches('/');
-    if trimmed.ends_with("/chat/completions") {
-        base = trimmed
-            .strip_suffix("/chat/completions")
-            .unwrap_or(trimmed)
-            .to_string();
+    // Users sometimes paste the full endpoint, or a path with stray slashes.
+    let mut base = base.trim_end_matches('/');
+    if let Some(stripped) = base.strip_suffix("/chat/completions") {
+        base = stripped.trim_end_matches('/');
+    }
+    // Collapse an accidentally doubled version segment (`/v1/v1`).
+    while let Some(stripped) = base.strip_suffix("/v1/v1") {
+        base = &base[..stripped.len() + "/v1".len()];
     }
 
-    // Only append /v1 when no path provided.
-    match url::Url::parse(&base) {
-        Ok(url) => {
-            let path = url.path();
-            if path.is_empty() || path == "/" {
-                return format!("{}/v1", base.trim_end_matches('/'));
+    // Only append /v1 when no path provided; custom proxy paths are kept as-is.
+    match url::Url::parse(base) {
+        Ok(url) if url.path().is_empty() || url.path() == "/" => format!("{base}/v1"),
+        _ => base.to_string(),
+    }
+}
+
+/// OpenAI rejects participant names with whitespace or any of `<|\/>`, or
+/// longer than 64 characters; replace the former and cut the latter.
+fn participant_name(raw: &str) -> Option<String> {
+    let name: String = raw
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_whitespace() || matches!(c, '<' | '|' | '\\' | '/' | '>') {
+                '_'
+            } else {
+                c
             }
-            if base.ends_with("/v1/") {
-                return base[..base.len() - 1].to_string();
+        })
+        .take(64)
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tools() -> Vec<ToolDefinition> {
+        vec![ToolDefinition {
+            name: "read_file".to_string(),
+            description: String::new(),
+            parameters: serde_json::json!({ "type": "object" }),
+        }]
+    }
+
+    fn message(role: MessageRole) -> Message {
+        Message {
+            role,
+            content: None,
+            name: None,
+            tool_call_id: None,
+            tool_calls: None,
+            images: Vec::new(),
+        }
+    }
+
+    fn content_of(message: serde_json::Value) -> Option<String> {
+        let response: ChatResponse = serde_json::from_value(serde_json::json!({
+            "choices": [{ "message": message, "finish_reason": "stop" }],
+            "model": "m",
+            "usage": null
+        }))
+        .unwrap();
+        response.choices.into_iter().next().unwrap().message.content
+    }
+
+    #[test]
+    fn string_content_is_read_as_is() {
+        assert_eq!(
+            content_of(serde_json::json!({ "content": "hello" })).as_deref(),
+            Some("hello")
+        );
+        assert_eq!(content_of(serde_json::json!({ "content": null })), None);
+        assert_eq!(content_of(serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn content_part_arrays_concatenate_their_text() {
+        let message = serde_json::json!({
+            "content": [
+                { "type": "text", "text": "Hello, " },
+                { "type": "image_url", "image_url": { "url": "data:" } },
+                { "type": "text", "text": "world" }
+            ]
+        });
+        assert_eq!(content_of(message).as_deref(), Some("Hello, world"));
+        let images_only = serde_json::json!({
+            "content": [{ "type": "image_url", "image_url": { "url": "data:" } }]
+        });
+        assert_eq!(content_of(images_only), None);
+    }
+
+    #[test]
+    fn tool_messages_round_trip_their_call_id() {
+        let mut assistant = message(MessageRole::Assistant);
+        assistant.tool_calls = Some(vec![ToolCall {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::json!({ "path": "a.txt" }),
+        }]);
+        let mut tool = message(MessageRole::Tool);
+        tool.tool_call_id = Some("call_1".to_string());
+
+        let assistant = to_openai_message(assistant).unwrap();
+        assert_eq!(assistant["content"], serde_json::Value::Null);
+        assert_eq!(assistant["tool_calls"][0]["id"], "call_1");
+        assert_eq!(
+            assistant["tool_calls"][0]["function"]["arguments"],
+            r#"{"path":"a.txt"}"#
+        );
+        let tool = to_openai_message(tool).unwrap();
+        assert_eq!(tool["tool_call_id"], "call_1");
+        assert_eq!(tool["content"], "");
+    }
+
+    #[test]
+    fn malformed_tool_messages_are_rejected() {
+        let err = to_openai_message(message(MessageRole::Tool)).unwrap_err();
+        assert!(err.to_string().contains("missing tool_call_id"));
+
+        let mut assistant = message(MessageRole::Assistant);
+        assistant.tool_calls = Some(vec![ToolCall {
+            id: String::new(),
+            name: "read_file".to_string(),
+            arguments: serde_json::Value::Null,
+        }]);
+        assert!(to_openai_message(assistant).is_err());
+
+        let mut user = message(MessageRole::User);
+        user.content = Some("hi".to_string());
+        user.tool_calls = Some(vec![ToolCall {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::Value::Null,
+        }]);
+        assert!(to_openai_message(user).is_err());
+
+        let mut empty = message(MessageRole::Assistant);
+        empty.content = Some("done".to_string());
+        empty.tool_calls = Some(Vec::new());
+        assert!(to_openai_message(empty)
+            .unwrap()
+            .get("tool_calls")
+            .is_none());
+    }
+
+    #[test]
+    fn reasoning_effort_is_sent_only_when_set() {
+        let provider = OpenAICompatibleProvider::new(
+            "key".to_string(),
+            "o3".to_string(),
+            None,
+            &HashMap::new(),
+        )
+        .unwrap();
+        let body = provider
+            .tools_request_body(Vec::new(), &tools(), &ToolChoice::Auto, 0.2, 64)
+            .unwrap();
+        assert!(body.get("reasoning_effort").is_none());
+        assert_eq!(
+            (body["temperature"].as_f64(), body["max_tokens"].as_u64()),
+            (Some(0.2), Some(64))
+        );
+
+        let provider = provider.with_reasoning_effort(Some("high".to_string()));
+        let body = provider
+            .tools_request_body(Vec::new(), &tools(), &ToolChoice::Auto, 0.2, 64)
+            .unwrap();
+        assert_eq!(body["reasoning_effort"], "high");
+        assert_eq!(body["max_completion_tokens"], 64);
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("temperature").is_none());
+    }
+
+    #[test]
+    fn sampling_params_are_sent_only_when_set() {
+        let provider = OpenAICompatibleProvider::new(
+            "key".to_string(),
+            "gpt".to_string(),
+            None,
+            &HashMap::new(),
+        )
+        .unwrap();
+        let body = provider
+            .tools_request_body(Vec::new(), &tools(), &ToolChoice::Auto, 0.2, 64)
+            .unwrap();
+        for key in ["top_p", "frequency_penalty", "presence_penalty", "top_k"] {
+            assert!(body.get(key).is_none(), "{key}");
+        }
+
+        let provider = provider.with_sampling(SamplingParams {
+            top_p: Some(0.9),
+            top_k: Some(40),
+            frequency_penalty: Some(0.5),
+            presence_penalty: Some(-0.25),
+        });
+        let body = provider
+            .tools_request_body(Vec::new(), &tools(), &ToolChoice::Auto, 0.2, 64)
+            .unwrap();
+        assert_eq!(body["top_p"], 0.9);
+        assert_eq!(body["frequency_penalty"], 0.5);
+        assert_eq!(body["presence_penalty"], -0.25);
+        assert!(body.get("top_k").is_none());
+    }
+
+    #[test]
+    fn reasoning_tokens_are_read_from_usage_details() {
+        let usage: ChatUsage = serde_json::from_str(
+            r#"{"prompt_tokens":5,"completion_tokens":50,"completion_tokens_details":{"reasoning_tokens":32}}"#,
+        )
+        .unwrap();
+        assert_eq!(reasoning_tokens(Some(&usage)), 32);
+        let plain: ChatUsage =
+            serde_json::from_str(r#"{"prompt_tokens":5,"completion_tokens":50}"#).unwrap();
+        assert_eq!(reasoning_tokens(Some(&plain)), 0);
+    }
+
+    #[test]
+    fn forced_tool_is_sent_as_tool_choice() {
+        let provider = OpenAICompatibleProvider::new(
+            "key".to_string(),
+            "gpt".to_string(),
+            None,
+            &HashMap::new(),
+        )
+        .unwrap();
+        let choice = ToolChoice::Tool("read_file".to_string());
+        let body = provider
+            .tools_request_body(Vec::new(), &tools(), &choice, 0.2, 64)
+            .unwrap();
+        assert_eq!(
+            body["tool_choice"],
+            serde_json::json!({ "type": "function", "function": { "name": "read_file" } })
+        );
+    }
+
+    #[test]
+    fn auto_tool_choice_by_default() {
+        let provider = OpenAICompatibleProvider::new(
+            "key".to_string(),
+            "gpt".to_string(),
+            None,
+            &HashMap::new(),
+        )
+        .unwrap();
+        let choice = ToolChoice::default();
+        let body = provider
+            .tools_request_body(Vec::new(), &tools(), &choice, 0.2, 64)
+            .unwrap();
+        assert_eq!(body["tool_choice"], serde_json::json!("auto"));
+        let body = provider
+            .tools_request_body(Vec::new(), &tools(), &ToolChoice::None, 0.2, 64)
+            .unwrap();
+        assert_eq!(body["tool_choice"], serde_json::json!("none"));
+    }
+
+    /// Serve one request on a local port, replying with a minimal chat
+    /// completion and handing back the raw request head.
+    fn echo_server() -> (String, std::sync::mpsc::Receiver<String>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                head.push_str(&line);
             }
-            base.trim_end_matches('/').to_string()
+            let len = head
+                .lines()
+                .find_map(|l| {
+                    l.to_ascii_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|v| v.trim().parse::<usize>().unwrap())
+                })
+                .unwrap_or(0);
+            let mut body = vec![0u8; len];
+            reader.read_exact(&mut body).unwrap();
+
+            let reply = r#"{"choices":[{"message":{"content":"ok"},"finish_reason":"stop"}],"usage":{"prompt_tokens":1,"completion_tokens":1}}"#;
+            let mut stream = stream;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{reply}",
+                reply.len()
+            )
+            .unwrap();
+            tx.send(head).unwrap();
+        });
+        (url, rx)
+    }
+
+    #[tokio::test]
+    async fn extra_headers_are_sent_with_requests() {
+        let (url, rx) = echo_server();
+        let extra = HashMap::from([
+            (
+                "HTTP-Referer".to_string(),
+                "https://agent-team.local".to_string(),
+            ),
+            ("X-Title".to_string(), "Agent Team".to_string()),
+        ]);
+        let provider = OpenAICompatibleProvider::new(
+            "sk-test".to_string(),
+            "gpt".to_string(),
+            Some(url),
+            &extra,
+        )
+        .unwrap();
+
+        let resp = provider.chat(Vec::new(), 0.2, 8).await.unwrap();
+        assert_eq!(resp.content, "ok");
+
+        let head = rx.recv().unwrap().to_ascii_lowercase();
+        assert!(head.contains("http-referer: https://agent-team.local"));
+        assert!(head.contains("x-title: agent team"));
+        assert!(head.contains("authorization: bearer sk-test"));
+    }
+
+    #[test]
+    fn normalizes_base_urls() {
+        let cases = [
+            (None, "https://api.openai.com/v1"),
+            (Some("   "), "https://api.openai.com/v1"),
+            (Some("https://host"), "https://host/v1"),
+            (Some("https://host/"), "https://host/v1"),
+            (Some("https://host/v1"), "https://host/v1"),
+            (Some("https://host/v1/"), "https://host/v1"),
+            (Some(" https://host/v1// "), "https://host/v1"),
+            (Some("https://host/v1/v1"), "https://host/v1"),
+            (Some("https://host/v1/v1/"), "https://host/v1"),
+            (Some("https://host/chat/completions"), "https://host/v1"),
+            (Some("https://host/v1/chat/completions"), "https://host/v1"),
+            (Some("https://host/v1/chat/completions/"), "https://host/v1"),
+            (Some("https://proxy/api/openai"), "https://proxy/api/openai"),
+            (
+                Some("https://proxy/api/openai/"),
+                "https://proxy/api/openai",
+            ),
+            (
+                Some("https://proxy/api/openai/chat/completions"),
+                "https://proxy/api/openai",
+            ),
+            (Some("http://127.0.0.1:8080"), "http://127.0.0.1:8080/v1"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_openai_compatible_base_url(input.map(str::to_string)),
+                expected,
+                "input: {input:?}"
+            );
         }
-        Err(_) => base.trim_end_matches('/').to_string(),
+    }
+
+    #[test]
+    fn participant_names_are_made_safe_for_the_api() {
+        let mut msg = message(MessageRole::User);
+        msg.content = Some("hi".to_string());
+        msg.name = Some(" 张 三/<lead> ".to_string());
+        let out = to_openai_message(msg).unwrap();
+        assert_eq!(out["name"], "张_三__lead_");
+
+        assert_eq!(participant_name(&"x".repeat(80)).unwrap().len(), 64);
+        assert_eq!(participant_name("  "), None);
     }
 }
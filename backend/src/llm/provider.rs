@@ -2,6 +2,7 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
 use crate::error::AppError;
+use crate::llm::rate_limit::RateLimitInfo;
 use crate::tools::definition::ToolCall;
 use crate::tools::definition::ToolDefinition;
 
@@ -25,6 +26,15 @@ pub struct Message {
     pub tool_call_id: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCall>>,
+    /// Image URLs or `data:` URIs; only vision-capable configs accept these.
+    #[serde(skip)]
+    pub images: Vec<String>,
+}
+
+impl Message {
+    pub fn has_images(&self) -> bool {
+        !self.images.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +43,17 @@ pub struct TokenUsage {
     pub output_tokens: u32,
     #[serde(default)]
     pub estimated: bool,
+    /// Hidden reasoning tokens, already included in `output_tokens`; 0 when
+    /// the provider doesn't report them.
+    #[serde(default)]
+    pub reasoning_tokens: u32,
+    /// Prompt tokens served from the provider's prompt cache. Anthropic
+    /// reports these separately from, not within, `input_tokens`.
+    #[serde(default)]
+    pub cache_read_tokens: u32,
+    /// Prompt tokens written to the provider's prompt cache.
+    #[serde(default)]
+    pub cache_write_tokens: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +65,20 @@ pub struct LLMResponse {
     pub finish_reason: Option<String>,
     #[serde(default)]
     pub tool_calls: Vec<ToolCall>,
+    /// Limits the provider reported in the response headers, if any.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitInfo>,
+}
+
+/// Whether the model may pick any tool, must call a specific one, or must
+/// answer in text. `None` keeps the tools declared, which a conversation
+/// that already holds tool turns needs, while ruling out another call.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ToolChoice {
+    #[default]
+    Auto,
+    Tool(String),
+    None,
 }
 
 #[async_trait]
@@ -64,14 +99,71 @@ pub trait LLMProvider: Send + Sync {
         &self,
         messages: Vec<Message>,
         tools: &[ToolDefinition],
+        tool_choice: &ToolChoice,
+        temperature: f64,
+        max_tokens: u32,
+    ) -> Result<LLMResponse, AppError> {
+        let _ = (tools, tool_choice);
+        self.chat(messages, temperature, max_tokens).await
+    }
+
+    /// Whether `chat_stream` actually streams; when false it is plain `chat`.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// Stream the reply and return it once complete. Providers that can't
+    /// stream answer with a single `chat`.
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
         temperature: f64,
         max_tokens: u32,
     ) -> Result<LLMResponse, AppError> {
-        let _ = tools;
         self.chat(messages, temperature, max_tokens).await
     }
 }
 
+/// Map a provider-specific stop reason onto a common vocabulary:
+/// `stop`, `length`, `tool_calls`, `content_filter` or `other`.
+pub fn normalize_finish_reason(raw: &str) -> &'static str {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "stop" | "end_turn" | "stop_sequence" | "eos" => "stop",
+        "length" | "max_tokens" | "model_context_window_exceeded" => "length",
+        "tool_calls" | "function_call" | "tool_use" => "tool_calls",
+        "content_filter" | "refusal" | "safety" => "content_filter",
+        _ => "other",
+    }
+}
+
 pub fn estimate_tokens(text: &str) -> u32 {
     (text.len() as f64 / 3.5).ceil() as u32
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_openai_finish_reasons() {
+        assert_eq!(normalize_finish_reason("stop"), "stop");
+        assert_eq!(normalize_finish_reason("length"), "length");
+        assert_eq!(normalize_finish_reason("tool_calls"), "tool_calls");
+        assert_eq!(normalize_finish_reason("content_filter"), "content_filter");
+    }
+
+    #[test]
+    fn normalizes_anthropic_stop_reasons() {
+        assert_eq!(normalize_finish_reason("end_turn"), "stop");
+        assert_eq!(normalize_finish_reason("stop_sequence"), "stop");
+        assert_eq!(normalize_finish_reason("max_tokens"), "length");
+        assert_eq!(normalize_finish_reason("tool_use"), "tool_calls");
+        assert_eq!(normalize_finish_reason("refusal"), "content_filter");
+    }
+
+    #[test]
+    fn unknown_finish_reason_is_other() {
+        assert_eq!(normalize_finish_reason("pause_turn"), "other");
+        assert_eq!(normalize_finish_reason(""), "other");
+    }
+}
@@ -0,0 +1,142 @@
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+/// Remaining share of a limit below which a `rate_limit` warning is raised.
+pub const LOW_RATE_LIMIT_FRACTION: f64 = 0.1;
+
+/// Request and token budgets a provider reported alongside a response, from
+/// OpenAI-style `x-ratelimit-*` or Anthropic `anthropic-ratelimit-*` headers.
+/// Fields the provider didn't send stay `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_limit: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_remaining: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_limit: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_remaining: Option<u64>,
+    /// When the request budget refills, verbatim: a duration such as `6m0s`
+    /// (OpenAI) or an RFC 3339 time (Anthropic).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_reset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_reset: Option<String>,
+}
+
+impl RateLimitInfo {
+    /// `None` when the response carried no rate-limit headers at all.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let text = |names: &[&str]| {
+            names.iter().find_map(|name| {
+                headers
+                    .get(*name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(str::to_string)
+            })
+        };
+        let number = |names: &[&str]| text(names).and_then(|v| v.parse::<u64>().ok());
+        let info = Self {
+            requests_limit: number(&[
+                "x-ratelimit-limit-requests",
+                "anthropic-ratelimit-requests-limit",
+                "x-ratelimit-limit",
+            ]),
+            requests_remaining: number(&[
+                "x-ratelimit-remaining-requests",
+                "anthropic-ratelimit-requests-remaining",
+                "x-ratelimit-remaining",
+            ]),
+            tokens_limit: number(&[
+                "x-ratelimit-limit-tokens",
+                "anthropic-ratelimit-tokens-limit",
+            ]),
+            tokens_remaining: number(&[
+                "x-ratelimit-remaining-tokens",
+                "anthropic-ratelimit-tokens-remaining",
+            ]),
+            requests_reset: text(&[
+                "x-ratelimit-reset-requests",
+                "anthropic-ratelimit-requests-reset",
+                "x-ratelimit-reset",
+            ]),
+            tokens_reset: text(&[
+                "x-ratelimit-reset-tokens",
+                "anthropic-ratelimit-tokens-reset",
+            ]),
+        };
+        (info != Self::default()).then_some(info)
+    }
+
+    /// The smaller of the remaining request and token shares, from 0 to 1;
+    /// `None` unless some limit came with its remaining count.
+    pub fn remaining_fraction(&self) -> Option<f64> {
+        let share = |remaining: Option<u64>, limit: Option<u64>| match (remaining, limit) {
+            (Some(remaining), Some(limit)) if limit > 0 => {
+                Some((remaining as f64 / limit as f64).min(1.0))
+            }
+            _ => None,
+        };
+        [
+            share(self.requests_remaining, self.requests_limit),
+            share(self.tokens_remaining, self.tokens_limit),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(f64::min)
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.remaining_fraction()
+            .is_some_and(|fraction| fraction < LOW_RATE_LIMIT_FRACTION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderName, HeaderValue};
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(k, v)| {
+                (
+                    HeaderName::from_static(k),
+                    HeaderValue::from_str(v).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn openai_and_anthropic_headers_parse() {
+        let openai = RateLimitInfo::from_headers(&headers(&[
+            ("x-ratelimit-limit-requests", "500"),
+            ("x-ratelimit-remaining-requests", "499"),
+            ("x-ratelimit-limit-tokens", "30000"),
+            ("x-ratelimit-remaining-tokens", "1200"),
+            ("x-ratelimit-reset-tokens", "6m0s"),
+        ]))
+        .unwrap();
+        assert_eq!(openai.requests_remaining, Some(499));
+        assert_eq!(openai.tokens_reset.as_deref(), Some("6m0s"));
+        assert_eq!(openai.remaining_fraction(), Some(0.04));
+        assert!(openai.is_low());
+
+        let anthropic = RateLimitInfo::from_headers(&headers(&[
+            ("anthropic-ratelimit-requests-limit", "50"),
+            ("anthropic-ratelimit-requests-remaining", "49"),
+            ("anthropic-ratelimit-requests-reset", "2026-10-16T12:00:30Z"),
+        ]))
+        .unwrap();
+        assert_eq!(anthropic.requests_limit, Some(50));
+        assert_eq!(anthropic.tokens_remaining, None);
+        assert!(!anthropic.is_low());
+
+        assert!(RateLimitInfo::from_headers(&headers(&[("content-type", "json")])).is_none());
+    }
+}
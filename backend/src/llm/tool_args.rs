@@ -0,0 +1,115 @@
+//! Parsing of the JSON-encoded tool-call arguments OpenAI-style APIs return.
+
+use serde_json::Value;
+
+/// Parse `raw` tool-call arguments, repairing common model mistakes (code
+/// fences, single quotes, raw newlines in strings, trailing commas). Input
+/// that still isn't JSON is returned as `Value::String` so the executor can
+/// tell the model to retry.
+pub fn parse_tool_arguments(raw: &str) -> Value {
+    if raw.trim().is_empty() {
+        return Value::Object(Default::default());
+    }
+    serde_json::from_str(raw)
+        .or_else(|_| serde_json::from_str(&repair_json(raw)))
+        .unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+fn repair_json(raw: &str) -> String {
+    let trimmed = strip_code_fence(raw.trim());
+    let chars = trimmed.chars().collect::<Vec<_>>();
+    let mut out = String::with_capacity(trimmed.len());
+    // The quote character of the string being copied, if inside one.
+    let mut quote: Option<char> = None;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match quote {
+            Some(q) => match c {
+                '\\' if chars.get(i + 1) == Some(&'\'') => {
+                    out.push('\'');
+                    i += 1;
+                }
+                '\\' => {
+                    out.push(c);
+                    if let Some(&next) = chars.get(i + 1) {
+                        out.push(next);
+                        i += 1;
+                    }
+                }
+                _ if c == q => {
+                    out.push('"');
+                    quote = None;
+                }
+                '"' => out.push_str("\\\""),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                _ => out.push(c),
+            },
+            None => match c {
+                '"' | '\'' => {
+                    out.push('"');
+                    quote = Some(c);
+                }
+                ',' if chars[i + 1..]
+                    .iter()
+                    .find(|c| !c.is_whitespace())
+                    .is_some_and(|c| matches!(c, '}' | ']')) => {}
+                _ => out.push(c),
+            },
+        }
+        i += 1;
+    }
+    out
+}
+
+pub(crate) fn strip_code_fence(s: &str) -> &str {
+    let Some(rest) = s.strip_prefix("```") else {
+        return s;
+    };
+    let body = rest.split_once('\n').map_or("", |(_, body)| body);
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn valid_and_empty_arguments_parse_directly() {
+        assert_eq!(
+            parse_tool_arguments(r#"{"path":"a.txt"}"#),
+            json!({ "path": "a.txt" })
+        );
+        assert_eq!(parse_tool_arguments("  "), json!({}));
+    }
+
+    #[test]
+    fn common_malformations_are_repaired() {
+        assert_eq!(
+            parse_tool_arguments("{'path': 'it\\'s \"here\".txt', 'limit': 5,}"),
+            json!({ "path": "it's \"here\".txt", "limit": 5 })
+        );
+        assert_eq!(
+            parse_tool_arguments("{\"path\": \"a.txt\", \"content\": \"line one\nline two\"}"),
+            json!({ "path": "a.txt", "content": "line one\nline two" })
+        );
+        assert_eq!(
+            parse_tool_arguments("```json\n{\"paths\": [\"a\", \"b\",],}\n```"),
+            json!({ "paths": ["a", "b"] })
+        );
+        // Commas inside strings are left alone.
+        assert_eq!(
+            parse_tool_arguments("{\"query\": \"a, }\",}"),
+            json!({ "query": "a, }" })
+        );
+    }
+
+    #[test]
+    fn unrepairable_arguments_stay_a_string() {
+        let raw = r#"{"path": "a.txt""#;
+        assert_eq!(parse_tool_arguments(raw), Value::String(raw.to_string()));
+    }
+}
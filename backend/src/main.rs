@@ -7,8 +7,10 @@ mod agents;
 mod commands;
 mod error;
 mod llm;
+mod metrics;
 mod models;
 mod orchestration;
+mod sanitize;
 mod seed;
 mod state;
 mod store;
@@ -22,6 +24,10 @@ fn main() {
         .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
             let state = AppState::init(app.handle())?;
+            orchestration::watchdog::spawn_watchdog(
+                state.store.clone(),
+                state.execution_tasks.clone(),
+            );
             app.manage(state);
             Ok(())
         })
@@ -41,18 +47,45 @@ fn main() {
             commands::teams::add_team_member,
             commands::teams::remove_team_member,
             commands::teams::reorder_team_members,
+            commands::teams::set_member_position,
+            commands::templates::clone_for_edit,
+            commands::benchmark::benchmark_team,
+            commands::templates::reseed_defaults,
             commands::executions::list_executions,
             commands::executions::get_execution,
+            commands::executions::get_execution_summary,
+            commands::executions::list_active_executions,
             commands::executions::create_execution,
             commands::executions::delete_execution,
             commands::executions::control_execution,
             commands::executions::start_execution,
             commands::executions::followup_execution,
+            commands::executions::regenerate_summary,
+            commands::executions::get_execution_cost_breakdown,
+            commands::executions::prune_orphan_messages,
             commands::executions::set_execution_workspace,
+            commands::executions::redact_execution,
+            commands::executions::export_execution,
             commands::fs::list_files,
             commands::fs::read_file,
             commands::fs::write_file,
-            commands::llm::test_llm
+            commands::fs::get_workspace_diff,
+            commands::llm::test_llm,
+            commands::settings::get_settings,
+            commands::settings::set_settings,
+            commands::settings::list_model_pricing,
+            commands::settings::set_model_pricing,
+            commands::settings::reset_model_pricing,
+            commands::backup::export_all,
+            commands::backup::import_all,
+            commands::knowledge::list_knowledge_docs,
+            commands::knowledge::get_knowledge_doc,
+            commands::knowledge::create_knowledge_doc,
+            commands::knowledge::update_knowledge_doc,
+            commands::knowledge::delete_knowledge_doc,
+            commands::metrics::get_metrics,
+            commands::tools::list_tools,
+            commands::tools::preview_tool
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Process-wide diagnostic counters. Reset only when the app restarts.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    llm_requests: AtomicU64,
+    stage_retries: AtomicU64,
+    llm_latency_ms_total: AtomicU64,
+    tool_executions: AtomicU64,
+    tool_timeouts: AtomicU64,
+    effective_concurrency: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MetricsSnapshot {
+    pub llm_requests: u64,
+    /// Pipeline stages retried under the `retry` stage error policy.
+    pub stage_retries: u64,
+    pub tool_executions: u64,
+    pub tool_timeouts: u64,
+    /// Mean wall-clock time per LLM request; `0.0` before the first request.
+    pub avg_request_latency_ms: f64,
+    /// LLM requests the most recently adjusted execution may have in flight;
+    /// `0` before any execution has started.
+    pub effective_concurrency: u64,
+}
+
+impl Metrics {
+    /// Count one completed LLM request (successful or not) and its latency.
+    pub fn record_llm_request(&self, elapsed: Duration) {
+        let ms = elapsed.as_millis().min(u128::from(u64::MAX)) as u64;
+        self.llm_requests.fetch_add(1, Ordering::Relaxed);
+        self.llm_latency_ms_total.fetch_add(ms, Ordering::Relaxed);
+    }
+
+    pub fn record_stage_retry(&self) {
+        self.stage_retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_tool_execution(&self, timed_out: bool) {
+        self.tool_executions.fetch_add(1, Ordering::Relaxed);
+        if timed_out {
+            self.tool_timeouts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn set_effective_concurrency(&self, limit: u64) {
+        self.effective_concurrency.store(limit, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let llm_requests = self.llm_requests.load(Ordering::Relaxed);
+        let latency_total = self.llm_latency_ms_total.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            llm_requests,
+            stage_retries: self.stage_retries.load(Ordering::Relaxed),
+            tool_executions: self.tool_executions.load(Ordering::Relaxed),
+            tool_timeouts: self.tool_timeouts.load(Ordering::Relaxed),
+            avg_request_latency_ms: if llm_requests == 0 {
+                0.0
+            } else {
+                latency_total as f64 / llm_requests as f64
+            },
+            effective_concurrency: self.effective_concurrency.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::agents::instance::AgentInstance;
+    use crate::llm::factory::with_metrics;
+    use crate::llm::mock::{self, MockProvider};
+    use crate::tools::definition::ToolCall;
+    use crate::tools::executor::{ToolExecutor, ToolLimits};
+    use crate::tools::lock::WorkspaceLocks;
+
+    #[test]
+    fn average_latency_is_zero_before_any_request() {
+        let metrics = Metrics::default();
+        assert_eq!(metrics.snapshot().avg_request_latency_ms, 0.0);
+        metrics.record_llm_request(Duration::from_millis(30));
+        metrics.record_llm_request(Duration::from_millis(10));
+        let snap = metrics.snapshot();
+        assert_eq!(snap.llm_requests, 2);
+        assert_eq!(snap.avg_request_latency_ms, 20.0);
+    }
+
+    #[tokio::test]
+    async fn a_run_counts_requests_and_tool_executions() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "hello\n").unwrap();
+        let metrics = Arc::new(Metrics::default());
+
+        let call = ToolCall {
+            id: "c1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::json!({ "path": "a.txt" }),
+        };
+        let provider = MockProvider::scripted(
+            vec![
+                mock::response("", vec![call]),
+                mock::response("done", vec![]),
+            ],
+            "done",
+        );
+        let mut agent = AgentInstance::from_agent(
+            &mock::agent("a1", "A"),
+            with_metrics(provider, metrics.clone()),
+        );
+        let executor = ToolExecutor::new(dir.path().to_path_buf())
+            .unwrap()
+            .with_metrics(metrics.clone());
+        let tools = executor.definitions();
+
+        agent
+            .generate_opinion_with_tools("topic", "", &[], "initial", &tools, Some(&executor))
+            .await
+            .unwrap();
+
+        let snap = metrics.snapshot();
+        assert_eq!(snap.llm_requests, 2);
+        assert_eq!(snap.tool_executions, 1);
+        assert_eq!(snap.tool_timeouts, 0);
+    }
+
+    #[tokio::test]
+    async fn tool_timeouts_are_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::default());
+        let locks = WorkspaceLocks::default();
+        let executor = ToolExecutor::new(dir.path().to_path_buf())
+            .unwrap()
+            .with_limits(ToolLimits {
+                timeout_ms: 20,
+                ..ToolLimits::default()
+            })
+            .with_locks(&locks)
+            .with_metrics(metrics.clone());
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        let _held = locks.for_root(&root).write_owned().await;
+
+        let result = executor
+            .execute(ToolCall {
+                id: "c1".to_string(),
+                name: "write_file".to_string(),
+                arguments: serde_json::json!({ "path": "a.txt", "content": "x" }),
+            })
+            .await;
+
+        assert!(!result.ok);
+        let snap = metrics.snapshot();
+        assert_eq!(snap.tool_executions, 1);
+        assert_eq!(snap.tool_timeouts, 1);
+    }
+}
@@ -45,7 +45,13 @@ pub struct Agent {
     pub speaking_priority: i32,
     #[serde(default)]
     pub interaction_rules: InteractionRules,
+    /// Bumped only when a field that changes the agent's replies changes;
+    /// see `Agent::behaves_like`.
     pub version: u32,
+    /// Bumped on every edit; clients send it back as `expected_revision` so
+    /// an edit made against an older copy is rejected.
+    #[serde(default)]
+    pub revision: u32,
     pub is_template: bool,
     pub is_public: bool,
     pub parent_id: Option<String>,
@@ -56,6 +62,29 @@ pub struct Agent {
     pub updated_at: DateTime<Utc>,
 }
 
+impl Agent {
+    /// Whether `other` would reply the same way: same prompt, model, tools,
+    /// temperature and token limit. Cosmetic fields such as `name`, `avatar`
+    /// or `tags` are ignored.
+    pub fn behaves_like(&self, other: &Agent) -> bool {
+        self.system_prompt == other.system_prompt
+            && self.model_id == other.model_id
+            && self.tools == other.tools
+            && self.temperature == other.temperature
+            && self.max_tokens == other.max_tokens
+    }
+}
+
+/// `create_agent` / `update_agent` payload: the stored agent plus a warning
+/// when its system prompt is longer than `max_system_prompt_chars`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedAgent {
+    #[serde(flatten)]
+    pub agent: Agent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentListItem {
     pub id: String,
@@ -132,6 +161,17 @@ pub struct AgentUpdate {
     pub is_public: Option<bool>,
 }
 
+/// Returns a warning when `prompt` is longer than `max_chars` characters.
+pub fn system_prompt_length_warning(prompt: &str, max_chars: usize) -> Option<String> {
+    let len = prompt.chars().count();
+    if len <= max_chars {
+        return None;
+    }
+    Some(format!(
+        "system_prompt is {len} characters, exceeding the recommended maximum of {max_chars}"
+    ))
+}
+
 fn default_true() -> bool {
     true
 }
@@ -155,3 +195,23 @@ fn default_speaking_priority() -> i32 {
 fn default_collaboration_style() -> String {
     "supportive".to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_prompt_length_warning_only_when_exceeded() {
+        assert!(system_prompt_length_warning("short", 10).is_none());
+        assert!(system_prompt_length_warning("exactly10!", 10).is_none());
+        let warning = system_prompt_length_warning("eleven chars", 10).unwrap();
+        assert!(warning.contains("12"));
+        assert!(warning.contains("10"));
+    }
+
+    #[test]
+    fn system_prompt_length_warning_counts_chars_not_bytes() {
+        assert!(system_prompt_length_warning("你好世界", 4).is_none());
+        assert!(system_prompt_length_warning("你好世界!", 4).is_some());
+    }
+}
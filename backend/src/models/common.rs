@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::error::AppError;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaginatedResponse<T> {
     pub items: Vec<T>,
@@ -24,3 +26,66 @@ fn default_success() -> bool {
 fn default_success_message() -> String {
     "Operation completed successfully".to_string()
 }
+
+/// Which agents/teams a list command returns, applied before other filters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ownership {
+    /// Items created locally, public or not.
+    Mine,
+    /// Items marked public, whoever owns them.
+    Public,
+    /// Both of the above.
+    #[default]
+    All,
+}
+
+impl Ownership {
+    pub fn allows(self, owned: bool, is_public: bool) -> bool {
+        match self {
+            Ownership::Mine => owned,
+            Ownership::Public => is_public,
+            Ownership::All => owned || is_public,
+        }
+    }
+}
+
+/// Optimistic concurrency check for an edit: reject it when the caller read
+/// revision `expected` but the stored item is now at `current`. No
+/// expectation means an unconditional write.
+pub fn check_revision(
+    kind: &str,
+    id: &str,
+    current: u32,
+    expected: Option<u32>,
+) -> Result<(), AppError> {
+    match expected {
+        Some(expected) if expected != current => Err(AppError::Message(format!(
+            "Conflict: {kind} {id} was changed elsewhere (revision {current}, you edited revision {expected}); reload it and try again"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ownership_modes_allow_the_expected_items() {
+        // (owned, is_public): private and public local items, then public
+        // and private ones owned by someone else.
+        let items = [(true, false), (true, true), (false, true), (false, false)];
+        let allowed =
+            |ownership: Ownership| items.map(|(owned, public)| ownership.allows(owned, public));
+
+        assert_eq!(allowed(Ownership::Mine), [true, true, false, false]);
+        assert_eq!(allowed(Ownership::Public), [false, true, true, false]);
+        assert_eq!(allowed(Ownership::All), [true, true, true, false]);
+        assert_eq!(Ownership::default(), Ownership::All);
+        assert_eq!(
+            serde_json::from_str::<Ownership>(r#""public""#).unwrap(),
+            Ownership::Public
+        );
+    }
+}
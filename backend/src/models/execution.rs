@@ -1,8 +1,16 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::models::llm::ExecutionLLMConfig;
+use crate::error::AppError;
+use crate::models::llm::{ExecutionLLMConfig, Pricing};
+use crate::tools::policy::ToolPolicy;
+use crate::tools::workspace_diff::WorkspaceSnapshot;
+
+/// Smallest `max_tokens` budget a new execution may request.
+pub const MIN_BUDGET_TOKENS: u32 = 1_000;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BudgetConfig {
@@ -24,6 +32,46 @@ impl Default for BudgetConfig {
     }
 }
 
+impl BudgetConfig {
+    /// Reject impossible budgets and normalize `warning_thresholds`: values
+    /// above 1.0 are clamped to 1.0, non-positive ones dropped, and the rest
+    /// sorted and deduplicated. An empty list falls back to the defaults.
+    pub fn validated(mut self) -> Result<Self, AppError> {
+        if !self.max_cost.is_finite() || self.max_cost < 0.0 {
+            return Err(AppError::Message(format!(
+                "Budget max_cost must be a non-negative number, got {}",
+                self.max_cost
+            )));
+        }
+        if self.max_tokens < MIN_BUDGET_TOKENS {
+            return Err(AppError::Message(format!(
+                "Budget max_tokens must be at least {MIN_BUDGET_TOKENS}, got {}",
+                self.max_tokens
+            )));
+        }
+        if let Some(bad) = self.warning_thresholds.iter().find(|t| !t.is_finite()) {
+            return Err(AppError::Message(format!(
+                "Budget warning threshold must be a number, got {bad}"
+            )));
+        }
+
+        let mut thresholds = self
+            .warning_thresholds
+            .iter()
+            .filter(|&&t| t > 0.0)
+            .map(|&t| t.min(1.0))
+            .collect::<Vec<_>>();
+        thresholds.sort_by(f64::total_cmp);
+        thresholds.dedup();
+        self.warning_thresholds = if thresholds.is_empty() {
+            default_warning_thresholds()
+        } else {
+            thresholds
+        };
+        Ok(self)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionCreate {
     pub team_id: String,
@@ -31,12 +79,27 @@ pub struct ExecutionCreate {
     pub input: String,
     #[serde(default)]
     pub title: Option<String>,
+    /// Falls back to the app settings default, then to `BudgetConfig::default()`.
     #[serde(default)]
-    pub budget: BudgetConfig,
+    pub budget: Option<BudgetConfig>,
     #[serde(default)]
     pub llm: Option<ExecutionLLMConfig>,
     #[serde(default)]
     pub workspace_path: Option<String>,
+    /// Extra named roots mounted beside `workspace_path`, keyed by prefix.
+    #[serde(default)]
+    pub workspace_roots: BTreeMap<String, String>,
+    /// Tool categories agents may use; read and search when omitted.
+    #[serde(default)]
+    pub tool_policy: ToolPolicy,
+    /// Stream replies from providers that support it; others fall back to
+    /// plain requests. Unset streams where it can without comment; `true`
+    /// also warns about the agents that fall back.
+    #[serde(default)]
+    pub stream: Option<bool>,
+    /// Start from this execution's output; `input`, if any, follows it.
+    #[serde(default)]
+    pub seed_from_execution_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,18 +116,78 @@ pub struct ExecutionMessage {
     pub responding_to: Option<String>,
     pub target_agent_id: Option<String>,
     pub wants_to_continue: bool,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "token_count")]
     pub input_tokens: u32,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "token_count")]
     pub output_tokens: u32,
     #[serde(default)]
     pub tokens_estimated: bool,
+    /// For agent opinions, an `agents::instance::MessageMetadata`; for tool
+    /// messages, the tool event. Older messages may predate the schema.
     #[serde(default)]
     pub metadata: Value,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Read a stored token count, clamping negative or oversized values (e.g. from
+/// rows written by older builds or foreign imports) into `u32` range so one bad
+/// row can't make a whole execution unreadable.
+fn token_count<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<serde_json::Number>::deserialize(deserializer)?;
+    let Some(n) = value else {
+        return Ok(0);
+    };
+    let count = if let Some(u) = n.as_u64() {
+        u.min(u64::from(u32::MAX)) as u32
+    } else if let Some(f) = n.as_f64() {
+        f.clamp(0.0, f64::from(u32::MAX)) as u32
+    } else {
+        0
+    };
+    Ok(count)
+}
+
+/// Prompt-cache `(read, write)` tokens recorded under `metadata.cached`;
+/// zero when the provider reported none.
+pub fn cached_tokens(metadata: &Value) -> (u32, u32) {
+    let cached = &metadata["cached"];
+    let count = |key: &str| {
+        cached
+            .get(key)
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0)
+            .min(u64::from(u32::MAX)) as u32
+    };
+    (count("read"), count("write"))
+}
+
+impl ExecutionMessage {
+    /// Every token the message was billed for, prompt-cache traffic included.
+    pub fn billed_tokens(&self) -> u32 {
+        let (read, write) = cached_tokens(&self.metadata);
+        self.input_tokens
+            .saturating_add(self.output_tokens)
+            .saturating_add(read)
+            .saturating_add(write)
+    }
+
+    pub fn cost(&self, pricing: &Pricing) -> f64 {
+        let (read, write) = cached_tokens(&self.metadata);
+        pricing.cost(self.input_tokens, self.output_tokens) + pricing.cache_cost(read, write)
+    }
+}
+
+/// Order messages by `(sequence, created_at, id)`. Older data may contain
+/// duplicate sequences, so the tie-breakers keep rendering deterministic.
+pub fn sort_messages(messages: &mut [ExecutionMessage]) {
+    messages
+        .sort_by(|a, b| (a.sequence, a.created_at, &a.id).cmp(&(b.sequence, b.created_at, &b.id)));
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionRecord {
     pub id: String,
@@ -87,15 +210,198 @@ pub struct ExecutionRecord {
     pub tokens_budget: u32,
     pub cost: f64,
     pub cost_budget: f64,
+    /// The dry-run estimate from the last `plan_execution`, if any.
+    #[serde(default)]
+    pub estimate: Option<CostEstimate>,
+    /// Estimated ms until the running round finishes; `None` when idle or unknown.
+    #[serde(default)]
+    pub eta_ms: Option<u64>,
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
     pub error_message: Option<String>,
     pub retry_count: u32,
+    /// Tool calls executed across all of the execution's rounds.
+    #[serde(default)]
+    pub tool_calls: u32,
     pub workspace_path: Option<String>,
+    /// Extra named roots mounted beside `workspace_path`, keyed by prefix.
+    #[serde(default)]
+    pub workspace_roots: BTreeMap<String, String>,
+    /// The workspace as the first round found it; `get_workspace_diff`
+    /// compares against this. Cleared when the workspace changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_baseline: Option<WorkspaceSnapshot>,
+    #[serde(default)]
+    pub tool_policy: ToolPolicy,
+    /// See `ExecutionCreate::stream`.
+    #[serde(default)]
+    pub stream: Option<bool>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+fn default_stream() -> bool {
+    true
+}
+
+impl ExecutionRecord {
+    pub fn used_tools(&self) -> bool {
+        self.tool_calls > 0
+    }
+
+    /// Whether the execution carries an LLM bundle; `run_round` fails one
+    /// that doesn't.
+    pub fn llm_configured(&self) -> bool {
+        self.llm.is_some()
+    }
+}
+
+/// Tokens and cost `plan_execution` predicted for the next round, and what
+/// that round used once it completed.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct CostEstimate {
+    pub tokens: u32,
+    pub cost: f64,
+    /// The round the estimate was made for.
+    #[serde(default)]
+    pub round: i32,
+    #[serde(default)]
+    pub actual_tokens: Option<u32>,
+    #[serde(default)]
+    pub actual_cost: Option<f64>,
+}
+
+impl CostEstimate {
+    /// Record what a completed round used, if it is the estimated one.
+    pub fn record_round(&mut self, round: i32, tokens: u32, cost: f64) {
+        if round == self.round {
+            self.actual_tokens = Some(tokens);
+            self.actual_cost = Some(cost);
+        }
+    }
+
+    /// How far the estimated round's usage ended up from the estimate, in
+    /// percent (positive when it cost more). Compares cost when the estimate
+    /// was priced and tokens otherwise; `None` until that round completes
+    /// having used something, or when the estimate is zero.
+    pub fn variance_pct(&self) -> Option<f64> {
+        let tokens_used = self.actual_tokens.filter(|t| *t > 0)?;
+        let (estimated, actual) = if self.cost > 0.0 {
+            (self.cost, self.actual_cost?)
+        } else {
+            (f64::from(self.tokens), f64::from(tokens_used))
+        };
+        (estimated > 0.0).then(|| (actual - estimated) / estimated * 100.0)
+    }
+}
+
+/// Tokens and cost for one agent, round or phase of a cost breakdown. Counts
+/// the provider reported and counts estimated locally are kept apart.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CostLine {
+    pub key: String,
+    /// The agent's name on `by_agent` lines.
+    pub label: Option<String>,
+    pub exact_tokens: u32,
+    pub estimated_tokens: u32,
+    pub exact_cost: f64,
+    pub estimated_cost: f64,
+    pub cost: f64,
+}
+
+impl CostLine {
+    fn new(key: String) -> Self {
+        Self {
+            key,
+            ..Self::default()
+        }
+    }
+
+    fn add(&mut self, message: &ExecutionMessage, cost: f64) {
+        let tokens = message.billed_tokens();
+        if message.tokens_estimated {
+            self.estimated_tokens = self.estimated_tokens.saturating_add(tokens);
+            self.estimated_cost += cost;
+        } else {
+            self.exact_tokens = self.exact_tokens.saturating_add(tokens);
+            self.exact_cost += cost;
+        }
+        self.cost += cost;
+    }
+}
+
+/// Where an execution's cost went, for `get_execution_cost_breakdown`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostBreakdown {
+    pub execution_id: String,
+    pub pricing: Pricing,
+    pub total: CostLine,
+    pub by_agent: Vec<CostLine>,
+    pub by_round: Vec<CostLine>,
+    pub by_phase: Vec<CostLine>,
+}
+
+impl CostBreakdown {
+    /// Price each message's tokens with `pricing`, the same way the run
+    /// charged them. Lines keep the order they first appear in `messages`,
+    /// except rounds, which are ascending.
+    pub fn from_messages(
+        execution_id: &str,
+        pricing: Pricing,
+        messages: &[ExecutionMessage],
+    ) -> Self {
+        fn line(lines: &mut Vec<CostLine>, key: String) -> &mut CostLine {
+            let idx = match lines.iter().position(|l| l.key == key) {
+                Some(idx) => idx,
+                None => {
+                    lines.push(CostLine::new(key));
+                    lines.len() - 1
+                }
+            };
+            &mut lines[idx]
+        }
+
+        let mut breakdown = Self {
+            execution_id: execution_id.to_string(),
+            pricing,
+            total: CostLine::new("total".to_string()),
+            by_agent: Vec::new(),
+            by_round: Vec::new(),
+            by_phase: Vec::new(),
+        };
+        for message in messages {
+            if message.billed_tokens() == 0 {
+                continue;
+            }
+            let cost = message.cost(&pricing);
+            breakdown.total.add(message, cost);
+
+            let sender = message
+                .sender_id
+                .clone()
+                .unwrap_or_else(|| message.sender_type.clone());
+            let agent = line(&mut breakdown.by_agent, sender);
+            if agent.label.is_none() {
+                agent.label = message.sender_name.clone();
+            }
+            agent.add(message, cost);
+            line(&mut breakdown.by_round, message.round.to_string()).add(message, cost);
+            line(&mut breakdown.by_phase, message.phase.clone()).add(message, cost);
+        }
+        breakdown
+            .by_round
+            .sort_by_key(|l| l.key.parse::<i32>().unwrap_or(i32::MAX));
+        breakdown
+    }
+}
+
+/// `ExecutionResponse::collaboration_mode` when the team no longer exists.
+pub const UNKNOWN_COLLABORATION_MODE: &str = "unknown";
+
+fn unknown_collaboration_mode() -> String {
+    UNKNOWN_COLLABORATION_MODE.to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionResponse {
     pub id: String,
@@ -103,11 +409,22 @@ pub struct ExecutionResponse {
     pub team_id: String,
     pub title: Option<String>,
     pub initial_input: String,
+    /// The team's current mode, or `UNKNOWN_COLLABORATION_MODE` if the team
+    /// has since been deleted.
+    #[serde(default = "unknown_collaboration_mode")]
+    pub collaboration_mode: String,
     pub status: String,
     pub current_stage: Option<String>,
     pub current_round: i32,
     #[serde(default)]
     pub shared_state: Value,
+    /// Orchestration phase from `shared_state` (`initializing` when unset),
+    /// so the UI doesn't have to parse orchestration internals.
+    #[serde(default = "initial_phase")]
+    pub phase: String,
+    /// Round from `shared_state`; `0` before the first round starts.
+    #[serde(default)]
+    pub round: i32,
     #[serde(default)]
     pub agent_states: Value,
     pub final_output: Option<String>,
@@ -116,28 +433,62 @@ pub struct ExecutionResponse {
     pub tokens_budget: u32,
     pub cost: f64,
     pub cost_budget: f64,
+    #[serde(default)]
+    pub estimate: Option<CostEstimate>,
+    /// Actual versus `estimate`; see `CostEstimate::variance_pct`.
+    #[serde(default)]
+    pub estimate_variance_pct: Option<f64>,
+    /// Estimated ms until the running round finishes; `None` when idle or unknown.
+    #[serde(default)]
+    pub eta_ms: Option<u64>,
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
     pub error_message: Option<String>,
     #[serde(default)]
+    pub tool_calls: u32,
+    /// Whether any tool was executed (`tool_calls > 0`).
+    #[serde(default)]
+    pub used_tools: bool,
+    /// False when the execution was created without an `llm` config and so
+    /// cannot be started.
+    #[serde(default)]
+    pub llm_configured: bool,
+    #[serde(default)]
     pub recent_messages: Vec<ExecutionMessage>,
     pub workspace_path: Option<String>,
+    #[serde(default)]
+    pub workspace_roots: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tool_policy: ToolPolicy,
+    #[serde(default = "default_stream")]
+    pub stream: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 impl ExecutionResponse {
-    pub fn from_record(record: ExecutionRecord, recent_messages: Vec<ExecutionMessage>) -> Self {
+    pub fn from_record(
+        record: ExecutionRecord,
+        collaboration_mode: String,
+        recent_messages: Vec<ExecutionMessage>,
+    ) -> Self {
+        let (phase, round) = progress(&record.shared_state);
+        let estimate_variance_pct = record.estimate.and_then(|estimate| estimate.variance_pct());
+        let used_tools = record.used_tools();
+        let llm_configured = record.llm_configured();
         Self {
             id: record.id,
             user_id: record.user_id,
             team_id: record.team_id,
             title: record.title,
             initial_input: record.initial_input,
+            collaboration_mode,
             status: record.status,
             current_stage: record.current_stage,
             current_round: record.current_round,
             shared_state: record.shared_state,
+            phase,
+            round,
             agent_states: record.agent_states,
             final_output: record.final_output,
             structured_output: record.structured_output,
@@ -145,17 +496,56 @@ impl ExecutionResponse {
             tokens_budget: record.tokens_budget,
             cost: record.cost,
             cost_budget: record.cost_budget,
+            estimate: record.estimate,
+            estimate_variance_pct,
+            eta_ms: record.eta_ms,
             started_at: record.started_at,
             completed_at: record.completed_at,
             error_message: record.error_message,
+            tool_calls: record.tool_calls,
+            used_tools,
+            llm_configured,
             recent_messages,
             workspace_path: record.workspace_path,
+            workspace_roots: record.workspace_roots,
+            tool_policy: record.tool_policy,
+            stream: record.stream.unwrap_or(true),
             created_at: record.created_at,
             updated_at: record.updated_at,
         }
     }
 }
 
+/// The orchestration phase and round recorded in `shared_state`, defaulting
+/// to `initializing` and `0` before the first round.
+pub fn progress(shared_state: &Value) -> (String, i32) {
+    let phase = shared_state
+        .get("phase")
+        .and_then(Value::as_str)
+        .filter(|phase| !phase.is_empty())
+        .map_or_else(initial_phase, str::to_string);
+    let round = shared_state
+        .get("round")
+        .and_then(Value::as_i64)
+        .and_then(|round| i32::try_from(round).ok())
+        .unwrap_or(0)
+        .max(0);
+    (phase, round)
+}
+
+/// An execution that is running right now, for `list_active_executions`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveExecution {
+    pub id: String,
+    pub team_id: String,
+    pub title: Option<String>,
+    pub phase: String,
+    pub round: i32,
+    pub started_at: Option<DateTime<Utc>>,
+    /// Time since `started_at`; `0` when the start time is unknown.
+    pub elapsed_ms: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionListItem {
     pub id: String,
@@ -163,13 +553,79 @@ pub struct ExecutionListItem {
     pub title: Option<String>,
     pub status: String,
     pub current_round: i32,
+    /// The running pipeline stage, e.g. `stage_3: Reviewer`.
+    pub current_stage: Option<String>,
     pub tokens_used: u32,
     pub cost: f64,
+    pub tool_calls: u32,
+    pub used_tools: bool,
+    pub llm_configured: bool,
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
 }
 
+impl From<&ExecutionRecord> for ExecutionListItem {
+    fn from(e: &ExecutionRecord) -> Self {
+        Self {
+            id: e.id.clone(),
+            team_id: e.team_id.clone(),
+            title: e.title.clone(),
+            status: e.status.clone(),
+            current_round: e.current_round,
+            current_stage: e.current_stage.clone(),
+            tokens_used: e.tokens_used,
+            cost: e.cost,
+            tool_calls: e.tool_calls,
+            used_tools: e.used_tools(),
+            llm_configured: e.llm_configured(),
+            started_at: e.started_at,
+            completed_at: e.completed_at,
+            created_at: e.created_at,
+        }
+    }
+}
+
+/// How many messages an execution has, counted in the store without
+/// loading them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageCounts {
+    pub message_count: u32,
+    /// Distinct rounds that have at least one message.
+    pub round_count: u32,
+    pub phase_counts: BTreeMap<String, u32>,
+}
+
+/// A lightweight view of an execution for previews: the list fields, final
+/// output and message counts, without any message bodies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionSummary {
+    #[serde(flatten)]
+    pub execution: ExecutionListItem,
+    pub final_output: Option<String>,
+    pub error_message: Option<String>,
+    #[serde(flatten)]
+    pub counts: MessageCounts,
+}
+
+/// One model's run in a `benchmark_team` comparison. Each run is its own
+/// execution, kept so its messages can be opened afterwards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub model_id: String,
+    pub execution_id: String,
+    pub status: String,
+    pub final_output: Option<String>,
+    pub error_message: Option<String>,
+    pub tokens_used: u32,
+    pub cost: f64,
+    pub latency_ms: u64,
+}
+
+fn initial_phase() -> String {
+    "initializing".to_string()
+}
+
 fn default_max_tokens() -> u32 {
     200_000
 }
@@ -181,3 +637,206 @@ fn default_max_cost() -> f64 {
 fn default_warning_thresholds() -> Vec<f64> {
     vec![0.5, 0.8, 0.95]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_token_counts_are_clamped_on_read() {
+        let mut json = serde_json::to_value(message("m", 1, Utc::now())).unwrap();
+        json["input_tokens"] = serde_json::json!(-12);
+        json["output_tokens"] = serde_json::json!(6_442_450_941u64);
+        let read: ExecutionMessage = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!((read.input_tokens, read.output_tokens), (0, u32::MAX));
+
+        json["input_tokens"] = Value::Null;
+        json.as_object_mut().unwrap().remove("output_tokens");
+        let read: ExecutionMessage = serde_json::from_value(json).unwrap();
+        assert_eq!((read.input_tokens, read.output_tokens), (0, 0));
+    }
+
+    fn budget(json: Value) -> BudgetConfig {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn negative_cost_budget_is_rejected() {
+        let err = budget(serde_json::json!({ "max_cost": -5.0 }))
+            .validated()
+            .unwrap_err();
+        assert!(err.to_string().contains("max_cost"));
+    }
+
+    #[test]
+    fn tiny_token_budget_is_rejected() {
+        let err = budget(serde_json::json!({ "max_tokens": 10 }))
+            .validated()
+            .unwrap_err();
+        assert!(err.to_string().contains("at least 1000"));
+    }
+
+    #[test]
+    fn unsorted_thresholds_are_sorted_and_deduped() {
+        let normalized = budget(serde_json::json!({ "warning_thresholds": [0.9, 0.5, 0.9, 0.7] }))
+            .validated()
+            .unwrap();
+        assert_eq!(normalized.warning_thresholds, vec![0.5, 0.7, 0.9]);
+    }
+
+    #[test]
+    fn out_of_range_thresholds_are_clamped_or_dropped() {
+        let normalized =
+            budget(serde_json::json!({ "warning_thresholds": [1.5, 0.0, -0.2, 0.8, 1.0] }))
+                .validated()
+                .unwrap();
+        assert_eq!(normalized.warning_thresholds, vec![0.8, 1.0]);
+
+        let fallback = budget(serde_json::json!({ "warning_thresholds": [] }))
+            .validated()
+            .unwrap();
+        assert_eq!(fallback.warning_thresholds, default_warning_thresholds());
+    }
+
+    fn message(id: &str, sequence: i32, created_at: DateTime<Utc>) -> ExecutionMessage {
+        ExecutionMessage {
+            id: id.to_string(),
+            sequence,
+            round: 1,
+            phase: "initial".to_string(),
+            sender_type: "agent".to_string(),
+            sender_id: None,
+            sender_name: None,
+            content: id.to_string(),
+            content_type: "text".to_string(),
+            responding_to: None,
+            target_agent_id: None,
+            wants_to_continue: true,
+            input_tokens: 0,
+            output_tokens: 0,
+            tokens_estimated: false,
+            metadata: Value::Null,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    fn priced(
+        id: &str,
+        round: i32,
+        phase: &str,
+        sender: &str,
+        tokens: (u32, u32),
+        estimated: bool,
+    ) -> ExecutionMessage {
+        let mut m = message(id, 0, Utc::now());
+        m.round = round;
+        m.phase = phase.to_string();
+        m.sender_id = Some(sender.to_string());
+        m.sender_name = Some(sender.to_uppercase());
+        (m.input_tokens, m.output_tokens) = tokens;
+        m.tokens_estimated = estimated;
+        m
+    }
+
+    #[test]
+    fn cost_breakdown_bills_prompt_cache_tokens() {
+        let pricing = Pricing {
+            input_per_1k: 1.0,
+            output_per_1k: 2.0,
+            cache_read_per_1k: 0.1,
+            cache_write_per_1k: 0.0,
+        };
+        let mut cached = priced("m1", 1, "initial", "a", (0, 0), false);
+        cached.metadata = serde_json::json!({ "cached": { "read": 1000, "write": 500 } });
+
+        assert_eq!(cached.billed_tokens(), 1_500);
+        // Reads at their own price, writes at the input price.
+        assert!((cached.cost(&pricing) - 0.6).abs() < 1e-9);
+        let breakdown = CostBreakdown::from_messages("e1", pricing, &[cached]);
+        assert_eq!(breakdown.total.exact_tokens, 1_500);
+        assert!((breakdown.total.cost - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cost_breakdown_groups_and_sums_to_the_total() {
+        let pricing = Pricing {
+            input_per_1k: 1.0,
+            output_per_1k: 2.0,
+            ..Pricing::default()
+        };
+        let mut user = message("u", 0, Utc::now());
+        user.sender_type = "user".to_string();
+        let messages = [
+            user,
+            priced("m1", 1, "initial", "a", (100, 50), false),
+            priced("m2", 1, "initial", "b", (200, 0), true),
+            priced("m3", 2, "responding", "a", (0, 100), false),
+            priced("m4", 2, "summary", "b", (100, 100), false),
+        ];
+        let execution_cost = messages
+            .iter()
+            .map(|m| pricing.cost(m.input_tokens, m.output_tokens))
+            .sum::<f64>();
+
+        let breakdown = CostBreakdown::from_messages("e1", pricing, &messages);
+        assert!((breakdown.total.cost - execution_cost).abs() < 1e-9);
+        assert_eq!(
+            (
+                breakdown.total.exact_tokens,
+                breakdown.total.estimated_tokens
+            ),
+            (450, 200)
+        );
+        assert!((breakdown.total.estimated_cost - 0.2).abs() < 1e-9);
+
+        let keys = |lines: &[CostLine]| lines.iter().map(|l| l.key.clone()).collect::<Vec<_>>();
+        assert_eq!(keys(&breakdown.by_agent), ["a", "b"]);
+        assert_eq!(keys(&breakdown.by_round), ["1", "2"]);
+        assert_eq!(
+            keys(&breakdown.by_phase),
+            ["initial", "responding", "summary"]
+        );
+        assert_eq!(breakdown.by_agent[0].label.as_deref(), Some("A"));
+        assert!((breakdown.by_agent[0].cost - 0.4).abs() < 1e-9);
+        assert_eq!(
+            (
+                breakdown.by_agent[1].exact_tokens,
+                breakdown.by_agent[1].estimated_tokens
+            ),
+            (200, 200)
+        );
+        for lines in [
+            &breakdown.by_agent,
+            &breakdown.by_round,
+            &breakdown.by_phase,
+        ] {
+            let sum = lines.iter().map(|l| l.cost).sum::<f64>();
+            assert!((sum - execution_cost).abs() < 1e-9);
+        }
+    }
+
+    fn ids(messages: &[ExecutionMessage]) -> Vec<&str> {
+        messages.iter().map(|m| m.id.as_str()).collect()
+    }
+
+    #[test]
+    fn colliding_sequences_sort_by_time_then_id() {
+        let t0 = Utc::now();
+        let t1 = t0 + chrono::Duration::milliseconds(5);
+        let mut a = vec![
+            message("c", 3, t0),
+            message("b", 2, t1),
+            message("z", 2, t0),
+            message("a", 2, t0),
+            message("first", 1, t1),
+        ];
+        let mut b = a.clone();
+        b.reverse();
+
+        sort_messages(&mut a);
+        sort_messages(&mut b);
+        assert_eq!(ids(&a), vec!["first", "a", "z", "b", "c"]);
+        assert_eq!(ids(&a), ids(&b));
+    }
+}
@@ -0,0 +1,31 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A document in a local knowledge base, retrieved into the context of
+/// agents whose `knowledge_base_id` matches `kb_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KnowledgeDoc {
+    pub id: String,
+    pub kb_id: String,
+    /// Where the content came from (a file path or title); shown as the heading.
+    #[serde(default)]
+    pub path: Option<String>,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeDocCreate {
+    pub kb_id: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct KnowledgeDocUpdate {
+    pub kb_id: Option<String>,
+    pub path: Option<String>,
+    pub content: Option<String>,
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -26,6 +26,196 @@ pub struct LLMRuntimeConfig {
     pub input_price_per_1k: f64,
     #[serde(default)]
     pub output_price_per_1k: f64,
+    /// Prices for prompt-cache reads and writes; zero bills them at
+    /// `input_price_per_1k`.
+    #[serde(default)]
+    pub cache_read_price_per_1k: f64,
+    #[serde(default)]
+    pub cache_write_price_per_1k: f64,
+    /// Sent with every request, e.g. `HTTP-Referer` / `X-Title` for gateways.
+    #[serde(default)]
+    pub extra_headers: HashMap<String, String>,
+    /// `low` / `medium` / `high` for reasoning models: OpenAI's
+    /// `reasoning_effort`, or an Anthropic extended-thinking budget (which
+    /// also accepts a token count). Omitted from requests when unset.
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+    /// Mark the system prompt cacheable (Anthropic `cache_control`), so
+    /// agents reusing it across rounds pay the cached-input rate.
+    #[serde(default)]
+    pub enable_prompt_cache: bool,
+    /// Nucleus sampling; sent to both providers when set.
+    #[serde(default)]
+    pub top_p: Option<f64>,
+    /// Anthropic only.
+    #[serde(default)]
+    pub top_k: Option<u32>,
+    /// OpenAI-compatible only.
+    #[serde(default)]
+    pub frequency_penalty: Option<f64>,
+    /// OpenAI-compatible only.
+    #[serde(default)]
+    pub presence_penalty: Option<f64>,
+}
+
+impl LLMRuntimeConfig {
+    /// `reasoning_effort`, trimmed and lowercased; `None` when blank.
+    pub fn reasoning_effort(&self) -> Option<String> {
+        self.reasoning_effort
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_lowercase)
+    }
+
+    pub fn sampling(&self) -> SamplingParams {
+        SamplingParams {
+            top_p: self.top_p,
+            top_k: self.top_k,
+            frequency_penalty: self.frequency_penalty,
+            presence_penalty: self.presence_penalty,
+        }
+    }
+
+    pub fn pricing(&self) -> Pricing {
+        Pricing {
+            input_per_1k: self.input_price_per_1k,
+            output_per_1k: self.output_price_per_1k,
+            cache_read_per_1k: self.cache_read_price_per_1k,
+            cache_write_per_1k: self.cache_write_price_per_1k,
+        }
+    }
+
+    /// The config's own prices, or the table's entry for `model_id` when
+    /// all of them are left at zero.
+    pub fn resolve_pricing(&self, table: &PricingTable) -> Pricing {
+        let own = self.pricing();
+        if own != Pricing::default() {
+            return own;
+        }
+        table.lookup(&self.model_id).unwrap_or_default()
+    }
+}
+
+/// Optional sampling controls beyond `temperature`. Unset values are left
+/// out of the request body, so the provider's defaults apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SamplingParams {
+    pub top_p: Option<f64>,
+    pub top_k: Option<u32>,
+    pub frequency_penalty: Option<f64>,
+    pub presence_penalty: Option<f64>,
+}
+
+/// Per-1k-token prices used to turn token counts into cost.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pricing {
+    pub input_per_1k: f64,
+    pub output_per_1k: f64,
+    /// Zero bills cache reads at `input_per_1k`.
+    #[serde(default)]
+    pub cache_read_per_1k: f64,
+    /// Zero bills cache writes at `input_per_1k`.
+    #[serde(default)]
+    pub cache_write_per_1k: f64,
+}
+
+impl Pricing {
+    pub fn cost(&self, input_tokens: u32, output_tokens: u32) -> f64 {
+        f64::from(input_tokens) / 1000.0 * self.input_per_1k
+            + f64::from(output_tokens) / 1000.0 * self.output_per_1k
+    }
+
+    /// Cost of prompt-cache traffic, which providers such as Anthropic report
+    /// apart from `input_tokens`.
+    pub fn cache_cost(&self, read_tokens: u32, write_tokens: u32) -> f64 {
+        let price = |own: f64| if own > 0.0 { own } else { self.input_per_1k };
+        f64::from(read_tokens) / 1000.0 * price(self.cache_read_per_1k)
+            + f64::from(write_tokens) / 1000.0 * price(self.cache_write_per_1k)
+    }
+}
+
+/// Built-in per-1k prices (input, output) in USD for well-known model ids.
+const BUILTIN_PRICING: &[(&str, f64, f64)] = &[
+    ("gpt-4o", 0.0025, 0.01),
+    ("gpt-4o-mini", 0.00015, 0.0006),
+    ("gpt-4.1", 0.002, 0.008),
+    ("gpt-4.1-mini", 0.0004, 0.0016),
+    ("gpt-4.1-nano", 0.0001, 0.0004),
+    ("o3-mini", 0.0011, 0.0044),
+    ("claude-opus-4", 0.015, 0.075),
+    ("claude-sonnet-4", 0.003, 0.015),
+    ("claude-3-5-sonnet", 0.003, 0.015),
+    ("claude-3-5-haiku", 0.0008, 0.004),
+    ("deepseek-chat", 0.00027, 0.0011),
+    ("deepseek-reasoner", 0.00055, 0.00219),
+    ("qwen-plus", 0.0004, 0.0012),
+    ("qwen-max", 0.0016, 0.0064),
+];
+
+/// Anthropic bills cache reads at a tenth of the input price and cache
+/// writes at a quarter over it.
+const CLAUDE_CACHE_READ_RATE: f64 = 0.1;
+const CLAUDE_CACHE_WRITE_RATE: f64 = 1.25;
+
+/// Prices keyed by model id, used for configs that leave their own prices
+/// at zero: the built-in entries with the user's overrides applied.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PricingTable {
+    models: BTreeMap<String, Pricing>,
+}
+
+impl PricingTable {
+    pub fn builtin() -> Self {
+        let models = BUILTIN_PRICING
+            .iter()
+            .map(|(id, input, output)| {
+                let (cache_read, cache_write) = if id.starts_with("claude") {
+                    (
+                        input * CLAUDE_CACHE_READ_RATE,
+                        input * CLAUDE_CACHE_WRITE_RATE,
+                    )
+                } else {
+                    (0.0, 0.0)
+                };
+                (
+                    id.to_string(),
+                    Pricing {
+                        input_per_1k: *input,
+                        output_per_1k: *output,
+                        cache_read_per_1k: cache_read,
+                        cache_write_per_1k: cache_write,
+                    },
+                )
+            })
+            .collect();
+        Self { models }
+    }
+
+    pub fn with_overrides(overrides: &BTreeMap<String, Pricing>) -> Self {
+        let mut table = Self::builtin();
+        for (id, pricing) in overrides {
+            table.models.insert(id.trim().to_lowercase(), *pricing);
+        }
+        table
+    }
+
+    pub fn models(&self) -> &BTreeMap<String, Pricing> {
+        &self.models
+    }
+
+    /// Price for `model_id`, ignoring case and any gateway prefix such as
+    /// `openai/`. Dated or suffixed ids (`gpt-4o-2024-08-06`) match the
+    /// longest entry they start with.
+    pub fn lookup(&self, model_id: &str) -> Option<Pricing> {
+        let id = model_id.trim().to_lowercase();
+        let id = id.rsplit('/').next().unwrap_or_default();
+        self.models
+            .iter()
+            .filter(|(key, _)| id.starts_with(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, pricing)| *pricing)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,3 +236,63 @@ fn default_max_context_length() -> u32 {
 fn default_supports_tools() -> bool {
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(model_id: &str, input: f64, output: f64) -> LLMRuntimeConfig {
+        serde_json::from_value(serde_json::json!({
+            "model_id": model_id,
+            "api_key": "k",
+            "input_price_per_1k": input,
+            "output_price_per_1k": output
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn zero_priced_config_falls_back_to_the_table() {
+        let table = PricingTable::builtin();
+        let pricing = config("openai/GPT-4o-mini-2024-07-18", 0.0, 0.0).resolve_pricing(&table);
+        assert_eq!(pricing, table.lookup("gpt-4o-mini").unwrap());
+        assert_ne!(pricing, table.lookup("gpt-4o").unwrap());
+
+        let unknown = config("local-llama", 0.0, 0.0).resolve_pricing(&table);
+        assert_eq!(unknown, Pricing::default());
+    }
+
+    #[test]
+    fn config_prices_and_overrides_win_over_builtin_entries() {
+        let own = config("gpt-4o", 1.0, 0.0).resolve_pricing(&PricingTable::builtin());
+        assert_eq!(own.input_per_1k, 1.0);
+        assert_eq!(own.output_per_1k, 0.0);
+
+        let custom = Pricing {
+            input_per_1k: 0.5,
+            output_per_1k: 0.25,
+            ..Pricing::default()
+        };
+        let table = PricingTable::with_overrides(&BTreeMap::from([
+            ("GPT-4o".to_string(), custom),
+            ("local-llama".to_string(), custom),
+        ]));
+        assert_eq!(config("gpt-4o", 0.0, 0.0).resolve_pricing(&table), custom);
+        assert_eq!(
+            config("local-llama", 0.0, 0.0).resolve_pricing(&table),
+            custom
+        );
+    }
+
+    #[test]
+    fn cache_tokens_use_their_own_price_or_the_input_price() {
+        let claude = PricingTable::builtin()
+            .lookup("claude-sonnet-4-20250514")
+            .unwrap();
+        // 1k reads at 0.0003 plus 1k writes at 0.00375.
+        assert!((claude.cache_cost(1_000, 1_000) - 0.00405).abs() < 1e-12);
+
+        let flat = config("local-llama", 0.5, 1.0).resolve_pricing(&PricingTable::builtin());
+        assert_eq!(flat.cache_cost(2_000, 2_000), 2.0);
+    }
+}
@@ -1,5 +1,7 @@
 pub mod agent;
 pub mod common;
 pub mod execution;
+pub mod knowledge;
 pub mod llm;
+pub mod settings;
 pub mod team;
@@ -0,0 +1,215 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::models::execution::{BudgetConfig, ExecutionCreate};
+use crate::models::llm::ExecutionLLMConfig;
+use crate::models::team::{default_collaboration_mode, TeamCreate};
+
+/// `AppSettings::max_team_members` when the settings leave it unset.
+pub const DEFAULT_MAX_TEAM_MEMBERS: usize = 20;
+/// `AppSettings::max_system_prompt_chars` when the settings leave it unset.
+pub const DEFAULT_MAX_SYSTEM_PROMPT_CHARS: usize = 20_000;
+
+/// App-wide defaults used when a new team or execution omits a field.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppSettings {
+    #[serde(default)]
+    pub default_llm: Option<ExecutionLLMConfig>,
+    #[serde(default)]
+    pub default_collaboration_mode: Option<String>,
+    #[serde(default)]
+    pub default_budget: Option<BudgetConfig>,
+    /// How many recent prior opinions a follow-up round sees; unset uses
+    /// `orchestration::state::DEFAULT_CONTEXT_LIMIT`. A team's
+    /// `mode_config.context_window` takes precedence.
+    #[serde(default)]
+    pub followup_context_limit: Option<usize>,
+    /// Most members a team may have; unset uses `DEFAULT_MAX_TEAM_MEMBERS`.
+    #[serde(default)]
+    pub max_team_members: Option<usize>,
+    /// Directories an execution's workspace must sit in, so a slip can't
+    /// point agents at `/` or the home directory. Empty allows any path.
+    #[serde(default)]
+    pub allowed_workspace_roots: Vec<String>,
+    /// System prompts longer than this many characters draw a warning; unset
+    /// uses `DEFAULT_MAX_SYSTEM_PROMPT_CHARS`.
+    #[serde(default)]
+    pub max_system_prompt_chars: Option<usize>,
+    /// Cut over-long system prompts to `max_system_prompt_chars` before
+    /// sending them, instead of only warning.
+    #[serde(default)]
+    pub truncate_system_prompt: bool,
+}
+
+impl AppSettings {
+    pub fn apply_to_execution(&self, execution: &mut ExecutionCreate) {
+        if execution.llm.is_none() {
+            execution.llm = self.default_llm.clone();
+        }
+        if execution.budget.is_none() {
+            execution.budget = self.default_budget.clone();
+        }
+    }
+
+    pub fn max_team_members(&self) -> usize {
+        self.max_team_members.unwrap_or(DEFAULT_MAX_TEAM_MEMBERS)
+    }
+
+    pub fn max_system_prompt_chars(&self) -> usize {
+        self.max_system_prompt_chars
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_MAX_SYSTEM_PROMPT_CHARS)
+    }
+
+    /// The length agents' system prompts are cut to, if truncation is on.
+    pub fn system_prompt_limit(&self) -> Option<usize> {
+        self.truncate_system_prompt
+            .then(|| self.max_system_prompt_chars())
+    }
+
+    /// Reject `path` unless it resolves to a directory at or under one of
+    /// `allowed_workspace_roots`. Both sides are canonicalized, so `..` and
+    /// symlinks can't step outside; roots that don't exist are ignored.
+    pub fn check_workspace(&self, path: &str) -> Result<(), AppError> {
+        if self.allowed_workspace_roots.is_empty() {
+            return Ok(());
+        }
+        let canonical = Path::new(path.trim())
+            .canonicalize()
+            .map_err(|e| AppError::Message(format!("Workspace {path} cannot be resolved: {e}")))?;
+        let allowed = self
+            .allowed_workspace_roots
+            .iter()
+            .filter_map(|root| PathBuf::from(root.trim()).canonicalize().ok())
+            .any(|root| canonical.starts_with(root));
+        if allowed {
+            Ok(())
+        } else {
+            Err(AppError::Message(format!(
+                "Workspace {path} is outside the allowed workspace roots"
+            )))
+        }
+    }
+
+    /// Resolve the collaboration mode for a new team, falling back to the
+    /// built-in default when neither the request nor the settings name one.
+    pub fn collaboration_mode_for(&self, team: &TeamCreate) -> String {
+        team.collaboration_mode
+            .clone()
+            .or_else(|| self.default_collaboration_mode.clone())
+            .unwrap_or_else(default_collaboration_mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn execution(json: serde_json::Value) -> ExecutionCreate {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn default_budget_applies_when_omitted() {
+        let settings = AppSettings {
+            default_budget: Some(BudgetConfig {
+                max_tokens: 5_000,
+                max_cost: 0.5,
+                warning_thresholds: vec![0.9],
+            }),
+            ..Default::default()
+        };
+        let mut create = execution(serde_json::json!({ "team_id": "t1" }));
+        settings.apply_to_execution(&mut create);
+
+        let budget = create.budget.unwrap();
+        assert_eq!(budget.max_tokens, 5_000);
+        assert_eq!(budget.max_cost, 0.5);
+    }
+
+    #[test]
+    fn explicit_budget_wins_over_default() {
+        let settings = AppSettings {
+            default_budget: Some(BudgetConfig {
+                max_tokens: 5_000,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut create = execution(serde_json::json!({
+            "team_id": "t1",
+            "budget": { "max_tokens": 123 }
+        }));
+        settings.apply_to_execution(&mut create);
+        assert_eq!(create.budget.unwrap().max_tokens, 123);
+    }
+
+    #[test]
+    fn team_mode_falls_back_to_settings_then_builtin() {
+        let team: TeamCreate = serde_json::from_value(serde_json::json!({ "name": "T" })).unwrap();
+        assert_eq!(
+            AppSettings::default().collaboration_mode_for(&team),
+            "roundtable"
+        );
+
+        let settings = AppSettings {
+            default_collaboration_mode: Some("debate".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(settings.collaboration_mode_for(&team), "debate");
+
+        let explicit: TeamCreate = serde_json::from_value(serde_json::json!({
+            "name": "T",
+            "collaboration_mode": "pipeline"
+        }))
+        .unwrap();
+        assert_eq!(settings.collaboration_mode_for(&explicit), "pipeline");
+    }
+
+    #[test]
+    fn system_prompts_are_only_cut_when_truncation_is_on() {
+        let defaults = AppSettings::default();
+        assert_eq!(
+            defaults.max_system_prompt_chars(),
+            DEFAULT_MAX_SYSTEM_PROMPT_CHARS
+        );
+        assert_eq!(defaults.system_prompt_limit(), None);
+
+        let settings = AppSettings {
+            max_system_prompt_chars: Some(500),
+            truncate_system_prompt: true,
+            ..Default::default()
+        };
+        assert_eq!(settings.system_prompt_limit(), Some(500));
+    }
+
+    #[test]
+    fn workspaces_must_sit_under_an_allowed_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let allowed = dir.path().join("projects");
+        let inside = allowed.join("app");
+        let outside = dir.path().join("secrets");
+        std::fs::create_dir_all(&inside).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        let path = |p: &Path| p.to_string_lossy().to_string();
+
+        let open = AppSettings::default();
+        assert!(open.check_workspace(&path(&outside)).is_ok());
+
+        let settings = AppSettings {
+            allowed_workspace_roots: vec![path(&allowed)],
+            ..Default::default()
+        };
+        assert!(settings.check_workspace(&path(&inside)).is_ok());
+        assert!(settings.check_workspace(&path(&allowed)).is_ok());
+        let err = settings.check_workspace(&path(&outside)).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("outside the allowed workspace roots"));
+        // `..` is resolved before the comparison.
+        let escape = allowed.join("..").join("secrets");
+        assert!(settings.check_workspace(&path(&escape)).is_err());
+    }
+}
@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::error::AppError;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CoordinationRules {
     #[serde(default = "default_first_speaker")]
@@ -74,6 +76,10 @@ pub struct Team {
     pub coordination_rules: CoordinationRules,
     #[serde(default)]
     pub output_rules: OutputRules,
+    /// Bumped on every change; clients send it back as `expected_revision`
+    /// so an edit made against an older copy is rejected.
+    #[serde(default)]
+    pub revision: u32,
     pub is_template: bool,
     pub is_public: bool,
     pub usage_count: u32,
@@ -85,6 +91,171 @@ pub struct Team {
     pub updated_at: DateTime<Utc>,
 }
 
+impl Team {
+    /// Record a change: bump `revision` and set `updated_at`.
+    pub fn touch(&mut self, now: DateTime<Utc>) {
+        self.revision = self.revision.saturating_add(1);
+        self.updated_at = now;
+    }
+
+    /// Active members in speaking position order.
+    pub fn active_members(&self) -> Vec<&TeamMember> {
+        let mut members = self
+            .members
+            .iter()
+            .filter(|m| m.is_active)
+            .collect::<Vec<_>>();
+        members.sort_by_key(|m| m.position);
+        members
+    }
+
+    /// Whether `mode_config.allow_commands` lets agents run command-backed tools.
+    pub fn allow_commands(&self) -> bool {
+        self.mode_config
+            .get("allow_commands")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// `mode_config.strict_path_case`: fail writes whose name differs only in
+    /// case from an existing file instead of warning.
+    pub fn strict_path_case(&self) -> bool {
+        self.mode_config
+            .get("strict_path_case")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// `mode_config.ordered_messages`: persist and emit each message under a
+    /// per-execution lock, with sequences allocated by the store, so the
+    /// stored order always matches the order events were sent in.
+    pub fn ordered_messages(&self) -> bool {
+        self.mode_config
+            .get("ordered_messages")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// `mode_config.strict_coordinator`: treat a `coordinator_id` whose agent
+    /// was deleted as an error instead of falling back to another member.
+    pub fn strict_coordinator(&self) -> bool {
+        self.mode_config
+            .get("strict_coordinator")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// `mode_config.context_window`: prior opinions shown to each agent,
+    /// overriding the app-wide `followup_context_limit`.
+    pub fn context_window(&self) -> Option<usize> {
+        self.mode_config
+            .get("context_window")
+            .and_then(|v| v.as_u64())
+            .filter(|&n| n > 0)
+            .map(|n| n as usize)
+    }
+
+    /// `mode_config.max_opinion_chars`: default opinion length cap for every
+    /// member; a member's `config_override` can replace it.
+    pub fn max_opinion_chars(&self) -> Option<usize> {
+        self.mode_config
+            .get("max_opinion_chars")
+            .and_then(|v| v.as_u64())
+            .filter(|&n| n > 0)
+            .map(|n| n as usize)
+    }
+
+    /// Wrap raw user input with `mode_config.input_template`, substituting the
+    /// `{{input}}` placeholder (or appending the input when it is absent).
+    /// Teams without a template, and empty input, pass through unchanged.
+    pub fn apply_input_template(&self, input: &str) -> String {
+        let template = self
+            .mode_config
+            .get("input_template")
+            .and_then(|v| v.as_str())
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty());
+        let Some(template) = template else {
+            return input.to_string();
+        };
+        if input.trim().is_empty() {
+            return input.to_string();
+        }
+        if template.contains("{{input}}") {
+            template.replace("{{input}}", input)
+        } else {
+            format!("{template}\n\n{input}")
+        }
+    }
+}
+
+/// How much a readiness issue matters: warnings lower the score, blockers
+/// stop the team from running at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+    Warning,
+    Blocker,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamIssue {
+    pub severity: IssueSeverity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessStatus {
+    Ready,
+    Warnings,
+    Blocked,
+}
+
+/// Score lost per warning; a blocked team always scores 0.
+pub const READINESS_WARNING_PENALTY: u32 = 20;
+
+/// A quick "can this team run" summary of its validation issues.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamReadiness {
+    pub status: ReadinessStatus,
+    /// 0-100.
+    pub score: u32,
+    pub issues: Vec<TeamIssue>,
+}
+
+impl TeamReadiness {
+    pub fn from_issues(issues: Vec<TeamIssue>) -> Self {
+        let warnings = issues
+            .iter()
+            .filter(|i| i.severity == IssueSeverity::Warning)
+            .count() as u32;
+        let (status, score) = if issues.iter().any(|i| i.severity == IssueSeverity::Blocker) {
+            (ReadinessStatus::Blocked, 0)
+        } else if warnings > 0 {
+            (
+                ReadinessStatus::Warnings,
+                100u32.saturating_sub(warnings * READINESS_WARNING_PENALTY),
+            )
+        } else {
+            (ReadinessStatus::Ready, 100)
+        };
+        Self {
+            status,
+            score,
+            issues,
+        }
+    }
+}
+
+/// `get_team` payload: the team itself plus its readiness.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamDetail {
+    #[serde(flatten)]
+    pub team: Team,
+    pub readiness: TeamReadiness,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TeamListItem {
     pub id: String,
@@ -120,8 +291,9 @@ pub struct TeamCreate {
     pub description: Option<String>,
     #[serde(default)]
     pub icon: Option<String>,
-    #[serde(default = "default_collaboration_mode")]
-    pub collaboration_mode: String,
+    /// Falls back to the app settings default, then to `roundtable`.
+    #[serde(default)]
+    pub collaboration_mode: Option<String>,
     #[serde(default)]
     pub mode_config: Value,
     #[serde(default)]
@@ -172,6 +344,145 @@ fn default_output_format() -> String {
     "markdown".to_string()
 }
 
-fn default_collaboration_mode() -> String {
+pub(crate) fn default_collaboration_mode() -> String {
     "roundtable".to_string()
 }
+
+/// Modes `run_round` accepts; `freeform` runs the roundtable flow.
+pub const COLLABORATION_MODES: &[&str] = &["roundtable", "debate", "pipeline", "freeform"];
+
+/// Trim `mode` and reject anything outside `COLLABORATION_MODES`, which
+/// would otherwise silently run as a roundtable.
+pub fn normalize_collaboration_mode(mode: &str) -> Result<String, AppError> {
+    let trimmed = mode.trim();
+    if COLLABORATION_MODES.contains(&trimmed) {
+        return Ok(trimmed.to_string());
+    }
+    Err(AppError::Message(format!(
+        "Unknown collaboration_mode '{mode}'; expected one of: {}",
+        COLLABORATION_MODES.join(", ")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collaboration_modes_are_trimmed_and_checked() {
+        for mode in COLLABORATION_MODES {
+            assert_eq!(normalize_collaboration_mode(mode).unwrap(), *mode);
+        }
+        assert_eq!(normalize_collaboration_mode("debate ").unwrap(), "debate");
+        let err = normalize_collaboration_mode("debtae").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Unknown collaboration_mode 'debtae'"));
+        assert!(normalize_collaboration_mode("").is_err());
+    }
+
+    fn team(mode_config: Value) -> Team {
+        let now = Utc::now();
+        Team {
+            id: "t1".to_string(),
+            user_id: "local".to_string(),
+            name: "Team".to_string(),
+            description: None,
+            icon: None,
+            collaboration_mode: default_collaboration_mode(),
+            mode_config,
+            coordinator_id: None,
+            coordination_rules: CoordinationRules::default(),
+            output_rules: OutputRules::default(),
+            revision: 0,
+            is_template: false,
+            is_public: false,
+            usage_count: 0,
+            rating: 0.0,
+            rating_count: 0,
+            members: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn member(agent_id: &str, position: i32, is_active: bool) -> TeamMember {
+        let now = Utc::now();
+        TeamMember {
+            id: format!("m-{agent_id}"),
+            agent_id: agent_id.to_string(),
+            role_override: None,
+            priority_override: None,
+            config_override: serde_json::json!({}),
+            position,
+            is_active,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn active_members_skips_inactive_and_sorts_by_position() {
+        let mut t = team(serde_json::json!({}));
+        t.members = vec![
+            member("c", 2, true),
+            member("a", 0, true),
+            member("b", 1, false),
+        ];
+        let ids = t
+            .active_members()
+            .iter()
+            .map(|m| m.agent_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["a", "c"]);
+    }
+
+    #[test]
+    fn context_window_reads_positive_mode_config_values() {
+        assert_eq!(
+            team(serde_json::json!({"context_window": 10})).context_window(),
+            Some(10)
+        );
+        assert_eq!(
+            team(serde_json::json!({"context_window": 0})).context_window(),
+            None
+        );
+        assert_eq!(team(serde_json::json!({})).context_window(), None);
+    }
+
+    #[test]
+    fn input_template_wraps_input() {
+        let t = team(serde_json::json!({
+            "input_template": "Review the following change:\n{{input}}\nFocus on security."
+        }));
+        assert_eq!(
+            t.apply_input_template("diff --git a b"),
+            "Review the following change:\ndiff --git a b\nFocus on security."
+        );
+    }
+
+    #[test]
+    fn input_template_without_placeholder_appends_input() {
+        let t = team(serde_json::json!({ "input_template": "Be concise." }));
+        assert_eq!(t.apply_input_template("Why?"), "Be concise.\n\nWhy?");
+    }
+
+    #[test]
+    fn team_without_template_uses_raw_input() {
+        assert_eq!(
+            team(serde_json::json!({})).apply_input_template("raw"),
+            "raw"
+        );
+        assert_eq!(team(Value::Null).apply_input_template("raw"), "raw");
+        assert_eq!(
+            team(serde_json::json!({ "input_template": "  " })).apply_input_template("raw"),
+            "raw"
+        );
+    }
+
+    #[test]
+    fn input_template_leaves_empty_input_empty() {
+        let t = team(serde_json::json!({ "input_template": "Task: {{input}}" }));
+        assert_eq!(t.apply_input_template(""), "");
+    }
+}
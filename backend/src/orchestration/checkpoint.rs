@@ -0,0 +1,183 @@
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+use crate::orchestration::state::{OrchestrationState, PhaseStart};
+
+/// Opinions between mid-round checkpoints of `shared_state`.
+pub const CHECKPOINT_EVERY_OPINIONS: u32 = 10;
+/// Longest a pending opinion waits before a checkpoint is written anyway.
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Decides when a running round writes a `checkpoint` of its state: after
+/// `every` opinions, once `interval` has passed with opinions pending, and
+/// at every phase boundary that added any. Each checkpoint is bounded in size
+/// (see `OrchestrationState::checkpoint`), so writes grow linearly with the
+/// number of opinions rather than quadratically.
+#[derive(Debug, Clone)]
+pub struct CheckpointThrottle {
+    every: u32,
+    interval: Duration,
+    pending: u32,
+    last: Option<Instant>,
+}
+
+impl Default for CheckpointThrottle {
+    fn default() -> Self {
+        Self::new(CHECKPOINT_EVERY_OPINIONS, CHECKPOINT_INTERVAL)
+    }
+}
+
+impl CheckpointThrottle {
+    pub fn new(every: u32, interval: Duration) -> Self {
+        Self {
+            every: every.max(1),
+            interval,
+            pending: 0,
+            last: None,
+        }
+    }
+
+    pub fn record_opinion(&mut self) {
+        self.pending = self.pending.saturating_add(1);
+    }
+
+    /// Whether to write a checkpoint now; resets the count when it is.
+    pub fn due(&mut self, boundary: bool, now: Instant) -> bool {
+        if self.pending == 0 {
+            return false;
+        }
+        let waited = self
+            .last
+            .is_none_or(|at| now.duration_since(at) >= self.interval);
+        if !(boundary || waited || self.pending >= self.every) {
+            return false;
+        }
+        self.pending = 0;
+        self.last = Some(now);
+        true
+    }
+}
+
+/// Emit `progress` after an opinion, then a `checkpoint` of the state if one
+/// is due.
+pub fn emit_progress(
+    emit: &mut impl FnMut(&str, serde_json::Value, Option<String>) -> Result<(), AppError>,
+    state: &mut OrchestrationState,
+) -> Result<(), AppError> {
+    emit("progress", state.progress(), None)?;
+    emit_checkpoint(emit, state, false)
+}
+
+/// Emit `round_complete` for the phase begun at `start`, then a checkpoint
+/// of the state at this boundary.
+pub fn emit_phase_complete(
+    emit: &mut impl FnMut(&str, serde_json::Value, Option<String>) -> Result<(), AppError>,
+    state: &mut OrchestrationState,
+    start: &PhaseStart,
+    phase: &str,
+) -> Result<(), AppError> {
+    emit("round_complete", state.phase_complete(start, phase), None)?;
+    emit_checkpoint(emit, state, true)
+}
+
+fn emit_checkpoint(
+    emit: &mut impl FnMut(&str, serde_json::Value, Option<String>) -> Result<(), AppError>,
+    state: &mut OrchestrationState,
+    boundary: bool,
+) -> Result<(), AppError> {
+    if state.checkpoints.due(boundary, Instant::now()) {
+        emit("checkpoint", state.checkpoint(), None)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestration::state::Opinion;
+
+    fn opinion(n: usize) -> Opinion {
+        Opinion {
+            agent_id: format!("a{}", n % 3),
+            agent_name: "Agent".to_string(),
+            content: "x".repeat(200),
+            round: 1,
+            phase: "initial".to_string(),
+            wants_to_continue: true,
+            responding_to: None,
+            input_tokens: 1,
+            output_tokens: 1,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
+        }
+    }
+
+    #[test]
+    fn throttle_fires_on_count_interval_and_boundaries() {
+        let start = Instant::now();
+        let mut throttle = CheckpointThrottle::new(3, Duration::from_secs(5));
+        assert!(!throttle.due(true, start), "nothing pending");
+        throttle.record_opinion();
+        assert!(
+            throttle.due(false, start),
+            "first opinion has no prior write"
+        );
+        throttle.record_opinion();
+        throttle.record_opinion();
+        assert!(!throttle.due(false, start));
+        throttle.record_opinion();
+        assert!(throttle.due(false, start), "every 3 opinions");
+        throttle.record_opinion();
+        assert!(!throttle.due(false, start + Duration::from_secs(1)));
+        assert!(
+            throttle.due(false, start + Duration::from_secs(6)),
+            "interval"
+        );
+        throttle.record_opinion();
+        assert!(
+            throttle.due(true, start + Duration::from_secs(7)),
+            "boundary"
+        );
+    }
+
+    #[test]
+    fn checkpoint_writes_stay_bounded_under_many_opinions() {
+        let mut state = OrchestrationState {
+            checkpoints: CheckpointThrottle::new(10, Duration::from_secs(3600)),
+            ..Default::default()
+        };
+        let mut checkpoints = Vec::new();
+        let mut emit = |event: &str, data: serde_json::Value, _: Option<String>| {
+            if event == "checkpoint" {
+                checkpoints.push(data);
+            }
+            Ok(())
+        };
+        let start = state.phase_start();
+        for n in 0..1_000 {
+            state.add_opinion(opinion(n));
+            emit_progress(&mut emit, &mut state).unwrap();
+        }
+        emit_phase_complete(&mut emit, &mut state, &start, "initial").unwrap();
+
+        // Opinions 1, 11, ..., 991, then the last nine at the boundary.
+        assert_eq!(checkpoints.len(), 101);
+        let window = state.context_window(0);
+        let sizes: Vec<usize> = checkpoints.iter().map(|c| c.to_string().len()).collect();
+        assert!(checkpoints
+            .iter()
+            .all(|c| c["opinions"].as_array().unwrap().len() <= window));
+        // Once the window is full, later checkpoints are no larger than early ones.
+        let steady = &sizes[1..];
+        assert!(steady.iter().max().unwrap() - steady.iter().min().unwrap() < 100);
+
+        let last = OrchestrationState::from_shared_state(checkpoints.last().unwrap());
+        assert_eq!(last.pruned_opinions as usize + last.opinions.len(), 1_000);
+        assert_eq!(last.tokens_used, 2_000);
+        assert_eq!(
+            state.opinions.len(),
+            1_000,
+            "the live state keeps everything"
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use similar::TextDiff;
+
+use crate::orchestration::critic::CRITIQUE_PHASE;
+use crate::orchestration::state::Opinion;
+
+/// `StopOnStable::threshold` when `mode_config.stable_threshold` is unset.
+pub const DEFAULT_STABLE_THRESHOLD: f64 = 0.9;
+/// Longest a single similarity diff may run before settling for an estimate.
+const DIFF_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// `mode_config.stop_on_stable`: once two consecutive rounds say nearly the
+/// same thing (similarity at or above `stable_threshold`, default 0.9), the
+/// round ends with a `converged` event so the UI can suggest stopping there.
+/// A follow-up the user still sends runs as usual.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StopOnStable {
+    pub threshold: f64,
+}
+
+impl StopOnStable {
+    /// `None` unless `stop_on_stable` is set.
+    pub fn from_mode_config(mode_config: &serde_json::Value) -> Option<Self> {
+        let enabled = mode_config
+            .get("stop_on_stable")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let threshold = mode_config
+            .get("stable_threshold")
+            .and_then(|v| v.as_f64())
+            .filter(|t| t.is_finite())
+            .map_or(DEFAULT_STABLE_THRESHOLD, |t| t.clamp(0.0, 1.0));
+        enabled.then_some(Self { threshold })
+    }
+
+    /// Similarity of `round` to the round before it, when it reaches the
+    /// threshold.
+    pub fn converged(&self, opinions: &[Opinion], round: i32) -> Option<f64> {
+        let similarity = round_similarity(opinions, round)?;
+        (similarity >= self.threshold).then_some(similarity)
+    }
+
+    /// Payload for the `converged` event.
+    pub fn event(&self, round: i32, similarity: f64) -> serde_json::Value {
+        serde_json::json!({
+            "message": "连续两轮的观点基本一致，讨论已收敛",
+            "round": round,
+            "similarity": similarity,
+            "threshold": self.threshold
+        })
+    }
+}
+
+/// How alike the opinions of `round` and `round - 1` are, from 0 to 1; `None`
+/// when either round has none. Critiques are left out, and each round's text
+/// is ordered by agent so speaking order doesn't count as a change.
+pub fn round_similarity(opinions: &[Opinion], round: i32) -> Option<f64> {
+    let current = round_text(opinions, round)?;
+    let previous = round_text(opinions, round - 1)?;
+    Some(text_similarity(&previous, &current))
+}
+
+/// Character-level similarity of two texts, from 0 (nothing shared) to 1.
+pub fn text_similarity(a: &str, b: &str) -> f64 {
+    let ratio = TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_chars(a, b)
+        .ratio();
+    f64::from(ratio)
+}
+
+fn round_text(opinions: &[Opinion], round: i32) -> Option<String> {
+    let mut parts = opinions
+        .iter()
+        .filter(|o| o.round == round && o.phase != CRITIQUE_PHASE)
+        .map(|o| (o.agent_id.as_str(), o.phase.as_str(), o.content.trim()))
+        .collect::<Vec<_>>();
+    if parts.is_empty() {
+        return None;
+    }
+    parts.sort();
+    Some(
+        parts
+            .into_iter()
+            .map(|(_, _, content)| content)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opinion(agent_id: &str, round: i32, content: &str) -> Opinion {
+        Opinion {
+            agent_id: agent_id.to_string(),
+            agent_name: agent_id.to_uppercase(),
+            content: content.to_string(),
+            round,
+            phase: "initial".to_string(),
+            wants_to_continue: true,
+            responding_to: None,
+            input_tokens: 1,
+            output_tokens: 1,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
+        }
+    }
+
+    fn stable() -> StopOnStable {
+        StopOnStable::from_mode_config(&serde_json::json!({ "stop_on_stable": true })).unwrap()
+    }
+
+    #[test]
+    fn near_identical_rounds_converge() {
+        let opinions = vec![
+            opinion("a", 1, "我们应该先做用户调研，再决定是否开发新功能。"),
+            opinion("b", 1, "同意，调研之后再排期比较稳妥。"),
+            // Same conclusions, different speaking order and one changed word.
+            opinion("b", 2, "同意，调研之后再排期最稳妥。"),
+            opinion("a", 2, "我们应该先做用户调研，再决定是否开发新功能。"),
+        ];
+        let similarity = stable().converged(&opinions, 2).unwrap();
+        assert!((DEFAULT_STABLE_THRESHOLD..1.0).contains(&similarity));
+        assert!(
+            stable().converged(&opinions, 1).is_none(),
+            "no earlier round"
+        );
+        assert_eq!(
+            stable().event(2, similarity)["threshold"],
+            DEFAULT_STABLE_THRESHOLD
+        );
+    }
+
+    #[test]
+    fn differing_rounds_do_not_converge() {
+        let opinions = vec![
+            opinion("a", 1, "我们应该先做用户调研，再决定是否开发新功能。"),
+            opinion("a", 2, "预算已经批下来了，下个季度直接上线并投放广告。"),
+        ];
+        assert!(stable().converged(&opinions, 2).is_none());
+        assert!(round_similarity(&opinions, 2).unwrap() < DEFAULT_STABLE_THRESHOLD);
+
+        let loose = StopOnStable::from_mode_config(
+            &serde_json::json!({ "stop_on_stable": true, "stable_threshold": 0.0 }),
+        )
+        .unwrap();
+        assert!(loose.converged(&opinions, 2).is_some());
+        assert!(StopOnStable::from_mode_config(&serde_json::json!({})).is_none());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::agents::instance::AgentInstance;
+use crate::error::AppError;
+use crate::orchestration::checkpoint::{emit_phase_complete, emit_progress};
+use crate::orchestration::state::{Opinion, OrchestrationState};
+
+/// Phase of the critic's opinion.
+pub const CRITIQUE_PHASE: &str = "critique";
+
+/// `mode_config.enable_critic`: after the main flow, one agent reviews the
+/// team's output. `critic_agent_id` picks it; otherwise the last agent does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CriticConfig {
+    pub agent_id: Option<String>,
+}
+
+impl CriticConfig {
+    /// `None` unless `enable_critic` is set.
+    pub fn from_mode_config(mode_config: &serde_json::Value) -> Option<Self> {
+        let enabled = mode_config
+            .get("enable_critic")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        enabled.then(|| Self {
+            agent_id: mode_config
+                .get("critic_agent_id")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+        })
+    }
+}
+
+/// The critic's prompt over this run's `opinions` and any summary or verdict.
+pub fn critic_prompt(topic: &str, opinions: &[Opinion], summary: &str) -> String {
+    let lines = opinions
+        .iter()
+        .map(|o| format!("- {}（{}）: {}", o.agent_name, o.phase, o.content))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let summary = if summary.trim().is_empty() {
+        String::new()
+    } else {
+        format!("\n\n总结：\n{}", summary.trim())
+    };
+    format!(
+        "作为评审，请审查团队对以下主题的产出：\n\n主题：{topic}\n\n团队观点：\n{lines}{summary}\n\n请指出：\n1. 论证中的薄弱环节或遗漏\n2. 事实或逻辑错误\n3. 改进建议"
+    )
+}
+
+/// Have the critic review the opinions added since `first_opinion` and emit
+/// its critique as a `critique` opinion. Does nothing when `agents` is empty
+/// or nobody spoke; an unknown `critic_agent_id` falls back to the last agent.
+pub async fn run_critic(
+    agents: &mut [AgentInstance],
+    config: &CriticConfig,
+    first_opinion: usize,
+    state: &mut OrchestrationState,
+    emit: &mut impl FnMut(&str, serde_json::Value, Option<String>) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    let opinions = state.opinions.get(first_opinion..).unwrap_or_default();
+    if opinions.is_empty() {
+        return Ok(());
+    }
+    let named = config
+        .agent_id
+        .as_deref()
+        .and_then(|id| agents.iter().position(|a| a.id == id));
+    let Some(critic) = named
+        .or_else(|| agents.len().checked_sub(1))
+        .and_then(|i| agents.get_mut(i))
+    else {
+        return Ok(());
+    };
+
+    let prompt = critic_prompt(&state.topic, opinions, &state.summary);
+    state.expected_opinions = state.expected_opinions.saturating_add(1);
+    let phase_start = state.phase_start();
+    emit(
+        "status",
+        serde_json::json!({ "message": format!("{} is reviewing the result", critic.name), "round": state.round, "phase": CRITIQUE_PHASE }),
+        Some(critic.id.clone()),
+    )?;
+    let (resp, _) = critic
+        .generate_opinion_with_tools(&prompt, "", &[], "initial", &[], None)
+        .await?;
+
+    let (input_tokens, output_tokens, tokens_estimated) = resp.token_counts();
+    let (cache_read_tokens, cache_write_tokens) = resp.cached_tokens();
+    state.add_opinion(Opinion {
+        agent_id: critic.id.clone(),
+        agent_name: critic.name.clone(),
+        content: resp.content.clone(),
+        round: state.round,
+        phase: CRITIQUE_PHASE.to_string(),
+        wants_to_continue: false,
+        responding_to: None,
+        input_tokens,
+        output_tokens,
+        cache_read_tokens,
+        cache_write_tokens,
+    });
+    emit(
+        "opinion",
+        serde_json::json!({
+            "agent_name": critic.name.clone(),
+            "content": resp.content,
+            "wants_to_continue": false,
+            "round": state.round,
+            "phase": CRITIQUE_PHASE,
+            "input_tokens": input_tokens,
+            "output_tokens": output_tokens,
+            "tokens_estimated": tokens_estimated,
+            "metadata": resp.metadata
+        }),
+        Some(critic.id.clone()),
+    )?;
+    emit_progress(emit, state)?;
+    emit_phase_complete(emit, state, &phase_start, CRITIQUE_PHASE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::{self, MockProvider};
+    use crate::orchestration::roundtable::{run_roundtable, InitialOrder};
+
+    fn agent(id: &str) -> AgentInstance {
+        AgentInstance::from_agent(
+            &mock::agent(id, id),
+            MockProvider::new(&format!("{id} says so")),
+        )
+    }
+
+    /// A one-phase roundtable, then the critic when `mode_config` enables it.
+    async fn run(mode_config: serde_json::Value) -> (OrchestrationState, Vec<String>) {
+        let mut state = OrchestrationState {
+            topic: "topic".to_string(),
+            ..Default::default()
+        };
+        let mut phases = Vec::new();
+        let mut emit = |event: &str, data: serde_json::Value, _: Option<String>| {
+            if event == "opinion" {
+                phases.push(data["phase"].as_str().unwrap_or_default().to_string());
+            }
+            Ok(())
+        };
+        let first_opinion = state.opinions.len();
+        let agents = vec![agent("ann"), agent("ben"), agent("cat")];
+        let mut agents = run_roundtable(
+            agents,
+            &mut state,
+            &mut emit,
+            false,
+            InitialOrder::Parallel,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+        if let Some(critic) = CriticConfig::from_mode_config(&mode_config) {
+            run_critic(&mut agents, &critic, first_opinion, &mut state, &mut emit)
+                .await
+                .unwrap();
+        }
+        (state, phases)
+    }
+
+    #[tokio::test]
+    async fn critic_reviews_the_run_when_enabled() {
+        let (state, phases) =
+            run(serde_json::json!({ "enable_critic": true, "critic_agent_id": "ben" })).await;
+        assert_eq!(phases, ["initial", "initial", "initial", CRITIQUE_PHASE]);
+        let critique = state.opinions.last().unwrap();
+        assert_eq!(critique.agent_id, "ben");
+        assert_eq!(state.expected_opinions, 4);
+
+        let output = state.final_output(&state.opinions).unwrap();
+        assert!(output.contains("- **ben**（第0轮）: ben says so"));
+        assert!(output.contains("### 评审意见（ben）\nben says so"));
+    }
+
+    #[tokio::test]
+    async fn critic_is_skipped_unless_enabled() {
+        let (state, phases) = run(serde_json::json!({ "critic_agent_id": "ben" })).await;
+        assert_eq!(phases, ["initial", "initial", "initial"]);
+        assert!(state.opinions.iter().all(|o| o.phase != CRITIQUE_PHASE));
+
+        let (state, _) = run(serde_json::json!({ "enable_critic": true })).await;
+        assert_eq!(state.opinions.last().unwrap().agent_id, "cat");
+    }
+}
@@ -1,74 +1,135 @@
 use crate::agents::instance::AgentInstance;
 use crate::error::AppError;
+use crate::orchestration::checkpoint::{emit_phase_complete, emit_progress};
 use crate::orchestration::state::{Opinion, OrchestrationPhase, OrchestrationState};
 use crate::orchestration::tool_events::emit_tool_traces;
 use crate::tools::definition::ToolDefinition;
 use crate::tools::executor::ToolExecutor;
 
-pub async fn run_debate(
-    agents: Vec<AgentInstance>,
-    state: &mut OrchestrationState,
-    emit: &mut impl FnMut(&str, serde_json::Value, Option<String>) -> Result<(), AppError>,
-    max_rounds: i32,
-    tool_defs: &[ToolDefinition],
-    tool_executor: Option<ToolExecutor>,
-) -> Result<Vec<AgentInstance>, AppError> {
-    state.phase = OrchestrationPhase::Initializing;
+/// The judge's prompt, listing each side's arguments from `opinions`.
+pub fn verdict_prompt(topic: &str, opinions: &[Opinion]) -> String {
+    let side = |prefix: &str| {
+        opinions
+            .iter()
+            .filter(|o| o.phase.starts_with(prefix))
+            .map(|o| format!("- {}: {}", o.agent_name, o.content))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    format!(
+        "作为裁判，请评判以下辩论：\n\n论题：{}\n\n正方观点：\n{}\n\n反方观点：\n{}\n\n请给出裁决：\n1. 双方论点总结\n2. 优势与不足\n3. 最终判断",
+        topic,
+        side("pro_"),
+        side("con_")
+    )
+}
 
-    let mut agents = agents;
-    if agents.is_empty() {
-        return Ok(agents);
+/// Phase sequence of a debate, picked by `mode_config.debate_format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebateFormat {
+    /// Openings, rebuttal rounds (pro first), verdict.
+    #[default]
+    Standard,
+    /// Openings, rebuttal rounds with the opposition answering first, then
+    /// closing statements (con first, so the proposition speaks last).
+    Oxford,
+    /// Openings, a cross-examination turn for each side, rebuttal rounds,
+    /// then closing statements.
+    LincolnDouglas,
+}
+
+impl DebateFormat {
+    pub fn from_mode_config(mode_config: &serde_json::Value) -> Self {
+        match mode_config.get("debate_format").and_then(|v| v.as_str()) {
+            Some("oxford") => DebateFormat::Oxford,
+            Some("lincoln_douglas") => DebateFormat::LincolnDouglas,
+            _ => DebateFormat::Standard,
+        }
     }
 
-    // Auto-assign: last agent as judge, split remaining into pro/con.
-    let judge = agents.pop().unwrap();
-    let mid = agents.len() / 2;
-    let mut pro = agents[..mid].to_vec();
-    let mut con = agents[mid..].to_vec();
+    fn cross_examination(self) -> bool {
+        self == DebateFormat::LincolnDouglas
+    }
 
-    emit(
-        "status",
-        serde_json::json!({
-            "message": "Debate started",
-            "pro_team": pro.iter().map(|a| a.name.clone()).collect::<Vec<_>>(),
-            "con_team": con.iter().map(|a| a.name.clone()).collect::<Vec<_>>(),
-            "judge": judge.name.clone()
-        }),
-        None,
-    )?;
+    fn closing_statements(self) -> bool {
+        self != DebateFormat::Standard
+    }
 
-    state.round = 1;
-    state.phase = OrchestrationPhase::Sequential;
+    /// Whether con speaks before pro in rebuttals and closings.
+    fn con_first(self) -> bool {
+        self == DebateFormat::Oxford
+    }
+}
 
-    // Opening: pro then con
-    let pro_prompt = format!("论题：{}\n\n你是正方，请给出开场陈述。", state.topic);
-    let mut pro_args = Vec::new();
-    for agent in pro.iter_mut() {
+/// The two sides in speaking order.
+fn sides(con_first: bool) -> [&'static str; 2] {
+    if con_first {
+        ["con", "pro"]
+    } else {
+        ["pro", "con"]
+    }
+}
+
+fn side_label(side: &str) -> &'static str {
+    if side == "pro" {
+        "正方"
+    } else {
+        "反方"
+    }
+}
+
+/// The last `window` opinions, as context for the next speakers.
+fn recent_context(state: &OrchestrationState, window: usize) -> Vec<serde_json::Value> {
+    state.opinions[state.opinions.len().saturating_sub(window)..]
+        .iter()
+        .map(|op| serde_json::json!({"agent_name": op.agent_name.clone(), "content": op.content.clone(), "phase": op.phase.clone()}))
+        .collect()
+}
+
+/// Have every agent on one side speak once, recording and emitting each
+/// opinion under `phase`. Returns `{agent_name, content}` per speaker.
+#[allow(clippy::too_many_arguments)]
+async fn speak(
+    agents: &mut [AgentInstance],
+    phase: &str,
+    prompt: &str,
+    context: &[serde_json::Value],
+    instruction_phase: &str,
+    state: &mut OrchestrationState,
+    emit: &mut impl FnMut(&str, serde_json::Value, Option<String>) -> Result<(), AppError>,
+    tool_defs: &[ToolDefinition],
+    tool_executor: Option<&ToolExecutor>,
+) -> Result<Vec<serde_json::Value>, AppError> {
+    let mut args = Vec::new();
+    for agent in agents.iter_mut() {
         let (resp, traces) = agent
             .generate_opinion_with_tools(
-                &pro_prompt,
+                prompt,
                 "",
-                &[],
-                "initial",
+                context,
+                instruction_phase,
                 tool_defs,
-                tool_executor.as_ref(),
+                tool_executor,
             )
             .await?;
         emit_tool_traces(emit, &traces, &agent.id, &agent.name, state.round)?;
 
         let (input_tokens, output_tokens, tokens_estimated) = resp.token_counts();
+        let (cache_read_tokens, cache_write_tokens) = resp.cached_tokens();
         state.add_opinion(Opinion {
             agent_id: agent.id.clone(),
             agent_name: agent.name.clone(),
             content: resp.content.clone(),
             round: state.round,
-            phase: "pro_opening".to_string(),
+            phase: phase.to_string(),
             wants_to_continue: true,
             responding_to: None,
             input_tokens,
             output_tokens,
+            cache_read_tokens,
+            cache_write_tokens,
         });
-        pro_args.push(
+        args.push(
             serde_json::json!({"agent_name": agent.name.clone(), "content": resp.content.clone()}),
         );
         emit(
@@ -77,7 +138,7 @@ pub async fn run_debate(
                 "agent_name": agent.name.clone(),
                 "content": resp.content.clone(),
                 "round": state.round,
-                "phase": "pro_opening",
+                "phase": phase,
                 "input_tokens": input_tokens,
                 "output_tokens": output_tokens,
                 "tokens_estimated": tokens_estimated,
@@ -85,169 +146,191 @@ pub async fn run_debate(
             }),
             Some(agent.id.clone()),
         )?;
+        emit_progress(emit, state)?;
     }
+    Ok(args)
+}
+
+pub async fn run_debate(
+    agents: Vec<AgentInstance>,
+    state: &mut OrchestrationState,
+    emit: &mut impl FnMut(&str, serde_json::Value, Option<String>) -> Result<(), AppError>,
+    max_rounds: i32,
+    format: DebateFormat,
+    tool_defs: &[ToolDefinition],
+    tool_executor: Option<ToolExecutor>,
+) -> Result<Vec<AgentInstance>, AppError> {
+    state.phase = OrchestrationPhase::Initializing;
+
+    // Auto-assign: last agent as judge, split remaining into pro/con.
+    let mut agents = agents;
+    let Some(judge) = agents.pop() else {
+        return Err(AppError::Message(
+            "A debate needs at least one active agent".to_string(),
+        ));
+    };
+    let mid = agents.len() / 2;
+    let mut pro = agents[..mid].to_vec();
+    let mut con = agents[mid..].to_vec();
+    // One turn per debater for the opening, each rebuttal round, and any
+    // cross-examination or closing phase; then the verdict.
+    let turns = 1
+        + max_rounds.max(0) as u32
+        + u32::from(format.cross_examination())
+        + u32::from(format.closing_statements());
+    state.expected_opinions = agents.len() as u32 * turns + 1;
+    let tool_executor = tool_executor.as_ref();
+
+    emit(
+        "status",
+        serde_json::json!({
+            "message": "Debate started",
+            "pro_team": pro.iter().map(|a| a.name.clone()).collect::<Vec<_>>(),
+            "con_team": con.iter().map(|a| a.name.clone()).collect::<Vec<_>>(),
+            "judge": judge.name.clone()
+        }),
+        None,
+    )?;
+
+    state.round = 1;
+    let phase_start = state.phase_start();
+    state.phase = OrchestrationPhase::Sequential;
+
+    // Opening: pro then con
+    let pro_prompt = format!("论题：{}\n\n你是正方，请给出开场陈述。", state.topic);
+    let pro_args = speak(
+        &mut pro,
+        "pro_opening",
+        &pro_prompt,
+        &[],
+        "initial",
+        state,
+        emit,
+        tool_defs,
+        tool_executor,
+    )
+    .await?;
 
     let con_prompt = format!(
         "论题：{}\n\n你是反方，请回应正方并给出开场陈述。",
         state.topic
     );
-    for agent in con.iter_mut() {
-        let (resp, traces) = agent
-            .generate_opinion_with_tools(
-                &con_prompt,
-                "",
-                &pro_args,
+    let con_args = speak(
+        &mut con,
+        "con_opening",
+        &con_prompt,
+        &pro_args,
+        "response",
+        state,
+        emit,
+        tool_defs,
+        tool_executor,
+    )
+    .await?;
+    emit_phase_complete(emit, state, &phase_start, "opening")?;
+
+    // Cross-examination: each side questions the other's opening.
+    if format.cross_examination() {
+        state.round += 1;
+        let phase_start = state.phase_start();
+        emit(
+            "status",
+            serde_json::json!({ "message": "Cross-examination", "round": state.round, "phase": "cross_examination" }),
+            None,
+        )?;
+        for side in ["con", "pro"] {
+            let (agents, opposing) = if side == "pro" {
+                (&mut pro, &con_args)
+            } else {
+                (&mut con, &pro_args)
+            };
+            let prompt = format!(
+                "论题：{}\n\n你是{}，请针对对方的开场陈述进行质询：提出尖锐的问题，指出其论证中的漏洞。",
+                state.topic,
+                side_label(side)
+            );
+            speak(
+                agents,
+                &format!("{side}_cross_examination"),
+                &prompt,
+                opposing,
                 "response",
+                state,
+                emit,
                 tool_defs,
-                tool_executor.as_ref(),
+                tool_executor,
             )
             .await?;
-        emit_tool_traces(emit, &traces, &agent.id, &agent.name, state.round)?;
-        let (input_tokens, output_tokens, tokens_estimated) = resp.token_counts();
-        state.add_opinion(Opinion {
-            agent_id: agent.id.clone(),
-            agent_name: agent.name.clone(),
-            content: resp.content.clone(),
-            round: state.round,
-            phase: "con_opening".to_string(),
-            wants_to_continue: true,
-            responding_to: None,
-            input_tokens,
-            output_tokens,
-        });
-        emit(
-            "opinion",
-            serde_json::json!({
-                "agent_name": agent.name.clone(),
-                "content": resp.content.clone(),
-                "round": state.round,
-                "phase": "con_opening",
-                "input_tokens": input_tokens,
-                "output_tokens": output_tokens,
-                "tokens_estimated": tokens_estimated,
-                "metadata": resp.metadata
-            }),
-            Some(agent.id.clone()),
-        )?;
+        }
+        emit_phase_complete(emit, state, &phase_start, "cross_examination")?;
     }
 
     // Rebuttals
     for round_num in 1..=max_rounds {
         state.round += 1;
+        let phase_start = state.phase_start();
         emit(
             "status",
             serde_json::json!({ "message": format!("Rebuttal round {}", round_num), "round": state.round, "phase": "rebuttal" }),
             None,
         )?;
-        let last = state
-            .opinions
-            .iter()
-            .rev()
-            .take(pro.len() + con.len())
-            .map(|op| serde_json::json!({"agent_name": op.agent_name.clone(), "content": op.content.clone(), "phase": op.phase.clone()}))
-            .collect::<Vec<_>>();
-
-        for agent in pro.iter_mut() {
-            let (resp, traces) = agent
-                .generate_opinion_with_tools(
-                    &state.topic,
-                    "",
-                    &last,
-                    "response",
-                    tool_defs,
-                    tool_executor.as_ref(),
-                )
-                .await?;
-            emit_tool_traces(emit, &traces, &agent.id, &agent.name, state.round)?;
-            let (input_tokens, output_tokens, tokens_estimated) = resp.token_counts();
-            state.add_opinion(Opinion {
-                agent_id: agent.id.clone(),
-                agent_name: agent.name.clone(),
-                content: resp.content.clone(),
-                round: state.round,
-                phase: "pro_rebuttal".to_string(),
-                wants_to_continue: true,
-                responding_to: None,
-                input_tokens,
-                output_tokens,
-            });
-            emit(
-                "opinion",
-                serde_json::json!({
-                    "agent_name": agent.name.clone(),
-                    "content": resp.content.clone(),
-                    "round": state.round,
-                    "phase": "pro_rebuttal",
-                    "input_tokens": input_tokens,
-                    "output_tokens": output_tokens,
-                    "tokens_estimated": tokens_estimated,
-                    "metadata": resp.metadata
-                }),
-                Some(agent.id.clone()),
-            )?;
+        let last = recent_context(state, state.context_window(pro.len() + con.len()));
+        let topic = state.topic.clone();
+        for side in sides(format.con_first()) {
+            let agents = if side == "pro" { &mut pro } else { &mut con };
+            speak(
+                agents,
+                &format!("{side}_rebuttal"),
+                &topic,
+                &last,
+                "response",
+                state,
+                emit,
+                tool_defs,
+                tool_executor,
+            )
+            .await?;
         }
+        emit_phase_complete(emit, state, &phase_start, "rebuttal")?;
+    }
 
-        for agent in con.iter_mut() {
-            let (resp, traces) = agent
-                .generate_opinion_with_tools(
-                    &state.topic,
-                    "",
-                    &last,
-                    "response",
-                    tool_defs,
-                    tool_executor.as_ref(),
-                )
-                .await?;
-            emit_tool_traces(emit, &traces, &agent.id, &agent.name, state.round)?;
-            let (input_tokens, output_tokens, tokens_estimated) = resp.token_counts();
-            state.add_opinion(Opinion {
-                agent_id: agent.id.clone(),
-                agent_name: agent.name.clone(),
-                content: resp.content.clone(),
-                round: state.round,
-                phase: "con_rebuttal".to_string(),
-                wants_to_continue: true,
-                responding_to: None,
-                input_tokens,
-                output_tokens,
-            });
-            emit(
-                "opinion",
-                serde_json::json!({
-                    "agent_name": agent.name.clone(),
-                    "content": resp.content.clone(),
-                    "round": state.round,
-                    "phase": "con_rebuttal",
-                    "input_tokens": input_tokens,
-                    "output_tokens": output_tokens,
-                    "tokens_estimated": tokens_estimated,
-                    "metadata": resp.metadata
-                }),
-                Some(agent.id.clone()),
-            )?;
+    // Closing statements
+    if format.closing_statements() {
+        state.round += 1;
+        let phase_start = state.phase_start();
+        emit(
+            "status",
+            serde_json::json!({ "message": "Closing statements", "round": state.round, "phase": "closing" }),
+            None,
+        )?;
+        let last = recent_context(state, state.context_window(pro.len() + con.len()));
+        for side in sides(format.con_first()) {
+            let agents = if side == "pro" { &mut pro } else { &mut con };
+            let prompt = format!(
+                "论题：{}\n\n你是{}，请做总结陈词：概括己方核心论点，回应对方最有力的反驳，不要提出新论点。",
+                state.topic,
+                side_label(side)
+            );
+            speak(
+                agents,
+                &format!("{side}_closing"),
+                &prompt,
+                &last,
+                "response",
+                state,
+                emit,
+                tool_defs,
+                tool_executor,
+            )
+            .await?;
         }
+        emit_phase_complete(emit, state, &phase_start, "closing")?;
     }
 
     // Judge verdict
     state.phase = OrchestrationPhase::Summarizing;
-    let pro_text = state
-        .opinions
-        .iter()
-        .filter(|o| o.phase.starts_with("pro_"))
-        .map(|o| format!("- {}: {}", o.agent_name, o.content))
-        .collect::<Vec<_>>()
-        .join("\n");
-    let con_text = state
-        .opinions
-        .iter()
-        .filter(|o| o.phase.starts_with("con_"))
-        .map(|o| format!("- {}: {}", o.agent_name, o.content))
-        .collect::<Vec<_>>()
-        .join("\n");
-    let verdict_prompt = format!(
-        "作为裁判，请评判以下辩论：\n\n论题：{}\n\n正方观点：\n{}\n\n反方观点：\n{}\n\n请给出裁决：\n1. 双方论点总结\n2. 优势与不足\n3. 最终判断",
-        state.topic, pro_text, con_text
-    );
+    let phase_start = state.phase_start();
+    let verdict_prompt = verdict_prompt(&state.topic, &state.opinions);
 
     let mut judge = judge;
     let (verdict, traces) = judge
@@ -257,13 +340,14 @@ pub async fn run_debate(
             &[],
             "initial",
             tool_defs,
-            tool_executor.as_ref(),
+            tool_executor,
         )
         .await?;
     emit_tool_traces(emit, &traces, &judge.id, &judge.name, state.round)?;
 
     state.summary = verdict.content.clone();
     let (input_tokens, output_tokens, tokens_estimated) = verdict.token_counts();
+    let (cache_read_tokens, cache_write_tokens) = verdict.cached_tokens();
     state.add_opinion(Opinion {
         agent_id: judge.id.clone(),
         agent_name: judge.name.clone(),
@@ -274,6 +358,8 @@ pub async fn run_debate(
         responding_to: None,
         input_tokens,
         output_tokens,
+        cache_read_tokens,
+        cache_write_tokens,
     });
     emit(
         "opinion",
@@ -289,6 +375,9 @@ pub async fn run_debate(
         }),
         Some(judge.id.clone()),
     )?;
+    emit_progress(emit, state)?;
+
+    emit_phase_complete(emit, state, &phase_start, "verdict")?;
 
     state.phase = OrchestrationPhase::Completed;
 
@@ -298,3 +387,117 @@ pub async fn run_debate(
     all.push(judge);
     Ok(all)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::{self, MockProvider};
+
+    fn debater(id: &str) -> AgentInstance {
+        AgentInstance::from_agent(
+            &mock::agent(id, id),
+            MockProvider::new(&format!("{id} argues")),
+        )
+    }
+
+    async fn phases(format: DebateFormat) -> (Vec<String>, OrchestrationState) {
+        let agents = vec![debater("pro"), debater("con"), debater("judge")];
+        let mut state = OrchestrationState {
+            topic: "topic".to_string(),
+            ..Default::default()
+        };
+        let mut emit = |_: &str, _: serde_json::Value, _: Option<String>| Ok(());
+        run_debate(agents, &mut state, &mut emit, 1, format, &[], None)
+            .await
+            .unwrap();
+        let phases = state.opinions.iter().map(|o| o.phase.clone()).collect();
+        (phases, state)
+    }
+
+    #[test]
+    fn format_parses_mode_config() {
+        let parse = |v| DebateFormat::from_mode_config(&serde_json::json!({ "debate_format": v }));
+        assert_eq!(parse("oxford"), DebateFormat::Oxford);
+        assert_eq!(parse("lincoln_douglas"), DebateFormat::LincolnDouglas);
+        assert_eq!(parse("standard"), DebateFormat::Standard);
+        assert_eq!(
+            DebateFormat::from_mode_config(&serde_json::json!({})),
+            DebateFormat::Standard
+        );
+    }
+
+    #[tokio::test]
+    async fn standard_format_keeps_the_original_flow() {
+        let (phases, state) = phases(DebateFormat::Standard).await;
+        assert_eq!(
+            phases,
+            [
+                "pro_opening",
+                "con_opening",
+                "pro_rebuttal",
+                "con_rebuttal",
+                "judge_verdict"
+            ]
+        );
+        assert_eq!(state.expected_opinions, 5);
+    }
+
+    #[tokio::test]
+    async fn oxford_adds_closing_statements_before_the_verdict() {
+        let (phases, state) = phases(DebateFormat::Oxford).await;
+        assert_eq!(
+            phases,
+            [
+                "pro_opening",
+                "con_opening",
+                "con_rebuttal",
+                "pro_rebuttal",
+                "con_closing",
+                "pro_closing",
+                "judge_verdict"
+            ]
+        );
+        assert_eq!(state.expected_opinions, 7);
+        assert!(verdict_prompt("topic", &state.opinions).contains("pro argues"));
+    }
+
+    #[tokio::test]
+    async fn lincoln_douglas_adds_cross_examination_and_closings() {
+        let (phases, state) = phases(DebateFormat::LincolnDouglas).await;
+        assert_eq!(
+            phases,
+            [
+                "pro_opening",
+                "con_opening",
+                "con_cross_examination",
+                "pro_cross_examination",
+                "pro_rebuttal",
+                "con_rebuttal",
+                "pro_closing",
+                "con_closing",
+                "judge_verdict"
+            ]
+        );
+        assert_eq!(state.expected_opinions as usize, phases.len());
+    }
+
+    #[tokio::test]
+    async fn an_empty_debate_is_an_error() {
+        let mut state = OrchestrationState::default();
+        let mut emit = |_: &str, _: serde_json::Value, _: Option<String>| Ok(());
+        let Err(err) = run_debate(
+            Vec::new(),
+            &mut state,
+            &mut emit,
+            1,
+            DebateFormat::Standard,
+            &[],
+            None,
+        )
+        .await
+        else {
+            panic!("an empty debate should fail");
+        };
+        assert!(err.to_string().contains("at least one active agent"));
+    }
+}
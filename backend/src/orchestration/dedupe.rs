@@ -0,0 +1,169 @@
+use crate::orchestration::convergence::text_similarity;
+use crate::orchestration::state::Opinion;
+
+/// Similarity at which `mode_config.dedupe_similar` treats two opinions as
+/// duplicates when `dedupe_threshold` is unset.
+pub const DEFAULT_DEDUPE_THRESHOLD: f64 = 0.85;
+
+/// `mode_config.dedupe_similar`: the threshold above which opinions in the
+/// same view are collapsed into one, or `None` when deduplication is off.
+/// `dedupe_threshold` overrides `DEFAULT_DEDUPE_THRESHOLD`.
+pub fn dedupe_threshold(mode_config: &serde_json::Value) -> Option<f64> {
+    let enabled = mode_config
+        .get("dedupe_similar")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let threshold = mode_config
+        .get("dedupe_threshold")
+        .and_then(|v| v.as_f64())
+        .filter(|t| t.is_finite())
+        .map_or(DEFAULT_DEDUPE_THRESHOLD, |t| t.clamp(0.0, 1.0));
+    enabled.then_some(threshold)
+}
+
+/// Opinions that say nearly the same thing, as indices into the slice given
+/// to `group_similar`. `kept` is the one shown for the whole group: the
+/// fullest of them, since opinions carry no explicit confidence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimilarGroup {
+    pub kept: usize,
+    pub members: Vec<usize>,
+}
+
+impl SimilarGroup {
+    pub fn is_duplicate(&self) -> bool {
+        self.members.len() > 1
+    }
+
+    /// "N agents gave similar views", naming the collapsed agents.
+    pub fn note(&self, opinions: &[&Opinion]) -> String {
+        let others = self
+            .members
+            .iter()
+            .filter(|&&idx| idx != self.kept)
+            .map(|&idx| opinions[idx].agent_name.as_str())
+            .collect::<Vec<_>>();
+        format!(
+            "{} 位专家观点相似，另有 {}",
+            self.members.len(),
+            others.join("、")
+        )
+    }
+}
+
+/// Cluster `opinions` so each lands in the group of the first earlier
+/// opinion it is at least `threshold` similar to. Every opinion is in
+/// exactly one group; groups are ordered by their first member.
+pub fn group_similar(opinions: &[&Opinion], threshold: f64) -> Vec<SimilarGroup> {
+    let mut groups: Vec<SimilarGroup> = Vec::new();
+    for (idx, opinion) in opinions.iter().enumerate() {
+        let content = opinion.content.trim();
+        let existing = groups.iter_mut().find(|group| {
+            text_similarity(opinions[group.members[0]].content.trim(), content) >= threshold
+        });
+        match existing {
+            Some(group) => {
+                if content.chars().count() > opinions[group.kept].content.trim().chars().count() {
+                    group.kept = idx;
+                }
+                group.members.push(idx);
+            }
+            None => groups.push(SimilarGroup {
+                kept: idx,
+                members: vec![idx],
+            }),
+        }
+    }
+    groups
+}
+
+/// Payload for a `duplicates_collapsed` event, or `None` when every opinion
+/// stands alone.
+pub fn collapsed_event(
+    opinions: &[&Opinion],
+    groups: &[SimilarGroup],
+    round: i32,
+    phase: &str,
+) -> Option<serde_json::Value> {
+    let collapsed = groups
+        .iter()
+        .filter(|group| group.is_duplicate())
+        .map(|group| {
+            serde_json::json!({
+                "kept_agent_id": opinions[group.kept].agent_id,
+                "agent_ids": group
+                    .members
+                    .iter()
+                    .map(|&idx| opinions[idx].agent_id.clone())
+                    .collect::<Vec<_>>(),
+                "note": group.note(opinions)
+            })
+        })
+        .collect::<Vec<_>>();
+    (!collapsed.is_empty()).then(|| {
+        serde_json::json!({
+            "round": round,
+            "phase": phase,
+            "groups": collapsed
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opinion(agent_id: &str, content: &str) -> Opinion {
+        Opinion {
+            agent_id: agent_id.to_string(),
+            agent_name: agent_id.to_uppercase(),
+            content: content.to_string(),
+            round: 1,
+            phase: "initial".to_string(),
+            wants_to_continue: true,
+            responding_to: None,
+            input_tokens: 1,
+            output_tokens: 1,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
+        }
+    }
+
+    #[test]
+    fn near_identical_opinions_collapse_into_the_fullest() {
+        let opinions = [
+            opinion("a", "建议先做小范围试点，验证效果后再推广。"),
+            opinion("b", "建议先做小范围试点，验证效果后再全面推广。"),
+            opinion("c", "成本太高，不建议推进这个项目。"),
+        ];
+        let refs = opinions.iter().collect::<Vec<_>>();
+        let groups = group_similar(&refs, DEFAULT_DEDUPE_THRESHOLD);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].members, vec![0, 1]);
+        assert_eq!(groups[0].kept, 1, "the longer opinion is kept");
+        assert_eq!(groups[0].note(&refs), "2 位专家观点相似，另有 A");
+
+        let event = collapsed_event(&refs, &groups, 1, "initial").unwrap();
+        assert_eq!(event["groups"].as_array().unwrap().len(), 1);
+        assert_eq!(event["groups"][0]["kept_agent_id"], "b");
+    }
+
+    #[test]
+    fn distinct_opinions_stay_separate() {
+        let opinions = [
+            opinion("a", "建议先做小范围试点，验证效果后再推广。"),
+            opinion("b", "成本太高，不建议推进这个项目。"),
+        ];
+        let refs = opinions.iter().collect::<Vec<_>>();
+        let groups = group_similar(&refs, DEFAULT_DEDUPE_THRESHOLD);
+        assert_eq!(groups.len(), 2);
+        assert!(groups.iter().all(|g| !g.is_duplicate()));
+        assert!(collapsed_event(&refs, &groups, 1, "initial").is_none());
+
+        assert_eq!(dedupe_threshold(&serde_json::json!({})), None);
+        assert_eq!(
+            dedupe_threshold(&serde_json::json!({ "dedupe_similar": true })),
+            Some(DEFAULT_DEDUPE_THRESHOLD)
+        );
+    }
+}
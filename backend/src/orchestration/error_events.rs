@@ -0,0 +1,77 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::AppError;
+
+/// Whether an `error` event ended the run or was recovered from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorSeverity {
+    /// e.g. one agent failed to reply; the round carried on without it.
+    Warning,
+    /// The execution stopped and is marked failed.
+    Fatal,
+}
+
+/// Payload for an `error` event:
+/// `{ severity, code, message, agent_id, provider_error }`.
+pub fn error_event(
+    severity: ErrorSeverity,
+    code: &str,
+    message: impl Into<String>,
+    agent_id: Option<&str>,
+) -> Value {
+    serde_json::json!({
+        "severity": severity,
+        "code": code,
+        "message": message.into(),
+        "agent_id": agent_id,
+        "provider_error": Value::Null
+    })
+}
+
+/// An `error_event` for `err`. Provider failures reuse the provider's
+/// classification (`rate_limited`, `invalid_api_key`, ...) as the code and
+/// carry the structured `provider_error`; anything else is `internal`.
+pub fn app_error_event(
+    severity: ErrorSeverity,
+    err: &AppError,
+    message: impl Into<String>,
+    agent_id: Option<&str>,
+) -> Value {
+    let provider_error = err.provider_error();
+    let code = provider_error.map_or("internal", |e| e.code.as_str());
+    let mut data = error_event(severity, code, message, agent_id);
+    data["provider_error"] = serde_json::json!(provider_error);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::error::ProviderError;
+
+    #[test]
+    fn provider_errors_use_their_classified_code() {
+        let err = AppError::Provider(ProviderError::from_response(
+            "openai_compatible",
+            429,
+            r#"{"error":{"message":"slow down","code":"rate_limit_exceeded"}}"#,
+        ));
+        let data = app_error_event(ErrorSeverity::Warning, &err, "failed", Some("a1"));
+        assert_eq!(data["severity"], "warning");
+        assert_eq!(data["code"], "rate_limited");
+        assert_eq!(data["agent_id"], "a1");
+        assert_eq!(data["provider_error"]["http_status"], 429);
+
+        let data = app_error_event(
+            ErrorSeverity::Fatal,
+            &AppError::Message("boom".to_string()),
+            "boom",
+            None,
+        );
+        assert_eq!(data["severity"], "fatal");
+        assert_eq!(data["code"], "internal");
+        assert!(data["provider_error"].is_null());
+    }
+}
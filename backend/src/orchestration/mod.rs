@@ -1,5 +1,14 @@
+pub mod checkpoint;
+pub mod convergence;
+pub mod critic;
 pub mod debate;
+pub mod dedupe;
+pub mod error_events;
+pub mod output;
 pub mod pipeline;
+pub mod plan;
 pub mod roundtable;
 pub mod state;
+pub mod status;
 pub mod tool_events;
+pub mod watchdog;
@@ -0,0 +1,158 @@
+use serde_json::Value;
+
+use crate::models::team::OutputRules;
+
+/// How `final_output` is rendered, from `OutputRules.format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Headers and lists, as the synthesis is built.
+    #[default]
+    Markdown,
+    /// The same synthesis with the markdown syntax stripped.
+    Plain,
+    /// The pretty-printed `structured_output`.
+    Json,
+}
+
+impl OutputFormat {
+    /// Unknown or empty formats fall back to markdown.
+    pub fn from_rules(rules: &OutputRules) -> Self {
+        match rules.format.trim().to_ascii_lowercase().as_str() {
+            "plain" | "text" => Self::Plain,
+            "json" => Self::Json,
+            _ => Self::Markdown,
+        }
+    }
+
+    pub fn render(self, markdown: &str, structured: &Value) -> String {
+        match self {
+            Self::Markdown => markdown.to_string(),
+            Self::Plain => strip_markdown(markdown),
+            Self::Json => {
+                serde_json::to_string_pretty(structured).unwrap_or_else(|_| structured.to_string())
+            }
+        }
+    }
+}
+
+/// Readable plain text from markdown: drops heading marks, emphasis, inline
+/// code ticks, code fences and quote markers, turns `[text](url)` into
+/// `text (url)` and normalizes list bullets to `- `.
+pub fn strip_markdown(text: &str) -> String {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            continue;
+        }
+        let indent = &line[..line.len() - trimmed.len()];
+        let body = trimmed.trim_start_matches('#');
+        let body = if body.len() < trimmed.len() {
+            body.trim_start()
+        } else {
+            trimmed.strip_prefix("> ").unwrap_or(trimmed)
+        };
+        let body = match body.strip_prefix("* ").or_else(|| body.strip_prefix("+ ")) {
+            Some(rest) => format!("- {rest}"),
+            None => body.to_string(),
+        };
+        let body = strip_links(&body)
+            .replace("**", "")
+            .replace("__", "")
+            .replace('`', "");
+        lines.push(format!("{indent}{body}"));
+    }
+    lines.join("\n")
+}
+
+fn strip_links(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('[') {
+        let after = &rest[open + 1..];
+        let Some((label, tail)) = after.split_once("](") else {
+            break;
+        };
+        let Some((url, tail)) = tail.split_once(')') else {
+            break;
+        };
+        if label.contains(']') {
+            break;
+        }
+        out.push_str(&rest[..open]);
+        out.push_str(&format!("{label} ({url})"));
+        rest = tail;
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestration::state::{Opinion, OrchestrationState};
+
+    fn opinion(id: &str, name: &str, content: &str) -> Opinion {
+        Opinion {
+            agent_id: id.to_string(),
+            agent_name: name.to_string(),
+            content: content.to_string(),
+            round: 1,
+            phase: "initial".to_string(),
+            wants_to_continue: true,
+            responding_to: None,
+            input_tokens: 0,
+            output_tokens: 0,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
+        }
+    }
+
+    fn rendered(format: &str) -> String {
+        let state = OrchestrationState {
+            topic: "Pick a database".to_string(),
+            summary: "Both work.".to_string(),
+            round: 1,
+            ..Default::default()
+        };
+        let opinions = vec![
+            opinion("a", "Ann", "Use **SQLite** for `local` data"),
+            opinion("b", "Ben", "See [the docs](https://pg.example)"),
+        ];
+        let rules = OutputRules {
+            format: format.to_string(),
+            ..OutputRules::default()
+        };
+        OutputFormat::from_rules(&rules).render(
+            &state.final_output(&opinions).unwrap(),
+            &state.structured_output(&opinions),
+        )
+    }
+
+    #[test]
+    fn markdown_output_has_headers_and_lists() {
+        let out = rendered("markdown");
+        assert!(out.starts_with("## 讨论结果\n主题：Pick a database"));
+        assert!(out.contains("### 摘要\nBoth work."));
+        assert!(out.contains("- **Ann**（第1轮）: Use **SQLite** for `local` data"));
+        assert_eq!(rendered("unknown"), out);
+    }
+
+    #[test]
+    fn plain_output_strips_markdown() {
+        let out = rendered("plain");
+        assert!(out.starts_with("讨论结果\n主题：Pick a database"));
+        assert!(!out.contains('#') && !out.contains("**") && !out.contains('`'));
+        assert!(out.contains("- Ann（第1轮）: Use SQLite for local data"));
+        assert!(out.contains("See the docs (https://pg.example)"));
+    }
+
+    #[test]
+    fn json_output_is_the_structured_view() {
+        let out: Value = serde_json::from_str(&rendered("JSON")).unwrap();
+        assert_eq!(out["topic"], "Pick a database");
+        assert_eq!(out["summary"], "Both work.");
+        assert_eq!(out["opinions"].as_array().unwrap().len(), 2);
+        assert_eq!(out["opinions"][1]["agent_name"], "Ben");
+    }
+}
@@ -1,18 +1,95 @@
 use crate::agents::instance::AgentInstance;
 use crate::error::AppError;
+use crate::llm::tool_args::strip_code_fence;
+use crate::orchestration::checkpoint::{emit_phase_complete, emit_progress};
 use crate::orchestration::state::{Opinion, OrchestrationPhase, OrchestrationState};
 use crate::orchestration::tool_events::emit_tool_traces;
 use crate::tools::definition::ToolDefinition;
 use crate::tools::executor::ToolExecutor;
 
+/// What the pipeline does when a stage's agent call fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StageErrorPolicy {
+    /// Fail the whole run.
+    #[default]
+    Abort,
+    /// Drop the stage and hand the previous stage's output to the next one.
+    Skip,
+    /// Run the stage once more; abort if it fails again.
+    Retry,
+}
+
+impl StageErrorPolicy {
+    pub fn from_mode_config(mode_config: &serde_json::Value) -> Self {
+        match mode_config.get("on_stage_error").and_then(|v| v.as_str()) {
+            Some("skip") => StageErrorPolicy::Skip,
+            Some("retry") => StageErrorPolicy::Retry,
+            _ => StageErrorPolicy::Abort,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            StageErrorPolicy::Abort => "abort",
+            StageErrorPolicy::Skip => "skip",
+            StageErrorPolicy::Retry => "retry",
+        }
+    }
+}
+
+/// A stage's reply split into what it shows and what it passes on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageOutput {
+    /// The stage's opinion, as shown and stored.
+    pub output: String,
+    /// What the next stage receives as the previous stage's output.
+    pub handoff: String,
+    pub structured: bool,
+}
+
+/// A stage may reply with a JSON object `{ "output": ..., "handoff": ... }`,
+/// optionally in a code fence: `output` becomes its opinion and only
+/// `handoff` feeds the next stage. Any other reply is plain text and serves
+/// as both. Non-string values are passed on as pretty-printed JSON.
+pub fn split_pipeline_output(content: &str) -> StageOutput {
+    let plain = || StageOutput {
+        output: content.to_string(),
+        handoff: content.to_string(),
+        structured: false,
+    };
+    let Ok(serde_json::Value::Object(fields)) =
+        serde_json::from_str::<serde_json::Value>(strip_code_fence(content.trim()))
+    else {
+        return plain();
+    };
+    let text = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => serde_json::to_string_pretty(other).unwrap_or_else(|_| other.to_string()),
+    };
+    let Some(handoff) = fields.get("handoff").filter(|v| !v.is_null()).map(text) else {
+        return plain();
+    };
+    StageOutput {
+        output: fields
+            .get("output")
+            .filter(|v| !v.is_null())
+            .map_or_else(|| content.to_string(), text),
+        handoff,
+        structured: true,
+    }
+}
+
 pub async fn run_pipeline(
     agents: Vec<AgentInstance>,
     state: &mut OrchestrationState,
     emit: &mut impl FnMut(&str, serde_json::Value, Option<String>) -> Result<(), AppError>,
+    on_stage_error: StageErrorPolicy,
     tool_defs: &[ToolDefinition],
     tool_executor: Option<ToolExecutor>,
 ) -> Result<Vec<AgentInstance>, AppError> {
     state.phase = OrchestrationPhase::Sequential;
+    state.expected_opinions = agents.len() as u32;
+    let phase_start = state.phase_start();
     emit(
         "status",
         serde_json::json!({ "message": "Pipeline started", "stages": agents.len(), "phase": "pipeline" }),
@@ -27,34 +104,64 @@ pub async fn run_pipeline(
         let stage = (idx + 1) as i32;
         emit(
             "status",
-            serde_json::json!({ "message": format!("Processing Stage {stage}: {}", agent.name), "stage": stage, "phase": "pipeline" }),
+            serde_json::json!({
+                "message": format!("Processing Stage {stage}: {}", agent.name),
+                "stage": stage,
+                "current_stage": format!("stage_{stage}: {}", agent.name),
+                "phase": "pipeline"
+            }),
             Some(agent.id.clone()),
         )?;
 
-        let (resp, traces) = agent
-            .generate_opinion_with_tools(
-                &current_input,
-                "",
-                &[],
-                "initial",
-                tool_defs,
-                tool_executor.as_ref(),
-            )
-            .await?;
+        let mut retried = false;
+        let result = loop {
+            let result = agent
+                .generate_opinion_with_tools(
+                    &current_input,
+                    "",
+                    &[],
+                    "initial",
+                    tool_defs,
+                    tool_executor.as_ref(),
+                )
+                .await;
+            match result {
+                Err(e) if on_stage_error == StageErrorPolicy::Retry && !retried => {
+                    emit_stage_error(emit, &agent, stage, state.round, on_stage_error, &e)?;
+                    retried = true;
+                }
+                other => break other,
+            }
+        };
+        let (resp, traces) = match result {
+            Ok(ok) => ok,
+            Err(e) if on_stage_error == StageErrorPolicy::Skip => {
+                emit_stage_error(emit, &agent, stage, state.round, on_stage_error, &e)?;
+                // `current_input` still carries the previous stage's output.
+                state.expected_opinions = state.expected_opinions.saturating_sub(1);
+                out_agents.push(agent);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
         emit_tool_traces(emit, &traces, &agent.id, &agent.name, state.round)?;
 
         let (input_tokens, output_tokens, tokens_estimated) = resp.token_counts();
+        let (cache_read_tokens, cache_write_tokens) = resp.cached_tokens();
+        let split = split_pipeline_output(&resp.content);
 
         let opinion = Opinion {
             agent_id: agent.id.clone(),
             agent_name: agent.name.clone(),
-            content: resp.content.clone(),
+            content: split.output.clone(),
             round: state.round,
             phase: format!("stage_{stage}"),
             wants_to_continue: true,
             responding_to: None,
             input_tokens,
             output_tokens,
+            cache_read_tokens,
+            cache_write_tokens,
         };
         state.add_opinion(opinion);
 
@@ -62,7 +169,8 @@ pub async fn run_pipeline(
             "opinion",
             serde_json::json!({
                 "agent_name": agent.name,
-                "content": resp.content,
+                "content": split.output,
+                "handoff": split.structured.then_some(&split.handoff),
                 "round": state.round,
                 "phase": format!("stage_{stage}"),
                 "stage": stage,
@@ -73,15 +181,199 @@ pub async fn run_pipeline(
             }),
             Some(agent.id.clone()),
         )?;
+        emit_progress(emit, state)?;
 
         current_input = format!(
             "原始任务：{original_topic}\n\n上一阶段（第{stage}阶段）的输出：\n{}\n\n请基于上述内容，从你的专业角度进行处理和完善。",
-            resp.content
+            split.handoff
         );
 
         out_agents.push(agent);
     }
 
+    emit_phase_complete(emit, state, &phase_start, "pipeline")?;
+    emit(
+        "status",
+        serde_json::json!({ "message": "Pipeline completed", "current_stage": null, "phase": "pipeline" }),
+        None,
+    )?;
+
     state.phase = OrchestrationPhase::Completed;
     Ok(out_agents)
 }
+
+fn emit_stage_error(
+    emit: &mut impl FnMut(&str, serde_json::Value, Option<String>) -> Result<(), AppError>,
+    agent: &AgentInstance,
+    stage: i32,
+    round: i32,
+    policy: StageErrorPolicy,
+    error: &AppError,
+) -> Result<(), AppError> {
+    emit(
+        "stage_error",
+        serde_json::json!({
+            "message": format!("第{stage}阶段 {} 失败: {error}", agent.name),
+            "agent_name": agent.name,
+            "stage": stage,
+            "round": round,
+            "phase": "pipeline",
+            "policy": policy.as_str(),
+            "provider_error": error.provider_error()
+        }),
+        Some(agent.id.clone()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::{self, MockProvider};
+    use std::sync::Arc;
+
+    fn stage(id: &str, provider: Arc<MockProvider>) -> AgentInstance {
+        AgentInstance::from_agent(&mock::agent(id, id), provider)
+    }
+
+    /// Run three stages where the second fails `failures` times; returns the
+    /// result, the event types emitted, and the third stage's provider.
+    async fn run(
+        policy: StageErrorPolicy,
+        failures: u32,
+    ) -> (
+        Result<Vec<AgentInstance>, AppError>,
+        OrchestrationState,
+        Vec<String>,
+        Arc<MockProvider>,
+    ) {
+        let third = MockProvider::new("third output");
+        let agents = vec![
+            stage("one", MockProvider::new("first output")),
+            stage("two", MockProvider::failing(failures, "second output")),
+            stage("three", third.clone()),
+        ];
+        let mut state = OrchestrationState {
+            topic: "topic".to_string(),
+            round: 1,
+            ..Default::default()
+        };
+        let mut events = Vec::new();
+        let mut emit = |event: &str, _: serde_json::Value, _: Option<String>| {
+            events.push(event.to_string());
+            Ok(())
+        };
+        let result = run_pipeline(agents, &mut state, &mut emit, policy, &[], None).await;
+        (result, state, events, third)
+    }
+
+    fn count(events: &[String], name: &str) -> usize {
+        events.iter().filter(|e| *e == name).count()
+    }
+
+    #[test]
+    fn policy_parses_mode_config() {
+        let parse =
+            |v| StageErrorPolicy::from_mode_config(&serde_json::json!({ "on_stage_error": v }));
+        assert_eq!(parse("skip"), StageErrorPolicy::Skip);
+        assert_eq!(parse("retry"), StageErrorPolicy::Retry);
+        assert_eq!(parse("abort"), StageErrorPolicy::Abort);
+        assert_eq!(
+            StageErrorPolicy::from_mode_config(&serde_json::json!({})),
+            StageErrorPolicy::Abort
+        );
+    }
+
+    #[tokio::test]
+    async fn structured_stages_hand_off_only_the_handoff() {
+        let second = MockProvider::new("plain second output");
+        let third = MockProvider::new("done");
+        let agents = vec![
+            stage(
+                "one",
+                MockProvider::new(
+                    "```json\n{\"output\": \"Full report\", \"handoff\": \"Key numbers only\"}\n```",
+                ),
+            ),
+            stage("two", second.clone()),
+            stage("three", third.clone()),
+        ];
+        let mut state = OrchestrationState {
+            topic: "topic".to_string(),
+            round: 1,
+            ..Default::default()
+        };
+        let mut handoffs = Vec::new();
+        let mut emit = |event: &str, data: serde_json::Value, _: Option<String>| {
+            if event == "opinion" {
+                handoffs.push(data["handoff"].clone());
+            }
+            Ok(())
+        };
+        run_pipeline(
+            agents,
+            &mut state,
+            &mut emit,
+            StageErrorPolicy::Abort,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(state.opinions[0].content, "Full report");
+        assert!(second.user_content(0).contains("Key numbers only"));
+        assert!(!second.user_content(0).contains("Full report"));
+        assert_eq!(handoffs[0], "Key numbers only");
+
+        // A plain-text stage hands its whole reply on.
+        assert_eq!(state.opinions[1].content, "plain second output");
+        assert!(third.user_content(0).contains("plain second output"));
+        assert!(handoffs[1].is_null());
+    }
+
+    #[test]
+    fn unstructured_replies_fall_back_to_plain_text() {
+        for content in ["just prose", r#"{"output": "no handoff"}"#, "[1, 2]"] {
+            let split = split_pipeline_output(content);
+            assert!(!split.structured, "{content}");
+            assert_eq!(split.handoff, content);
+        }
+        let split = split_pipeline_output(r#"{"handoff": {"rows": 2}}"#);
+        assert!(split.structured);
+        assert_eq!(split.handoff, "{\n  \"rows\": 2\n}");
+    }
+
+    #[tokio::test]
+    async fn abort_fails_the_run() {
+        let (result, state, events, third) = run(StageErrorPolicy::Abort, 1).await;
+        assert!(result.is_err());
+        assert_eq!(state.opinions.len(), 1);
+        assert_eq!(count(&events, "stage_error"), 0);
+        assert!(third.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn skip_passes_the_previous_output_through() {
+        let (result, state, events, third) = run(StageErrorPolicy::Skip, 1).await;
+        assert_eq!(result.unwrap().len(), 3);
+        let agents: Vec<_> = state.opinions.iter().map(|o| o.agent_id.as_str()).collect();
+        assert_eq!(agents, vec!["one", "three"]);
+        assert_eq!(count(&events, "stage_error"), 1);
+        assert!(third.user_content(0).contains("first output"));
+        assert_eq!(state.expected_opinions, 2);
+    }
+
+    #[tokio::test]
+    async fn retry_runs_a_failed_stage_once_more() {
+        let (result, state, events, third) = run(StageErrorPolicy::Retry, 1).await;
+        assert!(result.is_ok());
+        assert_eq!(state.opinions.len(), 3);
+        assert_eq!(count(&events, "stage_error"), 1);
+        assert!(third.user_content(0).contains("second output"));
+
+        let (result, state, events, _) = run(StageErrorPolicy::Retry, 2).await;
+        assert!(result.is_err());
+        assert_eq!(state.opinions.len(), 1);
+        assert_eq!(count(&events, "stage_error"), 1);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::agents::instance::AgentInstance;
+
+/// Describe what an execution would do without calling any provider: the
+/// collaboration mode, each participating agent with its resolved model, and
+/// an estimate of the prompt tokens its first turn would send.
+pub fn build_plan(
+    agents: &[AgentInstance],
+    mode: &str,
+    topic: &str,
+    discussion_summary: &str,
+    recent_opinions: &[serde_json::Value],
+    tools_enabled: bool,
+) -> serde_json::Value {
+    let mut total: u32 = 0;
+    let entries = agents
+        .iter()
+        .map(|agent| {
+            let estimated = agent.estimate_prompt_tokens(
+                topic,
+                discussion_summary,
+                recent_opinions,
+                tools_enabled,
+            );
+            total = total.saturating_add(estimated);
+            serde_json::json!({
+                "agent_id": agent.id.clone(),
+                "agent_name": agent.name.clone(),
+                "provider": agent.provider_name(),
+                "model": agent.model_id(),
+                "temperature": agent.temperature,
+                "max_tokens": agent.max_tokens,
+                "max_tool_iterations": agent.max_tool_iterations,
+                "speaking_priority": agent.speaking_priority,
+                "estimated_prompt_tokens": estimated
+            })
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::json!({
+        "mode": mode,
+        "topic": topic,
+        "tools_enabled": tools_enabled,
+        "agents": entries,
+        "estimated_prompt_tokens": total
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::{self, MockProvider};
+
+    #[test]
+    fn plan_lists_every_agent_without_calling_providers() {
+        let providers = [MockProvider::new("a"), MockProvider::new("b")];
+        let agents = vec![
+            AgentInstance::from_agent(&mock::agent("a1", "Alice"), providers[0].clone()),
+            AgentInstance::from_agent(&mock::agent("a2", "Bob"), providers[1].clone()),
+        ];
+
+        let plan = build_plan(&agents, "roundtable", "Pick a stack", "", &[], false);
+
+        assert_eq!(plan["mode"], "roundtable");
+        let listed = plan["agents"].as_array().unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0]["agent_id"], "a1");
+        assert_eq!(listed[1]["agent_id"], "a2");
+        assert_eq!(listed[0]["model"], "mock-model");
+        assert!(listed[0]["estimated_prompt_tokens"].as_u64().unwrap() > 0);
+        let sum = listed
+            .iter()
+            .map(|a| a["estimated_prompt_tokens"].as_u64().unwrap())
+            .sum::<u64>();
+        assert_eq!(plan["estimated_prompt_tokens"].as_u64().unwrap(), sum);
+
+        for p in providers.iter() {
+            assert!(p.calls().is_empty());
+        }
+    }
+
+    #[test]
+    fn plan_estimate_grows_with_context() {
+        let agents = vec![AgentInstance::from_agent(
+            &mock::agent("a1", "Alice"),
+            MockProvider::new(""),
+        )];
+        let small = build_plan(&agents, "roundtable", "t", "", &[], false);
+        let recent = vec![serde_json::json!({"agent_name": "Bob", "content": "x".repeat(400)})];
+        let large = build_plan(&agents, "roundtable", "t", "", &recent, true);
+        assert!(
+            large["estimated_prompt_tokens"].as_u64().unwrap()
+                > small["estimated_prompt_tokens"].as_u64().unwrap()
+        );
+    }
+}
@@ -1,33 +1,81 @@
 use crate::agents::instance::AgentInstance;
 use crate::error::AppError;
+use crate::orchestration::checkpoint::{emit_phase_complete, emit_progress};
+use crate::orchestration::dedupe::{collapsed_event, group_similar};
+use crate::orchestration::error_events::{app_error_event, ErrorSeverity};
 use crate::orchestration::state::{Opinion, OrchestrationPhase, OrchestrationState};
 use crate::orchestration::tool_events::emit_tool_traces;
 use crate::tools::definition::ToolDefinition;
 use crate::tools::executor::ToolExecutor;
 
+/// How the initial phase of a round is ordered, from `mode_config.initial_order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InitialOrder {
+    /// Every agent answers independently from the same context.
+    #[default]
+    Parallel,
+    /// Agents speak by descending `speaking_priority`, each seeing the
+    /// opinions already given in this phase.
+    Priority,
+}
+
+impl InitialOrder {
+    pub fn from_mode_config(mode_config: &serde_json::Value) -> Self {
+        match mode_config.get("initial_order").and_then(|v| v.as_str()) {
+            Some("priority") => InitialOrder::Priority,
+            _ => InitialOrder::Parallel,
+        }
+    }
+}
+
+/// Prompt asking one agent to summarize a finished roundtable from `opinions`.
+pub fn summary_prompt(topic: &str, opinions: &[Opinion]) -> String {
+    let lines = opinions
+        .iter()
+        .map(|o| format!("- {}（第{}轮）: {}", o.agent_name, o.round, o.content))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "请总结以下圆桌讨论：\n\n主题：{topic}\n\n各专家观点：\n{lines}\n\n请给出：\n1. 主要共识\n2. 分歧点\n3. 结论与建议"
+    )
+}
+
 pub async fn run_roundtable(
     mut agents: Vec<AgentInstance>,
     state: &mut OrchestrationState,
     emit: &mut impl FnMut(&str, serde_json::Value, Option<String>) -> Result<(), AppError>,
     enable_response_phase: bool,
+    initial_order: InitialOrder,
     tool_defs: &[ToolDefinition],
     tool_executor: Option<ToolExecutor>,
 ) -> Result<Vec<AgentInstance>, AppError> {
     state.phase = OrchestrationPhase::Parallel;
+    let phases = if enable_response_phase { 2 } else { 1 };
+    state.expected_opinions = agents.len() as u32 * phases;
 
-    let recent = state.recent_opinions_json(6);
+    let recent = state.context_opinions_json(agents.len());
     let topic = state.topic.clone();
     let summary = state.summary.clone();
 
+    if initial_order == InitialOrder::Priority {
+        state.phase = OrchestrationPhase::Sequential;
+        agents.sort_by_key(|a| std::cmp::Reverse(a.speaking_priority));
+    }
+
     let mut round_one = Vec::new();
+    let phase_start = state.phase_start();
 
     // 顺序执行：逐个 agent 发言
     for agent in agents.iter_mut() {
+        let context = match initial_order {
+            InitialOrder::Parallel => recent.clone(),
+            InitialOrder::Priority => recent.iter().chain(round_one.iter()).cloned().collect(),
+        };
         let result = agent
             .generate_opinion_with_tools(
                 &topic,
                 &summary,
-                &recent,
+                &context,
                 "initial",
                 tool_defs,
                 tool_executor.as_ref(),
@@ -40,6 +88,7 @@ pub async fn run_roundtable(
                 let agent_name = agent.name.clone();
                 emit_tool_traces(emit, &traces, &agent_id, &agent_name, state.round)?;
                 let (input_tokens, output_tokens, tokens_estimated) = resp.token_counts();
+                let (cache_read_tokens, cache_write_tokens) = resp.cached_tokens();
                 let opinion = Opinion {
                     agent_id: agent_id.clone(),
                     agent_name: agent_name.clone(),
@@ -50,6 +99,8 @@ pub async fn run_roundtable(
                     responding_to: resp.responding_to.clone(),
                     input_tokens,
                     output_tokens,
+                    cache_read_tokens,
+                    cache_write_tokens,
                 };
                 state.add_opinion(opinion);
                 round_one.push(serde_json::json!({"agent_id": agent_id.clone(), "agent_name": agent_name.clone(), "content": resp.content.clone()}));
@@ -69,22 +120,36 @@ pub async fn run_roundtable(
                     }),
                     Some(agent_id),
                 )?;
+                emit_progress(emit, state)?;
             }
             Err(e) => {
                 let agent_id = agent.id.clone();
-                emit(
-                    "status",
-                    serde_json::json!({
-                        "message": format!("{} 回复失败: {}", agent.name, e),
-                        "phase": "agent_error",
-                        "round": state.round
-                    }),
-                    Some(agent_id),
-                )?;
+                let mut data = app_error_event(
+                    ErrorSeverity::Warning,
+                    &e,
+                    format!("{} 回复失败: {}", agent.name, e),
+                    Some(&agent_id),
+                );
+                data["phase"] = serde_json::json!("agent_error");
+                data["round"] = serde_json::json!(state.round);
+                emit("error", data, Some(agent_id))?;
             }
         }
     }
 
+    emit_phase_complete(emit, state, &phase_start, "initial")?;
+    if let Some(threshold) = state.dedupe_threshold {
+        let initial = state
+            .opinions
+            .iter()
+            .filter(|o| o.round == state.round && o.phase == "initial")
+            .collect::<Vec<_>>();
+        let groups = group_similar(&initial, threshold);
+        if let Some(data) = collapsed_event(&initial, &groups, state.round, "initial") {
+            emit("duplicates_collapsed", data, None)?;
+        }
+    }
+
     // 检查是否所有 Agent 都认为讨论已完成
     let all_done = state.agent_wants_continue.values().all(|&wants| !wants);
 
@@ -105,6 +170,7 @@ pub async fn run_roundtable(
     }
 
     state.phase = OrchestrationPhase::Responding;
+    let phase_start = state.phase_start();
 
     // 顺序执行：逐个 agent 回应
     for agent in agents.iter_mut() {
@@ -125,6 +191,7 @@ pub async fn run_roundtable(
                 let agent_name = agent.name.clone();
                 emit_tool_traces(emit, &traces, &agent_id, &agent_name, state.round)?;
                 let (input_tokens, output_tokens, tokens_estimated) = resp.token_counts();
+                let (cache_read_tokens, cache_write_tokens) = resp.cached_tokens();
                 let opinion = Opinion {
                     agent_id: agent_id.clone(),
                     agent_name: agent_name.clone(),
@@ -135,6 +202,8 @@ pub async fn run_roundtable(
                     responding_to: resp.responding_to.clone(),
                     input_tokens,
                     output_tokens,
+                    cache_read_tokens,
+                    cache_write_tokens,
                 };
                 state.add_opinion(opinion);
 
@@ -146,6 +215,7 @@ pub async fn run_roundtable(
                         "wants_to_continue": resp.wants_to_continue,
                         "round": state.round,
                         "phase": "response",
+                        "target_agent_id": resp.responding_to,
                         "input_tokens": input_tokens,
                         "output_tokens": output_tokens,
                         "tokens_estimated": tokens_estimated,
@@ -153,22 +223,463 @@ pub async fn run_roundtable(
                     }),
                     Some(agent_id),
                 )?;
+                emit_progress(emit, state)?;
             }
             Err(e) => {
                 let agent_id = agent.id.clone();
-                emit(
-                    "status",
-                    serde_json::json!({
-                        "message": format!("{} 回复失败: {}", agent.name, e),
-                        "phase": "agent_error",
-                        "round": state.round
-                    }),
-                    Some(agent_id),
-                )?;
+                let mut data = app_error_event(
+                    ErrorSeverity::Warning,
+                    &e,
+                    format!("{} 回复失败: {}", agent.name, e),
+                    Some(&agent_id),
+                );
+                data["phase"] = serde_json::json!("agent_error");
+                data["round"] = serde_json::json!(state.round);
+                emit("error", data, Some(agent_id))?;
             }
         }
     }
 
+    emit_phase_complete(emit, state, &phase_start, "response")?;
+
     state.phase = OrchestrationPhase::Completed;
     Ok(agents)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::instance::MessageMetadata;
+    use crate::llm::mock::{self, MockProvider};
+
+    fn speaker(
+        id: &str,
+        name: &str,
+        priority: i32,
+    ) -> (AgentInstance, std::sync::Arc<MockProvider>) {
+        let provider = MockProvider::new(&format!("{name} thinks so"));
+        let mut agent = mock::agent(id, name);
+        agent.speaking_priority = priority;
+        (
+            AgentInstance::from_agent(&agent, provider.clone()),
+            provider,
+        )
+    }
+
+    #[test]
+    fn initial_order_parses_mode_config() {
+        assert_eq!(
+            InitialOrder::from_mode_config(&serde_json::json!({"initial_order": "priority"})),
+            InitialOrder::Priority
+        );
+        assert_eq!(
+            InitialOrder::from_mode_config(&serde_json::json!({"initial_order": "parallel"})),
+            InitialOrder::Parallel
+        );
+        assert_eq!(
+            InitialOrder::from_mode_config(&serde_json::json!({})),
+            InitialOrder::Parallel
+        );
+    }
+
+    #[tokio::test]
+    async fn priority_order_feeds_earlier_opinions_to_later_speakers() {
+        let (low, low_llm) = speaker("low", "Low", 1);
+        let (high, high_llm) = speaker("high", "High", 9);
+        let (mid, mid_llm) = speaker("mid", "Mid", 5);
+
+        let mut state = OrchestrationState {
+            topic: "topic".to_string(),
+            round: 1,
+            ..Default::default()
+        };
+        let mut emit = |_: &str, _: serde_json::Value, _: Option<String>| Ok(());
+        run_roundtable(
+            vec![low, high, mid],
+            &mut state,
+            &mut emit,
+            false,
+            InitialOrder::Priority,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        let order = state
+            .opinions
+            .iter()
+            .map(|o| o.agent_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(order, vec!["high", "mid", "low"]);
+
+        let first = high_llm.user_content(0);
+        assert!(!first.contains("thinks so"));
+        let second = mid_llm.user_content(0);
+        assert!(second.contains("High thinks so"));
+        let third = low_llm.user_content(0);
+        assert!(third.contains("High thinks so"));
+        assert!(third.contains("Mid thinks so"));
+    }
+
+    #[tokio::test]
+    async fn parallel_order_shares_the_same_context() {
+        let (a, a_llm) = speaker("a", "Ann", 1);
+        let (b, b_llm) = speaker("b", "Ben", 9);
+
+        let mut state = OrchestrationState {
+            topic: "topic".to_string(),
+            round: 1,
+            ..Default::default()
+        };
+        let mut emit = |_: &str, _: serde_json::Value, _: Option<String>| Ok(());
+        run_roundtable(
+            vec![a, b],
+            &mut state,
+            &mut emit,
+            false,
+            InitialOrder::Parallel,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(state.opinions[0].agent_id, "a");
+        assert!(!b_llm.user_content(0).contains("Ann thinks so"));
+        assert_eq!(a_llm.user_content(0), b_llm.user_content(0));
+    }
+
+    #[tokio::test]
+    async fn opinion_metadata_follows_the_schema() {
+        let agents = vec![AgentInstance::from_agent(
+            &mock::agent("a", "Ann"),
+            MockProvider::new("I agree."),
+        )];
+        let mut state = OrchestrationState::default();
+        let mut events = Vec::new();
+        let mut emit = |kind: &str, data: serde_json::Value, _: Option<String>| {
+            if kind == "opinion" {
+                events.push(data);
+            }
+            Ok(())
+        };
+        run_roundtable(
+            agents,
+            &mut state,
+            &mut emit,
+            false,
+            InitialOrder::Priority,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        let metadata = &events[0]["metadata"];
+        assert_eq!(metadata["model"], "mock-model");
+        assert_eq!(metadata["finish_reason"], "stop");
+        assert_eq!(metadata["tokens"]["input"], 10);
+        assert_eq!(metadata["tokens"]["output"], 5);
+        assert_eq!(metadata["tokens"]["estimated"], false);
+        assert_eq!(metadata["tool_calls"], serde_json::json!([]));
+        assert!(metadata.get("reasoning").is_none());
+        assert!(metadata.get("cached").is_none());
+        serde_json::from_value::<MessageMetadata>(metadata.clone()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn opinion_events_carry_normalized_finish_reason() {
+        let reply = |reason: &str| crate::llm::provider::LLMResponse {
+            finish_reason: Some(reason.to_string()),
+            ..mock::response("ok", Vec::new())
+        };
+        let openai = MockProvider::scripted(vec![reply("length")], "ok");
+        let anthropic = MockProvider::scripted(vec![reply("end_turn")], "ok");
+        let agents = vec![
+            AgentInstance::from_agent(&mock::agent("o", "OpenAI"), openai),
+            AgentInstance::from_agent(&mock::agent("a", "Anthropic"), anthropic),
+        ];
+
+        let mut state = OrchestrationState::default();
+        let mut events = Vec::new();
+        let mut emit = |kind: &str, data: serde_json::Value, _: Option<String>| {
+            if kind == "opinion" {
+                events.push(data);
+            }
+            Ok(())
+        };
+        run_roundtable(
+            agents,
+            &mut state,
+            &mut emit,
+            false,
+            InitialOrder::Parallel,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        let reasons = events
+            .iter()
+            .map(|e| {
+                (
+                    e["agent_name"].as_str().unwrap().to_string(),
+                    e["metadata"]["finish_reason"].clone(),
+                    e["metadata"]["raw_finish_reason"].clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert!(reasons.contains(&("OpenAI".to_string(), "length".into(), "length".into())));
+        assert!(reasons.contains(&("Anthropic".to_string(), "stop".into(), "end_turn".into())));
+    }
+
+    #[tokio::test]
+    async fn responses_name_the_peer_they_reply_to() {
+        let alice = MockProvider::scripted(
+            vec![
+                mock::response("Use Postgres", Vec::new()),
+                mock::response("@Bob SQLite won't scale here", Vec::new()),
+            ],
+            "ok",
+        );
+        let agents = vec![
+            AgentInstance::from_agent(&mock::agent("a", "Alice"), alice),
+            speaker("b", "Bob", 0).0,
+        ];
+
+        let mut state = OrchestrationState::default();
+        let mut responses = Vec::new();
+        let mut emit = |kind: &str, data: serde_json::Value, agent: Option<String>| {
+            if kind == "opinion" && data["phase"] == "response" {
+                responses.push((agent.unwrap(), data["target_agent_id"].clone()));
+            }
+            Ok(())
+        };
+        run_roundtable(
+            agents,
+            &mut state,
+            &mut emit,
+            true,
+            InitialOrder::Parallel,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            responses,
+            [
+                ("a".to_string(), serde_json::json!("b")),
+                ("b".to_string(), serde_json::Value::Null)
+            ]
+        );
+        let reply = state
+            .opinions
+            .iter()
+            .find(|o| o.phase == "response")
+            .unwrap();
+        assert_eq!(reply.responding_to.as_deref(), Some("b"));
+    }
+
+    #[tokio::test]
+    async fn followup_context_is_trimmed_to_the_most_recent_opinions() {
+        let (a, a_llm) = speaker("a", "Ann", 1);
+        let mut state = OrchestrationState {
+            topic: "follow-up".to_string(),
+            round: 2,
+            context_limit: Some(2),
+            ..Default::default()
+        };
+        for i in 1..=5 {
+            state.add_opinion(Opinion {
+                agent_id: "prior".to_string(),
+                agent_name: "Prior".to_string(),
+                content: format!("earlier point {i}"),
+                round: 1,
+                phase: "initial".to_string(),
+                wants_to_continue: true,
+                responding_to: None,
+                input_tokens: 0,
+                output_tokens: 0,
+                cache_read_tokens: 0,
+                cache_write_tokens: 0,
+            });
+        }
+        let mut emit = |_: &str, _: serde_json::Value, _: Option<String>| Ok(());
+        run_roundtable(
+            vec![a],
+            &mut state,
+            &mut emit,
+            false,
+            InitialOrder::Parallel,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        let context = a_llm.user_content(0);
+        assert!(context.contains("earlier point 4"));
+        assert!(context.contains("earlier point 5"));
+        for i in 1..=3 {
+            assert!(
+                !context.contains(&format!("earlier point {i}")),
+                "{context}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn large_teams_see_every_peer_in_context() {
+        let speakers = (0..10)
+            .map(|i| speaker(&format!("a{i}"), &format!("Expert{i}"), 1))
+            .collect::<Vec<_>>();
+        let providers = speakers.iter().map(|(_, p)| p.clone()).collect::<Vec<_>>();
+        let mut state = OrchestrationState {
+            topic: "topic".to_string(),
+            round: 1,
+            context_limit: Some(10),
+            ..Default::default()
+        };
+        let mut emit = |_: &str, _: serde_json::Value, _: Option<String>| Ok(());
+        let agents = run_roundtable(
+            speakers.into_iter().map(|(a, _)| a).collect(),
+            &mut state,
+            &mut emit,
+            true,
+            InitialOrder::Parallel,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Response phase: the last speaker sees all ten initial opinions.
+        let response = providers[9].user_content(1);
+        for i in 0..10 {
+            assert!(
+                response.contains(&format!("Expert{i} thinks so")),
+                "{response}"
+            );
+        }
+
+        // Next round: even with the default limit, the window covers the team.
+        state.context_limit = None;
+        state.start_new_round();
+        run_roundtable(
+            agents,
+            &mut state,
+            &mut emit,
+            false,
+            InitialOrder::Parallel,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+        let followup = providers[0].user_content(2);
+        for i in 0..10 {
+            assert!(followup.contains(&format!("Expert{i}")), "{followup}");
+        }
+    }
+
+    #[tokio::test]
+    async fn recovered_agent_error_is_a_warning() {
+        let (a, _) = speaker("a", "Ann", 1);
+        let broken =
+            AgentInstance::from_agent(&mock::agent("b", "Ben"), MockProvider::failing(1, "unused"));
+        let mut state = OrchestrationState {
+            topic: "topic".to_string(),
+            round: 1,
+            ..Default::default()
+        };
+        let mut errors = Vec::new();
+        let mut emit = |event: &str, data: serde_json::Value, _: Option<String>| {
+            if event == "error" {
+                errors.push(data);
+            }
+            Ok(())
+        };
+        run_roundtable(
+            vec![a, broken],
+            &mut state,
+            &mut emit,
+            false,
+            InitialOrder::Parallel,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(state.opinions.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0]["severity"], "warning");
+        assert_eq!(errors[0]["agent_id"], "b");
+        assert_eq!(errors[0]["code"], "internal");
+        assert_eq!(errors[0]["phase"], "agent_error");
+    }
+
+    #[tokio::test]
+    async fn round_complete_events_count_each_phase() {
+        let (a, _) = speaker("a", "Ann", 1);
+        let (b, _) = speaker("b", "Ben", 2);
+        let mut state = OrchestrationState {
+            topic: "topic".to_string(),
+            round: 1,
+            ..Default::default()
+        };
+        state.add_opinion(Opinion {
+            agent_id: "old".to_string(),
+            agent_name: "Old".to_string(),
+            content: "before".to_string(),
+            round: 0,
+            phase: "initial".to_string(),
+            wants_to_continue: true,
+            responding_to: None,
+            input_tokens: 7,
+            output_tokens: 7,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
+        });
+
+        let mut completed = Vec::new();
+        let mut emit = |kind: &str, data: serde_json::Value, _: Option<String>| {
+            if kind == "round_complete" {
+                completed.push(data);
+            }
+            Ok(())
+        };
+        run_roundtable(
+            vec![a, b],
+            &mut state,
+            &mut emit,
+            true,
+            InitialOrder::Parallel,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+
+        let phases = completed
+            .iter()
+            .map(|e| {
+                (
+                    e["phase"].as_str().unwrap(),
+                    e["opinion_count"].as_u64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(phases, vec![("initial", 2), ("response", 2)]);
+        let tokens = completed
+            .iter()
+            .map(|e| e["tokens"].as_u64().unwrap())
+            .sum::<u64>();
+        assert_eq!(tokens, u64::from(state.tokens_used) - 14);
+        assert_eq!(state.opinions.len(), 5);
+    }
+}
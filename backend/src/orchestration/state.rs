@@ -1,7 +1,21 @@
 use std::collections::HashMap;
+use std::time::Instant;
 
 use serde::{Deserialize, Serialize};
 
+use crate::models::execution::{cached_tokens, ExecutionMessage};
+use crate::models::llm::Pricing;
+use crate::orchestration::checkpoint::CheckpointThrottle;
+use crate::orchestration::critic::CRITIQUE_PHASE;
+use crate::orchestration::dedupe::{group_similar, SimilarGroup};
+
+/// Prior opinions shown to agents when no `context_limit` is configured.
+pub const DEFAULT_CONTEXT_LIMIT: usize = 6;
+
+/// How many opinions `shared_state` keeps once an execution is persisted.
+/// Older ones remain available from `execution_messages`.
+pub const MAX_RETAINED_OPINIONS: usize = 50;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[derive(Default)]
@@ -26,16 +40,30 @@ pub struct Opinion {
     pub phase: String,
     #[serde(default = "default_true")]
     pub wants_to_continue: bool,
+    /// Agent id of the peer this opinion replies to; stored on the message
+    /// as `target_agent_id`.
     #[serde(default)]
     pub responding_to: Option<String>,
     #[serde(default)]
     pub input_tokens: u32,
     #[serde(default)]
     pub output_tokens: u32,
+    /// Prompt-cache traffic, billed apart from `input_tokens`.
+    #[serde(default)]
+    pub cache_read_tokens: u32,
+    #[serde(default)]
+    pub cache_write_tokens: u32,
 }
 
+/// Schema version written into `shared_state`. Bump it when a field changes
+/// shape so older records can be told apart; see `from_shared_state`.
+pub const STATE_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct OrchestrationState {
+    /// `STATE_VERSION` when saved; `0` for records from before versioning.
+    #[serde(default)]
+    pub version: u32,
     #[serde(default)]
     pub topic: String,
     #[serde(default)]
@@ -50,12 +78,32 @@ pub struct OrchestrationState {
 
     #[serde(default)]
     pub opinions: Vec<Opinion>,
+    /// Number of opinions dropped from `opinions` by `prune_opinions`.
+    #[serde(default)]
+    pub pruned_opinions: u32,
 
     #[serde(default)]
     pub summary: String,
 
+    /// How many of the most recent prior opinions are injected into a new
+    /// round's context; `None` means `DEFAULT_CONTEXT_LIMIT`. Never narrower
+    /// than the team (see `context_window`), and capped in practice by
+    /// `MAX_RETAINED_OPINIONS`.
+    #[serde(default)]
+    pub context_limit: Option<usize>,
+
+    /// Near-duplicate opinions are collapsed in events and the final output
+    /// at this similarity; see `orchestration::dedupe`. Storage keeps them all.
+    #[serde(default)]
+    pub dedupe_threshold: Option<f64>,
+
     #[serde(default)]
     pub agent_wants_continue: HashMap<String, bool>,
+    /// Similarity of the last round to the one before it, when
+    /// `mode_config.stop_on_stable` judged the discussion converged. Cleared
+    /// when the next round starts.
+    #[serde(default)]
+    pub converged: Option<f64>,
 
     #[serde(default)]
     pub tokens_used: u32,
@@ -65,20 +113,136 @@ pub struct OrchestrationState {
     pub cost: f64,
     #[serde(default = "default_cost_budget")]
     pub cost_budget: f64,
+    /// Prices applied to each opinion's tokens to accumulate `cost`.
+    #[serde(default)]
+    pub pricing: Pricing,
+
+    /// Opinions the current round is expected to produce, set by the mode.
+    #[serde(default)]
+    pub expected_opinions: u32,
+    #[serde(default)]
+    pub round_opinions: u32,
+    /// Wall-clock time spent on the opinions counted in `timed_opinions`.
+    #[serde(default)]
+    pub opinion_latency_ms: u64,
+    #[serde(default)]
+    pub timed_opinions: u32,
+    #[serde(skip)]
+    pub last_opinion_at: Option<Instant>,
+    /// When the running round next writes a mid-round checkpoint.
+    #[serde(skip)]
+    pub checkpoints: CheckpointThrottle,
 }
 
 impl OrchestrationState {
+    /// Load an execution's `shared_state`. A record that no longer parses as
+    /// a whole (e.g. written by an older schema) is migrated field by field,
+    /// keeping everything that still fits and logging what is dropped, rather
+    /// than resetting the execution's round and opinions.
+    pub fn from_shared_state(value: &serde_json::Value) -> Self {
+        let error = match serde_json::from_value::<Self>(value.clone()) {
+            Ok(state) => return state,
+            Err(e) => e,
+        };
+        let Some(stored) = value.as_object() else {
+            if !value.is_null() {
+                eprintln!("shared_state is not an object, starting from defaults: {error}");
+            }
+            return Self::default();
+        };
+
+        let mut migrated = serde_json::json!({});
+        let mut dropped = Vec::new();
+        for (key, field) in stored {
+            let mut candidate = migrated.clone();
+            candidate[key] = if key == "opinions" {
+                salvage_opinions(field)
+            } else {
+                field.clone()
+            };
+            if serde_json::from_value::<Self>(candidate.clone()).is_ok() {
+                migrated = candidate;
+            } else {
+                dropped.push(key.as_str());
+            }
+        }
+        let version = stored.get("version").and_then(|v| v.as_u64()).unwrap_or(0);
+        eprintln!(
+            "shared_state v{version} did not match v{STATE_VERSION} ({error}); migrated it, dropping: [{}]",
+            dropped.join(", ")
+        );
+        serde_json::from_value(migrated).unwrap_or_default()
+    }
+
+    /// The value to store as `shared_state`, stamped with `STATE_VERSION`.
+    pub fn to_shared_state(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_else(|_| serde_json::json!({}));
+        value["version"] = serde_json::json!(STATE_VERSION);
+        value
+    }
+
+    /// A mid-round `shared_state` snapshot whose size doesn't grow with the
+    /// round: only the opinions the next round would see are kept, and the
+    /// rest are counted as pruned, since they are already persisted as messages
+    /// and `full_opinions` rebuilds them from there.
+    pub fn checkpoint(&mut self) -> serde_json::Value {
+        let opinions = std::mem::take(&mut self.opinions);
+        let skip = opinions.len().saturating_sub(self.context_window(0));
+        let mut value = self.to_shared_state();
+        self.opinions = opinions;
+        value["opinions"] =
+            serde_json::to_value(&self.opinions[skip..]).unwrap_or_else(|_| serde_json::json!([]));
+        value["pruned_opinions"] =
+            serde_json::json!(self.pruned_opinions.saturating_add(skip as u32));
+        value
+    }
+
     pub fn start_new_round(&mut self) {
         self.round += 1;
+        self.converged = None;
+        self.expected_opinions = 0;
+        self.round_opinions = 0;
+        self.last_opinion_at = Some(Instant::now());
     }
 
     pub fn add_opinion(&mut self, opinion: Opinion) {
-        self.tokens_used = self
-            .tokens_used
-            .saturating_add(opinion.input_tokens.saturating_add(opinion.output_tokens));
+        let now = Instant::now();
+        if let Some(started) = self.last_opinion_at.replace(now) {
+            let elapsed = now.duration_since(started).as_millis() as u64;
+            self.opinion_latency_ms = self.opinion_latency_ms.saturating_add(elapsed);
+            self.timed_opinions = self.timed_opinions.saturating_add(1);
+        }
+        self.round_opinions = self.round_opinions.saturating_add(1);
+        self.tokens_used = self.tokens_used.saturating_add(
+            opinion
+                .input_tokens
+                .saturating_add(opinion.output_tokens)
+                .saturating_add(opinion.cache_read_tokens)
+                .saturating_add(opinion.cache_write_tokens),
+        );
+        self.cost += self
+            .pricing
+            .cost(opinion.input_tokens, opinion.output_tokens)
+            + self
+                .pricing
+                .cache_cost(opinion.cache_read_tokens, opinion.cache_write_tokens);
         self.agent_wants_continue
             .insert(opinion.agent_id.clone(), opinion.wants_to_continue);
         self.opinions.push(opinion);
+        self.checkpoints.record_opinion();
+    }
+
+    /// How many prior opinions to show a team of `peers` agents: the
+    /// configured limit, widened so every peer's latest opinion fits.
+    pub fn context_window(&self, peers: usize) -> usize {
+        self.context_limit
+            .unwrap_or(DEFAULT_CONTEXT_LIMIT)
+            .max(peers)
+    }
+
+    /// The configured window of prior opinions, most recent last.
+    pub fn context_opinions_json(&self, peers: usize) -> Vec<serde_json::Value> {
+        self.recent_opinions_json(self.context_window(peers))
     }
 
     pub fn recent_opinions_json(&self, limit: usize) -> Vec<serde_json::Value> {
@@ -88,6 +252,191 @@ impl OrchestrationState {
             .map(|op| serde_json::json!({"agent_name": op.agent_name.clone(), "content": op.content.clone(), "agent_id": op.agent_id.clone()}))
             .collect()
     }
+
+    /// Estimated time until the round's remaining opinions are in, from the
+    /// average latency so far. `None` until at least one opinion was timed.
+    pub fn eta_ms(&self) -> Option<u64> {
+        if self.timed_opinions == 0 {
+            return None;
+        }
+        let average = self.opinion_latency_ms / u64::from(self.timed_opinions);
+        let remaining = self.expected_opinions.saturating_sub(self.round_opinions);
+        Some(average.saturating_mul(u64::from(remaining)))
+    }
+
+    /// Payload for a `progress` event.
+    pub fn progress(&self) -> serde_json::Value {
+        serde_json::json!({
+            "round": self.round,
+            "completed": self.round_opinions,
+            "expected": self.expected_opinions,
+            "eta_ms": self.eta_ms()
+        })
+    }
+
+    /// Snapshot taken when a phase starts, for `phase_complete`.
+    pub fn phase_start(&self) -> PhaseStart {
+        PhaseStart {
+            opinions: self.opinions.len(),
+            tokens_used: self.tokens_used,
+            cost: self.cost,
+        }
+    }
+
+    /// Payload for a `round_complete` event: what was added since `start`.
+    pub fn phase_complete(&self, start: &PhaseStart, phase: &str) -> serde_json::Value {
+        serde_json::json!({
+            "round": self.round,
+            "phase": phase,
+            "opinion_count": self.opinions.len().saturating_sub(start.opinions),
+            "tokens": self.tokens_used.saturating_sub(start.tokens_used),
+            "cost": (self.cost - start.cost).max(0.0)
+        })
+    }
+
+    /// Drop all but the last `keep` opinions, returning how many were removed.
+    pub fn prune_opinions(&mut self, keep: usize) -> usize {
+        let drop = self.opinions.len().saturating_sub(keep);
+        if drop > 0 {
+            self.opinions.drain(..drop);
+            self.pruned_opinions = self.pruned_opinions.saturating_add(drop as u32);
+        }
+        drop
+    }
+
+    /// The complete opinion history. When nothing has been pruned this is just
+    /// `opinions`; otherwise it is rebuilt from the execution's agent messages.
+    pub fn full_opinions(&self, messages: &[ExecutionMessage]) -> Vec<Opinion> {
+        if self.pruned_opinions == 0 {
+            return self.opinions.clone();
+        }
+        messages.iter().filter_map(Opinion::from_message).collect()
+    }
+
+    /// A readable stand-in for `final_output` when a run is stopped early:
+    /// the summary so far (if any) and each agent's latest opinion, in the
+    /// order agents first spoke. `None` when nobody has spoken yet.
+    pub fn partial_output(&self, opinions: &[Opinion]) -> Option<String> {
+        self.synthesis("讨论已停止（部分结果）", "各专家最新观点", opinions)
+    }
+
+    /// Markdown `final_output` for a completed round, laid out like
+    /// `partial_output` with each agent's final opinion.
+    pub fn final_output(&self, opinions: &[Opinion]) -> Option<String> {
+        self.synthesis("讨论结果", "各专家最终观点", opinions)
+    }
+
+    fn synthesis(&self, heading: &str, list_heading: &str, opinions: &[Opinion]) -> Option<String> {
+        let mut latest: Vec<&Opinion> = Vec::new();
+        let mut critique = None;
+        for op in opinions {
+            if op.phase == CRITIQUE_PHASE {
+                critique = Some(op);
+                continue;
+            }
+            match latest.iter_mut().find(|o| o.agent_id == op.agent_id) {
+                Some(slot) => *slot = op,
+                None => latest.push(op),
+            }
+        }
+        if latest.is_empty() {
+            return None;
+        }
+
+        let mut parts = vec![format!("## {heading}\n主题：{}", self.topic.trim())];
+        if !self.summary.trim().is_empty() {
+            parts.push(format!("### 摘要\n{}", self.summary.trim()));
+        }
+        let groups = match self.dedupe_threshold {
+            Some(threshold) => group_similar(&latest, threshold),
+            None => (0..latest.len())
+                .map(|idx| SimilarGroup {
+                    kept: idx,
+                    members: vec![idx],
+                })
+                .collect(),
+        };
+        let lines = groups
+            .iter()
+            .map(|group| {
+                let op = latest[group.kept];
+                let note = if group.is_duplicate() {
+                    format!("（{}）", group.note(&latest))
+                } else {
+                    String::new()
+                };
+                format!(
+                    "- **{}**（第{}轮）{note}: {}",
+                    op.agent_name,
+                    op.round,
+                    op.content.trim()
+                )
+            })
+            .collect::<Vec<_>>();
+        parts.push(format!("### {list_heading}\n{}", lines.join("\n")));
+        if let Some(critique) = critique {
+            parts.push(format!(
+                "### 评审意见（{}）\n{}",
+                critique.agent_name,
+                critique.content.trim()
+            ));
+        }
+        Some(parts.join("\n\n"))
+    }
+
+    /// Machine-readable view of the discussion for `ExecutionRecord.structured_output`,
+    /// listing the given opinions alongside the topic, round count and summary.
+    pub fn structured_output(&self, opinions: &[Opinion]) -> serde_json::Value {
+        let opinions = opinions
+            .iter()
+            .map(|op| {
+                serde_json::json!({
+                    "agent_id": op.agent_id.clone(),
+                    "agent_name": op.agent_name.clone(),
+                    "phase": op.phase.clone(),
+                    "content": op.content.clone(),
+                    "tokens": op.input_tokens.saturating_add(op.output_tokens)
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "topic": self.topic.clone(),
+            "rounds": self.round,
+            "opinions": opinions,
+            "summary": self.summary.clone()
+        })
+    }
+}
+
+/// Counters at the start of a phase; see `OrchestrationState::phase_start`.
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseStart {
+    opinions: usize,
+    tokens_used: u32,
+    cost: f64,
+}
+
+impl Opinion {
+    /// Rebuild an opinion from a persisted agent message.
+    pub fn from_message(message: &ExecutionMessage) -> Option<Self> {
+        if message.sender_type != "agent" {
+            return None;
+        }
+        let (cache_read_tokens, cache_write_tokens) = cached_tokens(&message.metadata);
+        Some(Self {
+            agent_id: message.sender_id.clone()?,
+            agent_name: message.sender_name.clone().unwrap_or_default(),
+            content: message.content.clone(),
+            round: message.round,
+            phase: message.phase.clone(),
+            wants_to_continue: message.wants_to_continue,
+            responding_to: message.target_agent_id.clone(),
+            input_tokens: message.input_tokens,
+            output_tokens: message.output_tokens,
+            cache_read_tokens,
+            cache_write_tokens,
+        })
+    }
 }
 
 fn default_true() -> bool {
@@ -102,6 +451,27 @@ fn default_cost_budget() -> f64 {
     10.0
 }
 
+/// The opinions in `value` that still parse, so one malformed entry doesn't
+/// cost the whole history.
+fn salvage_opinions(value: &serde_json::Value) -> serde_json::Value {
+    let Some(items) = value.as_array() else {
+        return value.clone();
+    };
+    let kept = items
+        .iter()
+        .filter(|item| serde_json::from_value::<Opinion>((*item).clone()).is_ok())
+        .cloned()
+        .collect::<Vec<_>>();
+    if kept.len() < items.len() {
+        eprintln!(
+            "shared_state: dropped {} of {} opinions that no longer parse",
+            items.len() - kept.len(),
+            items.len()
+        );
+    }
+    serde_json::Value::Array(kept)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -117,6 +487,8 @@ mod tests {
             responding_to: None,
             input_tokens: input,
             output_tokens: output,
+            cache_read_tokens: 0,
+            cache_write_tokens: 0,
         }
     }
 
@@ -132,6 +504,25 @@ mod tests {
         assert_eq!(state.agent_wants_continue.get("a2"), Some(&false));
     }
 
+    #[test]
+    fn add_opinion_bills_prompt_cache_tokens() {
+        let mut state = OrchestrationState {
+            pricing: Pricing {
+                input_per_1k: 1.0,
+                output_per_1k: 2.0,
+                cache_read_per_1k: 0.1,
+                cache_write_per_1k: 1.25,
+            },
+            ..Default::default()
+        };
+        let mut cached = opinion("a1", "Alice", 0, 0, true);
+        (cached.cache_read_tokens, cached.cache_write_tokens) = (1_000, 1_000);
+        state.add_opinion(cached);
+
+        assert_eq!(state.tokens_used, 2_000);
+        assert!((state.cost - 1.35).abs() < 1e-9);
+    }
+
     #[test]
     fn add_opinion_latest_continuation_wins_for_same_agent() {
         let mut state = OrchestrationState::default();
@@ -168,12 +559,200 @@ mod tests {
         assert_eq!(state.recent_opinions_json(10).len(), 1);
     }
 
+    #[test]
+    fn structured_output_mirrors_opinions() {
+        let mut state = OrchestrationState {
+            topic: "Pick a database".to_string(),
+            round: 2,
+            summary: "Use SQLite".to_string(),
+            ..Default::default()
+        };
+        state.add_opinion(opinion("a1", "Alice", 10, 5, true));
+        state.add_opinion(opinion("a2", "Bob", 3, 4, false));
+
+        let out = state.structured_output(&state.opinions);
+        assert_eq!(out["topic"], "Pick a database");
+        assert_eq!(out["rounds"], 2);
+        assert_eq!(out["summary"], "Use SQLite");
+
+        let opinions = out["opinions"].as_array().unwrap();
+        assert_eq!(opinions.len(), state.opinions.len());
+        for (json, op) in opinions.iter().zip(state.opinions.iter()) {
+            assert_eq!(json["agent_id"], op.agent_id.as_str());
+            assert_eq!(json["agent_name"], op.agent_name.as_str());
+            assert_eq!(json["phase"], op.phase.as_str());
+            assert_eq!(json["content"], op.content.as_str());
+            assert_eq!(json["tokens"], op.input_tokens + op.output_tokens);
+        }
+    }
+
     #[test]
     fn start_new_round_increments_round() {
         let mut state = OrchestrationState::default();
         assert_eq!(state.round, 0);
         state.start_new_round();
+        state.converged = Some(0.95);
         state.start_new_round();
         assert_eq!(state.round, 2);
+        assert_eq!(state.converged, None);
+    }
+
+    fn message_for(op: &Opinion, sequence: i32) -> ExecutionMessage {
+        let now = chrono::Utc::now();
+        ExecutionMessage {
+            id: format!("m{sequence}"),
+            sequence,
+            round: op.round,
+            phase: op.phase.clone(),
+            sender_type: "agent".to_string(),
+            sender_id: Some(op.agent_id.clone()),
+            sender_name: Some(op.agent_name.clone()),
+            content: op.content.clone(),
+            content_type: "text".to_string(),
+            responding_to: None,
+            target_agent_id: None,
+            wants_to_continue: op.wants_to_continue,
+            input_tokens: op.input_tokens,
+            output_tokens: op.output_tokens,
+            tokens_estimated: false,
+            metadata: serde_json::json!({}),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn pruning_bounds_opinions_while_messages_keep_history() {
+        let mut state = OrchestrationState::default();
+        let mut messages = Vec::new();
+        for i in 0..12 {
+            let op = opinion(&format!("a{i}"), &format!("Agent{i}"), 1, 1, true);
+            messages.push(message_for(&op, i));
+            state.add_opinion(op);
+            state.prune_opinions(5);
+            assert!(state.opinions.len() <= 5);
+        }
+
+        assert_eq!(state.opinions.len(), 5);
+        assert_eq!(state.pruned_opinions, 7);
+        assert_eq!(state.opinions[0].agent_name, "Agent7");
+        assert_eq!(state.tokens_used, 24);
+        assert_eq!(state.recent_opinions_json(2)[1]["agent_name"], "Agent11");
+
+        let full = state.full_opinions(&messages);
+        assert_eq!(full.len(), 12);
+        assert_eq!(full[0].agent_name, "Agent0");
+        assert_eq!(
+            state.structured_output(&full)["opinions"]
+                .as_array()
+                .unwrap()
+                .len(),
+            12
+        );
+    }
+
+    #[test]
+    fn full_opinions_skips_messages_when_nothing_pruned() {
+        let mut state = OrchestrationState::default();
+        state.add_opinion(opinion("a1", "Alice", 0, 0, true));
+        assert_eq!(state.prune_opinions(5), 0);
+
+        let user = ExecutionMessage {
+            sender_type: "user".to_string(),
+            ..message_for(&opinion("u", "you", 0, 0, true), 0)
+        };
+        assert_eq!(state.full_opinions(&[user]).len(), 1);
+        assert!(Opinion::from_message(&message_for(&opinion("x", "X", 0, 0, true), 1)).is_some());
+    }
+
+    #[test]
+    fn eta_is_unknown_until_an_opinion_is_timed() {
+        let mut state = OrchestrationState {
+            expected_opinions: 3,
+            ..Default::default()
+        };
+        assert_eq!(state.eta_ms(), None);
+        // Without a round start there is nothing to time the first opinion against.
+        state.add_opinion(opinion("a1", "Alice", 0, 0, true));
+        assert_eq!(state.eta_ms(), None);
+        assert_eq!(state.progress()["eta_ms"], serde_json::Value::Null);
+        assert_eq!(state.progress()["completed"], 1);
+    }
+
+    #[test]
+    fn eta_decreases_as_opinions_complete() {
+        let mut state = OrchestrationState::default();
+        state.start_new_round();
+        state.expected_opinions = 4;
+
+        let mut etas = Vec::new();
+        for i in 0..4 {
+            state.last_opinion_at = Some(Instant::now() - std::time::Duration::from_millis(500));
+            state.add_opinion(opinion(&format!("a{i}"), "Agent", 0, 0, true));
+            etas.push(state.eta_ms().unwrap());
+        }
+
+        assert!(etas.windows(2).all(|w| w[1] < w[0]), "{etas:?}");
+        assert!(etas[0] >= 1500);
+        assert_eq!(etas[3], 0);
+    }
+
+    #[test]
+    fn phase_complete_counts_only_the_phase() {
+        let mut state = OrchestrationState {
+            round: 2,
+            pricing: Pricing {
+                input_per_1k: 1.0,
+                output_per_1k: 2.0,
+                ..Pricing::default()
+            },
+            ..Default::default()
+        };
+        state.add_opinion(opinion("a0", "Earlier", 500, 500, true));
+
+        let start = state.phase_start();
+        state.add_opinion(opinion("a1", "Alice", 1000, 0, true));
+        state.add_opinion(opinion("a2", "Bob", 0, 1000, true));
+        let done = state.phase_complete(&start, "response");
+
+        assert_eq!(done["round"], 2);
+        assert_eq!(done["phase"], "response");
+        assert_eq!(done["opinion_count"], 2);
+        assert_eq!(done["tokens"], 2000);
+        assert!((done["cost"].as_f64().unwrap() - 3.0).abs() < 1e-9);
+        assert!((state.cost - 4.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn older_shared_state_is_migrated_instead_of_reset() {
+        let legacy = serde_json::json!({
+            "topic": "Pick a database",
+            "round": 3,
+            "phase": "discussing",
+            "tokens_used": 1200,
+            "opinions": [
+                { "agent_id": "a1", "agent_name": "Alice", "content": "Postgres", "round": 1, "phase": "initial" },
+                { "agent_id": "a2", "agent_name": "Bob", "content": "SQLite" },
+                { "agent_id": "a2", "agent_name": "Bob", "content": "Fine, Postgres", "round": 2, "phase": "response" }
+            ]
+        });
+        assert!(serde_json::from_value::<OrchestrationState>(legacy.clone()).is_err());
+
+        let state = OrchestrationState::from_shared_state(&legacy);
+        assert_eq!(state.topic, "Pick a database");
+        assert_eq!(state.round, 3);
+        assert_eq!(state.tokens_used, 1200);
+        assert!(matches!(state.phase, OrchestrationPhase::Initializing));
+        let contents = state
+            .opinions
+            .iter()
+            .map(|o| o.content.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, ["Postgres", "Fine, Postgres"]);
+        assert_eq!(state.version, 0);
+
+        let saved = state.to_shared_state();
+        assert_eq!(saved["version"], STATE_VERSION);
+        assert_eq!(OrchestrationState::from_shared_state(&saved).round, 3);
     }
 }
@@ -0,0 +1,131 @@
+use crate::error::AppError;
+
+/// Resolve the status an execution moves to when `action` is applied while it
+/// is in status `from`. Every status change in the app goes through here so
+/// that user controls and the orchestration loop agree on the lifecycle:
+///
+/// - `pending`   → `running` (start), `paused` (await_input)
+/// - `running`   ↔ `paused` (pause / resume)
+/// - `running` / `paused` → `completed` (stop, complete)
+/// - `paused` / `completed` → `running` (followup)
+/// - any non-terminal status → `failed` (fail)
+///
+/// `extend_budget` keeps the current status but is rejected for `failed`
+/// executions.
+pub fn can_transition(from: &str, action: &str) -> Result<&'static str, AppError> {
+    let next = match (from, action) {
+        ("pending", "start") => Some("running"),
+        ("pending", "await_input") => Some("paused"),
+        ("running", "pause") => Some("paused"),
+        ("paused", "resume") => Some("running"),
+        ("running" | "paused", "stop" | "complete") => Some("completed"),
+        ("paused" | "completed", "followup") => Some("running"),
+        ("pending" | "running" | "paused", "fail") => Some("failed"),
+        ("pending", "extend_budget") => Some("pending"),
+        ("running", "extend_budget") => Some("running"),
+        ("paused", "extend_budget") => Some("paused"),
+        ("completed", "extend_budget") => Some("completed"),
+        _ => None,
+    };
+    next.ok_or_else(|| {
+        AppError::Message(format!(
+            "Invalid action '{action}' for execution in status '{from}'"
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUSES: [&str; 5] = ["pending", "running", "paused", "completed", "failed"];
+    const ACTIONS: [&str; 9] = [
+        "start",
+        "await_input",
+        "pause",
+        "resume",
+        "stop",
+        "complete",
+        "followup",
+        "fail",
+        "extend_budget",
+    ];
+
+    #[test]
+    fn allowed_transitions() {
+        let allowed = [
+            ("pending", "start", "running"),
+            ("pending", "await_input", "paused"),
+            ("pending", "fail", "failed"),
+            ("pending", "extend_budget", "pending"),
+            ("running", "pause", "paused"),
+            ("running", "stop", "completed"),
+            ("running", "complete", "completed"),
+            ("running", "fail", "failed"),
+            ("running", "extend_budget", "running"),
+            ("paused", "resume", "running"),
+            ("paused", "stop", "completed"),
+            ("paused", "complete", "completed"),
+            ("paused", "followup", "running"),
+            ("paused", "fail", "failed"),
+            ("paused", "extend_budget", "paused"),
+            ("completed", "followup", "running"),
+            ("completed", "extend_budget", "completed"),
+        ];
+        for (from, action, to) in allowed {
+            assert_eq!(
+                can_transition(from, action).unwrap(),
+                to,
+                "{from} --{action}-->"
+            );
+        }
+    }
+
+    #[test]
+    fn forbidden_transitions() {
+        let forbidden = [
+            ("pending", "pause"),
+            ("pending", "resume"),
+            ("pending", "stop"),
+            ("pending", "followup"),
+            ("running", "start"),
+            ("running", "resume"),
+            ("running", "followup"),
+            ("paused", "pause"),
+            ("paused", "start"),
+            ("completed", "start"),
+            ("completed", "pause"),
+            ("completed", "resume"),
+            ("completed", "stop"),
+            ("completed", "fail"),
+            ("failed", "extend_budget"),
+            ("failed", "followup"),
+            ("failed", "resume"),
+            ("failed", "fail"),
+            ("running", "bogus"),
+        ];
+        for (from, action) in forbidden {
+            assert!(
+                can_transition(from, action).is_err(),
+                "{from} --{action}--> should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn failed_is_terminal() {
+        for action in ACTIONS {
+            assert!(can_transition("failed", action).is_err());
+        }
+    }
+
+    #[test]
+    fn unknown_status_rejects_everything() {
+        for action in ACTIONS {
+            assert!(can_transition("archived", action).is_err());
+        }
+        for status in STATUSES {
+            assert!(can_transition(status, "").is_err());
+        }
+    }
+}
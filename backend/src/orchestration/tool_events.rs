@@ -45,6 +45,7 @@ pub fn emit_tool_traces(
                 "tool_name": t.result.name,
                 "tool_call_id": t.result.tool_call_id,
                 "ok": ok,
+                "denied": t.result.denied,
                 "output": t.result.output,
                 "error": t.result.error,
                 "duration_ms": t.result.duration_ms,
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::task::AbortHandle;
+
+use crate::error::AppError;
+use crate::orchestration::status::can_transition;
+use crate::store::sqlite::SqliteStore;
+
+/// How often the watchdog looks for lost executions.
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(60);
+/// A `running` execution without a task must also have been quiet this long
+/// before it is failed, so one that is just being claimed isn't reaped.
+pub const LOST_TASK_IDLE: Duration = Duration::from_secs(10 * 60);
+
+pub const TASK_LOST_ERROR: &str =
+    "Execution task lost: it stopped without finishing (crash or restart)";
+
+/// Tasks per execution, each under the token `track` handed out.
+type TaskSlots = HashMap<String, HashMap<u64, Option<AbortHandle>>>;
+
+/// Executions with a live `run_execution` task in this process. A task holds
+/// the `TaskGuard` from `track` for its whole life; the guard is dropped on
+/// return and on panic alike, so a task that dies leaves no entry behind.
+/// Each guard only removes its own slot, so a second task for the same id
+/// (a start that loses the claim race) can't unregister the first.
+/// Once spawned, a task's `AbortHandle` is attached so `cancel` can stop it.
+#[derive(Debug, Default)]
+pub struct ExecutionTasks {
+    active: Mutex<TaskSlots>,
+    next_token: AtomicU64,
+}
+
+impl ExecutionTasks {
+    pub fn track(self: &Arc<Self>, execution_id: &str) -> TaskGuard {
+        let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+        self.lock()
+            .entry(execution_id.to_string())
+            .or_default()
+            .insert(token, None);
+        TaskGuard {
+            tasks: self.clone(),
+            execution_id: execution_id.to_string(),
+            token,
+        }
+    }
+
+    /// Attach the handle of the task holding `token`; ignored if it already
+    /// finished.
+    pub fn set_abort(&self, execution_id: &str, token: u64, handle: AbortHandle) {
+        let mut active = self.lock();
+        if let Some(slot) = active
+            .get_mut(execution_id)
+            .and_then(|slots| slots.get_mut(&token))
+        {
+            *slot = Some(handle);
+        }
+    }
+
+    /// Abort the execution's tasks, if any are running; returns whether any were.
+    pub fn cancel(&self, execution_id: &str) -> bool {
+        let handles: Vec<AbortHandle> = self
+            .lock()
+            .get_mut(execution_id)
+            .map(|slots| slots.values_mut().filter_map(Option::take).collect())
+            .unwrap_or_default();
+        handles.iter().for_each(AbortHandle::abort);
+        !handles.is_empty()
+    }
+
+    pub fn is_active(&self, execution_id: &str) -> bool {
+        self.lock().contains_key(execution_id)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TaskSlots> {
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub struct TaskGuard {
+    tasks: Arc<ExecutionTasks>,
+    execution_id: String,
+    token: u64,
+}
+
+impl TaskGuard {
+    /// Identifies this task to `ExecutionTasks::set_abort`.
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        let mut active = self.tasks.lock();
+        if let Some(slots) = active.get_mut(&self.execution_id) {
+            slots.remove(&self.token);
+            if slots.is_empty() {
+                active.remove(&self.execution_id);
+            }
+        }
+    }
+}
+
+/// Fail every `running` execution that has no task in `tasks` and no
+/// activity (record or message) within `idle` of `now`. Returns their ids.
+pub fn reap_lost_executions(
+    store: &SqliteStore,
+    tasks: &ExecutionTasks,
+    now: DateTime<Utc>,
+    idle: Duration,
+) -> Result<Vec<String>, AppError> {
+    let idle = chrono::Duration::from_std(idle).unwrap_or(chrono::Duration::MAX);
+    let mut reaped = Vec::new();
+    for execution in store.executions_list()? {
+        if execution.status != "running" || tasks.is_active(&execution.id) {
+            continue;
+        }
+        let last_message = store
+            .execution_messages_list(&execution.id)?
+            .iter()
+            .map(|m| m.created_at)
+            .max();
+        let last_activity =
+            last_message.map_or(execution.updated_at, |at| at.max(execution.updated_at));
+        if now - last_activity < idle {
+            continue;
+        }
+        // Re-checked under the write lock: a follow-up may have just claimed it.
+        let failed = store.executions_update(&execution.id, |execution| {
+            if execution.status != "running" || tasks.is_active(&execution.id) {
+                return Ok(());
+            }
+            execution.status = can_transition(&execution.status, "fail")?.to_string();
+            execution.error_message = Some(TASK_LOST_ERROR.to_string());
+            execution.current_stage = None;
+            execution.eta_ms = None;
+            execution.updated_at = now;
+            Ok(())
+        })?;
+        if failed.status == "failed" {
+            reaped.push(failed.id);
+        }
+    }
+    Ok(reaped)
+}
+
+/// Run `reap_lost_executions` every `WATCHDOG_INTERVAL` for the life of the app.
+pub fn spawn_watchdog(store: Arc<SqliteStore>, tasks: Arc<ExecutionTasks>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(WATCHDOG_INTERVAL).await;
+            match reap_lost_executions(&store, &tasks, Utc::now(), LOST_TASK_IDLE) {
+                Ok(reaped) if !reaped.is_empty() => {
+                    eprintln!("watchdog: failed lost executions {reaped:?}");
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("watchdog: {e}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::execution::ExecutionRecord;
+
+    fn running(id: &str, updated_at: DateTime<Utc>) -> ExecutionRecord {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "user_id": "local",
+            "team_id": "t1",
+            "title": null,
+            "initial_input": "topic",
+            "status": "running",
+            "current_stage": "stage_1",
+            "current_round": 1,
+            "final_output": null,
+            "structured_output": null,
+            "tokens_used": 0,
+            "tokens_budget": 1000,
+            "cost": 0.0,
+            "cost_budget": 1.0,
+            "started_at": updated_at,
+            "completed_at": null,
+            "error_message": null,
+            "retry_count": 0,
+            "created_at": updated_at,
+            "updated_at": updated_at
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn lost_tasks_are_failed_once_idle() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let tasks = Arc::new(ExecutionTasks::default());
+        let now = Utc::now();
+        let long_ago = now - chrono::Duration::hours(1);
+        for id in ["lost", "alive", "fresh"] {
+            store.executions_upsert(&running(id, long_ago)).unwrap();
+        }
+        store.executions_upsert(&running("fresh", now)).unwrap();
+
+        // A task that panics still drops its guard while unwinding.
+        let lost = tasks.clone();
+        std::thread::spawn(move || {
+            let _guard = lost.track("lost");
+            panic!("unwrap on None");
+        })
+        .join()
+        .unwrap_err();
+        let _alive = tasks.track("alive");
+        assert!(!tasks.is_active("lost"));
+
+        let reaped = reap_lost_executions(&store, &tasks, now, LOST_TASK_IDLE).unwrap();
+        assert_eq!(reaped, ["lost"]);
+        let lost = store.executions_get("lost").unwrap().unwrap();
+        assert_eq!(lost.status, "failed");
+        assert_eq!(lost.error_message.as_deref(), Some(TASK_LOST_ERROR));
+        assert_eq!(lost.current_stage, None);
+        for id in ["alive", "fresh"] {
+            assert_eq!(store.executions_get(id).unwrap().unwrap().status, "running");
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_aborts_a_tracked_task() {
+        let tasks = Arc::new(ExecutionTasks::default());
+        let guard = tasks.track("e1");
+        let token = guard.token();
+        let handle = tokio::spawn(async move {
+            let _guard = guard;
+            std::future::pending::<()>().await;
+        });
+        tasks.set_abort("e1", token, handle.abort_handle());
+
+        assert!(tasks.cancel("e1"));
+        assert!(handle.await.unwrap_err().is_cancelled());
+        assert!(!tasks.is_active("e1"));
+        assert!(!tasks.cancel("e1"));
+    }
+
+    #[tokio::test]
+    async fn a_second_guard_for_the_same_execution_leaves_the_first_alone() {
+        let tasks = Arc::new(ExecutionTasks::default());
+        let first = tasks.track("e1");
+        let token = first.token();
+        let handle = tokio::spawn(async move {
+            let _guard = first;
+            std::future::pending::<()>().await;
+        });
+        tasks.set_abort("e1", token, handle.abort_handle());
+
+        // A start that lost the claim race tracks the same id, then gives up.
+        let second = tasks.track("e1");
+        assert_ne!(second.token(), token);
+        drop(second);
+
+        assert!(tasks.is_active("e1"));
+        assert!(tasks.cancel("e1"));
+        assert!(handle.await.unwrap_err().is_cancelled());
+        assert!(!tasks.is_active("e1"));
+    }
+}
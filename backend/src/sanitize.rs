@@ -0,0 +1,357 @@
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+
+use crate::error::AppError;
+
+const WORKSPACE_PLACEHOLDER: &str = "<workspace>";
+const REDACTED: &str = "<redacted>";
+
+/// Patterns for secrets that commonly end up pasted into prompts or tool output.
+const SECRET_PATTERNS: &[&str] = &[
+    r"sk-[A-Za-z0-9_\-]{16,}",
+    r"AKIA[0-9A-Z]{16}",
+    r"gh[pousr]_[A-Za-z0-9]{20,}",
+    r"xox[abprs]-[A-Za-z0-9\-]{10,}",
+    r"(?i)bearer\s+[A-Za-z0-9._\-]{16,}",
+];
+
+/// `key=value` / `key: value` assignments whose value should be hidden.
+const SECRET_ASSIGNMENT: &str =
+    r#"(?i)((?:api[_-]?key|secret|token|password|passwd)["']?\s*[:=]\s*["']?)[^\s"',;]{8,}"#;
+
+/// Strips workspace paths and secrets from exported transcripts.
+pub struct Sanitizer {
+    workspace_roots: Vec<String>,
+    secrets: Vec<Regex>,
+    assignment: Regex,
+}
+
+impl Sanitizer {
+    pub fn new(workspace_root: Option<&str>) -> Self {
+        let mut workspace_roots = Vec::new();
+        if let Some(root) = workspace_root
+            .map(|s| s.trim().trim_end_matches(['/', '\\']))
+            .filter(|s| !s.is_empty())
+        {
+            workspace_roots.push(root.to_string());
+            if let Ok(canonical) = std::path::Path::new(root).canonicalize() {
+                let canonical = canonical.to_string_lossy().to_string();
+                if !workspace_roots.contains(&canonical) {
+                    workspace_roots.push(canonical);
+                }
+            }
+        }
+        // Replace the longest prefix first so a canonical path is not half-replaced.
+        workspace_roots.sort_by_key(|r| std::cmp::Reverse(r.len()));
+
+        Self {
+            workspace_roots,
+            secrets: SECRET_PATTERNS
+                .iter()
+                .map(|p| Regex::new(p).expect("valid secret pattern"))
+                .collect(),
+            assignment: Regex::new(SECRET_ASSIGNMENT).expect("valid assignment pattern"),
+        }
+    }
+
+    pub fn sanitize_text(&self, text: &str) -> String {
+        let mut out = text.to_string();
+        for root in &self.workspace_roots {
+            out = replace_path(&out, root, WORKSPACE_PLACEHOLDER);
+        }
+        out = self
+            .assignment
+            .replace_all(&out, format!("${{1}}{REDACTED}").as_str())
+            .to_string();
+        for rx in &self.secrets {
+            out = rx.replace_all(&out, REDACTED).to_string();
+        }
+        out
+    }
+
+    /// Recursively sanitize every string in `value`. Credential fields
+    /// (`api_key`, `extra_headers` values) are redacted wholesale.
+    pub fn sanitize_value(&self, value: &mut Value) {
+        match value {
+            Value::String(s) => *s = self.sanitize_text(s),
+            Value::Array(items) => {
+                for item in items {
+                    self.sanitize_value(item);
+                }
+            }
+            Value::Object(map) => {
+                for (key, v) in map.iter_mut() {
+                    if !redact_credential(key, v) {
+                        self.sanitize_value(v);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Replace `root` wherever it stands as a whole path, not where it is part
+/// of a longer name such as `/work/app2`, `/work/app.bak` or `/x/work/app`.
+fn replace_path(text: &str, root: &str, with: &str) -> String {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (start, _) in text.match_indices(root) {
+        let end = start + root.len();
+        let joined_before = text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|c| is_name_char(c) || c == '.');
+        let mut after = text[end..].chars();
+        let joined_after = match (after.next(), after.next()) {
+            (Some('.'), Some(c)) => is_name_char(c),
+            (Some(c), _) => is_name_char(c),
+            (None, _) => false,
+        };
+        if !joined_before && !joined_after {
+            out.push_str(&text[copied..start]);
+            out.push_str(with);
+            copied = end;
+        }
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+/// Redact credential fields anywhere in `value`, leaving all other text
+/// as is. Exports apply this whether or not they are sanitized.
+pub fn redact_credentials(value: &mut Value) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(redact_credentials),
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if !redact_credential(key, v) {
+                    redact_credentials(v);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Undo `redact_credentials` on a model config being restored, using
+/// `local`, the stored copy of the same record: each redacted value takes the
+/// local one, or is dropped (an empty `api_key`) when there is none.
+pub fn restore_credentials(value: &mut Value, local: Option<&Value>) {
+    let Some(map) = value.as_object_mut() else {
+        return;
+    };
+    if map.get("api_key").and_then(Value::as_str) == Some(REDACTED) {
+        let key = local
+            .and_then(|l| l.get("api_key"))
+            .cloned()
+            .unwrap_or_else(|| Value::String(String::new()));
+        map.insert("api_key".to_string(), key);
+    }
+    if let Some(Value::Object(headers)) = map.get_mut("extra_headers") {
+        let local_headers = local
+            .and_then(|l| l.get("extra_headers"))
+            .and_then(Value::as_object);
+        headers.retain(|name, v| {
+            if v.as_str() != Some(REDACTED) {
+                return true;
+            }
+            match local_headers.and_then(|h| h.get(name)) {
+                Some(local) => {
+                    *v = local.clone();
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+}
+
+/// Redact `v` when `key` names a credential; returns whether it did.
+/// `extra_headers` often carries custom auth tokens, so all of its values
+/// are hidden while the header names stay visible.
+fn redact_credential(key: &str, v: &mut Value) -> bool {
+    match (key, v) {
+        ("api_key", v @ Value::String(_)) => {
+            *v = Value::String(REDACTED.to_string());
+            true
+        }
+        ("extra_headers", Value::Object(headers)) => {
+            for value in headers.values_mut() {
+                *value = Value::String(REDACTED.to_string());
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Longest pattern `Redaction::new` accepts.
+const MAX_REDACTION_PATTERN_CHARS: usize = 1_000;
+/// Compiled-size cap for redaction patterns; the `regex` crate never
+/// backtracks, so this bounds the remaining cost of a pathological pattern.
+const REDACTION_SIZE_LIMIT: usize = 1 << 20;
+
+/// A user-supplied regex replacement applied to stored history. `$1`-style
+/// group references in the replacement are expanded.
+pub struct Redaction {
+    rx: Regex,
+    replacement: String,
+}
+
+impl Redaction {
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self, AppError> {
+        if pattern.chars().count() > MAX_REDACTION_PATTERN_CHARS {
+            return Err(AppError::Message(format!(
+                "Redaction pattern is longer than {MAX_REDACTION_PATTERN_CHARS} characters"
+            )));
+        }
+        let rx = RegexBuilder::new(pattern)
+            .size_limit(REDACTION_SIZE_LIMIT)
+            .dfa_size_limit(REDACTION_SIZE_LIMIT)
+            .build()
+            .map_err(|e| AppError::Message(format!("Invalid redaction pattern: {e}")))?;
+        // A pattern that matches nothing would insert the replacement everywhere.
+        if rx.is_match("") {
+            return Err(AppError::Message(
+                "Redaction pattern must not match an empty string".to_string(),
+            ));
+        }
+        Ok(Self {
+            rx,
+            replacement: replacement.to_string(),
+        })
+    }
+
+    /// The redacted text, or `None` when nothing matched.
+    pub fn redact_text(&self, text: &str) -> Option<String> {
+        self.rx.is_match(text).then(|| {
+            self.rx
+                .replace_all(text, self.replacement.as_str())
+                .into_owned()
+        })
+    }
+
+    /// Redact every string in `value`; returns whether anything changed.
+    pub fn redact_value(&self, value: &mut Value) -> bool {
+        match value {
+            Value::String(s) => match self.redact_text(s) {
+                Some(out) => {
+                    *s = out;
+                    true
+                }
+                None => false,
+            },
+            Value::Array(items) => {
+                let mut changed = false;
+                for item in items {
+                    changed |= self.redact_value(item);
+                }
+                changed
+            }
+            Value::Object(map) => {
+                let mut changed = false;
+                for v in map.values_mut() {
+                    changed |= self.redact_value(v);
+                }
+                changed
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_workspace_root_prefix() {
+        let s = Sanitizer::new(Some("/home/alice/project/"));
+        assert_eq!(
+            s.sanitize_text("opened /home/alice/project/src/main.rs"),
+            "opened <workspace>/src/main.rs"
+        );
+    }
+
+    #[test]
+    fn workspace_root_is_only_replaced_as_a_whole_path() {
+        let s = Sanitizer::new(Some("/work/app"));
+        assert_eq!(
+            s.sanitize_text("cd /work/app; cat /work/app/a.rs /work/app2/b.rs"),
+            "cd <workspace>; cat <workspace>/a.rs /work/app2/b.rs"
+        );
+        assert_eq!(
+            s.sanitize_text("\"/work/app\" and /work/app.bak, not /x/work/app."),
+            "\"<workspace>\" and /work/app.bak, not /x/work/app."
+        );
+        assert_eq!(s.sanitize_text("see /work/app."), "see <workspace>.");
+    }
+
+    #[test]
+    fn redacts_fake_keys() {
+        let s = Sanitizer::new(None);
+        let out = s.sanitize_text("use sk-abcdefghijklmnopqrstuvwx to call the API");
+        assert_eq!(out, "use <redacted> to call the API");
+
+        let out = s.sanitize_text("OPENAI_API_KEY=abcd1234efgh5678");
+        assert_eq!(out, "OPENAI_API_KEY=<redacted>");
+    }
+
+    #[test]
+    fn preserves_ordinary_content() {
+        let s = Sanitizer::new(Some("/tmp/ws"));
+        let text = "The skeleton key idea: tokens are cheap. See src/lib.rs.";
+        assert_eq!(s.sanitize_text(text), text);
+    }
+
+    #[test]
+    fn sanitizes_nested_json_and_api_key_fields() {
+        let s = Sanitizer::new(Some("/tmp/ws"));
+        let mut value = serde_json::json!({
+            "workspace_path": "/tmp/ws",
+            "llm": { "default": {
+                "api_key": "plain",
+                "model_id": "gpt-4o",
+                "extra_headers": { "X-Auth-Token": "plain-token", "X-Title": "app" }
+            } },
+            "messages": [
+                { "content": "read /tmp/ws/a.txt", "metadata": { "output": { "path": "/tmp/ws/a.txt" } } },
+                { "content": "token: sk-live-0123456789abcdefXYZ" }
+            ],
+            "round": 2
+        });
+        s.sanitize_value(&mut value);
+
+        assert_eq!(value["workspace_path"], "<workspace>");
+        assert_eq!(value["llm"]["default"]["api_key"], "<redacted>");
+        assert_eq!(value["llm"]["default"]["model_id"], "gpt-4o");
+        assert_eq!(
+            value["llm"]["default"]["extra_headers"],
+            serde_json::json!({ "X-Auth-Token": "<redacted>", "X-Title": "<redacted>" })
+        );
+        assert_eq!(value["messages"][0]["content"], "read <workspace>/a.txt");
+        assert_eq!(
+            value["messages"][0]["metadata"]["output"]["path"],
+            "<workspace>/a.txt"
+        );
+        assert_eq!(value["messages"][1]["content"], "token: <redacted>");
+        assert_eq!(value["round"], 2);
+    }
+
+    #[test]
+    fn redaction_rejects_empty_and_oversized_patterns() {
+        assert!(Redaction::new("", "x").is_err());
+        assert!(Redaction::new("a*", "x").is_err());
+        assert!(Redaction::new("(", "x").is_err());
+        assert!(Redaction::new(&"a".repeat(2_000), "x").is_err());
+        assert!(Redaction::new(r"\w{1000}{1000}", "x").is_err());
+
+        let r = Redaction::new(r"key-(\d+)", "key-<$1>").unwrap();
+        assert_eq!(r.redact_text("no match"), None);
+        let mut v = serde_json::json!({ "a": ["key-12"], "b": 3 });
+        assert!(r.redact_value(&mut v));
+        assert_eq!(v["a"][0], "key-<12>");
+    }
+}
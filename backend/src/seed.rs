@@ -1,5 +1,7 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use serde_json::Value;
 use std::collections::HashSet;
 
 use crate::error::AppError;
@@ -10,12 +12,15 @@ use crate::store::sqlite::SqliteStore;
 const DEFAULTS_JSON: &str = include_str!("../assets/defaults.json");
 const LOCAL_USER_ID: &str = "local";
 
-#[derive(Debug, Deserialize)]
 struct SeedDefaults {
     agents: Vec<SeedAgent>,
     teams: Vec<SeedTeam>,
 }
 
+// Entries are not `deny_unknown_fields`: defaults.json may gain a field before
+// the struct does, and a startup failure over that would be worse than losing
+// it. Unknown keys are logged instead so typos still show up, and every
+// field that isn't essential to a usable record is `#[serde(default)]`.
 #[derive(Debug, Deserialize)]
 struct SeedAgent {
     id: String,
@@ -24,6 +29,8 @@ struct SeedAgent {
     avatar: Option<String>,
     #[serde(default)]
     description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
     collaboration_style: String,
     speaking_priority: i32,
     system_prompt: String,
@@ -33,8 +40,25 @@ struct SeedAgent {
     max_tokens: Option<u32>,
     #[serde(default)]
     max_tool_iterations: Option<u32>,
+    #[serde(default = "first_seed_version")]
+    since: u32,
 }
 
+const SEED_AGENT_FIELDS: &[&str] = &[
+    "id",
+    "name",
+    "avatar",
+    "description",
+    "tags",
+    "collaboration_style",
+    "speaking_priority",
+    "system_prompt",
+    "temperature",
+    "max_tokens",
+    "max_tool_iterations",
+    "since",
+];
+
 #[derive(Debug, Deserialize)]
 struct SeedTeam {
     id: String,
@@ -45,15 +69,148 @@ struct SeedTeam {
     icon: Option<String>,
     collaboration_mode: String,
     members: Vec<String>,
+    #[serde(default = "first_seed_version")]
+    since: u32,
+}
+
+const SEED_TEAM_FIELDS: &[&str] = &[
+    "id",
+    "name",
+    "description",
+    "icon",
+    "collaboration_mode",
+    "members",
+    "since",
+];
+
+fn first_seed_version() -> u32 {
+    1
+}
+
+/// Bump when `defaults.json` gains agents or teams that existing installs
+/// should receive on their next startup, and give those entries a `since` of
+/// the new version (entries without one date from version 1).
+pub const SEED_VERSION: u32 = 1;
+
+/// Startup entry point: seeds an empty store, or adds the defaults shipped
+/// after the stored marker to an existing one.
+pub fn seed_on_startup(store: &SqliteStore) -> Result<(), AppError> {
+    if seed_if_empty(store)? {
+        store.seed_version_set(SEED_VERSION)?;
+        return Ok(());
+    }
+    upgrade_defaults(store, load_defaults()?, SEED_VERSION)?;
+    Ok(())
+}
+
+/// Insert the defaults introduced after the store's marker, up to `current`,
+/// whose ids aren't in the store yet, then move the marker to `current`.
+/// Defaults from earlier versions are never re-inserted, so one the user
+/// deleted stays deleted. Returns how many records were added.
+fn upgrade_defaults(
+    store: &SqliteStore,
+    defaults: SeedDefaults,
+    current: u32,
+) -> Result<usize, AppError> {
+    let stored = store.seed_version_get()?;
+    // Stores from before the marker existed were seeded with version 1.
+    let marker = stored.max(1);
+    if stored >= current {
+        return Ok(0);
+    }
+    let newer = |since: u32| since > marker && since <= current;
+    let defaults = SeedDefaults {
+        agents: defaults
+            .agents
+            .into_iter()
+            .filter(|a| newer(a.since))
+            .collect(),
+        teams: defaults
+            .teams
+            .into_iter()
+            .filter(|t| newer(t.since))
+            .collect(),
+    };
+    let written = write_defaults(store, defaults, false)?;
+    store.seed_version_set(current)?;
+    Ok(written)
 }
 
 pub fn seed_if_empty(store: &SqliteStore) -> Result<bool, AppError> {
     if !store.is_empty()? {
         return Ok(false);
     }
+    seed_upsert_defaults(store)?;
+    Ok(true)
+}
+
+/// Insert every default agent and team whose id isn't in the store yet.
+/// Existing records, including customized defaults, are left untouched.
+/// Returns how many records were added.
+pub fn seed_upsert_defaults(store: &SqliteStore) -> Result<usize, AppError> {
+    reseed_defaults(store, false)
+}
+
+/// Restore the shipped defaults: anything missing by id is re-inserted, and
+/// with `overwrite` a record sharing a default's id is reset to the shipped
+/// version (keeping its `created_at` and bumping `version`). Records with
+/// other ids are never touched. Returns how many records were written.
+pub fn reseed_defaults(store: &SqliteStore, overwrite: bool) -> Result<usize, AppError> {
+    write_defaults(store, load_defaults()?, overwrite)
+}
+
+fn write_defaults(
+    store: &SqliteStore,
+    parsed: SeedDefaults,
+    overwrite: bool,
+) -> Result<usize, AppError> {
+    let now = Utc::now();
+    let mut written = 0;
+
+    for a in parsed.agents {
+        let mut agent = a.into_agent(now);
+        if let Some(existing) = store.agents_get(&agent.id)? {
+            if !overwrite {
+                continue;
+            }
+            agent.created_at = existing.created_at;
+            agent.version = existing.version + 1;
+            agent.revision = existing.revision + 1;
+        }
+        store.agents_upsert(&agent)?;
+        written += 1;
+    }
+
+    for t in parsed.teams {
+        let mut team = t.into_team(now);
+        if let Some(existing) = store.teams_get(&team.id)? {
+            if !overwrite {
+                continue;
+            }
+            team.created_at = existing.created_at;
+            team.revision = existing.revision + 1;
+        }
+        store.teams_upsert(&team)?;
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+fn load_defaults() -> Result<SeedDefaults, AppError> {
+    parse_defaults(DEFAULTS_JSON)
+}
 
-    let parsed: SeedDefaults = serde_json::from_str(DEFAULTS_JSON)
+fn parse_defaults(json: &str) -> Result<SeedDefaults, AppError> {
+    if json.trim().is_empty() {
+        return Err(AppError::Message("defaults.json is empty".to_string()));
+    }
+    let root: Value = serde_json::from_str(json)
         .map_err(|e| AppError::Message(format!("Invalid defaults.json: {e}")))?;
+    let parsed = SeedDefaults {
+        agents: parse_section(&root, "agents", SEED_AGENT_FIELDS)?,
+        teams: parse_section(&root, "teams", SEED_TEAM_FIELDS)?,
+    };
 
     let agent_ids: HashSet<String> = parsed.agents.iter().map(|a| a.id.clone()).collect();
     if agent_ids.len() != parsed.agents.len() {
@@ -78,29 +235,102 @@ pub fn seed_if_empty(store: &SqliteStore) -> Result<bool, AppError> {
         }
     }
 
-    let now = Utc::now();
+    Ok(parsed)
+}
 
-    for a in parsed.agents {
-        let record = Agent {
-            id: a.id,
+/// Parse each entry of `root[section]` on its own, so a bad entry is
+/// reported by index, id and field rather than as one generic error.
+fn parse_section<T: DeserializeOwned>(
+    root: &Value,
+    section: &str,
+    known_fields: &[&str],
+) -> Result<Vec<T>, AppError> {
+    let entries = root
+        .get(section)
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| {
+            AppError::Message(format!("defaults.json is missing the '{section}' array"))
+        })?;
+
+    let mut out = Vec::with_capacity(entries.len());
+    for (idx, entry) in entries.iter().enumerate() {
+        let label = match entry.get("id").and_then(|v| v.as_str()) {
+            Some(id) => format!("{section}[{idx}] '{id}'"),
+            None => format!("{section}[{idx}]"),
+        };
+        let parsed = serde_json::from_value::<T>(entry.clone()).map_err(|e| {
+            let field = offending_field::<T>(entry)
+                .map(|f| format!(" field '{f}'"))
+                .unwrap_or_default();
+            AppError::Message(format!("defaults.json {label}{field}: {e}"))
+        })?;
+        for key in entry.as_object().into_iter().flat_map(|o| o.keys()) {
+            if !known_fields.contains(&key.as_str()) {
+                eprintln!("defaults.json {label}: ignoring unknown field '{key}'");
+            }
+        }
+        for field in ["id", "name"] {
+            let blank = entry
+                .get(field)
+                .and_then(|v| v.as_str())
+                .is_some_and(|v| v.trim().is_empty());
+            if blank {
+                return Err(AppError::Message(format!(
+                    "defaults.json {label} field '{field}': must not be empty"
+                )));
+            }
+        }
+        out.push(parsed);
+    }
+    Ok(out)
+}
+
+/// The key behind a failed entry: the one whose removal either fixes the
+/// entry or turns the failure into a "missing field" error for that key.
+fn offending_field<T: DeserializeOwned>(entry: &Value) -> Option<String> {
+    let object = entry.as_object()?;
+    if let Err(e) = serde_json::from_value::<T>(entry.clone()) {
+        let message = e.to_string();
+        if let Some(rest) = message.strip_prefix("missing field `") {
+            return rest.split('`').next().map(str::to_string);
+        }
+    }
+    object.keys().find_map(|key| {
+        let mut without = object.clone();
+        without.remove(key);
+        match serde_json::from_value::<T>(Value::Object(without)) {
+            Ok(_) => Some(key.clone()),
+            Err(e) if e.to_string().starts_with(&format!("missing field `{key}`")) => {
+                Some(key.clone())
+            }
+            Err(_) => None,
+        }
+    })
+}
+
+impl SeedAgent {
+    fn into_agent(self, now: DateTime<Utc>) -> Agent {
+        Agent {
+            id: self.id,
             user_id: LOCAL_USER_ID.to_string(),
-            name: a.name,
-            avatar: a.avatar,
-            description: a.description,
-            tags: Vec::new(),
-            system_prompt: a.system_prompt,
+            name: self.name,
+            avatar: self.avatar,
+            description: self.description,
+            tags: self.tags,
+            system_prompt: self.system_prompt,
             model_id: None,
-            temperature: a.temperature.unwrap_or(0.7),
-            max_tokens: a.max_tokens.unwrap_or(2000),
-            max_tool_iterations: a.max_tool_iterations.or(Some(10)),
+            temperature: self.temperature.unwrap_or(0.7),
+            max_tokens: self.max_tokens.unwrap_or(2000),
+            max_tool_iterations: self.max_tool_iterations.or(Some(10)),
             tools: Vec::new(),
             knowledge_base_id: None,
             memory_enabled: false,
             domain: None,
-            collaboration_style: a.collaboration_style,
-            speaking_priority: a.speaking_priority,
+            collaboration_style: self.collaboration_style,
+            speaking_priority: self.speaking_priority,
             interaction_rules: InteractionRules::default(),
             version: 1,
+            revision: 0,
             is_template: true,
             is_public: false,
             parent_id: None,
@@ -109,17 +339,18 @@ pub fn seed_if_empty(store: &SqliteStore) -> Result<bool, AppError> {
             rating_count: 0,
             created_at: now,
             updated_at: now,
-        };
-        store.agents_upsert(&record)?;
+        }
     }
+}
 
-    for t in parsed.teams {
-        let members = t
+impl SeedTeam {
+    fn into_team(self, now: DateTime<Utc>) -> Team {
+        let members = self
             .members
             .iter()
             .enumerate()
             .map(|(idx, agent_id)| TeamMember {
-                id: format!("{}-member-{}", t.id, idx + 1),
+                id: format!("{}-member-{}", self.id, idx + 1),
                 agent_id: agent_id.clone(),
                 role_override: None,
                 priority_override: None,
@@ -131,17 +362,18 @@ pub fn seed_if_empty(store: &SqliteStore) -> Result<bool, AppError> {
             })
             .collect();
 
-        let record = Team {
-            id: t.id,
+        Team {
+            id: self.id,
             user_id: LOCAL_USER_ID.to_string(),
-            name: t.name,
-            description: t.description,
-            icon: t.icon,
-            collaboration_mode: t.collaboration_mode,
+            name: self.name,
+            description: self.description,
+            icon: self.icon,
+            collaboration_mode: self.collaboration_mode,
             mode_config: serde_json::json!({}),
             coordinator_id: None,
             coordination_rules: CoordinationRules::default(),
             output_rules: OutputRules::default(),
+            revision: 0,
             is_template: true,
             is_public: false,
             usage_count: 0,
@@ -150,9 +382,176 @@ pub fn seed_if_empty(store: &SqliteStore) -> Result<bool, AppError> {
             members,
             created_at: now,
             updated_at: now,
-        };
-        store.teams_upsert(&record)?;
+        }
     }
+}
 
-    Ok(true)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> (tempfile::TempDir, SqliteStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn empty_store_is_fully_seeded_and_marked() {
+        let (_dir, store) = store();
+        seed_on_startup(&store).unwrap();
+
+        let defaults = load_defaults().unwrap();
+        assert_eq!(store.agents_list().unwrap().len(), defaults.agents.len());
+        assert_eq!(store.teams_list().unwrap().len(), defaults.teams.len());
+        assert_eq!(store.seed_version_get().unwrap(), SEED_VERSION);
+    }
+
+    #[test]
+    fn new_defaults_are_added_without_overwriting_customized_agents() {
+        let (_dir, store) = store();
+        let json = r#"{
+            "agents": [
+                {"id": "kept", "name": "Kept", "collaboration_style": "supportive",
+                 "speaking_priority": 5, "system_prompt": "p"},
+                {"id": "gone", "name": "Gone", "collaboration_style": "supportive",
+                 "speaking_priority": 5, "system_prompt": "p"},
+                {"id": "added", "name": "Added", "collaboration_style": "supportive",
+                 "speaking_priority": 5, "system_prompt": "p", "since": 2}
+            ],
+            "teams": [
+                {"id": "team", "name": "Team", "collaboration_mode": "roundtable",
+                 "members": ["kept", "added"], "since": 2}
+            ]
+        }"#;
+        let mut custom = parse_defaults(json)
+            .unwrap()
+            .agents
+            .remove(0)
+            .into_agent(Utc::now());
+        custom.name = "My Kept".to_string();
+        store.agents_upsert(&custom).unwrap();
+        store.seed_version_set(1).unwrap();
+
+        let added = upgrade_defaults(&store, parse_defaults(json).unwrap(), 2).unwrap();
+
+        assert_eq!(added, 2);
+        assert_eq!(store.agents_get("kept").unwrap().unwrap().name, "My Kept");
+        assert!(store.agents_get("gone").unwrap().is_none());
+        assert!(store.agents_get("added").unwrap().is_some());
+        assert!(store.teams_get("team").unwrap().is_some());
+        assert_eq!(store.seed_version_get().unwrap(), 2);
+        assert_eq!(
+            upgrade_defaults(&store, parse_defaults(json).unwrap(), 2).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn defaults_deleted_before_the_marker_existed_stay_deleted() {
+        let (_dir, store) = store();
+        reseed_defaults(&store, false).unwrap();
+        let first = load_defaults().unwrap().agents.remove(0);
+        store.agents_delete(&first.id).unwrap();
+        assert_eq!(store.seed_version_get().unwrap(), 0);
+
+        seed_on_startup(&store).unwrap();
+
+        assert!(store.agents_get(&first.id).unwrap().is_none());
+        assert_eq!(store.seed_version_get().unwrap(), SEED_VERSION);
+    }
+
+    #[test]
+    fn current_seed_version_skips_upsert() {
+        let (_dir, store) = store();
+        seed_on_startup(&store).unwrap();
+        let first = load_defaults().unwrap().agents.remove(0);
+        store.agents_delete(&first.id).unwrap();
+
+        seed_on_startup(&store).unwrap();
+
+        assert!(store.agents_get(&first.id).unwrap().is_none());
+        assert_eq!(seed_upsert_defaults(&store).unwrap(), 1);
+    }
+
+    #[test]
+    fn reseeding_restores_deleted_defaults_and_leaves_user_items_alone() {
+        let (_dir, store) = store();
+        seed_on_startup(&store).unwrap();
+        let defaults = load_defaults().unwrap();
+        let deleted = &defaults.agents[0].id;
+        let edited = &defaults.agents[1].id;
+        store.agents_delete(deleted).unwrap();
+        let mut custom = store.agents_get(edited).unwrap().unwrap();
+        custom.name = "My Analyst".to_string();
+        store.agents_upsert(&custom).unwrap();
+        let mut own = custom.clone();
+        own.id = "user-agent".to_string();
+        store.agents_upsert(&own).unwrap();
+
+        assert_eq!(reseed_defaults(&store, false).unwrap(), 1);
+        assert!(store.agents_get(deleted).unwrap().is_some());
+        assert_eq!(
+            store.agents_get(edited).unwrap().unwrap().name,
+            "My Analyst"
+        );
+        assert_eq!(
+            store.agents_get("user-agent").unwrap().unwrap().name,
+            "My Analyst"
+        );
+
+        let written = reseed_defaults(&store, true).unwrap();
+        assert_eq!(written, defaults.agents.len() + defaults.teams.len());
+        let reset = store.agents_get(edited).unwrap().unwrap();
+        assert_eq!(reset.name, defaults.agents[1].name);
+        assert_eq!(reset.created_at, custom.created_at);
+        assert_eq!(reset.version, custom.version + 1);
+        assert_eq!(
+            store.agents_get("user-agent").unwrap().unwrap().name,
+            "My Analyst"
+        );
+    }
+
+    #[test]
+    fn malformed_entries_name_the_offender() {
+        let err = |json: &str| parse_defaults(json).err().unwrap().to_string();
+
+        assert!(err("  ").contains("defaults.json is empty"));
+        assert!(err(r#"{"agents": []}"#).contains("missing the 'teams' array"));
+
+        let agent = r#"{"id": "a1", "name": "A", "collaboration_style": "x",
+            "speaking_priority": 1, "system_prompt": "p"}"#;
+        let bad_type = format!(
+            r#"{{"agents": [{agent}, {{"id": "a2", "name": "B", "collaboration_style": "x",
+                "speaking_priority": "high", "system_prompt": "p"}}], "teams": []}}"#
+        );
+        let message = err(&bad_type);
+        assert!(
+            message.contains("agents[1] 'a2' field 'speaking_priority'"),
+            "{message}"
+        );
+
+        let missing = format!(
+            r#"{{"agents": [{agent}], "teams": [{{"id": "t1", "name": "T", "members": ["a1"]}}]}}"#
+        );
+        let message = err(&missing);
+        assert!(
+            message.contains("teams[0] 't1' field 'collaboration_mode'"),
+            "{message}"
+        );
+
+        let blank = r#"{"agents": [{"id": " ", "name": "A", "collaboration_style": "x",
+            "speaking_priority": 1, "system_prompt": "p"}], "teams": []}"#;
+        assert!(err(blank).contains("field 'id': must not be empty"));
+
+        // Unknown fields are tolerated; new optional ones default.
+        let extra = format!(
+            r#"{{"agents": [{agent}, {{"id": "a3", "name": "C", "collaboration_style": "x",
+                "speaking_priority": 1, "system_prompt": "p", "tags": ["t"], "mood": "calm"}}],
+                "teams": []}}"#
+        );
+        let parsed = parse_defaults(&extra).unwrap();
+        assert!(parsed.agents[0].tags.is_empty());
+        assert_eq!(parsed.agents[1].tags, vec!["t".to_string()]);
+    }
 }
@@ -3,20 +3,29 @@ use std::sync::Arc;
 use tauri::AppHandle;
 
 use crate::error::AppError;
+use crate::metrics::Metrics;
+use crate::orchestration::watchdog::ExecutionTasks;
 use crate::seed;
 use crate::store::sqlite::SqliteStore;
+use crate::tools::lock::WorkspaceLocks;
 
 #[derive(Clone)]
 pub struct AppState {
     pub store: Arc<SqliteStore>,
+    pub workspace_locks: Arc<WorkspaceLocks>,
+    pub metrics: Arc<Metrics>,
+    pub execution_tasks: Arc<ExecutionTasks>,
 }
 
 impl AppState {
     pub fn init(_app: &AppHandle) -> Result<Self, AppError> {
         let store = SqliteStore::new("agent-team")?;
-        let _ = seed::seed_if_empty(&store)?;
+        seed::seed_on_startup(&store)?;
         Ok(Self {
             store: Arc::new(store),
+            workspace_locks: Arc::new(WorkspaceLocks::default()),
+            metrics: Arc::new(Metrics::default()),
+            execution_tasks: Arc::new(ExecutionTasks::default()),
         })
     }
 }
@@ -0,0 +1,555 @@
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension, TransactionBehavior};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::models::agent::Agent;
+use crate::models::execution::{ExecutionMessage, ExecutionRecord};
+use crate::models::knowledge::KnowledgeDoc;
+use crate::models::team::Team;
+use crate::sanitize::{redact_credentials, restore_credentials};
+use crate::store::sqlite::SqliteStore;
+
+/// Bumped when the archive layout changes incompatibly.
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+/// Every agent, team, model config, execution, message and knowledge-base
+/// doc, stored as the raw rows so ids and unknown fields survive a round trip
+/// unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    pub format_version: u32,
+    pub exported_at: DateTime<Utc>,
+    #[serde(default)]
+    pub agents: Vec<BackupRow>,
+    #[serde(default)]
+    pub teams: Vec<BackupRow>,
+    #[serde(default)]
+    pub model_configs: Vec<BackupRow>,
+    #[serde(default)]
+    pub executions: Vec<BackupRow>,
+    #[serde(default)]
+    pub execution_messages: Vec<BackupRow>,
+    #[serde(default)]
+    pub knowledge_docs: Vec<BackupRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupRow {
+    pub id: String,
+    /// Only set for `execution_messages`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<i64>,
+    /// Only set for `knowledge_docs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kb_id: Option<String>,
+    pub data: Value,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Upsert backup rows by id, keeping local rows the backup doesn't have.
+    Merge,
+    /// Clear the backed-up tables first, leaving exactly the backup's contents.
+    Replace,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BackupSummary {
+    pub agents: usize,
+    pub teams: usize,
+    pub model_configs: usize,
+    pub executions: usize,
+    pub execution_messages: usize,
+    pub knowledge_docs: usize,
+}
+
+impl Backup {
+    pub fn summary(&self) -> BackupSummary {
+        BackupSummary {
+            agents: self.agents.len(),
+            teams: self.teams.len(),
+            model_configs: self.model_configs.len(),
+            executions: self.executions.len(),
+            execution_messages: self.execution_messages.len(),
+            knowledge_docs: self.knowledge_docs.len(),
+        }
+    }
+
+    /// Replace API keys and `extra_headers` values with `<redacted>`. Importing
+    /// such a backup keeps the keys already stored for the same model configs.
+    pub fn redact_secrets(&mut self) {
+        for rows in [
+            &mut self.agents,
+            &mut self.teams,
+            &mut self.model_configs,
+            &mut self.executions,
+            &mut self.execution_messages,
+            &mut self.knowledge_docs,
+        ] {
+            for row in rows.iter_mut() {
+                redact_credentials(&mut row.data);
+            }
+        }
+    }
+
+    pub fn read_from(path: &Path) -> Result<Self, AppError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| AppError::Message(format!("Failed to read backup: {e}")))?;
+        let backup: Backup = serde_json::from_str(&json)
+            .map_err(|e| AppError::Message(format!("Invalid backup file: {e}")))?;
+        if backup.format_version > BACKUP_FORMAT_VERSION {
+            return Err(AppError::Message(format!(
+                "Backup format {} is newer than supported ({BACKUP_FORMAT_VERSION})",
+                backup.format_version
+            )));
+        }
+        Ok(backup)
+    }
+
+    pub fn write_to(&self, path: &Path) -> Result<(), AppError> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .map_err(|e| AppError::Message(format!("Failed to write backup: {e}")))
+    }
+
+    /// Check that every row parses as its model before anything is written.
+    fn validate(&self) -> Result<(), AppError> {
+        validate_rows::<Agent>("agents", &self.agents)?;
+        validate_rows::<Team>("teams", &self.teams)?;
+        validate_rows::<ExecutionRecord>("executions", &self.executions)?;
+        validate_rows::<ExecutionMessage>("execution_messages", &self.execution_messages)?;
+        validate_rows::<KnowledgeDoc>("knowledge_docs", &self.knowledge_docs)?;
+        if let Some(row) = self
+            .execution_messages
+            .iter()
+            .find(|r| r.execution_id.is_none())
+        {
+            return Err(AppError::Message(format!(
+                "Backup execution_messages row {} has no execution_id",
+                row.id
+            )));
+        }
+        if let Some(row) = self.knowledge_docs.iter().find(|r| r.kb_id.is_none()) {
+            return Err(AppError::Message(format!(
+                "Backup knowledge_docs row {} has no kb_id",
+                row.id
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn validate_rows<T: DeserializeOwned>(table: &str, rows: &[BackupRow]) -> Result<(), AppError> {
+    for row in rows {
+        serde_json::from_value::<T>(row.data.clone()).map_err(|e| {
+            AppError::Message(format!("Backup {table} row {} is invalid: {e}", row.id))
+        })?;
+    }
+    Ok(())
+}
+
+impl SqliteStore {
+    pub fn export_backup(&self) -> Result<Backup, AppError> {
+        Ok(Backup {
+            format_version: BACKUP_FORMAT_VERSION,
+            exported_at: Utc::now(),
+            agents: self.backup_rows("agents")?,
+            teams: self.backup_rows("teams")?,
+            model_configs: self.backup_rows("model_configs")?,
+            executions: self.backup_rows("executions")?,
+            execution_messages: self.backup_rows("execution_messages")?,
+            knowledge_docs: self.backup_rows("knowledge_docs")?,
+        })
+    }
+
+    /// Restore `backup` in one transaction; on any error nothing is changed.
+    pub fn import_backup(&self, backup: &Backup, mode: ImportMode) -> Result<(), AppError> {
+        backup.validate()?;
+
+        let mut conn = self.open()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        // Read before a replace clears the table, so redacted keys keep theirs.
+        let mut model_configs = backup.model_configs.clone();
+        for row in model_configs.iter_mut() {
+            let local: Option<String> = tx
+                .query_row(
+                    "SELECT data_json FROM model_configs WHERE id=?1;",
+                    params![row.id],
+                    |r| r.get(0),
+                )
+                .optional()?;
+            let local = local
+                .map(|json| serde_json::from_str::<Value>(&json))
+                .transpose()?;
+            restore_credentials(&mut row.data, local.as_ref());
+        }
+        let tables = [
+            ("agents", &backup.agents),
+            ("teams", &backup.teams),
+            ("model_configs", &model_configs),
+            ("executions", &backup.executions),
+        ];
+        if mode == ImportMode::Replace {
+            for (table, _) in tables {
+                tx.execute(&format!("DELETE FROM {table};"), [])?;
+            }
+            tx.execute("DELETE FROM execution_messages;", [])?;
+            tx.execute("DELETE FROM knowledge_docs;", [])?;
+        }
+
+        for (table, rows) in tables {
+            let sql = format!(
+                r#"
+                INSERT INTO {table}(id, data_json, created_at, updated_at)
+                VALUES(?1, ?2, ?3, ?4)
+                ON CONFLICT(id) DO UPDATE SET
+                    data_json=excluded.data_json,
+                    created_at=excluded.created_at,
+                    updated_at=excluded.updated_at;
+                "#
+            );
+            for row in rows {
+                tx.execute(
+                    &sql,
+                    params![
+                        row.id,
+                        serde_json::to_string(&row.data)?,
+                        row.created_at,
+                        row.updated_at
+                    ],
+                )?;
+            }
+        }
+        for row in &backup.execution_messages {
+            tx.execute(
+                r#"
+                INSERT INTO execution_messages(id, execution_id, sequence, data_json, created_at, updated_at)
+                VALUES(?1, ?2, ?3, ?4, ?5, ?6)
+                ON CONFLICT(id) DO UPDATE SET
+                    execution_id=excluded.execution_id,
+                    sequence=excluded.sequence,
+                    data_json=excluded.data_json,
+                    created_at=excluded.created_at,
+                    updated_at=excluded.updated_at;
+                "#,
+                params![
+                    row.id,
+                    row.execution_id,
+                    row.sequence,
+                    serde_json::to_string(&row.data)?,
+                    row.created_at,
+                    row.updated_at
+                ],
+            )?;
+        }
+        for row in &backup.knowledge_docs {
+            tx.execute(
+                r#"
+                INSERT INTO knowledge_docs(id, kb_id, data_json, created_at, updated_at)
+                VALUES(?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT(id) DO UPDATE SET
+                    kb_id=excluded.kb_id,
+                    data_json=excluded.data_json,
+                    created_at=excluded.created_at,
+                    updated_at=excluded.updated_at;
+                "#,
+                params![
+                    row.id,
+                    row.kb_id,
+                    serde_json::to_string(&row.data)?,
+                    row.created_at,
+                    row.updated_at
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn backup_rows(&self, table: &str) -> Result<Vec<BackupRow>, AppError> {
+        let conn = self.open()?;
+        let with_execution = table == "execution_messages";
+        let with_kb = table == "knowledge_docs";
+        let sql = if with_execution {
+            "SELECT id, data_json, created_at, updated_at, execution_id, sequence FROM execution_messages ORDER BY execution_id, sequence, created_at, id;".to_string()
+        } else if with_kb {
+            "SELECT id, data_json, created_at, updated_at, kb_id FROM knowledge_docs ORDER BY kb_id, id;".to_string()
+        } else {
+            format!("SELECT id, data_json, created_at, updated_at FROM {table} ORDER BY id;")
+        };
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                if with_execution {
+                    row.get::<_, Option<String>>(4)?
+                } else {
+                    None
+                },
+                if with_execution {
+                    row.get::<_, Option<i64>>(5)?
+                } else {
+                    None
+                },
+                if with_kb {
+                    row.get::<_, Option<String>>(4)?
+                } else {
+                    None
+                },
+            ))
+        })?;
+
+        let mut out = Vec::new();
+        for row in rows {
+            let (id, json, created_at, updated_at, execution_id, sequence, kb_id) = row?;
+            out.push(BackupRow {
+                id,
+                execution_id,
+                sequence,
+                kb_id,
+                data: serde_json::from_str(&json)?,
+                created_at,
+                updated_at,
+            });
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock;
+
+    fn store(dir: &tempfile::TempDir, name: &str) -> SqliteStore {
+        SqliteStore::open_at(dir.path().join(name)).unwrap()
+    }
+
+    fn message(id: &str, sequence: i32) -> ExecutionMessage {
+        let now = Utc::now();
+        ExecutionMessage {
+            id: id.to_string(),
+            sequence,
+            round: 1,
+            phase: "initial".to_string(),
+            sender_type: "agent".to_string(),
+            sender_id: Some("a1".to_string()),
+            sender_name: Some("Alice".to_string()),
+            content: format!("message {id}"),
+            content_type: "text".to_string(),
+            responding_to: None,
+            target_agent_id: None,
+            wants_to_continue: true,
+            input_tokens: 3,
+            output_tokens: 4,
+            tokens_estimated: false,
+            metadata: serde_json::json!({ "finish_reason": "stop" }),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn populate(store: &SqliteStore) {
+        store.agents_upsert(&mock::agent("a1", "Alice")).unwrap();
+        crate::seed::seed_upsert_defaults(store).unwrap();
+        store
+            .open()
+            .unwrap()
+            .execute(
+                "INSERT INTO model_configs(id, data_json, created_at, updated_at) VALUES('mc_1', '{\"id\":\"mc_1\",\"model_id\":\"gpt\"}', 'c', 'u');",
+                [],
+            )
+            .unwrap();
+        let execution: ExecutionRecord = serde_json::from_value(serde_json::json!({
+            "id": "e1",
+            "user_id": "local",
+            "team_id": "default-team",
+            "title": "Backup me",
+            "initial_input": "topic",
+            "status": "completed",
+            "current_stage": null,
+            "current_round": 1,
+            "final_output": null,
+            "structured_output": null,
+            "tokens_used": 7,
+            "tokens_budget": 1000,
+            "cost": 0.0,
+            "cost_budget": 1.0,
+            "started_at": null,
+            "completed_at": null,
+            "error_message": null,
+            "retry_count": 0,
+            "workspace_path": null,
+            "created_at": Utc::now(),
+            "updated_at": Utc::now()
+        }))
+        .unwrap();
+        store.executions_upsert(&execution).unwrap();
+        store
+            .execution_messages_upsert("e1", &message("m1", 1))
+            .unwrap();
+        store
+            .execution_messages_upsert("e1", &message("m2", 2))
+            .unwrap();
+        store
+            .knowledge_docs_upsert(&KnowledgeDoc {
+                id: "d1".to_string(),
+                kb_id: "kb".to_string(),
+                path: Some("guide.md".to_string()),
+                content: "Prefer SQLite.".to_string(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .unwrap();
+    }
+
+    fn rows(backup: &Backup) -> Vec<Vec<BackupRow>> {
+        vec![
+            backup.agents.clone(),
+            backup.teams.clone(),
+            backup.model_configs.clone(),
+            backup.executions.clone(),
+            backup.execution_messages.clone(),
+            backup.knowledge_docs.clone(),
+        ]
+    }
+
+    #[test]
+    fn export_then_import_into_fresh_store_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = store(&dir, "source.db");
+        populate(&source);
+        let path = dir.path().join("backup.json");
+        source.export_backup().unwrap().write_to(&path).unwrap();
+
+        let target = store(&dir, "target.db");
+        let backup = Backup::read_from(&path).unwrap();
+        target.import_backup(&backup, ImportMode::Merge).unwrap();
+
+        assert_eq!(
+            rows(&target.export_backup().unwrap()),
+            rows(&source.export_backup().unwrap())
+        );
+        assert_eq!(backup.summary().execution_messages, 2);
+        assert_eq!(backup.summary().model_configs, 1);
+        let messages = target.execution_messages_list("e1").unwrap();
+        assert_eq!(
+            messages.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(),
+            vec!["m1", "m2"]
+        );
+        assert_eq!(target.agents_get("a1").unwrap().unwrap().name, "Alice");
+        assert_eq!(backup.summary().knowledge_docs, 1);
+        let docs = target.knowledge_docs_list("kb").unwrap();
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0].content, "Prefer SQLite.");
+    }
+
+    #[test]
+    fn merge_keeps_local_rows_and_replace_drops_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = store(&dir, "source.db");
+        populate(&source);
+        let backup = source.export_backup().unwrap();
+
+        let target = store(&dir, "target.db");
+        target
+            .agents_upsert(&mock::agent("local", "Local"))
+            .unwrap();
+        let stale = KnowledgeDoc {
+            id: "stale".to_string(),
+            kb_id: "kb".to_string(),
+            path: None,
+            content: "Old notes".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        target.knowledge_docs_upsert(&stale).unwrap();
+        target.import_backup(&backup, ImportMode::Merge).unwrap();
+        assert!(target.knowledge_docs_get("stale").unwrap().is_some());
+        assert!(target.agents_get("local").unwrap().is_some());
+        assert!(target.agents_get("a1").unwrap().is_some());
+
+        target.import_backup(&backup, ImportMode::Replace).unwrap();
+        assert!(target.agents_get("local").unwrap().is_none());
+        assert!(target.knowledge_docs_get("stale").unwrap().is_none());
+        assert_eq!(rows(&target.export_backup().unwrap()), rows(&backup));
+    }
+
+    #[test]
+    fn secrets_are_redacted_unless_asked_for_and_survive_a_redacted_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = store(&dir, "source.db");
+        populate(&source);
+        source
+            .open()
+            .unwrap()
+            .execute(
+                "UPDATE model_configs SET data_json='{\"id\":\"mc_1\",\"api_key\":\"sk-live-123\",\"extra_headers\":{\"X-Auth\":\"tok-9\"}}';",
+                [],
+            )
+            .unwrap();
+
+        let full = source.export_backup().unwrap();
+        assert!(serde_json::to_string(&full)
+            .unwrap()
+            .contains("sk-live-123"));
+        let mut redacted = full.clone();
+        redacted.redact_secrets();
+        let path = dir.path().join("backup.json");
+        redacted.write_to(&path).unwrap();
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(!json.contains("sk-live-123"));
+        assert!(!json.contains("tok-9"));
+
+        let config =
+            |store: &SqliteStore| store.export_backup().unwrap().model_configs[0].data.clone();
+        let target = store(&dir, "target.db");
+        target.import_backup(&full, ImportMode::Merge).unwrap();
+        target
+            .import_backup(&redacted, ImportMode::Replace)
+            .unwrap();
+        assert_eq!(config(&target)["api_key"], "sk-live-123");
+        assert_eq!(config(&target)["extra_headers"]["X-Auth"], "tok-9");
+
+        let fresh = store(&dir, "fresh.db");
+        fresh.import_backup(&redacted, ImportMode::Merge).unwrap();
+        assert_eq!(config(&fresh)["api_key"], "");
+        assert_eq!(config(&fresh)["extra_headers"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn invalid_backup_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = store(&dir, "source.db");
+        populate(&source);
+        let mut backup = source.export_backup().unwrap();
+        backup.teams[0].data = serde_json::json!({ "id": "broken" });
+
+        let target = store(&dir, "target.db");
+        target
+            .agents_upsert(&mock::agent("local", "Local"))
+            .unwrap();
+        let err = target
+            .import_backup(&backup, ImportMode::Replace)
+            .unwrap_err();
+        assert!(err.to_string().contains("teams row"));
+        assert!(target.agents_get("local").unwrap().is_some());
+        assert!(target.agents_get("a1").unwrap().is_none());
+    }
+}
@@ -1,24 +1,45 @@
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, OptionalExtension};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use crate::error::AppError;
 use crate::models::agent::Agent;
-use crate::models::execution::{ExecutionMessage, ExecutionRecord};
+use crate::models::execution::{ExecutionMessage, ExecutionRecord, MessageCounts};
+use crate::models::knowledge::KnowledgeDoc;
+use crate::models::llm::Pricing;
+use crate::models::settings::AppSettings;
 use crate::models::team::Team;
 
+/// The `settings` table holds a single row under this id.
+const SETTINGS_ROW_ID: &str = "app";
+/// Row in `settings` recording which `seed::SEED_VERSION` was last applied.
+const SEED_VERSION_ROW_ID: &str = "seed_version";
+/// Row in `settings` holding the user's per-model pricing overrides.
+const MODEL_PRICING_ROW_ID: &str = "model_pricing";
+
 pub struct SqliteStore {
     db_path: PathBuf,
+    /// Per-execution locks for writers that need message persistence and
+    /// emission to happen as one ordered step; see `execution_message_lock`.
+    message_locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
 }
 
 impl SqliteStore {
     pub fn new(app_name: &str) -> Result<Self, AppError> {
-        let db_path = default_sqlite_path(app_name)?;
+        Self::open_at(default_sqlite_path(app_name)?)
+    }
+
+    pub fn open_at(db_path: PathBuf) -> Result<Self, AppError> {
         init_db(&db_path)?;
-        Ok(Self { db_path })
+        Ok(Self {
+            db_path,
+            message_locks: Mutex::new(HashMap::new()),
+        })
     }
 
     pub fn is_empty(&self) -> Result<bool, AppError> {
@@ -67,6 +88,14 @@ impl SqliteStore {
         )
     }
 
+    /// Like `executions_update`, for one agent.
+    pub fn agents_update<F>(&self, agent_id: &str, update: F) -> Result<Agent, AppError>
+    where
+        F: FnOnce(&mut Agent) -> Result<(), AppError>,
+    {
+        self.update_table("agents", "Agent", agent_id, update, |r| r.updated_at)
+    }
+
     pub fn agents_delete(&self, agent_id: &str) -> Result<(), AppError> {
         self.delete("agents", agent_id)
     }
@@ -89,6 +118,14 @@ impl SqliteStore {
         )
     }
 
+    /// Like `executions_update`, for one team.
+    pub fn teams_update<F>(&self, team_id: &str, update: F) -> Result<Team, AppError>
+    where
+        F: FnOnce(&mut Team) -> Result<(), AppError>,
+    {
+        self.update_table("teams", "Team", team_id, update, |r| r.updated_at)
+    }
+
     pub fn teams_delete(&self, team_id: &str) -> Result<(), AppError> {
         self.delete("teams", team_id)
     }
@@ -111,6 +148,22 @@ impl SqliteStore {
         )
     }
 
+    /// Read-modify-write one execution inside a single `BEGIN IMMEDIATE`
+    /// transaction, so concurrent callers can't both act on the same prior state.
+    /// Nothing is written when `update` returns an error.
+    pub fn executions_update<F>(
+        &self,
+        execution_id: &str,
+        update: F,
+    ) -> Result<ExecutionRecord, AppError>
+    where
+        F: FnOnce(&mut ExecutionRecord) -> Result<(), AppError>,
+    {
+        self.update_table("executions", "Execution", execution_id, update, |r| {
+            r.updated_at
+        })
+    }
+
     pub fn executions_delete(&self, execution_id: &str) -> Result<(), AppError> {
         let conn = self.open()?;
         conn.execute("DELETE FROM executions WHERE id=?1;", params![execution_id])?;
@@ -121,13 +174,23 @@ impl SqliteStore {
         Ok(())
     }
 
+    /// Delete messages whose execution row no longer exists, e.g. left behind
+    /// by a crash inside `executions_delete`. Returns how many were removed.
+    pub fn prune_orphan_messages(&self) -> Result<usize, AppError> {
+        let conn = self.open()?;
+        Ok(conn.execute(
+            "DELETE FROM execution_messages WHERE execution_id NOT IN (SELECT id FROM executions);",
+            [],
+        )?)
+    }
+
     pub fn execution_messages_list(
         &self,
         execution_id: &str,
     ) -> Result<Vec<ExecutionMessage>, AppError> {
         let conn = self.open()?;
         let mut stmt = conn.prepare(
-            "SELECT data_json FROM execution_messages WHERE execution_id=?1 ORDER BY sequence;",
+            "SELECT data_json FROM execution_messages WHERE execution_id=?1 ORDER BY sequence, created_at, id;",
         )?;
         let rows = stmt.query_map(params![execution_id], |row| row.get::<_, String>(0))?;
         let mut messages = Vec::new();
@@ -139,6 +202,34 @@ impl SqliteStore {
         Ok(messages)
     }
 
+    /// Message, round and per-phase counts for an execution, computed with
+    /// `COUNT` queries so no message body is loaded.
+    pub fn execution_message_counts(&self, execution_id: &str) -> Result<MessageCounts, AppError> {
+        let conn = self.open()?;
+        let (message_count, round_count) = conn.query_row(
+            r#"
+            SELECT COUNT(*), COUNT(DISTINCT json_extract(data_json, '$.round'))
+            FROM execution_messages WHERE execution_id=?1;
+            "#,
+            params![execution_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT IFNULL(json_extract(data_json, '$.phase'), ''), COUNT(*)
+            FROM execution_messages WHERE execution_id=?1 GROUP BY 1;
+            "#,
+        )?;
+        let phase_counts = stmt
+            .query_map(params![execution_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        Ok(MessageCounts {
+            message_count,
+            round_count,
+            phase_counts,
+        })
+    }
+
     pub fn execution_messages_upsert(
         &self,
         execution_id: &str,
@@ -167,6 +258,46 @@ impl SqliteStore {
         Ok(())
     }
 
+    /// Insert `message` with the execution's next sequence, allocated in the
+    /// same `BEGIN IMMEDIATE` transaction so concurrent writers never share one.
+    pub fn execution_messages_append(
+        &self,
+        execution_id: &str,
+        message: &mut ExecutionMessage,
+    ) -> Result<(), AppError> {
+        let mut conn = self.open()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        message.sequence = tx.query_row(
+            "SELECT IFNULL(MAX(sequence), 0) + 1 FROM execution_messages WHERE execution_id=?1;",
+            params![execution_id],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            r#"
+            INSERT INTO execution_messages(id, execution_id, sequence, data_json, created_at, updated_at)
+            VALUES(?1, ?2, ?3, ?4, ?5, ?6);
+            "#,
+            params![
+                message.id,
+                execution_id,
+                message.sequence,
+                serde_json::to_string(message)?,
+                message.created_at.to_rfc3339(),
+                message.updated_at.to_rfc3339()
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The lock ordering an execution's message writes. Holding it across
+    /// `execution_messages_append` and the matching event emission keeps the
+    /// emitted order identical to the persisted one.
+    pub fn execution_message_lock(&self, execution_id: &str) -> Arc<Mutex<()>> {
+        let mut locks = self.message_locks.lock().unwrap_or_else(|e| e.into_inner());
+        locks.entry(execution_id.to_string()).or_default().clone()
+    }
+
     pub fn execution_messages_next_sequence(&self, execution_id: &str) -> Result<i32, AppError> {
         let conn = self.open()?;
         let next: i32 = conn.query_row(
@@ -177,7 +308,93 @@ impl SqliteStore {
         Ok(next)
     }
 
-    fn open(&self) -> Result<Connection, AppError> {
+    pub fn knowledge_docs_list(&self, kb_id: &str) -> Result<Vec<KnowledgeDoc>, AppError> {
+        let conn = self.open()?;
+        let mut stmt = conn.prepare(
+            "SELECT data_json FROM knowledge_docs WHERE kb_id=?1 ORDER BY created_at, id;",
+        )?;
+        let rows = stmt.query_map(params![kb_id], |row| row.get::<_, String>(0))?;
+        let mut docs = Vec::new();
+        for row in rows {
+            let json = row?;
+            docs.push(serde_json::from_str(&json)?);
+        }
+        Ok(docs)
+    }
+
+    pub fn knowledge_docs_get(&self, doc_id: &str) -> Result<Option<KnowledgeDoc>, AppError> {
+        self.get_table("knowledge_docs", doc_id)
+    }
+
+    pub fn knowledge_docs_upsert(&self, doc: &KnowledgeDoc) -> Result<(), AppError> {
+        let payload = serde_json::to_string(doc)?;
+        let conn = self.open()?;
+        conn.execute(
+            r#"
+            INSERT INTO knowledge_docs(id, kb_id, data_json, created_at, updated_at)
+            VALUES(?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(id) DO UPDATE SET
+                kb_id=excluded.kb_id,
+                data_json=excluded.data_json,
+                updated_at=excluded.updated_at;
+            "#,
+            params![
+                doc.id,
+                doc.kb_id,
+                payload,
+                doc.created_at.to_rfc3339(),
+                doc.updated_at.to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn knowledge_docs_delete(&self, doc_id: &str) -> Result<(), AppError> {
+        self.delete("knowledge_docs", doc_id)
+    }
+
+    pub fn settings_get(&self) -> Result<AppSettings, AppError> {
+        Ok(self
+            .get_table("settings", SETTINGS_ROW_ID)?
+            .unwrap_or_default())
+    }
+
+    pub fn settings_set(&self, settings: &AppSettings) -> Result<(), AppError> {
+        let now = Utc::now();
+        self.upsert_table("settings", SETTINGS_ROW_ID, settings, &now, &now)
+    }
+
+    pub fn model_pricing_get(&self) -> Result<BTreeMap<String, Pricing>, AppError> {
+        Ok(self
+            .get_table("settings", MODEL_PRICING_ROW_ID)?
+            .unwrap_or_default())
+    }
+
+    pub fn model_pricing_set(&self, pricing: &BTreeMap<String, Pricing>) -> Result<(), AppError> {
+        let now = Utc::now();
+        self.upsert_table("settings", MODEL_PRICING_ROW_ID, pricing, &now, &now)
+    }
+
+    /// The last applied seed version; 0 for stores seeded before versioning.
+    pub fn seed_version_get(&self) -> Result<u32, AppError> {
+        let marker: Option<serde_json::Value> = self.get_table("settings", SEED_VERSION_ROW_ID)?;
+        Ok(marker
+            .and_then(|m| m.get("version").and_then(|v| v.as_u64()))
+            .unwrap_or(0) as u32)
+    }
+
+    pub fn seed_version_set(&self, version: u32) -> Result<(), AppError> {
+        let now = Utc::now();
+        self.upsert_table(
+            "settings",
+            SEED_VERSION_ROW_ID,
+            &serde_json::json!({ "version": version }),
+            &now,
+            &now,
+        )
+    }
+
+    pub(super) fn open(&self) -> Result<Connection, AppError> {
         Ok(Connection::open(&self.db_path)?)
     }
 
@@ -207,6 +424,46 @@ impl SqliteStore {
         }
     }
 
+    /// Read-modify-write row `id` of `table` in one `BEGIN IMMEDIATE`
+    /// transaction; `label` names the record in the not-found error.
+    fn update_table<T, F>(
+        &self,
+        table: &str,
+        label: &str,
+        id: &str,
+        update: F,
+        updated_at: impl Fn(&T) -> DateTime<Utc>,
+    ) -> Result<T, AppError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(&mut T) -> Result<(), AppError>,
+    {
+        let mut conn = self.open()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let json: Option<String> = tx
+            .query_row(
+                &format!("SELECT data_json FROM {table} WHERE id=?1;"),
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(json) = json else {
+            return Err(AppError::Message(format!("{label} {id} not found")));
+        };
+        let mut record: T = serde_json::from_str(&json)?;
+        update(&mut record)?;
+        tx.execute(
+            &format!("UPDATE {table} SET data_json=?2, updated_at=?3 WHERE id=?1;"),
+            params![
+                id,
+                serde_json::to_string(&record)?,
+                updated_at(&record).to_rfc3339()
+            ],
+        )?;
+        tx.commit()?;
+        Ok(record)
+    }
+
     fn upsert_table<T: Serialize>(
         &self,
         table: &str,
@@ -302,6 +559,24 @@ fn init_db(db_path: &Path) -> Result<(), AppError> {
 
         CREATE INDEX IF NOT EXISTS idx_execution_messages_exec_seq
         ON execution_messages (execution_id, sequence);
+
+        CREATE TABLE IF NOT EXISTS knowledge_docs (
+            id TEXT PRIMARY KEY,
+            kb_id TEXT NOT NULL,
+            data_json TEXT NOT NULL,
+            created_at TEXT,
+            updated_at TEXT
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_knowledge_docs_kb
+        ON knowledge_docs (kb_id);
+
+        CREATE TABLE IF NOT EXISTS settings (
+            id TEXT PRIMARY KEY,
+            data_json TEXT NOT NULL,
+            created_at TEXT,
+            updated_at TEXT
+        );
         "#,
     )?;
 
@@ -358,3 +633,74 @@ fn expand_tilde(path: PathBuf) -> PathBuf {
     }
     path
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, sequence: i32) -> ExecutionMessage {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "sequence": sequence,
+            "round": 1,
+            "phase": "initial",
+            "sender_type": "agent",
+            "sender_id": "a1",
+            "sender_name": "Alice",
+            "content": id,
+            "content_type": "text",
+            "responding_to": null,
+            "target_agent_id": null,
+            "wants_to_continue": true,
+            "created_at": chrono::Utc::now(),
+            "updated_at": chrono::Utc::now()
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn pruning_removes_only_messages_without_an_execution() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open_at(dir.path().join("app.db")).unwrap();
+        let execution: ExecutionRecord = serde_json::from_value(serde_json::json!({
+            "id": "e1",
+            "user_id": "local",
+            "team_id": "t1",
+            "title": null,
+            "initial_input": "topic",
+            "status": "completed",
+            "current_stage": null,
+            "current_round": 1,
+            "final_output": null,
+            "structured_output": null,
+            "tokens_used": 0,
+            "tokens_budget": 1000,
+            "cost": 0.0,
+            "cost_budget": 1.0,
+            "started_at": null,
+            "completed_at": null,
+            "error_message": null,
+            "retry_count": 0,
+            "created_at": chrono::Utc::now(),
+            "updated_at": chrono::Utc::now()
+        }))
+        .unwrap();
+        store.executions_upsert(&execution).unwrap();
+        store
+            .execution_messages_upsert("e1", &message("kept", 1))
+            .unwrap();
+        store
+            .execution_messages_upsert("gone", &message("o1", 1))
+            .unwrap();
+        store
+            .execution_messages_upsert("gone", &message("o2", 2))
+            .unwrap();
+
+        assert_eq!(store.prune_orphan_messages().unwrap(), 2);
+        assert!(store.execution_messages_list("gone").unwrap().is_empty());
+        let kept = store.execution_messages_list("e1").unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].id, "kept");
+        assert_eq!(store.prune_orphan_messages().unwrap(), 0);
+    }
+}
@@ -1,10 +1,12 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
 use std::path::Path;
 
 use regex::Regex;
 use serde::Serialize;
 
 use crate::error::AppError;
-use crate::tools::builtin::search;
+use crate::tools::builtin::{files, search};
 use crate::tools::security;
 
 #[derive(Debug, Clone, Serialize)]
@@ -14,6 +16,18 @@ pub struct CodeMatch {
     pub snippet: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct DefinitionMatch {
+    pub path: String,
+    pub line: u32,
+    pub snippet: String,
+    /// The declaration up to its body, which may span several lines.
+    pub signature: String,
+}
+
+/// How far past the matching line a wrapped signature is followed.
+const MAX_SIGNATURE_LINES: usize = 20;
+
 fn default_code_file_pattern() -> &'static str {
     // Match common code file extensions.
     r"re:.*\.(rs|ts|tsx|js|jsx|py|go|java|kt|swift|c|cc|cpp|h|hpp)$"
@@ -26,7 +40,7 @@ pub fn find_definition(
     max_matches: usize,
     max_files: usize,
     max_read_bytes: u64,
-) -> Result<Vec<CodeMatch>, AppError> {
+) -> Result<Vec<DefinitionMatch>, AppError> {
     let escaped = regex::escape(name);
     let patterns = [
         format!(r"^\s*(pub\s+)?(async\s+)?fn\s+{escaped}\b"),
@@ -52,44 +66,273 @@ pub fn find_definition(
             max_matches.saturating_sub(results.len()),
             max_files,
             max_read_bytes,
-        )?;
-        results.extend(hits.into_iter().map(|m| CodeMatch {
+        )?
+        .matches;
+        results.extend(hits.into_iter().map(|m| DefinitionMatch {
+            signature: m.snippet.clone(),
             path: m.path,
             line: m.line,
             snippet: m.snippet,
         }));
     }
 
+    let mut files: HashMap<String, String> = HashMap::new();
+    for m in &mut results {
+        if !files.contains_key(&m.path) {
+            let text = files::read_file(root, &m.path, None, None, max_read_bytes)
+                .map(|(text, _, _)| text)
+                .unwrap_or_default();
+            files.insert(m.path.clone(), text);
+        }
+        let lines = files[&m.path]
+            .lines()
+            .skip(m.line.saturating_sub(1) as usize);
+        if let Some(signature) = signature_from(lines, m.path.ends_with(".py")) {
+            m.signature = signature;
+        }
+    }
+
     Ok(results)
 }
 
+/// Read a declaration forward from its first line until the body opens
+/// (`{`, or `:` in Python) or a `;` ends it, outside any brackets. A line
+/// ending with every bracket closed also ends it, which covers `const x = 1`.
+fn signature_from<'a>(lines: impl Iterator<Item = &'a str>, python: bool) -> Option<String> {
+    let mut out: Vec<&str> = Vec::new();
+    let mut indent = 0;
+    let mut depth = 0i32;
+    for (idx, line) in lines.take(MAX_SIGNATURE_LINES).enumerate() {
+        let leading = line.len() - line.trim_start_matches([' ', '\t']).len();
+        if idx == 0 {
+            indent = leading;
+        }
+        let mut end = None;
+        for (at, c) in line.char_indices() {
+            match c {
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth -= 1,
+                '{' | ';' if depth <= 0 => end = Some(at),
+                ':' if python && depth <= 0 => end = Some(at + 1),
+                _ => {}
+            }
+            if end.is_some() {
+                break;
+            }
+        }
+        // Keep the declaration's own indentation relative to its first line.
+        let start = leading.min(indent);
+        out.push(line[start..end.unwrap_or(line.len())].trim_end());
+        if end.is_some() || depth <= 0 {
+            break;
+        }
+    }
+    let signature = out.join("\n").trim().to_string();
+    (!signature.is_empty()).then_some(signature)
+}
+
+/// Filters for `find_references`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReferenceOptions {
+    /// Drop matches on lines that `find_definition` reports.
+    pub exclude_definitions: bool,
+    /// Ignore occurrences inside comments and string literals.
+    pub code_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferenceMatch {
+    pub path: String,
+    pub line: u32,
+    pub snippet: String,
+    pub is_definition: bool,
+}
+
 pub fn find_references(
     root: &Path,
     name: &str,
     path: Option<&str>,
+    options: ReferenceOptions,
     max_matches: usize,
     max_files: usize,
     max_read_bytes: u64,
-) -> Result<Vec<CodeMatch>, AppError> {
-    let escaped = regex::escape(name);
-    let pattern = format!(r"\b{escaped}\b");
-    let matches = search::search_content(
-        root,
-        &pattern,
-        path,
-        Some(default_code_file_pattern()),
-        max_matches,
-        max_files,
-        max_read_bytes,
-    )?;
-    Ok(matches
+) -> Result<Vec<ReferenceMatch>, AppError> {
+    let definitions = find_definition(root, name, path, max_matches, max_files, max_read_bytes)?
         .into_iter()
-        .map(|m| CodeMatch {
-            path: m.path,
-            line: m.line,
-            snippet: m.snippet,
-        })
-        .collect())
+        .map(|m| (m.path, m.line))
+        .collect::<HashSet<_>>();
+
+    let rx = Regex::new(&format!(r"\b{}\b", regex::escape(name)))
+        .map_err(|e| AppError::Message(e.to_string()))?;
+    let root = security::canonicalize_root(root)?;
+    let rel_dir = path
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(security::validate_relative_path)
+        .transpose()?
+        .unwrap_or_default();
+
+    let mut out = Vec::new();
+    for file in search::walk_files(&root, &rel_dir, max_files)? {
+        if out.len() >= max_matches {
+            break;
+        }
+        let file_name = file.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        if !search::matches_file_pattern(file_name, Some(default_code_file_pattern()))? {
+            continue;
+        }
+        let rel = file
+            .strip_prefix(&root)
+            .unwrap_or(&file)
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        let mut bytes = Vec::new();
+        std::fs::File::open(&file)
+            .and_then(|f| {
+                f.take(max_read_bytes.saturating_mul(10))
+                    .read_to_end(&mut bytes)
+            })
+            .map_err(|e| AppError::Message(e.to_string()))?;
+        // The same sniff `search_content` uses, so both skip the same files.
+        if security::looks_binary(&bytes) {
+            continue;
+        }
+        let text = String::from_utf8_lossy(&bytes);
+        let scanned = if options.code_only {
+            let ext = Path::new(file_name)
+                .extension()
+                .and_then(|s| s.to_str())
+                .unwrap_or("");
+            mask_comments_and_strings(&text, &Syntax::for_extension(ext))
+        } else {
+            text.to_string()
+        };
+
+        for (idx, (code, original)) in scanned.lines().zip(text.lines()).enumerate() {
+            if out.len() >= max_matches {
+                break;
+            }
+            if !rx.is_match(code) {
+                continue;
+            }
+            let line = (idx + 1) as u32;
+            let is_definition = definitions.contains(&(rel.clone(), line));
+            if is_definition && options.exclude_definitions {
+                continue;
+            }
+            out.push(ReferenceMatch {
+                path: rel.clone(),
+                line,
+                snippet: original.trim().to_string(),
+                is_definition,
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// Comment and string delimiters of a language family, close enough for
+/// filtering search hits (raw strings and nested comments are not modeled).
+struct Syntax {
+    line_comment: &'static str,
+    block_comment: Option<(&'static str, &'static str)>,
+    /// Longest first, so `"""` wins over `"`.
+    quotes: &'static [&'static str],
+}
+
+impl Syntax {
+    fn for_extension(ext: &str) -> Self {
+        match ext {
+            "py" => Syntax {
+                line_comment: "#",
+                block_comment: None,
+                quotes: &["\"\"\"", "'''", "\"", "'"],
+            },
+            // `'` also starts lifetimes in Rust, so only double quotes delimit strings.
+            "rs" => Syntax {
+                line_comment: "//",
+                block_comment: Some(("/*", "*/")),
+                quotes: &["\""],
+            },
+            "ts" | "tsx" | "js" | "jsx" | "go" => Syntax {
+                line_comment: "//",
+                block_comment: Some(("/*", "*/")),
+                quotes: &["\"", "'", "`"],
+            },
+            _ => Syntax {
+                line_comment: "//",
+                block_comment: Some(("/*", "*/")),
+                quotes: &["\"", "'"],
+            },
+        }
+    }
+}
+
+/// Blank out comments and string literals with spaces, keeping newlines and
+/// byte offsets so line numbers still line up with the original text.
+fn mask_comments_and_strings(text: &str, syntax: &Syntax) -> String {
+    let bytes = text.as_bytes();
+    let mut out = bytes.to_vec();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        let end = if rest.starts_with(syntax.line_comment.as_bytes()) {
+            find_from(bytes, i, b"\n").unwrap_or(bytes.len())
+        } else if let Some((open, close)) = syntax
+            .block_comment
+            .filter(|(open, _)| rest.starts_with(open.as_bytes()))
+        {
+            find_from(bytes, i + open.len(), close.as_bytes())
+                .map(|at| at + close.len())
+                .unwrap_or(bytes.len())
+        } else if let Some(quote) = syntax
+            .quotes
+            .iter()
+            .find(|q| rest.starts_with(q.as_bytes()))
+        {
+            string_end(bytes, i + quote.len(), quote.as_bytes())
+        } else {
+            i += 1;
+            continue;
+        };
+        for b in &mut out[i..end] {
+            if *b != b'\n' {
+                *b = b' ';
+            }
+        }
+        i = end;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn find_from(bytes: &[u8], start: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(start..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|at| start + at)
+}
+
+/// End (exclusive) of a string literal whose body starts at `start`. Single-
+/// character quotes other than backticks stop at the end of the line.
+fn string_end(bytes: &[u8], start: usize, quote: &[u8]) -> usize {
+    let multiline = quote.len() > 1 || quote == b"`";
+    let mut j = start;
+    while j < bytes.len() {
+        if bytes[j] == b'\\' {
+            j += 2;
+            continue;
+        }
+        if bytes[j..].starts_with(quote) {
+            return j + quote.len();
+        }
+        if bytes[j] == b'\n' && !multiline {
+            return j;
+        }
+        j += 1;
+    }
+    bytes.len()
 }
 
 pub fn list_functions(
@@ -129,6 +372,128 @@ pub fn list_functions(
     Ok(out)
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionBody {
+    pub path: String,
+    pub name: String,
+    pub start_line: u32,
+    pub end_line: u32,
+    pub body: String,
+    /// The function is longer than `MAX_FUNCTION_LINES`; `body` holds its
+    /// first lines and `end_line` is still the real end.
+    pub truncated: bool,
+}
+
+/// Longest function body `read_function` returns in full.
+const MAX_FUNCTION_LINES: usize = 400;
+
+/// Return the first function named `name` in `path` (as found by
+/// `list_functions`), from its declaration to the brace that closes it, or in
+/// Python to the last line indented under the `def`.
+pub fn read_function(
+    root: &Path,
+    path: &str,
+    name: &str,
+    max_read_bytes: u64,
+) -> Result<FunctionBody, AppError> {
+    let start = list_functions(root, path, max_read_bytes)?
+        .into_iter()
+        .find(|m| m.snippet == name)
+        .map(|m| m.line as usize - 1)
+        .ok_or_else(|| AppError::Message(format!("Function '{name}' not found in {path}")))?;
+
+    let (text, _total_size, _truncated) = files::read_file(root, path, None, None, max_read_bytes)?;
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("");
+    // Match on masked text so braces and colons in comments or strings don't count.
+    let masked = mask_comments_and_strings(&text, &Syntax::for_extension(ext));
+    let lines: Vec<&str> = text.lines().collect();
+    let masked_lines: Vec<&str> = masked.lines().collect();
+    let end = if ext == "py" {
+        indented_block_end(&lines, &masked_lines, start)
+    } else {
+        brace_block_end(&masked_lines, start)
+    };
+
+    let shown = (end + 1 - start).min(MAX_FUNCTION_LINES);
+    Ok(FunctionBody {
+        path: path.to_string(),
+        name: name.to_string(),
+        start_line: (start + 1) as u32,
+        end_line: (end + 1) as u32,
+        body: lines[start..start + shown].join("\n"),
+        truncated: shown < end + 1 - start,
+    })
+}
+
+/// Index of the line holding the brace that closes the body opened after
+/// `start`. A `;` before any body (a trait or extern declaration) ends it.
+fn brace_block_end(masked: &[&str], start: usize) -> usize {
+    let mut depth = 0i32;
+    let mut brackets = 0i32;
+    let mut opened = false;
+    for (idx, line) in masked.iter().enumerate().skip(start) {
+        for c in line.chars() {
+            match c {
+                '(' | '[' => brackets += 1,
+                ')' | ']' => brackets -= 1,
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => depth -= 1,
+                ';' if !opened && brackets <= 0 => return idx,
+                _ => {}
+            }
+            if opened && depth <= 0 {
+                return idx;
+            }
+        }
+    }
+    masked.len().saturating_sub(1)
+}
+
+/// Index of the last line of a Python block: the header (which may wrap
+/// inside brackets) plus every following line indented deeper than it.
+fn indented_block_end(lines: &[&str], masked: &[&str], start: usize) -> usize {
+    let indent_of = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let indent = indent_of(lines[start]);
+
+    let mut brackets = 0i32;
+    let mut end = start;
+    for (idx, line) in masked.iter().enumerate().skip(start) {
+        end = idx;
+        for c in line.chars() {
+            match c {
+                '(' | '[' | '{' => brackets += 1,
+                ')' | ']' | '}' => brackets -= 1,
+                _ => {}
+            }
+        }
+        if brackets <= 0 {
+            break;
+        }
+    }
+
+    for idx in end + 1..lines.len() {
+        if masked[idx].trim().is_empty() {
+            // Blank lines don't end a block; lines inside a multi-line
+            // string are masked blank but still belong to it.
+            if !lines[idx].trim().is_empty() {
+                end = idx;
+            }
+            continue;
+        }
+        if indent_of(lines[idx]) <= indent {
+            break;
+        }
+        end = idx;
+    }
+    end
+}
+
 pub fn list_imports(
     root: &Path,
     path: &str,
@@ -156,3 +521,200 @@ pub fn list_imports(
     }
     Ok(out)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refs(root: &Path, name: &str, options: ReferenceOptions) -> Vec<(u32, bool)> {
+        find_references(root, name, None, options, 100, 100, 100_000)
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.line, m.is_definition))
+            .collect()
+    }
+
+    #[test]
+    fn flags_and_excludes_the_definition_line() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("lib.rs"),
+            "pub fn helper() -> u32 {\n    1\n}\n\nfn main() {\n    helper();\n}\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            refs(dir.path(), "helper", ReferenceOptions::default()),
+            vec![(1, true), (6, false)]
+        );
+        let usages = refs(
+            dir.path(),
+            "helper",
+            ReferenceOptions {
+                exclude_definitions: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(usages, vec![(6, false)]);
+    }
+
+    #[test]
+    fn binary_files_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "fn main() { helper(); }\n").unwrap();
+        std::fs::write(dir.path().join("blob.rs"), b"\0\x01helper\nhelper()\n").unwrap();
+
+        let hits = find_references(
+            dir.path(),
+            "helper",
+            None,
+            ReferenceOptions::default(),
+            100,
+            100,
+            100_000,
+        )
+        .unwrap();
+        let paths = hits.iter().map(|m| m.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["lib.rs"]);
+    }
+
+    #[test]
+    fn code_only_skips_comments_and_strings() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("main.rs"),
+            "// helper is deprecated\nlet s = \"helper\";\n/* helper\n   helper */\nlet r: &'static str = s; helper(); // helper\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("tool.py"),
+            "# helper\ndoc = \"\"\"\nhelper\n\"\"\"\nhelper()\n",
+        )
+        .unwrap();
+
+        let code_only = ReferenceOptions {
+            code_only: true,
+            ..Default::default()
+        };
+        let mut hits = find_references(dir.path(), "helper", None, code_only, 100, 100, 100_000)
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.path, m.line))
+            .collect::<Vec<_>>();
+        hits.sort();
+        assert_eq!(
+            hits,
+            vec![("main.rs".to_string(), 5), ("tool.py".to_string(), 5)]
+        );
+        assert_eq!(
+            refs(dir.path(), "helper", ReferenceOptions::default()).len(),
+            8
+        );
+    }
+
+    fn signature(root: &Path, name: &str) -> String {
+        let hits = find_definition(root, name, None, 10, 100, 100_000).unwrap();
+        assert_eq!(hits.len(), 1, "{hits:?}");
+        hits[0].signature.clone()
+    }
+
+    #[test]
+    fn definition_signature_on_a_single_line() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("lib.rs"),
+            "    pub fn helper(a: u32) -> u32 { a }\npub struct Point(u32, u32);\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("tool.py"),
+            "def run(x: int) -> int:\n    return x\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            signature(dir.path(), "helper"),
+            "pub fn helper(a: u32) -> u32"
+        );
+        assert_eq!(signature(dir.path(), "Point"), "pub struct Point(u32, u32)");
+        assert_eq!(signature(dir.path(), "run"), "def run(x: int) -> int:");
+    }
+
+    #[test]
+    fn definition_signature_follows_wrapped_parameters() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("lib.rs"),
+            "impl A {\n    pub async fn load(\n        root: &Path,\n        opts: Options,\n    ) -> Result<(), E> {\n        todo!()\n    }\n}\n",
+        )
+        .unwrap();
+
+        let hits = find_definition(dir.path(), "load", None, 10, 100, 100_000).unwrap();
+        assert_eq!(hits[0].snippet, "pub async fn load(");
+        assert_eq!(
+            hits[0].signature,
+            "pub async fn load(\n    root: &Path,\n    opts: Options,\n) -> Result<(), E>"
+        );
+    }
+
+    #[test]
+    fn definition_signature_lookahead_is_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let unclosed = format!("fn open(\n{}", "    a: u32,\n".repeat(50));
+        std::fs::write(dir.path().join("lib.rs"), unclosed).unwrap();
+
+        let sig = signature(dir.path(), "open");
+        assert_eq!(sig.lines().count(), MAX_SIGNATURE_LINES);
+    }
+
+    #[test]
+    fn reads_a_rust_function_with_nested_braces() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("lib.rs"),
+            "fn before() {}\n\npub fn build(items: [u8; 2]) -> Point {\n    let f = |x: u8| { x + 1 };\n    // stray } in a comment\n    let s = \"}\";\n    Point { x: f(items[0]), y: 0 }\n}\n\nfn after() {}\n",
+        )
+        .unwrap();
+
+        let found = read_function(dir.path(), "lib.rs", "build", 1 << 20).unwrap();
+        assert_eq!((found.start_line, found.end_line), (3, 8));
+        assert!(found.body.starts_with("pub fn build("));
+        assert!(found
+            .body
+            .ends_with("    Point { x: f(items[0]), y: 0 }\n}"));
+        assert!(!found.truncated);
+
+        let err = read_function(dir.path(), "lib.rs", "missing", 1 << 20).unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[test]
+    fn reads_a_python_def_by_indentation() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("app.py"),
+            "import os\n\ndef load(\n    path,\n):\n    \"\"\"Load it.\n\nStill the docstring.\n\"\"\"\n    if path:\n        return {\n  'a': 1,\n        }\n\n    return None\n\n\ndef other():\n    pass\n",
+        )
+        .unwrap();
+
+        let found = read_function(dir.path(), "app.py", "load", 1 << 20).unwrap();
+        assert_eq!((found.start_line, found.end_line), (3, 15));
+        assert!(found.body.ends_with("    return None"));
+        assert!(!found.body.contains("other"));
+    }
+
+    #[test]
+    fn long_function_bodies_are_truncated() {
+        let dir = tempfile::tempdir().unwrap();
+        let long = format!(
+            "fn big() {{\n{}}}\n",
+            "    step();\n".repeat(MAX_FUNCTION_LINES)
+        );
+        std::fs::write(dir.path().join("big.rs"), long).unwrap();
+
+        let found = read_function(dir.path(), "big.rs", "big", 1 << 20).unwrap();
+        assert!(found.truncated);
+        assert_eq!(found.end_line as usize, MAX_FUNCTION_LINES + 2);
+        assert_eq!(found.body.lines().count(), MAX_FUNCTION_LINES);
+    }
+}
@@ -73,7 +73,7 @@ pub fn read_file(
     let full = security::resolve_existing_path(&root, &rel)?;
     let meta = std::fs::metadata(&full).map_err(|e| AppError::Message(e.to_string()))?;
     if !meta.is_file() {
-        return Err(AppError::Message("Path is not a file".to_string()));
+        return Err(AppError::Message("Path is not a regular file".to_string()));
     }
 
     let total_size = meta.len();
@@ -115,7 +115,7 @@ pub fn read_file(
 
 pub fn write_file(root: &Path, path: &str, content: &str) -> Result<(), AppError> {
     let root = security::canonicalize_root(root)?;
-    let rel = security::validate_relative_path(path)?;
+    let rel = security::validate_writable_path(path)?;
     let full = security::resolve_write_path(&root, &rel)?;
     std::fs::write(full, content).map_err(|e| AppError::Message(e.to_string()))?;
     Ok(())
@@ -124,7 +124,7 @@ pub fn write_file(root: &Path, path: &str, content: &str) -> Result<(), AppError
 pub fn append_to_file(root: &Path, path: &str, content: &str) -> Result<(), AppError> {
     use std::io::Write;
     let root = security::canonicalize_root(root)?;
-    let rel = security::validate_relative_path(path)?;
+    let rel = security::validate_writable_path(path)?;
     let full = security::resolve_write_path(&root, &rel)?;
     let mut f = std::fs::OpenOptions::new()
         .create(true)
@@ -136,16 +136,34 @@ pub fn append_to_file(root: &Path, path: &str, content: &str) -> Result<(), AppE
     Ok(())
 }
 
-pub fn create_directory(root: &Path, path: &str) -> Result<(), AppError> {
+/// Whether `path` exists, is non-empty and does not end with a newline.
+pub fn lacks_trailing_newline(root: &Path, path: &str) -> Result<bool, AppError> {
+    use std::io::{Read, Seek, SeekFrom};
     let root = security::canonicalize_root(root)?;
     let rel = security::validate_relative_path(path)?;
+    let full = security::resolve_write_path(&root, &rel)?;
+    let Ok(mut f) = std::fs::File::open(full) else {
+        return Ok(false);
+    };
+    if f.seek(SeekFrom::End(-1)).is_err() {
+        return Ok(false);
+    }
+    let mut last = [0u8; 1];
+    f.read_exact(&mut last)
+        .map_err(|e| AppError::Message(e.to_string()))?;
+    Ok(last[0] != b'\n')
+}
+
+pub fn create_directory(root: &Path, path: &str) -> Result<(), AppError> {
+    let root = security::canonicalize_root(root)?;
+    let rel = security::validate_writable_path(path)?;
     let _ = security::ensure_safe_dir(&root, &rel)?;
     Ok(())
 }
 
 pub fn delete_file(root: &Path, path: &str) -> Result<(), AppError> {
     let root = security::canonicalize_root(root)?;
-    let rel = security::validate_relative_path(path)?;
+    let rel = security::validate_writable_path(path)?;
     let full = security::resolve_existing_path(&root, &rel)?;
 
     let meta = std::fs::metadata(&full).map_err(|e| AppError::Message(e.to_string()))?;
@@ -170,8 +188,8 @@ pub fn delete_file(root: &Path, path: &str) -> Result<(), AppError> {
 
 pub fn rename_file(root: &Path, old_path: &str, new_path: &str) -> Result<(), AppError> {
     let root = security::canonicalize_root(root)?;
-    let rel_old = security::validate_relative_path(old_path)?;
-    let rel_new = security::validate_relative_path(new_path)?;
+    let rel_old = security::validate_writable_path(old_path)?;
+    let rel_new = security::validate_writable_path(new_path)?;
 
     let src = security::resolve_existing_path(&root, &rel_old)?;
     let dst = security::resolve_write_path(&root, &rel_new)?;
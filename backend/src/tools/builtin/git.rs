@@ -0,0 +1,338 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::error::AppError;
+use crate::tools::definition::ToolDefinition;
+use crate::tools::security;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct StatusEntry {
+    pub path: String,
+    pub old_path: Option<String>,
+    /// Porcelain `X` column: state in the index (`' '` when unchanged).
+    pub index: char,
+    /// Porcelain `Y` column: state in the working tree.
+    pub worktree: char,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GitDiff {
+    pub diff: String,
+    pub truncated: bool,
+}
+
+pub fn definitions() -> Vec<ToolDefinition> {
+    vec![
+        ToolDefinition {
+            name: "git_status".to_string(),
+            description:
+                "List changed, staged and untracked files in the workspace git repository."
+                    .to_string(),
+            parameters: json!({ "type": "object", "properties": {}, "required": [] }),
+        },
+        ToolDefinition {
+            name: "git_diff".to_string(),
+            description:
+                "Show the unified diff of uncommitted changes in the workspace git repository."
+                    .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Limit the diff to this relative path (optional)." },
+                    "staged": { "type": "boolean", "description": "Diff the index against HEAD instead of the working tree (default false)." }
+                },
+                "required": []
+            }),
+        },
+    ]
+}
+
+/// Whether a `git` binary can be run at all; checked once per process.
+pub fn git_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        Command::new("git")
+            .arg("--version")
+            .stdin(Stdio::null())
+            .output()
+            .is_ok_and(|out| out.status.success())
+    })
+}
+
+pub fn git_status(root: &Path) -> Result<Vec<StatusEntry>, AppError> {
+    let prefix = repo_prefix(root)?;
+    let out = run_git(
+        root,
+        &[
+            "status",
+            "--porcelain=v1",
+            "-z",
+            "--untracked-files=all",
+            "--",
+            ".",
+        ],
+    )?;
+    Ok(parse_porcelain(&out)
+        .into_iter()
+        .map(|mut entry| {
+            entry.path = strip_prefix(&entry.path, &prefix);
+            entry.old_path = entry.old_path.map(|p| strip_prefix(&p, &prefix));
+            entry
+        })
+        .collect())
+}
+
+pub fn git_diff(
+    root: &Path,
+    path: Option<&str>,
+    staged: bool,
+    max_bytes: u64,
+) -> Result<GitDiff, AppError> {
+    repo_prefix(root)?;
+    let pathspec = match path.map(str::trim).filter(|p| !p.is_empty()) {
+        Some(p) => {
+            let rel = security::validate_relative_path(p)?;
+            format!(":(literal){}", rel.to_string_lossy())
+        }
+        None => ".".to_string(),
+    };
+    let mut args = vec![
+        "diff",
+        "--no-color",
+        "--no-ext-diff",
+        "--no-textconv",
+        "--relative",
+    ];
+    if staged {
+        args.push("--cached");
+    }
+    args.push("--");
+    args.push(&pathspec);
+
+    let mut diff = run_git(root, &args)?;
+    let truncated = diff.len() as u64 > max_bytes;
+    if truncated {
+        let mut end = max_bytes as usize;
+        while !diff.is_char_boundary(end) {
+            end -= 1;
+        }
+        diff.truncate(end);
+    }
+    Ok(GitDiff { diff, truncated })
+}
+
+/// The workspace's path inside its repository (e.g. `sub/dir/`), or an error
+/// if the workspace isn't in a git work tree.
+fn repo_prefix(root: &Path) -> Result<String, AppError> {
+    run_git(root, &["rev-parse", "--show-prefix"])
+        .map(|out| out.trim_end_matches('\n').to_string())
+        .map_err(|_| AppError::Message("Workspace is not a git repository".to_string()))
+}
+
+/// Run a read-only git subcommand in `root` without prompts, pagers or
+/// user/system config hooks that could execute other programs.
+fn run_git(root: &Path, args: &[&str]) -> Result<String, AppError> {
+    if !git_available() {
+        return Err(AppError::Message("git is not available".to_string()));
+    }
+    let out = Command::new("git")
+        .args([
+            "--no-pager",
+            "--no-optional-locks",
+            "-c",
+            "core.fsmonitor=false",
+            "-c",
+            "core.hooksPath=/dev/null",
+            "-c",
+            "diff.external=",
+            "-c",
+            "core.pager=cat",
+            "-c",
+            "core.attributesFile=/dev/null",
+        ])
+        .args(args)
+        .current_dir(root)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| AppError::Message(format!("Failed to run git: {e}")))?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        return Err(AppError::Message(format!("git failed: {}", stderr.trim())));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+fn parse_porcelain(out: &str) -> Vec<StatusEntry> {
+    let mut entries = Vec::new();
+    let mut fields = out.split('\0').filter(|f| !f.is_empty());
+    while let Some(field) = fields.next() {
+        let mut chars = field.chars();
+        let (Some(index), Some(worktree)) = (chars.next(), chars.next()) else {
+            continue;
+        };
+        let path = field.get(3..).unwrap_or_default().to_string();
+        // Renames and copies are followed by their source path.
+        let old_path = if matches!(index, 'R' | 'C') || matches!(worktree, 'R' | 'C') {
+            fields.next().map(str::to_string)
+        } else {
+            None
+        };
+        entries.push(StatusEntry {
+            path,
+            old_path,
+            index,
+            worktree,
+        });
+    }
+    entries
+}
+
+fn strip_prefix(path: &str, prefix: &str) -> String {
+    path.strip_prefix(prefix).unwrap_or(path).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(root: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+            .args(args)
+            .current_dir(root)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?}");
+    }
+
+    fn repo() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q"]);
+        std::fs::write(dir.path().join("kept.txt"), "one\n").unwrap();
+        std::fs::write(dir.path().join("old.txt"), "move me\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "init"]);
+        dir
+    }
+
+    #[test]
+    fn parses_porcelain_status_with_renames() {
+        let out = " M src/a.rs\0R  new.rs\0old.rs\0?? notes.md\0A  added.txt\0";
+        assert_eq!(
+            parse_porcelain(out),
+            vec![
+                StatusEntry {
+                    path: "src/a.rs".to_string(),
+                    old_path: None,
+                    index: ' ',
+                    worktree: 'M'
+                },
+                StatusEntry {
+                    path: "new.rs".to_string(),
+                    old_path: Some("old.rs".to_string()),
+                    index: 'R',
+                    worktree: ' '
+                },
+                StatusEntry {
+                    path: "notes.md".to_string(),
+                    old_path: None,
+                    index: '?',
+                    worktree: '?'
+                },
+                StatusEntry {
+                    path: "added.txt".to_string(),
+                    old_path: None,
+                    index: 'A',
+                    worktree: ' '
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_status_and_diff_in_a_repo() {
+        if !git_available() {
+            return;
+        }
+        let dir = repo();
+        std::fs::write(dir.path().join("kept.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(dir.path().join("new file.txt"), "x\n").unwrap();
+        git(dir.path(), &["mv", "old.txt", "moved.txt"]);
+
+        let status = git_status(dir.path()).unwrap();
+        let find = |p: &str| status.iter().find(|e| e.path == p).cloned().unwrap();
+        assert_eq!(find("kept.txt").worktree, 'M');
+        assert_eq!(find("new file.txt").index, '?');
+        assert_eq!(find("moved.txt").old_path.as_deref(), Some("old.txt"));
+
+        let diff = git_diff(dir.path(), Some("kept.txt"), false, 10_000).unwrap();
+        assert!(diff.diff.contains("+two"));
+        assert!(!diff.truncated);
+        let staged = git_diff(dir.path(), None, true, 10_000).unwrap();
+        assert!(staged.diff.contains("moved.txt"));
+        assert!(!staged.diff.contains("+two"));
+    }
+
+    #[test]
+    fn paths_are_relative_to_a_workspace_inside_the_repo() {
+        if !git_available() {
+            return;
+        }
+        let dir = repo();
+        let sub = dir.path().join("sub");
+        std::fs::create_dir(&sub).unwrap();
+        std::fs::write(sub.join("inner.txt"), "x\n").unwrap();
+        std::fs::write(dir.path().join("kept.txt"), "changed\n").unwrap();
+
+        let status = git_status(&sub).unwrap();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].path, "inner.txt");
+    }
+
+    #[test]
+    fn non_repo_workspace_is_a_clear_error() {
+        if !git_available() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let err = git_status(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("not a git repository"));
+        assert!(git_diff(dir.path(), None, false, 100).is_err());
+    }
+
+    #[test]
+    fn diff_never_runs_textconv_filters() {
+        if !git_available() {
+            return;
+        }
+        let dir = repo();
+        let marker = dir.path().join("textconv-ran");
+        let textconv = format!("touch '{}' && cat", marker.display());
+        git(dir.path(), &["config", "diff.conv.textconv", &textconv]);
+        std::fs::write(dir.path().join(".gitattributes"), "*.txt diff=conv\n").unwrap();
+        std::fs::write(dir.path().join("kept.txt"), "one\ntwo\n").unwrap();
+
+        let diff = git_diff(dir.path(), Some("kept.txt"), false, 10_000).unwrap();
+        assert!(diff.diff.contains("+two"));
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn diff_is_truncated_to_max_bytes() {
+        if !git_available() {
+            return;
+        }
+        let dir = repo();
+        std::fs::write(dir.path().join("kept.txt"), "x\n".repeat(500)).unwrap();
+        let diff = git_diff(dir.path(), None, false, 64).unwrap();
+        assert!(diff.truncated);
+        assert_eq!(diff.diff.len(), 64);
+    }
+}
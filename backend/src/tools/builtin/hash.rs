@@ -0,0 +1,234 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use md5::Md5;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::AppError;
+use crate::tools::builtin::search::walk_files;
+use crate::tools::security;
+
+/// Files are hashed in chunks of this size, so no size cap applies.
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Digest used by `hash_file` and `hash_directory`, from their `algo` argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Md5,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub fn parse(raw: Option<&str>) -> Result<Self, AppError> {
+        match raw.map(|s| s.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") | Some("sha256") => Ok(Self::Sha256),
+            Some("md5") => Ok(Self::Md5),
+            Some("blake3") => Ok(Self::Blake3),
+            Some(other) => Err(AppError::Message(format!(
+                "Unknown hash algorithm '{other}': use sha256, md5 or blake3"
+            ))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Md5 => "md5",
+            Self::Blake3 => "blake3",
+        }
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            Self::Sha256 => Hasher::Sha256(Sha256::new()),
+            Self::Md5 => Hasher::Md5(Md5::new()),
+            Self::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+}
+
+enum Hasher {
+    Sha256(Sha256),
+    Md5(Md5),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Sha256(h) => h.update(bytes),
+            Self::Md5(h) => h.update(bytes),
+            Self::Blake3(h) => {
+                h.update(bytes);
+            }
+        }
+    }
+
+    fn finalize_hex(self) -> String {
+        let bytes = match self {
+            Self::Sha256(h) => h.finalize().to_vec(),
+            Self::Md5(h) => h.finalize().to_vec(),
+            Self::Blake3(h) => return h.finalize().to_hex().to_string(),
+        };
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryHash {
+    pub path: String,
+    pub algo: &'static str,
+    pub hash: String,
+    pub files: usize,
+}
+
+/// Hex digest of a whole file, read in chunks rather than through the text
+/// read cap.
+pub fn hash_file(root: &Path, path: &str, algo: HashAlgorithm) -> Result<String, AppError> {
+    let root = security::canonicalize_root(root)?;
+    let rel = security::validate_relative_path(path)?;
+    let full = security::resolve_existing_path(&root, &rel)?;
+    if !full.is_file() {
+        return Err(AppError::Message(format!("{path} is not a file")));
+    }
+    digest_file(&full, algo)
+}
+
+/// A tree hash of every file under `path`: the digest of a manifest listing
+/// each file's digest and path relative to `path`, sorted by path. Two
+/// directories with the same files and contents hash equally wherever they
+/// are. Symlinks are skipped; more than `max_files` files is an error.
+pub fn hash_directory(
+    root: &Path,
+    path: Option<&str>,
+    algo: HashAlgorithm,
+    max_files: usize,
+) -> Result<DirectoryHash, AppError> {
+    let root = security::canonicalize_root(root)?;
+    let rel_dir = path
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(security::validate_relative_path)
+        .transpose()?
+        .unwrap_or_else(|| PathBuf::from(""));
+    let dir = security::resolve_existing_path(&root, &rel_dir)?;
+
+    let files = walk_files(&root, &rel_dir, max_files.saturating_add(1))?;
+    if files.len() > max_files {
+        return Err(AppError::Message(format!(
+            "Directory has more than {max_files} files; hash a subdirectory instead"
+        )));
+    }
+    let mut entries = files
+        .into_iter()
+        .map(|file| {
+            let rel = file
+                .strip_prefix(&dir)
+                .unwrap_or(&file)
+                .to_string_lossy()
+                .replace('\\', "/");
+            (rel, file)
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut tree = algo.hasher();
+    for (rel, file) in &entries {
+        let digest = digest_file(file, algo)?;
+        tree.update(format!("{digest}  {rel}\n").as_bytes());
+    }
+    Ok(DirectoryHash {
+        path: path.unwrap_or_default().trim().to_string(),
+        algo: algo.as_str(),
+        hash: tree.finalize_hex(),
+        files: entries.len(),
+    })
+}
+
+pub(crate) fn digest_file(full: &Path, algo: HashAlgorithm) -> Result<String, AppError> {
+    let mut file = std::fs::File::open(full).map_err(|e| AppError::Message(e.to_string()))?;
+    let mut hasher = algo.hasher();
+    let mut buf = vec![0u8; CHUNK_BYTES];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .map_err(|e| AppError::Message(e.to_string()))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn tmp_root() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn file_digests_match_known_values() {
+        let (_d, root) = tmp_root();
+        fs::write(root.join("abc.txt"), "abc").unwrap();
+        assert_eq!(
+            hash_file(&root, "abc.txt", HashAlgorithm::Sha256).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hash_file(&root, "abc.txt", HashAlgorithm::Md5).unwrap(),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            hash_file(&root, "abc.txt", HashAlgorithm::Blake3)
+                .unwrap()
+                .len(),
+            64
+        );
+        assert!(HashAlgorithm::parse(Some("crc32")).is_err());
+
+        // Larger than one chunk and than the default text read cap.
+        let big = "x".repeat(300_000);
+        fs::write(root.join("big.txt"), &big).unwrap();
+        let expected = Sha256::digest(big.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        assert_eq!(
+            hash_file(&root, "big.txt", HashAlgorithm::Sha256).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn identical_files_and_trees_hash_equally() {
+        let (_d, root) = tmp_root();
+        for dir in ["a", "b"] {
+            fs::create_dir_all(root.join(dir).join("nested")).unwrap();
+            fs::write(root.join(dir).join("one.txt"), "same").unwrap();
+            fs::write(root.join(dir).join("nested/two.txt"), "also same").unwrap();
+        }
+        assert_eq!(
+            hash_file(&root, "a/one.txt", HashAlgorithm::Sha256).unwrap(),
+            hash_file(&root, "b/one.txt", HashAlgorithm::Sha256).unwrap()
+        );
+
+        let a = hash_directory(&root, Some("a"), HashAlgorithm::Sha256, 100).unwrap();
+        let b = hash_directory(&root, Some("b"), HashAlgorithm::Sha256, 100).unwrap();
+        assert_eq!(a.hash, b.hash);
+        assert_eq!(a.files, 2);
+
+        fs::write(root.join("b/nested/two.txt"), "different").unwrap();
+        let changed = hash_directory(&root, Some("b"), HashAlgorithm::Sha256, 100).unwrap();
+        assert_ne!(a.hash, changed.hash);
+        assert!(hash_directory(&root, Some("a"), HashAlgorithm::Sha256, 1).is_err());
+    }
+}
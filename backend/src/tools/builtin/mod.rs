@@ -1,5 +1,7 @@
 pub mod code;
 pub mod files;
+pub mod git;
+pub mod hash;
 pub mod search;
 pub mod text;
 
@@ -31,13 +33,27 @@ pub fn definitions() -> Vec<ToolDefinition> {
                 "required": ["path"]
             }),
         },
+        ToolDefinition {
+            name: "summarize_file".to_string(),
+            description: "Summarize a text file of any size by reading it in chunks. Use for files too large for read_file."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": { "path": { "type": "string" } },
+                "required": ["path"]
+            }),
+        },
         ToolDefinition {
             name: "write_file".to_string(),
             description: "Write or create a UTF-8 text file under the execution workspace."
                 .to_string(),
             parameters: json!({
                 "type": "object",
-                "properties": { "path": { "type": "string" }, "content": { "type": "string" } },
+                "properties": {
+                    "path": { "type": "string" },
+                    "content": { "type": "string" },
+                    "ensure_trailing_newline": { "type": "boolean", "description": "End the file with a newline (default false)." }
+                },
                 "required": ["path", "content"]
             }),
         },
@@ -78,7 +94,8 @@ pub fn definitions() -> Vec<ToolDefinition> {
                 "properties": {
                     "pattern": { "type": "string" },
                     "path": { "type": "string", "description": "Relative directory path (optional)." },
-                    "file_pattern": { "type": "string", "description": "Optional filename filter (glob-like, e.g. \"*.rs\")." }
+                    "file_pattern": { "type": "string", "description": "Optional filename filter (glob-like, e.g. \"*.rs\")." },
+                    "report_skipped": { "type": "boolean", "description": "Return { matches, skipped_binary_files } instead of the bare match list (default false). Binary files are never searched." }
                 },
                 "required": ["pattern"]
             }),
@@ -114,19 +131,51 @@ pub fn definitions() -> Vec<ToolDefinition> {
                 "required": ["path"]
             }),
         },
+        ToolDefinition {
+            name: "hash_file".to_string(),
+            description: "Compute the hex digest of a whole file under the workspace, e.g. to verify it or detect changes.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "algo": { "type": "string", "enum": ["sha256", "md5", "blake3"], "description": "Defaults to sha256." }
+                },
+                "required": ["path"]
+            }),
+        },
+        ToolDefinition {
+            name: "hash_directory".to_string(),
+            description: "Compute one tree hash over every file in a directory under the workspace; identical trees hash equally.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Relative directory path (optional)." },
+                    "algo": { "type": "string", "enum": ["sha256", "md5", "blake3"], "description": "Defaults to sha256." }
+                },
+                "required": []
+            }),
+        },
         ToolDefinition {
             name: "diff_files".to_string(),
-            description: "Compute a unified diff between two text files under the workspace."
+            description: "Compute a diff between two text files under the workspace."
                 .to_string(),
             parameters: json!({
                 "type": "object",
-                "properties": { "path1": { "type": "string" }, "path2": { "type": "string" } },
+                "properties": {
+                    "path1": { "type": "string" },
+                    "path2": { "type": "string" },
+                    "format": {
+                        "type": "string",
+                        "enum": ["unified", "context", "inline", "stats"],
+                        "description": "unified (default), context, inline (changed words marked [-old-]/{+new+}) or stats (added/removed line counts)."
+                    }
+                },
                 "required": ["path1", "path2"]
             }),
         },
         ToolDefinition {
             name: "find_definition".to_string(),
-            description: "Find likely function/class/type definitions by name (regex-based)."
+            description: "Find likely function/class/type definitions by name (regex-based), with each one's full signature."
                 .to_string(),
             parameters: json!({
                 "type": "object",
@@ -145,7 +194,9 @@ pub fn definitions() -> Vec<ToolDefinition> {
                 "type": "object",
                 "properties": {
                     "name": { "type": "string" },
-                    "path": { "type": "string", "description": "Relative directory path (optional)." }
+                    "path": { "type": "string", "description": "Relative directory path (optional)." },
+                    "exclude_definitions": { "type": "boolean", "description": "Skip lines where the name is defined (default false)." },
+                    "code_only": { "type": "boolean", "description": "Ignore matches in comments and string literals (default false)." }
                 },
                 "required": ["name"]
             }),
@@ -159,6 +210,18 @@ pub fn definitions() -> Vec<ToolDefinition> {
                 "required": ["path"]
             }),
         },
+        ToolDefinition {
+            name: "read_function".to_string(),
+            description: "Read one function's full body by name, with its start and end lines, instead of the whole file.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "name": { "type": "string", "description": "Function name as reported by list_functions." }
+                },
+                "required": ["path", "name"]
+            }),
+        },
         ToolDefinition {
             name: "list_imports".to_string(),
             description: "List imports in a file (regex-based).".to_string(),
@@ -170,50 +233,72 @@ pub fn definitions() -> Vec<ToolDefinition> {
         },
         ToolDefinition {
             name: "replace_in_file".to_string(),
-            description: "Replace content in a file using a regular expression.".to_string(),
+            description: "Replace content in a file using a regular expression, or exact text with literal=true."
+                .to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
                     "path": { "type": "string" },
                     "search": { "type": "string" },
                     "replace": { "type": "string" },
-                    "all": { "type": "boolean" }
+                    "all": { "type": "boolean" },
+                    "literal": { "type": "boolean", "description": "Match and insert the text exactly, without regex syntax (default false)." }
                 },
                 "required": ["path", "search", "replace"]
             }),
         },
         ToolDefinition {
             name: "insert_at_line".to_string(),
-            description: "Insert content at a 1-based line number in a file.".to_string(),
+            description: "Insert content at a 1-based line number in a file. Negative lines count from the end: -1 appends at EOF.".to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
                     "path": { "type": "string" },
-                    "line": { "type": "integer", "minimum": 1 },
-                    "content": { "type": "string" }
+                    "line": { "type": "integer", "description": "1-based line to insert before, or negative to count from the end (-1 appends)." },
+                    "content": { "type": "string" },
+                    "auto_indent": { "type": "boolean", "description": "Re-indent content to match the line it is inserted at (default false)." }
                 },
                 "required": ["path", "line", "content"]
             }),
         },
         ToolDefinition {
             name: "delete_lines".to_string(),
-            description: "Delete an inclusive 1-based line range in a file.".to_string(),
+            description: "Delete an inclusive 1-based line range in a file. Negative bounds count from the end: -1 is the last line.".to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
                     "path": { "type": "string" },
-                    "start": { "type": "integer", "minimum": 1 },
-                    "end": { "type": "integer", "minimum": 1 }
+                    "start": { "type": "integer" },
+                    "end": { "type": "integer" }
                 },
                 "required": ["path", "start", "end"]
             }),
         },
+        ToolDefinition {
+            name: "replace_lines".to_string(),
+            description: "Replace an inclusive 1-based line range in a file with new content in one step.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "start": { "type": "integer", "minimum": 1 },
+                    "end": { "type": "integer", "minimum": 1 },
+                    "content": { "type": "string", "description": "Replacement lines; empty deletes the range." }
+                },
+                "required": ["path", "start", "end", "content"]
+            }),
+        },
         ToolDefinition {
             name: "append_to_file".to_string(),
             description: "Append content to a file.".to_string(),
             parameters: json!({
                 "type": "object",
-                "properties": { "path": { "type": "string" }, "content": { "type": "string" } },
+                "properties": {
+                    "path": { "type": "string" },
+                    "content": { "type": "string" },
+                    "ensure_trailing_newline": { "type": "boolean", "description": "End the appended block with a newline (default false)." },
+                    "prepend_newline_if_needed": { "type": "boolean", "description": "Start on a new line if the file does not end with one (default false)." }
+                },
                 "required": ["path", "content"]
             }),
         },
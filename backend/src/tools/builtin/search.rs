@@ -1,7 +1,9 @@
+use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
 
 use regex::Regex;
 use serde::Serialize;
+use similar::{ChangeTag, DiffTag};
 
 use crate::error::AppError;
 use crate::tools::builtin::files;
@@ -15,6 +17,13 @@ pub struct ContentMatch {
     pub snippet: String,
 }
 
+/// What `search_content` found, and how many files it passed over as binary.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentSearch {
+    pub matches: Vec<ContentMatch>,
+    pub skipped_binary_files: usize,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct FileInfo {
     pub path: String,
@@ -23,7 +32,11 @@ pub struct FileInfo {
     pub modified_unix_ms: Option<u64>,
 }
 
-fn walk_files(root: &Path, start_rel: &Path, max_files: usize) -> Result<Vec<PathBuf>, AppError> {
+pub(crate) fn walk_files(
+    root: &Path,
+    start_rel: &Path,
+    max_files: usize,
+) -> Result<Vec<PathBuf>, AppError> {
     let root = security::canonicalize_root(root)?;
     let start = security::resolve_existing_path(&root, start_rel)?;
     if !start.is_dir() {
@@ -59,7 +72,10 @@ fn walk_files(root: &Path, start_rel: &Path, max_files: usize) -> Result<Vec<Pat
     Ok(out)
 }
 
-fn matches_file_pattern(file_name: &str, pattern: Option<&str>) -> Result<bool, AppError> {
+pub(crate) fn matches_file_pattern(
+    file_name: &str,
+    pattern: Option<&str>,
+) -> Result<bool, AppError> {
     let Some(pat) = pattern.map(|s| s.trim()).filter(|s| !s.is_empty()) else {
         return Ok(true);
     };
@@ -99,7 +115,7 @@ pub fn search_content(
     max_matches: usize,
     max_files: usize,
     max_read_bytes: u64,
-) -> Result<Vec<ContentMatch>, AppError> {
+) -> Result<ContentSearch, AppError> {
     let root = security::canonicalize_root(root)?;
     let rel_dir = path
         .map(|s| s.trim())
@@ -112,6 +128,7 @@ pub fn search_content(
     let files = walk_files(&root, &rel_dir, max_files)?;
 
     let mut out = Vec::new();
+    let mut skipped_binary_files = 0;
     for file in files {
         if out.len() >= max_matches {
             break;
@@ -126,28 +143,47 @@ pub fn search_content(
             .to_string_lossy()
             .replace('\\', "/");
 
-        let meta = std::fs::metadata(&file).map_err(|e| AppError::Message(e.to_string()))?;
-        if meta.len() > max_read_bytes.saturating_mul(10) {
+        // Stream line by line so only one line is held in memory at a time; very
+        // large files are scanned up to a byte cap instead of being skipped.
+        let handle = std::fs::File::open(&file).map_err(|e| AppError::Message(e.to_string()))?;
+        let mut reader = BufReader::with_capacity(
+            security::BINARY_SNIFF_BYTES,
+            handle.take(max_read_bytes.saturating_mul(10)),
+        );
+        let head = reader
+            .fill_buf()
+            .map_err(|e| AppError::Message(e.to_string()))?;
+        if security::looks_binary(head) {
+            skipped_binary_files += 1;
             continue;
         }
-
-        let (text, _total_size, _truncated) =
-            files::read_file(&root, &rel, None, None, max_read_bytes)?;
-        for (idx, line) in text.lines().enumerate() {
-            if out.len() >= max_matches {
+        let mut buf = Vec::new();
+        let mut line_no: u32 = 0;
+        while out.len() < max_matches {
+            buf.clear();
+            let read = reader
+                .read_until(b'\n', &mut buf)
+                .map_err(|e| AppError::Message(e.to_string()))?;
+            if read == 0 {
                 break;
             }
+            line_no += 1;
+            let raw = String::from_utf8_lossy(&buf);
+            let line = raw.trim_end_matches(['\n', '\r']);
             if let Some(m) = rx.find(line) {
                 out.push(ContentMatch {
                     path: rel.clone(),
-                    line: (idx + 1) as u32,
+                    line: line_no,
                     column: (m.start() + 1) as u32,
                     snippet: line.trim().to_string(),
                 });
             }
         }
     }
-    Ok(out)
+    Ok(ContentSearch {
+        matches: out,
+        skipped_binary_files,
+    })
 }
 
 pub fn search_files(
@@ -215,20 +251,322 @@ pub fn count_lines(root: &Path, path: &str, max_read_bytes: u64) -> Result<u64,
     Ok(text.lines().count() as u64)
 }
 
+/// Output shape of `diff_files`, from its `format` argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffFormat {
+    /// `diff -u` style hunks.
+    #[default]
+    Unified,
+    /// `diff -c` style hunks, showing each side of a change separately.
+    Context,
+    /// Unified hunks with changed words marked `[-old-]` / `{+new+}`.
+    Inline,
+    /// Only the added/removed line counts.
+    Stats,
+}
+
+impl DiffFormat {
+    pub fn parse(raw: Option<&str>) -> Result<Self, AppError> {
+        match raw.map(|s| s.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") | Some("unified") => Ok(Self::Unified),
+            Some("context") => Ok(Self::Context),
+            Some("inline") => Ok(Self::Inline),
+            Some("stats") => Ok(Self::Stats),
+            Some(other) => Err(AppError::Message(format!(
+                "Unknown diff format '{other}' (expected unified, context, inline or stats)"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DiffStats {
+    pub added: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffOutput {
+    Text(String),
+    Stats(DiffStats),
+}
+
+/// Lines of unchanged context around each hunk.
+const DIFF_CONTEXT_LINES: usize = 3;
+
 pub fn diff_files(
     root: &Path,
     path1: &str,
     path2: &str,
+    format: DiffFormat,
     max_read_bytes: u64,
-) -> Result<String, AppError> {
+) -> Result<DiffOutput, AppError> {
     let (a, _a_total_size, a_trunc) = files::read_file(root, path1, None, None, max_read_bytes)?;
     let (b, _b_total_size, b_trunc) = files::read_file(root, path2, None, None, max_read_bytes)?;
-    let diff = similar::TextDiff::from_lines(&a, &b)
-        .unified_diff()
-        .header(path1, path2)
-        .to_string();
+    let text_diff = similar::TextDiff::from_lines(&a, &b);
+    let diff = match format {
+        DiffFormat::Stats => {
+            let mut stats = DiffStats {
+                added: 0,
+                removed: 0,
+            };
+            for change in text_diff.iter_all_changes() {
+                match change.tag() {
+                    ChangeTag::Insert => stats.added += 1,
+                    ChangeTag::Delete => stats.removed += 1,
+                    ChangeTag::Equal => {}
+                }
+            }
+            return Ok(DiffOutput::Stats(stats));
+        }
+        DiffFormat::Unified => text_diff
+            .unified_diff()
+            .context_radius(DIFF_CONTEXT_LINES)
+            .header(path1, path2)
+            .to_string(),
+        DiffFormat::Context => context_diff(&text_diff, path1, path2),
+        DiffFormat::Inline => inline_diff(&text_diff, path1, path2),
+    };
     if a_trunc || b_trunc {
-        return Ok(format!("{diff}\n\n[diff truncated due to file size limit]"));
+        return Ok(DiffOutput::Text(format!(
+            "{diff}\n\n[diff truncated due to file size limit]"
+        )));
+    }
+    Ok(DiffOutput::Text(diff))
+}
+
+fn push_line(out: &mut String, prefix: &str, line: &str) {
+    out.push_str(prefix);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// `diff -c` layout: each hunk lists the old lines, then the new ones, marking
+/// removals `- `, additions `+ ` and replaced lines `! `. A side with no
+/// changes of its own is left out, as GNU diff does.
+fn context_diff(diff: &similar::TextDiff<'_, '_, '_, str>, path1: &str, path2: &str) -> String {
+    let mut out = String::new();
+    let groups = diff.grouped_ops(DIFF_CONTEXT_LINES);
+    if groups.is_empty() {
+        return out;
+    }
+    out.push_str(&format!("*** {path1}\n--- {path2}\n"));
+    let (old, new) = (diff.old_slices(), diff.new_slices());
+    for group in groups {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+        let old_changed = group
+            .iter()
+            .any(|op| matches!(op.tag(), DiffTag::Delete | DiffTag::Replace));
+        let new_changed = group
+            .iter()
+            .any(|op| matches!(op.tag(), DiffTag::Insert | DiffTag::Replace));
+
+        out.push_str("***************\n");
+        out.push_str(&format!("*** {} ****\n", line_range(&old_range)));
+        if old_changed {
+            for op in &group {
+                let prefix = match op.tag() {
+                    DiffTag::Equal => "  ",
+                    DiffTag::Delete => "- ",
+                    DiffTag::Replace => "! ",
+                    DiffTag::Insert => continue,
+                };
+                for line in &old[op.old_range()] {
+                    push_line(&mut out, prefix, line);
+                }
+            }
+        }
+        out.push_str(&format!("--- {} ----\n", line_range(&new_range)));
+        if new_changed {
+            for op in &group {
+                let prefix = match op.tag() {
+                    DiffTag::Equal => "  ",
+                    DiffTag::Insert => "+ ",
+                    DiffTag::Replace => "! ",
+                    DiffTag::Delete => continue,
+                };
+                for line in &new[op.new_range()] {
+                    push_line(&mut out, prefix, line);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// A 0-based line range as `diff -c` prints it: 1-based `start,end`, or a
+/// single number for a one-line range.
+fn line_range(range: &std::ops::Range<usize>) -> String {
+    match range.len() {
+        0 => range.start.to_string(),
+        1 => (range.start + 1).to_string(),
+        _ => format!("{},{}", range.start + 1, range.end),
+    }
+}
+
+/// Unified hunks where the words that changed inside a modified line are
+/// wrapped as `[-removed-]` and `{+added+}`.
+fn inline_diff<'a>(
+    diff: &'a similar::TextDiff<'a, 'a, '_, str>,
+    path1: &str,
+    path2: &str,
+) -> String {
+    let mut out = String::new();
+    let groups = diff.grouped_ops(DIFF_CONTEXT_LINES);
+    if groups.is_empty() {
+        return out;
+    }
+    out.push_str(&format!("--- {path1}\n+++ {path2}\n"));
+    for group in groups {
+        out.push_str("@@\n");
+        for op in &group {
+            for change in diff.iter_inline_changes(op) {
+                let (prefix, open, close) = match change.tag() {
+                    ChangeTag::Equal => (" ", "", ""),
+                    ChangeTag::Delete => ("-", "[-", "-]"),
+                    ChangeTag::Insert => ("+", "{+", "+}"),
+                };
+                let mut line = String::new();
+                for (emphasized, value) in change.iter_strings_lossy() {
+                    if emphasized {
+                        line.push_str(open);
+                        line.push_str(&value);
+                        line.push_str(close);
+                    } else {
+                        line.push_str(&value);
+                    }
+                }
+                push_line(&mut out, prefix, &line);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn tmp_root() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn search_content_stops_at_max_matches_on_large_file() {
+        let (_d, root) = tmp_root();
+        let body = (1..=200_000)
+            .map(|i| format!("line {i} needle"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        fs::write(root.join("big.txt"), body).unwrap();
+
+        let matches = search_content(&root, "needle", None, None, 5, 10, 1_000_000)
+            .unwrap()
+            .matches;
+        assert_eq!(matches.len(), 5);
+        let lines = matches.iter().map(|m| m.line).collect::<Vec<_>>();
+        assert_eq!(lines, vec![1, 2, 3, 4, 5]);
+        assert_eq!(matches[0].snippet, "line 1 needle");
+    }
+
+    #[test]
+    fn search_content_reports_line_and_column_deep_in_file() {
+        let (_d, root) = tmp_root();
+        let mut body = "filler\r\n".repeat(50_000);
+        body.push_str("  let target = 1;\n");
+        fs::write(root.join("deep.rs"), body).unwrap();
+
+        let matches = search_content(&root, "target", None, None, 10, 10, 1_000_000)
+            .unwrap()
+            .matches;
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path, "deep.rs");
+        assert_eq!(matches[0].line, 50_001);
+        assert_eq!(matches[0].column, 7);
+        assert_eq!(matches[0].snippet, "let target = 1;");
+    }
+
+    #[test]
+    fn search_content_skips_binary_files() {
+        let (_d, root) = tmp_root();
+        fs::write(root.join("notes.txt"), "find the needle\n").unwrap();
+        let mut blob = b"needle\0\x89PNG".to_vec();
+        blob.extend_from_slice(b"\nneedle again\n");
+        fs::write(root.join("image.bin"), blob).unwrap();
+
+        let found = search_content(&root, "needle", None, None, 10, 10, 1_000_000).unwrap();
+        let paths = found
+            .matches
+            .iter()
+            .map(|m| m.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["notes.txt"]);
+        assert_eq!(found.skipped_binary_files, 1);
+    }
+
+    fn diff_pair(root: &Path) {
+        fs::write(root.join("a.txt"), "one\nthe quick fox\nthree\nfour\n").unwrap();
+        fs::write(root.join("b.txt"), "one\nthe slow fox\nthree\nfive\nsix\n").unwrap();
+    }
+
+    fn text(out: DiffOutput) -> String {
+        match out {
+            DiffOutput::Text(s) => s,
+            DiffOutput::Stats(stats) => panic!("expected text, got {stats:?}"),
+        }
+    }
+
+    #[test]
+    fn diff_stats_count_added_and_removed_lines() {
+        let (_dir, root) = tmp_root();
+        diff_pair(&root);
+        let out = diff_files(&root, "a.txt", "b.txt", DiffFormat::Stats, 10_000).unwrap();
+        assert_eq!(
+            out,
+            DiffOutput::Stats(DiffStats {
+                added: 3,
+                removed: 2
+            })
+        );
+    }
+
+    #[test]
+    fn inline_diff_marks_changed_words() {
+        let (_dir, root) = tmp_root();
+        diff_pair(&root);
+        let out = text(diff_files(&root, "a.txt", "b.txt", DiffFormat::Inline, 10_000).unwrap());
+        assert!(out.contains("-the [-quick-] fox\n"), "{out}");
+        assert!(out.contains("+the {+slow+} fox\n"), "{out}");
+        assert!(out.contains(" one\n"));
+    }
+
+    #[test]
+    fn diff_formats_default_to_unified() {
+        let (_dir, root) = tmp_root();
+        diff_pair(&root);
+        assert_eq!(DiffFormat::parse(None).unwrap(), DiffFormat::Unified);
+        assert!(DiffFormat::parse(Some("side-by-side")).is_err());
+
+        let unified =
+            text(diff_files(&root, "a.txt", "b.txt", DiffFormat::Unified, 10_000).unwrap());
+        assert!(unified.starts_with("--- a.txt\n+++ b.txt\n@@"));
+        assert!(unified.contains("-the quick fox\n+the slow fox\n"));
+
+        let context =
+            text(diff_files(&root, "a.txt", "b.txt", DiffFormat::Context, 10_000).unwrap());
+        assert!(context.starts_with("*** a.txt\n--- b.txt\n***************\n*** 1,4 ****\n"));
+        assert!(context.contains("! the quick fox\n"));
+        assert!(context.contains("--- 1,5 ----\n"));
+        assert!(context.contains("! the slow fox\n"));
+        assert!(context.contains("! five\n") || context.contains("+ six\n"));
     }
-    Ok(diff)
 }
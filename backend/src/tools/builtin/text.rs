@@ -5,73 +5,166 @@ use regex::Regex;
 use crate::error::AppError;
 use crate::tools::builtin::files;
 
+/// The whole of `path`, for a tool that rewrites it. A file over
+/// `max_read_bytes` is refused: writing back its truncated text would cut
+/// off everything past the limit.
+fn read_for_edit(root: &Path, path: &str, max_read_bytes: u64) -> Result<String, AppError> {
+    let (text, total_size, truncated) = files::read_file(root, path, None, None, max_read_bytes)?;
+    if truncated {
+        return Err(AppError::Message(format!(
+            "{path} is {total_size} bytes, over the {max_read_bytes}-byte read limit, so it can't be edited in place"
+        )));
+    }
+    Ok(text)
+}
+
+/// Replace `search` in a file. With `literal`, both `search` and `replace`
+/// are taken as exact text instead of a regex and its `$n` expansions.
 pub fn replace_in_file(
     root: &Path,
     path: &str,
     search: &str,
     replace: &str,
     all: bool,
+    literal: bool,
     max_read_bytes: u64,
 ) -> Result<u64, AppError> {
-    let (text, _total_size, _truncated) = files::read_file(root, path, None, None, max_read_bytes)?;
-    let rx = Regex::new(search).map_err(|e| AppError::Message(e.to_string()))?;
+    let text = read_for_edit(root, path, max_read_bytes)?;
+    let pattern = if literal {
+        regex::escape(search)
+    } else {
+        search.to_string()
+    };
+    let rx = Regex::new(&pattern).map_err(|e| AppError::Message(e.to_string()))?;
 
-    let mut count: u64 = 0;
-    let next = if all {
-        count = rx.find_iter(&text).count() as u64;
-        rx.replace_all(&text, replace).to_string()
+    let (limit, count) = if all {
+        (0, rx.find_iter(&text).count() as u64)
+    } else {
+        (1, u64::from(rx.is_match(&text)))
+    };
+    let next = if literal {
+        rx.replacen(&text, limit, regex::NoExpand(replace))
+            .to_string()
     } else {
-        if rx.is_match(&text) {
-            count = 1;
-        }
-        rx.replace(&text, replace).to_string()
+        rx.replacen(&text, limit, replace).to_string()
     };
 
     files::write_file(root, path, &next)?;
     Ok(count)
 }
 
+/// Resolve a negative line address against the `slots` addressable
+/// positions, counting back from the last one: `-1` is the last slot. Zero
+/// and positive lines pass through unchanged.
+fn from_end(line: i64, slots: usize) -> Result<u64, AppError> {
+    if line >= 0 {
+        return Ok(line as u64);
+    }
+    let resolved = slots as i64 + 1 + line;
+    if resolved < 1 {
+        return Err(AppError::Message(format!(
+            "Line {line} counts back past the start of the file"
+        )));
+    }
+    Ok(resolved as u64)
+}
+
+/// Insert `content` before the 1-based `line`, or append it past EOF. A
+/// negative `line` counts from the end: `-1` appends, `-2` inserts before the
+/// last line. Returns the 1-based line the content now starts at. One
+/// trailing newline is taken as the content's line terminator; blank lines
+/// inside or after it are kept. With `auto_indent`, the content is
+/// re-indented to the leading whitespace of the target line (or the nearest
+/// non-blank line above it), keeping its own relative indentation.
 pub fn insert_at_line(
     root: &Path,
     path: &str,
-    line: u64,
+    line: i64,
     content: &str,
+    auto_indent: bool,
     max_read_bytes: u64,
-) -> Result<(), AppError> {
-    let (text, _total_size, _truncated) = files::read_file(root, path, None, None, max_read_bytes)?;
+) -> Result<u64, AppError> {
+    let text = read_for_edit(root, path, max_read_bytes)?;
     let mut lines: Vec<String> = text.lines().map(|s| s.to_string()).collect();
-    let idx = line.saturating_sub(1) as usize;
-    let insert = content.trim_end_matches('\n').to_string();
-    if idx >= lines.len() {
-        lines.push(insert);
-    } else {
-        lines.insert(idx, insert);
+    let line = from_end(line, lines.len() + 1)?;
+    let idx = (line.saturating_sub(1) as usize).min(lines.len());
+    let content = content
+        .strip_suffix('\n')
+        .map(|c| c.strip_suffix('\r').unwrap_or(c))
+        .unwrap_or(content);
+    let mut insert: Vec<String> = content.split('\n').map(|s| s.to_string()).collect();
+    if auto_indent {
+        let indent = lines[..(idx + 1).min(lines.len())]
+            .iter()
+            .rev()
+            .find(|l| !l.trim().is_empty())
+            .map(|l| leading_whitespace(l).to_string())
+            .unwrap_or_default();
+        insert = reindent(&insert, &indent);
     }
+    lines.splice(idx..idx, insert);
     let next = if text.ends_with('\n') {
         format!("{}\n", lines.join("\n"))
     } else {
         lines.join("\n")
     };
     files::write_file(root, path, &next)?;
-    Ok(())
+    Ok(idx as u64 + 1)
 }
 
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Replace the indentation the non-blank `lines` share with `indent`.
+/// Blank lines are left empty rather than padded.
+fn reindent(lines: &[String], indent: &str) -> Vec<String> {
+    let common = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| leading_whitespace(l))
+        .reduce(|a, b| {
+            let shared: usize = a
+                .chars()
+                .zip(b.chars())
+                .take_while(|(x, y)| x == y)
+                .map(|(x, _)| x.len_utf8())
+                .sum();
+            &a[..shared]
+        })
+        .unwrap_or("");
+    lines
+        .iter()
+        .map(|l| {
+            if l.trim().is_empty() {
+                String::new()
+            } else {
+                format!("{indent}{}", l.strip_prefix(common).unwrap_or(l))
+            }
+        })
+        .collect()
+}
+
+/// Delete the inclusive 1-based line range `start..=end`. Negative bounds
+/// count from the end, so `-1` is the last line and `-3..=-1` the last
+/// three. Returns the resolved range.
 pub fn delete_lines(
     root: &Path,
     path: &str,
-    start: u64,
-    end: u64,
+    start: i64,
+    end: i64,
     max_read_bytes: u64,
-) -> Result<(), AppError> {
+) -> Result<(u64, u64), AppError> {
+    let text = read_for_edit(root, path, max_read_bytes)?;
+    let mut lines: Vec<String> = text.lines().map(|s| s.to_string()).collect();
+    let (start, end) = (from_end(start, lines.len())?, from_end(end, lines.len())?);
     if end < start {
         return Err(AppError::Message("end must be >= start".to_string()));
     }
-    let (text, _total_size, _truncated) = files::read_file(root, path, None, None, max_read_bytes)?;
-    let mut lines: Vec<String> = text.lines().map(|s| s.to_string()).collect();
     let s = start.saturating_sub(1) as usize;
     let e = end.saturating_sub(1) as usize;
     if s >= lines.len() {
-        return Ok(());
+        return Ok((start, end));
     }
     let end_idx = e.min(lines.len().saturating_sub(1));
     lines.drain(s..=end_idx);
@@ -81,9 +174,320 @@ pub fn delete_lines(
         lines.join("\n")
     };
     files::write_file(root, path, &next)?;
-    Ok(())
+    Ok((start, end))
+}
+
+/// Replace the inclusive 1-based line range `start..=end` with `content` in
+/// one write. An `end` past EOF is clamped; a `start` past EOF appends.
+/// Returns how many existing lines were replaced.
+pub fn replace_lines(
+    root: &Path,
+    path: &str,
+    start: u64,
+    end: u64,
+    content: &str,
+    max_read_bytes: u64,
+) -> Result<u64, AppError> {
+    if start == 0 {
+        return Err(AppError::Message("start must be >= 1".to_string()));
+    }
+    if end < start {
+        return Err(AppError::Message("end must be >= start".to_string()));
+    }
+    let text = read_for_edit(root, path, max_read_bytes)?;
+    let mut lines: Vec<String> = text.lines().map(|s| s.to_string()).collect();
+    let s = ((start - 1) as usize).min(lines.len());
+    let e = (end as usize).min(lines.len());
+    let replacement = content
+        .trim_end_matches('\n')
+        .lines()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+    let removed = lines.splice(s..e, replacement).count() as u64;
+    let next = if text.ends_with('\n') {
+        format!("{}\n", lines.join("\n"))
+    } else {
+        lines.join("\n")
+    };
+    files::write_file(root, path, &next)?;
+    Ok(removed)
+}
+
+/// Optional newline handling for `write_file` and `append_to_file`. Both flags
+/// default to off so content is written exactly as given.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NewlineOptions {
+    pub ensure_trailing_newline: bool,
+    /// Append only: start on a new line if the file doesn't already end with one.
+    pub prepend_newline_if_needed: bool,
+}
+
+pub fn write_file(
+    root: &Path,
+    path: &str,
+    content: &str,
+    opts: NewlineOptions,
+) -> Result<(), AppError> {
+    if opts.ensure_trailing_newline && !content.ends_with('\n') {
+        return files::write_file(root, path, &format!("{content}\n"));
+    }
+    files::write_file(root, path, content)
 }
 
-pub fn append_to_file(root: &Path, path: &str, content: &str) -> Result<(), AppError> {
-    files::append_to_file(root, path, content)
+pub fn append_to_file(
+    root: &Path,
+    path: &str,
+    content: &str,
+    opts: NewlineOptions,
+) -> Result<(), AppError> {
+    let mut block = String::with_capacity(content.len() + 2);
+    if opts.prepend_newline_if_needed
+        && !content.starts_with('\n')
+        && files::lacks_trailing_newline(root, path)?
+    {
+        block.push('\n');
+    }
+    block.push_str(content);
+    if opts.ensure_trailing_newline && !block.ends_with('\n') {
+        block.push('\n');
+    }
+    files::append_to_file(root, path, &block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOTH: NewlineOptions = NewlineOptions {
+        ensure_trailing_newline: true,
+        prepend_newline_if_needed: true,
+    };
+
+    fn read(root: &Path, path: &str) -> String {
+        std::fs::read_to_string(root.join(path)).unwrap()
+    }
+
+    #[test]
+    fn appended_blocks_are_newline_separated_when_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("log.md"), "first").unwrap();
+
+        append_to_file(root, "log.md", "second", BOTH).unwrap();
+        append_to_file(root, "log.md", "third", BOTH).unwrap();
+        assert_eq!(read(root, "log.md"), "first\nsecond\nthird\n");
+    }
+
+    #[test]
+    fn prepend_only_adds_missing_separator() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let prepend = NewlineOptions {
+            prepend_newline_if_needed: true,
+            ..Default::default()
+        };
+
+        append_to_file(root, "new.md", "a", prepend).unwrap();
+        assert_eq!(read(root, "new.md"), "a");
+        std::fs::write(root.join("done.md"), "a\n").unwrap();
+        append_to_file(root, "done.md", "b", prepend).unwrap();
+        assert_eq!(read(root, "done.md"), "a\nb");
+    }
+
+    #[test]
+    fn raw_behavior_is_the_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_file(root, "x.txt", "one", NewlineOptions::default()).unwrap();
+        append_to_file(root, "x.txt", "two", NewlineOptions::default()).unwrap();
+        assert_eq!(read(root, "x.txt"), "onetwo");
+
+        write_file(root, "y.txt", "line", BOTH).unwrap();
+        assert_eq!(read(root, "y.txt"), "line\n");
+    }
+
+    #[test]
+    fn replace_lines_swaps_a_mid_file_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("a.txt"), "one\ntwo\nthree\nfour\n").unwrap();
+
+        let replaced = replace_lines(root, "a.txt", 2, 3, "TWO\nTHREE\nextra\n", 10_000).unwrap();
+        assert_eq!(replaced, 2);
+        assert_eq!(read(root, "a.txt"), "one\nTWO\nTHREE\nextra\nfour\n");
+    }
+
+    #[test]
+    fn replace_lines_handles_the_last_line_and_past_eof() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("a.txt"), "one\ntwo").unwrap();
+
+        assert_eq!(
+            replace_lines(root, "a.txt", 2, 9, "last", 10_000).unwrap(),
+            1
+        );
+        assert_eq!(read(root, "a.txt"), "one\nlast");
+        assert_eq!(
+            replace_lines(root, "a.txt", 5, 6, "tail", 10_000).unwrap(),
+            0
+        );
+        assert_eq!(read(root, "a.txt"), "one\nlast\ntail");
+    }
+
+    #[test]
+    fn replace_lines_rejects_invalid_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("a.txt"), "one\ntwo\n").unwrap();
+
+        assert!(replace_lines(root, "a.txt", 2, 1, "x", 10_000).is_err());
+        assert!(replace_lines(root, "a.txt", 0, 1, "x", 10_000).is_err());
+        assert_eq!(read(root, "a.txt"), "one\ntwo\n");
+    }
+
+    #[test]
+    fn files_over_the_read_limit_are_not_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let body = "line\n".repeat(100);
+        std::fs::write(root.join("big.txt"), &body).unwrap();
+
+        let err = replace_lines(root, "big.txt", 1, 1, "first", 64).unwrap_err();
+        assert!(err.to_string().contains("read limit"));
+        assert!(replace_in_file(root, "big.txt", "line", "x", true, true, 64).is_err());
+        // `-1` would otherwise resolve against the first 64 bytes only.
+        assert!(insert_at_line(root, "big.txt", -1, "tail", false, 64).is_err());
+        assert!(delete_lines(root, "big.txt", -1, -1, 64).is_err());
+        assert_eq!(read(root, "big.txt"), body);
+    }
+
+    #[test]
+    fn literal_mode_replaces_metacharacters_exactly() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("a.js"), "a.b();\naxb();\n").unwrap();
+
+        let count = replace_in_file(root, "a.js", "a.b()", "c.d($1)", true, true, 10_000).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(read(root, "a.js"), "c.d($1);\naxb();\n");
+    }
+
+    #[test]
+    fn regex_mode_treats_metacharacters_as_pattern() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("a.js"), "a.b();\naxb();\n").unwrap();
+
+        // `.` matches any character and `()` is an empty group.
+        let count = replace_in_file(root, "a.js", "a.b()", "z", true, false, 10_000).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(read(root, "a.js"), "z();\nz();\n");
+
+        let first_only = replace_in_file(root, "a.js", "z", "y", false, false, 10_000).unwrap();
+        assert_eq!(first_only, 1);
+        assert_eq!(read(root, "a.js"), "y();\nz();\n");
+    }
+
+    #[test]
+    fn insert_matches_the_target_lines_indentation() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let source = "fn main() {\n    let a = 1;\n}\n";
+        std::fs::write(root.join("a.rs"), source).unwrap();
+
+        insert_at_line(root, "a.rs", 2, "if a > 0 {\n    go();\n}\n", true, 10_000).unwrap();
+        assert_eq!(
+            read(root, "a.rs"),
+            "fn main() {\n    if a > 0 {\n        go();\n    }\n    let a = 1;\n}\n"
+        );
+
+        // A missing target line takes the indentation above it.
+        std::fs::write(root.join("b.rs"), "    x();\n").unwrap();
+        insert_at_line(root, "b.rs", 9, "\t\ty();", true, 10_000).unwrap();
+        assert_eq!(read(root, "b.rs"), "    x();\n    y();\n");
+
+        std::fs::write(root.join("c.rs"), "    x();\n").unwrap();
+        insert_at_line(root, "c.rs", 1, "y();", false, 10_000).unwrap();
+        assert_eq!(read(root, "c.rs"), "y();\n    x();\n");
+    }
+
+    #[test]
+    fn reindent_compares_indentation_by_character() {
+        // EM SPACE and EN SPACE share their first two UTF-8 bytes.
+        let lines = vec!["\u{2003}a".to_string(), "\u{2002}b".to_string()];
+        assert_eq!(reindent(&lines, "  "), vec!["  \u{2003}a", "  \u{2002}b"]);
+
+        let lines = vec!["\u{3000}\u{3000}a".to_string(), "\u{3000}b".to_string()];
+        assert_eq!(reindent(&lines, ""), vec!["\u{3000}a", "b"]);
+    }
+
+    #[test]
+    fn multi_line_inserts_keep_blank_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("a.py"), "def f():\n    pass\n").unwrap();
+
+        insert_at_line(
+            root,
+            "a.py",
+            2,
+            "  first()\n\n\n  second()\n\n",
+            true,
+            10_000,
+        )
+        .unwrap();
+        assert_eq!(
+            read(root, "a.py"),
+            "def f():\n    first()\n\n\n    second()\n\n    pass\n"
+        );
+    }
+
+    #[test]
+    fn negative_lines_count_from_the_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+
+        assert_eq!(
+            insert_at_line(root, "a.txt", -1, "four", false, 10_000).unwrap(),
+            4
+        );
+        assert_eq!(read(root, "a.txt"), "one\ntwo\nthree\nfour\n");
+        assert_eq!(
+            insert_at_line(root, "a.txt", -2, "3.5", false, 10_000).unwrap(),
+            4
+        );
+        assert_eq!(read(root, "a.txt"), "one\ntwo\nthree\n3.5\nfour\n");
+
+        assert_eq!(delete_lines(root, "a.txt", -2, -1, 10_000).unwrap(), (4, 5));
+        assert_eq!(read(root, "a.txt"), "one\ntwo\nthree\n");
+        assert_eq!(delete_lines(root, "a.txt", 2, -1, 10_000).unwrap(), (2, 3));
+        assert_eq!(read(root, "a.txt"), "one\n");
+
+        // Positive lines keep their 1-based meaning.
+        std::fs::write(root.join("b.txt"), "a\nb\n").unwrap();
+        assert_eq!(
+            insert_at_line(root, "b.txt", 1, "z", false, 10_000).unwrap(),
+            1
+        );
+        assert_eq!(read(root, "b.txt"), "z\na\nb\n");
+    }
+
+    #[test]
+    fn negative_lines_past_the_start_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("a.txt"), "one\ntwo\n").unwrap();
+
+        // Three insert positions in a two-line file: -3 is the top, -4 is out.
+        assert!(insert_at_line(root, "a.txt", -4, "x", false, 10_000).is_err());
+        assert_eq!(
+            insert_at_line(root, "a.txt", -3, "zero", false, 10_000).unwrap(),
+            1
+        );
+        assert!(delete_lines(root, "a.txt", -4, -1, 10_000).is_err());
+        assert!(delete_lines(root, "a.txt", -1, -2, 10_000).is_err());
+        assert_eq!(read(root, "a.txt"), "zero\none\ntwo\n");
+    }
 }
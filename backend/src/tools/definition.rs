@@ -28,6 +28,9 @@ pub struct ToolResult {
     pub error: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub duration_ms: Option<u64>,
+    /// Refused by the execution's tool_policy without running.
+    #[serde(default)]
+    pub denied: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
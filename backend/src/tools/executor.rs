@@ -1,11 +1,15 @@
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Instant;
 
 use serde_json::Value;
 
 use crate::error::AppError;
+use crate::metrics::Metrics;
 use crate::tools::builtin;
 use crate::tools::definition::{ToolCall, ToolResult};
+use crate::tools::lock::{is_mutating, WorkspaceLocks};
+use crate::tools::policy::ToolPolicy;
 use crate::tools::security;
 
 #[derive(Debug, Clone)]
@@ -31,48 +35,161 @@ impl Default for ToolLimits {
 pub struct ToolExecutor {
     root: PathBuf,
     limits: ToolLimits,
+    lock: Option<Arc<tokio::sync::RwLock<()>>>,
+    allow_commands: bool,
+    /// Refuse, rather than warn about, writes whose name differs only in case
+    /// from an existing entry; see `security::case_collision`.
+    strict_path_case: bool,
+    policy: ToolPolicy,
+    metrics: Option<Arc<Metrics>>,
+    /// Extra roots besides `root`; empty for a single-root workspace.
+    mounts: Vec<Mount>,
 }
 
+/// A named extra workspace root, addressed by prefixing tool paths with `name:`.
+#[derive(Debug, Clone)]
+struct Mount {
+    name: String,
+    root: PathBuf,
+    lock: Option<Arc<tokio::sync::RwLock<()>>>,
+}
+
+/// Where one call runs, after any root prefix was stripped from its paths.
+struct Target {
+    root: PathBuf,
+    lock: Option<Arc<tokio::sync::RwLock<()>>>,
+    args: Value,
+}
+
+/// Tool arguments that hold workspace paths and may carry a root prefix.
+const PATH_KEYS: [&str; 5] = ["path", "old_path", "new_path", "path1", "path2"];
+
 impl ToolExecutor {
     pub fn new(workspace_root: PathBuf) -> Result<Self, AppError> {
         let root = security::canonicalize_root(&workspace_root)?;
         Ok(Self {
             root,
             limits: ToolLimits::default(),
+            lock: None,
+            allow_commands: false,
+            strict_path_case: false,
+            policy: ToolPolicy::unrestricted(),
+            metrics: None,
+            mounts: Vec::new(),
         })
     }
 
+    /// Mount another directory as `name`, so tool paths like `name:doc.md`
+    /// resolve inside it. Add roots before calling `with_locks`.
+    pub fn with_root(mut self, name: &str, path: PathBuf) -> Result<Self, AppError> {
+        let name = name.trim();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(AppError::Message(format!(
+                "Invalid workspace root name '{name}': use letters, digits, '_' or '-'"
+            )));
+        }
+        if self.mount(name).is_some() {
+            return Err(AppError::Message(format!(
+                "Workspace root '{name}' is configured twice"
+            )));
+        }
+        let root = security::canonicalize_root(&path)?;
+        self.mounts.push(Mount {
+            name: name.to_string(),
+            root,
+            lock: None,
+        });
+        Ok(self)
+    }
+
+    /// Share the workspace locks from `locks` with every other executor on the same roots.
+    pub fn with_locks(mut self, locks: &WorkspaceLocks) -> Self {
+        self.lock = Some(locks.for_root(&self.root));
+        for mount in &mut self.mounts {
+            mount.lock = Some(locks.for_root(&mount.root));
+        }
+        self
+    }
+
     #[allow(dead_code)]
     pub fn with_limits(mut self, limits: ToolLimits) -> Self {
         self.limits = limits;
         self
     }
 
+    /// Allow tools that shell out to external programs (currently read-only `git`).
+    pub fn with_commands(mut self, allow: bool) -> Self {
+        self.allow_commands = allow;
+        self
+    }
+
+    pub fn with_strict_path_case(mut self, strict: bool) -> Self {
+        self.strict_path_case = strict;
+        self
+    }
+
+    /// Limit which tool categories may run; others are hidden from
+    /// `definitions` and fail in `execute`.
+    pub fn with_policy(mut self, policy: ToolPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Count every execution (and timeout) in the app-wide `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     pub fn definitions(&self) -> Vec<crate::tools::definition::ToolDefinition> {
-        builtin::definitions()
+        let mut defs = builtin::definitions();
+        if self.allow_commands && builtin::git::git_available() {
+            defs.extend(builtin::git::definitions());
+        }
+        defs.retain(|def| self.policy.allows(&def.name));
+        if !self.mounts.is_empty() {
+            let names: Vec<String> = self
+                .mounts
+                .iter()
+                .map(|m| format!("`{}:`", m.name))
+                .collect();
+            let note = format!(
+                " Prefix a path with {} to use that workspace root instead of the default one; results are relative to the root used.",
+                names.join(" or ")
+            );
+            for def in &mut defs {
+                let takes_path = PATH_KEYS
+                    .iter()
+                    .any(|key| def.parameters["properties"].get(key).is_some());
+                if takes_path {
+                    def.description.push_str(&note);
+                }
+            }
+        }
+        defs
     }
 
     pub async fn execute(&self, call: ToolCall) -> ToolResult {
         let started = Instant::now();
-        let root = self.root.clone();
-        let limits = self.limits.clone();
         let name = call.name.clone();
         let id = call.id.clone();
-        let args = call.arguments.clone();
 
-        let timeout_ms = limits.timeout_ms;
-        let name_for_exec = name.clone();
-        let fut = tokio::task::spawn_blocking(move || {
-            execute_blocking(&root, &limits, &name_for_exec, &args)
-        });
-        let output =
-            match tokio::time::timeout(std::time::Duration::from_millis(timeout_ms), fut).await {
-                Ok(Ok(res)) => res,
-                Ok(Err(join_err)) => Err(AppError::Message(join_err.to_string())),
-                Err(_) => Err(AppError::Message("Tool execution timed out".to_string())),
-            };
+        let mut timed_out = false;
+        let allowed = self.policy.check(&name);
+        let denied = allowed.is_err();
+        let output = match allowed.and_then(|_| self.route(&call.arguments)) {
+            Ok(target) => self.run(target, &name, &mut timed_out).await,
+            Err(e) => Err(e),
+        };
 
         let duration_ms = started.elapsed().as_millis().min(u128::from(u64::MAX)) as u64;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_tool_execution(timed_out);
+        }
         match output {
             Ok(v) => ToolResult {
                 tool_call_id: id,
@@ -81,6 +198,7 @@ impl ToolExecutor {
                 output: v,
                 error: None,
                 duration_ms: Some(duration_ms),
+                denied,
             },
             Err(e) => ToolResult {
                 tool_call_id: id,
@@ -89,11 +207,144 @@ impl ToolExecutor {
                 output: serde_json::json!({}),
                 error: Some(e.to_string()),
                 duration_ms: Some(duration_ms),
+                denied,
             },
         }
     }
 }
 
+// Held only for its `Drop`.
+#[allow(dead_code)]
+enum WorkspaceGuard {
+    Shared(tokio::sync::OwnedRwLockReadGuard<()>),
+    Exclusive(tokio::sync::OwnedRwLockWriteGuard<()>),
+}
+
+impl ToolExecutor {
+    /// Pick the root a call's paths refer to and strip their `name:` prefixes.
+    /// Every path in one call must resolve to the same root.
+    fn route(&self, args: &Value) -> Result<Target, AppError> {
+        let mut target = Target {
+            root: self.root.clone(),
+            lock: self.lock.clone(),
+            args: args.clone(),
+        };
+        if self.mounts.is_empty() {
+            return Ok(target);
+        }
+
+        let mut mount: Option<&Mount> = None;
+        let mut unprefixed = false;
+        for key in PATH_KEYS {
+            let Some(value) = args.get(key).and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let named = value
+                .split_once(':')
+                .and_then(|(prefix, rest)| Some((self.mount(prefix)?, rest)));
+            let Some((m, rest)) = named else {
+                unprefixed = true;
+                continue;
+            };
+            if mount.is_some_and(|prev| prev.name != m.name) {
+                return Err(AppError::Message(
+                    "All paths in one tool call must be in the same workspace root".to_string(),
+                ));
+            }
+            mount = Some(m);
+            target.args[key] = Value::String(rest.to_string());
+        }
+        if let Some(m) = mount {
+            if unprefixed {
+                return Err(AppError::Message(
+                    "All paths in one tool call must be in the same workspace root".to_string(),
+                ));
+            }
+            target.root = m.root.clone();
+            target.lock = m.lock.clone();
+        }
+        Ok(target)
+    }
+
+    fn mount(&self, name: &str) -> Option<&Mount> {
+        self.mounts.iter().find(|m| m.name == name)
+    }
+
+    async fn run(
+        &self,
+        target: Target,
+        name: &str,
+        timed_out: &mut bool,
+    ) -> Result<Value, AppError> {
+        let Target { root, lock, args } = target;
+        let limits = self.limits.clone();
+        let strict_path_case = self.strict_path_case;
+        let timeout = std::time::Duration::from_millis(limits.timeout_ms);
+        match self.acquire(lock, &root, name, timeout).await {
+            // Arguments the provider couldn't parse or repair as JSON.
+            Ok(_) if args.is_string() => Err(AppError::Message(format!(
+                "Invalid JSON arguments for tool '{name}'. Call it again with the arguments as a valid JSON object."
+            ))),
+            Ok(_) if is_command(name) && !self.allow_commands => Err(AppError::Message(format!(
+                "Tool '{name}' requires allow_commands"
+            ))),
+            Ok(guard) => {
+                let name_for_exec = name.to_string();
+                let fut = tokio::task::spawn_blocking(move || {
+                    // Held until the blocking work finishes, even if the call times out.
+                    let _guard = guard;
+                    execute_blocking(&root, &limits, strict_path_case, &name_for_exec, &args)
+                });
+                match tokio::time::timeout(timeout, fut).await {
+                    Ok(Ok(res)) => res,
+                    Ok(Err(join_err)) => Err(AppError::Message(join_err.to_string())),
+                    Err(_) => {
+                        *timed_out = true;
+                        Err(AppError::Message("Tool execution timed out".to_string()))
+                    }
+                }
+            }
+            // Waiting for the workspace lock ran out the same timeout.
+            Err(e) => {
+                *timed_out = true;
+                Err(e)
+            }
+        }
+    }
+
+    async fn acquire(
+        &self,
+        lock: Option<Arc<tokio::sync::RwLock<()>>>,
+        root: &Path,
+        tool_name: &str,
+        timeout: std::time::Duration,
+    ) -> Result<Option<WorkspaceGuard>, AppError> {
+        let Some(lock) = lock else {
+            return Ok(None);
+        };
+        let guard = if is_mutating(tool_name) {
+            tokio::time::timeout(timeout, lock.write_owned())
+                .await
+                .map(WorkspaceGuard::Exclusive)
+        } else {
+            tokio::time::timeout(timeout, lock.read_owned())
+                .await
+                .map(WorkspaceGuard::Shared)
+        };
+        guard.map(Some).map_err(|_| {
+            AppError::Message(format!(
+                "Workspace {} is locked by another execution",
+                root.display()
+            ))
+        })
+    }
+}
+
+/// Tools that run an external program rather than touching files directly.
+fn is_command(tool_name: &str) -> bool {
+    matches!(tool_name, "git_status" | "git_diff")
+}
+
 fn as_str(args: &Value, key: &str) -> Option<String> {
     args.get(key)
         .and_then(|v| v.as_str())
@@ -104,17 +355,61 @@ fn as_bool(args: &Value, key: &str) -> Option<bool> {
     args.get(key).and_then(|v| v.as_bool())
 }
 
+fn newline_options(args: &Value) -> builtin::text::NewlineOptions {
+    builtin::text::NewlineOptions {
+        ensure_trailing_newline: as_bool(args, "ensure_trailing_newline").unwrap_or(false),
+        prepend_newline_if_needed: as_bool(args, "prepend_newline_if_needed").unwrap_or(false),
+    }
+}
+
 fn as_u64(args: &Value, key: &str) -> Option<u64> {
     args.get(key).and_then(|v| v.as_u64())
 }
 
+fn as_i64(args: &Value, key: &str) -> Option<i64> {
+    args.get(key).and_then(|v| v.as_i64())
+}
+
+/// Warn about `path` differing only in case from an existing entry, or
+/// refuse it when `strict`. `renaming_from` is allowed to match, so a
+/// case-only rename goes through.
+fn case_warning(
+    root: &Path,
+    path: &str,
+    renaming_from: Option<&str>,
+    strict: bool,
+) -> Result<Option<String>, AppError> {
+    let rel = security::validate_relative_path(path)?;
+    let Some(existing) = security::case_collision(root, &rel)? else {
+        return Ok(None);
+    };
+    let renaming_from = renaming_from
+        .map(security::validate_relative_path)
+        .transpose()?;
+    if renaming_from.as_deref() == Some(existing.as_path()) {
+        return Ok(None);
+    }
+    let message = format!(
+        "'{path}' differs only in case from existing '{}'; on case-insensitive filesystems (macOS, Windows) they are the same file",
+        existing.to_string_lossy().replace('\\', "/")
+    );
+    if strict {
+        return Err(AppError::Message(message));
+    }
+    Ok(Some(message))
+}
+
 fn execute_blocking(
     root: &Path,
     limits: &ToolLimits,
+    strict_path_case: bool,
     tool_name: &str,
     args: &Value,
 ) -> Result<Value, AppError> {
     match tool_name {
+        "summarize_file" => Err(AppError::Message(
+            "summarize_file needs a model and must be run by the agent".to_string(),
+        )),
         "list_files" => {
             let path = as_str(args, "path");
             let entries = builtin::files::list_files(root, path.as_deref())?;
@@ -138,15 +433,25 @@ fn execute_blocking(
             let path = as_str(args, "path")
                 .ok_or_else(|| AppError::Message("Missing path".to_string()))?;
             let content = as_str(args, "content").unwrap_or_default();
-            builtin::files::write_file(root, &path, &content)?;
-            Ok(serde_json::json!({ "path": path, "written": content.len() }))
+            let warning = case_warning(root, &path, None, strict_path_case)?;
+            builtin::text::write_file(root, &path, &content, newline_options(args))?;
+            let mut out = serde_json::json!({ "path": path, "written": content.len() });
+            if let Some(warning) = warning {
+                out["warning"] = Value::String(warning);
+            }
+            Ok(out)
         }
         "append_to_file" => {
             let path = as_str(args, "path")
                 .ok_or_else(|| AppError::Message("Missing path".to_string()))?;
             let content = as_str(args, "content").unwrap_or_default();
-            builtin::text::append_to_file(root, &path, &content)?;
-            Ok(serde_json::json!({ "path": path, "appended": content.len() }))
+            let warning = case_warning(root, &path, None, strict_path_case)?;
+            builtin::text::append_to_file(root, &path, &content, newline_options(args))?;
+            let mut out = serde_json::json!({ "path": path, "appended": content.len() });
+            if let Some(warning) = warning {
+                out["warning"] = Value::String(warning);
+            }
+            Ok(out)
         }
         "delete_file" => {
             let path = as_str(args, "path")
@@ -159,8 +464,13 @@ fn execute_blocking(
                 .ok_or_else(|| AppError::Message("Missing old_path".to_string()))?;
             let new_path = as_str(args, "new_path")
                 .ok_or_else(|| AppError::Message("Missing new_path".to_string()))?;
+            let warning = case_warning(root, &new_path, Some(&old_path), strict_path_case)?;
             builtin::files::rename_file(root, &old_path, &new_path)?;
-            Ok(serde_json::json!({ "old_path": old_path, "new_path": new_path }))
+            let mut out = serde_json::json!({ "old_path": old_path, "new_path": new_path });
+            if let Some(warning) = warning {
+                out["warning"] = Value::String(warning);
+            }
+            Ok(out)
         }
         "create_directory" => {
             let path = as_str(args, "path")
@@ -173,7 +483,7 @@ fn execute_blocking(
                 .ok_or_else(|| AppError::Message("Missing pattern".to_string()))?;
             let path = as_str(args, "path");
             let file_pattern = as_str(args, "file_pattern");
-            let matches = builtin::search::search_content(
+            let found = builtin::search::search_content(
                 root,
                 &pattern,
                 path.as_deref(),
@@ -182,7 +492,12 @@ fn execute_blocking(
                 limits.max_search_files,
                 limits.max_read_bytes,
             )?;
-            Ok(serde_json::to_value(matches).map_err(|e| AppError::Message(e.to_string()))?)
+            let out = if as_bool(args, "report_skipped").unwrap_or(false) {
+                serde_json::to_value(found)
+            } else {
+                serde_json::to_value(found.matches)
+            };
+            Ok(out.map_err(|e| AppError::Message(e.to_string()))?)
         }
         "search_files" => {
             let pattern = as_str(args, "pattern")
@@ -209,13 +524,45 @@ fn execute_blocking(
             let lines = builtin::search::count_lines(root, &path, limits.max_read_bytes)?;
             Ok(serde_json::json!({ "path": path, "lines": lines }))
         }
+        "hash_file" => {
+            let path = as_str(args, "path")
+                .ok_or_else(|| AppError::Message("Missing path".to_string()))?;
+            let algo = builtin::hash::HashAlgorithm::parse(as_str(args, "algo").as_deref())?;
+            let hash = builtin::hash::hash_file(root, &path, algo)?;
+            Ok(serde_json::json!({ "path": path, "algo": algo.as_str(), "hash": hash }))
+        }
+        "hash_directory" => {
+            let path = as_str(args, "path");
+            let algo = builtin::hash::HashAlgorithm::parse(as_str(args, "algo").as_deref())?;
+            let tree = builtin::hash::hash_directory(
+                root,
+                path.as_deref(),
+                algo,
+                limits.max_search_files,
+            )?;
+            Ok(serde_json::to_value(tree).map_err(|e| AppError::Message(e.to_string()))?)
+        }
         "diff_files" => {
             let path1 = as_str(args, "path1")
                 .ok_or_else(|| AppError::Message("Missing path1".to_string()))?;
             let path2 = as_str(args, "path2")
                 .ok_or_else(|| AppError::Message("Missing path2".to_string()))?;
-            let diff = builtin::search::diff_files(root, &path1, &path2, limits.max_read_bytes)?;
-            Ok(serde_json::json!({ "path1": path1, "path2": path2, "diff": diff }))
+            let format = builtin::search::DiffFormat::parse(as_str(args, "format").as_deref())?;
+            let out = match builtin::search::diff_files(
+                root,
+                &path1,
+                &path2,
+                format,
+                limits.max_read_bytes,
+            )? {
+                builtin::search::DiffOutput::Text(diff) => {
+                    serde_json::json!({ "path1": path1, "path2": path2, "diff": diff })
+                }
+                builtin::search::DiffOutput::Stats(stats) => {
+                    serde_json::json!({ "path1": path1, "path2": path2, "stats": stats })
+                }
+            };
+            Ok(out)
         }
         "find_definition" => {
             let name = as_str(args, "name")
@@ -235,10 +582,15 @@ fn execute_blocking(
             let name = as_str(args, "name")
                 .ok_or_else(|| AppError::Message("Missing name".to_string()))?;
             let path = as_str(args, "path");
+            let options = builtin::code::ReferenceOptions {
+                exclude_definitions: as_bool(args, "exclude_definitions").unwrap_or(false),
+                code_only: as_bool(args, "code_only").unwrap_or(false),
+            };
             let matches = builtin::code::find_references(
                 root,
                 &name,
                 path.as_deref(),
+                options,
                 limits.max_search_matches,
                 limits.max_search_files,
                 limits.max_read_bytes,
@@ -251,6 +603,14 @@ fn execute_blocking(
             let matches = builtin::code::list_functions(root, &path, limits.max_read_bytes)?;
             Ok(serde_json::json!({ "functions": matches }))
         }
+        "read_function" => {
+            let path = as_str(args, "path")
+                .ok_or_else(|| AppError::Message("Missing path".to_string()))?;
+            let name = as_str(args, "name")
+                .ok_or_else(|| AppError::Message("Missing name".to_string()))?;
+            let function = builtin::code::read_function(root, &path, &name, limits.max_read_bytes)?;
+            Ok(serde_json::json!(function))
+        }
         "list_imports" => {
             let path = as_str(args, "path")
                 .ok_or_else(|| AppError::Message("Missing path".to_string()))?;
@@ -264,12 +624,14 @@ fn execute_blocking(
                 .ok_or_else(|| AppError::Message("Missing search".to_string()))?;
             let replace = as_str(args, "replace").unwrap_or_default();
             let all = as_bool(args, "all").unwrap_or(true);
+            let literal = as_bool(args, "literal").unwrap_or(false);
             let count = builtin::text::replace_in_file(
                 root,
                 &path,
                 &search,
                 &replace,
                 all,
+                literal,
                 limits.max_read_bytes,
             )?;
             Ok(serde_json::json!({ "path": path, "replaced": count }))
@@ -277,22 +639,321 @@ fn execute_blocking(
         "insert_at_line" => {
             let path = as_str(args, "path")
                 .ok_or_else(|| AppError::Message("Missing path".to_string()))?;
-            let line = as_u64(args, "line")
+            let line = as_i64(args, "line")
                 .ok_or_else(|| AppError::Message("Missing line".to_string()))?;
             let content = as_str(args, "content").unwrap_or_default();
-            builtin::text::insert_at_line(root, &path, line, &content, limits.max_read_bytes)?;
+            let auto_indent = as_bool(args, "auto_indent").unwrap_or(false);
+            let line = builtin::text::insert_at_line(
+                root,
+                &path,
+                line,
+                &content,
+                auto_indent,
+                limits.max_read_bytes,
+            )?;
             Ok(serde_json::json!({ "path": path, "inserted_at": line }))
         }
         "delete_lines" => {
+            let path = as_str(args, "path")
+                .ok_or_else(|| AppError::Message("Missing path".to_string()))?;
+            let start = as_i64(args, "start")
+                .ok_or_else(|| AppError::Message("Missing start".to_string()))?;
+            let end =
+                as_i64(args, "end").ok_or_else(|| AppError::Message("Missing end".to_string()))?;
+            let (start, end) =
+                builtin::text::delete_lines(root, &path, start, end, limits.max_read_bytes)?;
+            Ok(serde_json::json!({ "path": path, "deleted_lines": { "start": start, "end": end } }))
+        }
+        "replace_lines" => {
             let path = as_str(args, "path")
                 .ok_or_else(|| AppError::Message("Missing path".to_string()))?;
             let start = as_u64(args, "start")
                 .ok_or_else(|| AppError::Message("Missing start".to_string()))?;
             let end =
                 as_u64(args, "end").ok_or_else(|| AppError::Message("Missing end".to_string()))?;
-            builtin::text::delete_lines(root, &path, start, end, limits.max_read_bytes)?;
-            Ok(serde_json::json!({ "path": path, "deleted_lines": { "start": start, "end": end } }))
+            let content = as_str(args, "content").unwrap_or_default();
+            let replaced = builtin::text::replace_lines(
+                root,
+                &path,
+                start,
+                end,
+                &content,
+                limits.max_read_bytes,
+            )?;
+            Ok(
+                serde_json::json!({ "path": path, "replaced_lines": replaced, "start": start, "end": end }),
+            )
+        }
+        "git_status" => {
+            let entries = builtin::git::git_status(root)?;
+            Ok(serde_json::json!({ "entries": entries }))
+        }
+        "git_diff" => {
+            let path = as_str(args, "path");
+            let staged = as_bool(args, "staged").unwrap_or(false);
+            let diff =
+                builtin::git::git_diff(root, path.as_deref(), staged, limits.max_read_bytes)?;
+            Ok(serde_json::to_value(diff).map_err(|e| AppError::Message(e.to_string()))?)
         }
         _ => Err(AppError::Message(format!("Unknown tool '{tool_name}'"))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_call(path: &str, content: &str) -> ToolCall {
+        ToolCall {
+            id: "w".to_string(),
+            name: "write_file".to_string(),
+            arguments: serde_json::json!({ "path": path, "content": content }),
+        }
+    }
+
+    fn executor(root: &Path, locks: &WorkspaceLocks, timeout_ms: u64) -> ToolExecutor {
+        ToolExecutor::new(root.to_path_buf())
+            .unwrap()
+            .with_limits(ToolLimits {
+                timeout_ms,
+                ..ToolLimits::default()
+            })
+            .with_locks(locks)
+    }
+
+    #[tokio::test]
+    async fn case_only_name_clashes_warn_or_fail_when_strict() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("Notes.md"), "x").unwrap();
+        let exec = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+
+        let result = exec.execute(write_call("notes.md", "y")).await;
+        assert!(result.ok);
+        let warning = result.output["warning"].as_str().unwrap();
+        assert!(warning.contains("'Notes.md'"), "{warning}");
+
+        let rename = exec
+            .execute(ToolCall {
+                id: "r".to_string(),
+                name: "rename_file".to_string(),
+                arguments: serde_json::json!({ "old_path": "Notes.md", "new_path": "NOTES.md" }),
+            })
+            .await;
+        assert!(rename.ok);
+
+        let strict = ToolExecutor::new(dir.path().to_path_buf())
+            .unwrap()
+            .with_strict_path_case(true);
+        let result = strict.execute(write_call("NOTES.MD", "z")).await;
+        assert!(!result.ok);
+        assert!(result.error.unwrap().contains("differs only in case"));
+        assert!(!dir.path().join("NOTES.MD").exists());
+    }
+
+    #[tokio::test]
+    async fn tool_policy_gates_writes() {
+        use crate::tools::policy::ToolCategory;
+
+        let dir = tempfile::tempdir().unwrap();
+        let read_only = ToolExecutor::new(dir.path().to_path_buf())
+            .unwrap()
+            .with_policy(ToolPolicy::default());
+        assert!(!read_only
+            .definitions()
+            .iter()
+            .any(|d| d.name == "write_file"));
+        assert!(read_only
+            .definitions()
+            .iter()
+            .any(|d| d.name == "search_content"));
+
+        let denied = read_only.execute(write_call("a.txt", "x")).await;
+        assert!(!denied.ok);
+        assert!(denied.denied);
+        let error = denied.error.unwrap();
+        assert!(error.contains("'write' category"), "{error}");
+        assert!(error.contains("allowed: read, search"), "{error}");
+        assert!(!dir.path().join("a.txt").exists());
+
+        let writable = ToolExecutor::new(dir.path().to_path_buf())
+            .unwrap()
+            .with_policy(ToolPolicy {
+                allow: vec![ToolCategory::Read, ToolCategory::Write],
+            });
+        assert!(writable.execute(write_call("a.txt", "x")).await.ok);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "x"
+        );
+    }
+
+    #[tokio::test]
+    async fn unparseable_arguments_ask_the_model_to_retry() {
+        let dir = tempfile::tempdir().unwrap();
+        let exec = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        let result = exec
+            .execute(ToolCall {
+                id: "r".to_string(),
+                name: "read_file".to_string(),
+                arguments: crate::llm::tool_args::parse_tool_arguments(r#"{"path": "#),
+            })
+            .await;
+        assert!(!result.ok);
+        assert!(result.error.unwrap().contains("Invalid JSON arguments"));
+    }
+
+    #[tokio::test]
+    async fn writes_wait_for_the_workspace_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let locks = Arc::new(WorkspaceLocks::default());
+        let first = executor(dir.path(), &locks, 5_000);
+        let second = executor(dir.path(), &locks, 5_000);
+
+        // Execution one is mid-write.
+        let held = locks.for_root(&first.root).write_owned().await;
+        let pending = tokio::spawn(async move { second.execute(write_call("a.txt", "two")).await });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!dir.path().join("a.txt").exists());
+
+        drop(held);
+        let result = pending.await.unwrap();
+        assert!(result.ok, "{:?}", result.error);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "two"
+        );
+    }
+
+    #[tokio::test]
+    async fn busy_workspace_reports_a_clear_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let locks = WorkspaceLocks::default();
+        let exec = executor(dir.path(), &locks, 50);
+
+        let _held = locks.for_root(&exec.root).write_owned().await;
+        let result = exec.execute(write_call("a.txt", "x")).await;
+        assert!(!result.ok);
+        assert!(result
+            .error
+            .unwrap()
+            .contains("locked by another execution"));
+    }
+
+    #[tokio::test]
+    async fn reads_share_the_workspace_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "hello").unwrap();
+        let locks = WorkspaceLocks::default();
+        let exec = executor(dir.path(), &locks, 1_000);
+
+        let _reader = locks.for_root(&exec.root).read_owned().await;
+        let result = exec
+            .execute(ToolCall {
+                id: "r".to_string(),
+                name: "read_file".to_string(),
+                arguments: serde_json::json!({ "path": "a.txt" }),
+            })
+            .await;
+        assert!(result.ok, "{:?}", result.error);
+        assert_eq!(result.output["content"], "hello");
+    }
+
+    #[tokio::test]
+    async fn git_tools_require_allow_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let exec = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        assert!(!exec.definitions().iter().any(|d| d.name == "git_status"));
+
+        let call = ToolCall {
+            id: "g".to_string(),
+            name: "git_status".to_string(),
+            arguments: serde_json::json!({}),
+        };
+        let denied = exec.execute(call.clone()).await;
+        assert!(denied.error.unwrap().contains("allow_commands"));
+
+        let exec = exec.with_commands(true);
+        if builtin::git::git_available() {
+            assert!(exec.definitions().iter().any(|d| d.name == "git_diff"));
+        }
+        let result = exec.execute(call).await;
+        assert!(!result.error.unwrap_or_default().contains("allow_commands"));
+    }
+
+    fn read_call(path: &str) -> ToolCall {
+        ToolCall {
+            id: "r".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::json!({ "path": path }),
+        }
+    }
+
+    fn two_roots() -> (tempfile::TempDir, tempfile::TempDir, ToolExecutor) {
+        let code = tempfile::tempdir().unwrap();
+        let spec = tempfile::tempdir().unwrap();
+        std::fs::write(code.path().join("main.rs"), "fn main() {}").unwrap();
+        std::fs::write(spec.path().join("doc.md"), "# Spec").unwrap();
+        let exec = ToolExecutor::new(code.path().to_path_buf())
+            .unwrap()
+            .with_root("spec", spec.path().to_path_buf())
+            .unwrap();
+        (code, spec, exec)
+    }
+
+    #[tokio::test]
+    async fn reads_across_two_roots() {
+        let (_code, _spec, exec) = two_roots();
+
+        let default = exec.execute(read_call("main.rs")).await;
+        assert!(default.ok, "{:?}", default.error);
+        assert_eq!(default.output["content"], "fn main() {}");
+
+        let spec = exec.execute(read_call("spec:doc.md")).await;
+        assert!(spec.ok, "{:?}", spec.error);
+        assert_eq!(spec.output["content"], "# Spec");
+
+        // Without the prefix the default root is used.
+        assert!(!exec.execute(read_call("doc.md")).await.ok);
+
+        let mixed = exec
+            .execute(ToolCall {
+                id: "d".to_string(),
+                name: "diff_files".to_string(),
+                arguments: serde_json::json!({ "path1": "spec:doc.md", "path2": "main.rs" }),
+            })
+            .await;
+        assert!(mixed.error.unwrap().contains("same workspace root"));
+    }
+
+    #[tokio::test]
+    async fn paths_cannot_escape_a_named_root() {
+        let (code, _spec, exec) = two_roots();
+        let code_name = code.path().file_name().unwrap().to_string_lossy();
+
+        let escaped = exec
+            .execute(read_call(&format!("spec:../{code_name}/main.rs")))
+            .await;
+        assert!(!escaped.ok);
+        let write = exec.execute(write_call("spec:../x.txt", "x")).await;
+        assert!(!write.ok);
+        assert!(!code.path().parent().unwrap().join("x.txt").exists());
+    }
+
+    #[test]
+    fn single_root_paths_keep_their_colons() {
+        let dir = tempfile::tempdir().unwrap();
+        let exec = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        let target = exec
+            .route(&serde_json::json!({ "path": "spec:doc.md" }))
+            .unwrap();
+        assert_eq!(target.args["path"], "spec:doc.md");
+        assert!(exec
+            .definitions()
+            .iter()
+            .all(|d| !d.description.contains("workspace root")));
+
+        assert!(exec
+            .with_root("bad name", dir.path().to_path_buf())
+            .is_err());
+    }
+}
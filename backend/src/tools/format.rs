@@ -0,0 +1,118 @@
+//! Compact text renderings of tool output for the model. Raw JSON is verbose
+//! and easy to misread; the full JSON still goes into the `ToolTrace`.
+
+use serde_json::Value;
+
+/// The model-facing text for a successful `name` result, or `None` for tools
+/// without a compact form (their tool message keeps the JSON payload).
+pub fn format_result(name: &str, output: &Value) -> Option<String> {
+    match name {
+        "search_content" => format_content_matches(output),
+        "search_files" => format_paths(output.get("matches")?),
+        "list_files" => format_entries(output),
+        _ => None,
+    }
+}
+
+/// One `path:line: snippet` line per match. `output` is the bare match array,
+/// or `{matches, skipped_binary_files}` when skipped files were requested.
+fn format_content_matches(output: &Value) -> Option<String> {
+    let matches = output.get("matches").unwrap_or(output).as_array()?;
+    let mut lines = matches
+        .iter()
+        .map(|m| {
+            Some(format!(
+                "{}:{}: {}",
+                m.get("path")?.as_str()?,
+                m.get("line")?.as_u64()?,
+                m.get("snippet")?.as_str()?.trim()
+            ))
+        })
+        .collect::<Option<Vec<String>>>()?;
+    if lines.is_empty() {
+        lines.push("No matches.".to_string());
+    }
+    match output.get("skipped_binary_files").and_then(|v| v.as_u64()) {
+        Some(skipped) if skipped > 0 => lines.push(format!("(skipped {skipped} binary files)")),
+        _ => {}
+    }
+    Some(lines.join("\n"))
+}
+
+fn format_paths(paths: &Value) -> Option<String> {
+    let paths = paths
+        .as_array()?
+        .iter()
+        .map(|p| p.as_str().map(str::to_string))
+        .collect::<Option<Vec<_>>>()?;
+    if paths.is_empty() {
+        return Some("No matches.".to_string());
+    }
+    Some(paths.join("\n"))
+}
+
+/// Directories end in `/`; files show their size.
+fn format_entries(output: &Value) -> Option<String> {
+    let entries = output
+        .as_array()?
+        .iter()
+        .map(|e| {
+            let path = e.get("path")?.as_str()?;
+            if e.get("is_dir")?.as_bool()? {
+                return Some(format!("{path}/"));
+            }
+            Some(match e.get("size").and_then(|s| s.as_u64()) {
+                Some(size) => format!("{path} ({size} bytes)"),
+                None => path.to_string(),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if entries.is_empty() {
+        return Some("Empty directory.".to_string());
+    }
+    Some(entries.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn search_matches_render_as_path_line_snippet() {
+        let output = json!([
+            { "path": "src/main.rs", "line": 12, "column": 5, "snippet": "    let cache = rebuild();" },
+            { "path": "README.md", "line": 3, "column": 1, "snippet": "cache is rebuilt nightly" }
+        ]);
+        assert_eq!(
+            format_result("search_content", &output).unwrap(),
+            "src/main.rs:12: let cache = rebuild();\nREADME.md:3: cache is rebuilt nightly"
+        );
+
+        let reported = json!({ "matches": [], "skipped_binary_files": 2 });
+        assert_eq!(
+            format_result("search_content", &reported).unwrap(),
+            "No matches.\n(skipped 2 binary files)"
+        );
+    }
+
+    #[test]
+    fn other_tools_render_compactly_or_keep_json() {
+        let entries = json!([
+            { "path": "src", "is_dir": true, "size": null },
+            { "path": "a.txt", "is_dir": false, "size": 42 }
+        ]);
+        assert_eq!(
+            format_result("list_files", &entries).unwrap(),
+            "src/\na.txt (42 bytes)"
+        );
+        let files = json!({ "matches": ["a.rs", "b/c.rs"] });
+        assert_eq!(
+            format_result("search_files", &files).unwrap(),
+            "a.rs\nb/c.rs"
+        );
+        assert_eq!(format_result("read_file", &json!({ "content": "x" })), None);
+        // Unexpected shapes fall back to JSON rather than losing data.
+        assert_eq!(format_result("search_content", &json!({ "oops": 1 })), None);
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::RwLock;
+
+/// Advisory locks keyed by canonical workspace root, shared by every execution
+/// in the process. Mutating tools take the write side and read-only tools the
+/// read side, so two executions on the same path never interleave writes.
+/// Each tool call holds at most one lock, which keeps the scheme deadlock-free.
+#[derive(Debug, Default)]
+pub struct WorkspaceLocks {
+    locks: Mutex<HashMap<PathBuf, Arc<RwLock<()>>>>,
+}
+
+impl WorkspaceLocks {
+    /// The lock for `root`, which must already be canonicalized.
+    pub fn for_root(&self, root: &Path) -> Arc<RwLock<()>> {
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        locks.entry(root.to_path_buf()).or_default().clone()
+    }
+}
+
+/// Whether a builtin tool may modify the workspace.
+pub fn is_mutating(tool_name: &str) -> bool {
+    matches!(
+        tool_name,
+        "write_file"
+            | "append_to_file"
+            | "delete_file"
+            | "rename_file"
+            | "create_directory"
+            | "replace_in_file"
+            | "insert_at_line"
+            | "delete_lines"
+            | "replace_lines"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_root_shares_one_lock() {
+        let locks = WorkspaceLocks::default();
+        let a = locks.for_root(Path::new("/tmp/ws"));
+        let b = locks.for_root(Path::new("/tmp/ws"));
+        let c = locks.for_root(Path::new("/tmp/other"));
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+    }
+
+    #[test]
+    fn classifies_mutating_tools() {
+        assert!(is_mutating("write_file"));
+        assert!(is_mutating("delete_lines"));
+        assert!(is_mutating("replace_lines"));
+        assert!(!is_mutating("read_file"));
+        assert!(!is_mutating("search_content"));
+    }
+}
@@ -1,4 +1,9 @@
 pub mod builtin;
 pub mod definition;
 pub mod executor;
+pub mod format;
+pub mod lock;
+pub mod policy;
 pub mod security;
+pub mod summarize;
+pub mod workspace_diff;
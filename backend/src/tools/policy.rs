@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::tools::lock::is_mutating;
+
+/// What a tool can do, for `ToolPolicy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCategory {
+    Read,
+    Search,
+    Write,
+    /// Runs an external program (currently read-only `git`).
+    Exec,
+    /// Reaches outside the machine; no builtin tool does yet.
+    Network,
+}
+
+impl ToolCategory {
+    /// A tool this doesn't know is `Network`, the category executions never
+    /// allow by default, so a new tool left out here is refused, not run.
+    pub fn of(tool_name: &str) -> Self {
+        match tool_name {
+            name if is_mutating(name) => Self::Write,
+            "git_status" | "git_diff" => Self::Exec,
+            "search_content" | "search_files" | "find_definition" | "find_references" => {
+                Self::Search
+            }
+            "read_file" | "list_files" | "get_file_info" | "count_lines" | "summarize_file"
+            | "hash_file" | "hash_directory" | "diff_files" | "list_functions"
+            | "read_function" | "list_imports" => Self::Read,
+            _ => Self::Network,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Search => "search",
+            Self::Write => "write",
+            Self::Exec => "exec",
+            Self::Network => "network",
+        }
+    }
+}
+
+/// Tool categories an execution's agents may use. Executions default to
+/// read and search, so modifying the workspace is an explicit opt-in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolPolicy {
+    pub allow: Vec<ToolCategory>,
+}
+
+impl Default for ToolPolicy {
+    fn default() -> Self {
+        Self {
+            allow: vec![ToolCategory::Read, ToolCategory::Search],
+        }
+    }
+}
+
+impl ToolPolicy {
+    /// Every category; what a bare `ToolExecutor` starts with.
+    pub fn unrestricted() -> Self {
+        Self {
+            allow: vec![
+                ToolCategory::Read,
+                ToolCategory::Search,
+                ToolCategory::Write,
+                ToolCategory::Exec,
+                ToolCategory::Network,
+            ],
+        }
+    }
+
+    pub fn allows(&self, tool_name: &str) -> bool {
+        self.allow.contains(&ToolCategory::of(tool_name))
+    }
+
+    pub fn check(&self, tool_name: &str) -> Result<(), AppError> {
+        if self.allows(tool_name) {
+            return Ok(());
+        }
+        let allowed = self
+            .allow
+            .iter()
+            .map(|c| c.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        Err(AppError::Message(format!(
+            "Tool '{tool_name}' is in the '{}' category, which this execution's tool_policy does not allow (allowed: {})",
+            ToolCategory::of(tool_name).as_str(),
+            if allowed.is_empty() { "none" } else { &allowed }
+        )))
+    }
+}
@@ -28,6 +28,25 @@ pub fn validate_relative_path(input: &str) -> Result<PathBuf, AppError> {
     Ok(out)
 }
 
+/// `validate_relative_path` for a path a tool will create, change or remove.
+/// Git's own files are refused: `.git/config` and `.gitattributes` can name
+/// filter and diff drivers, which a later `git_diff` would then run.
+pub fn validate_writable_path(input: &str) -> Result<PathBuf, AppError> {
+    let rel = validate_relative_path(input)?;
+    let protected = rel.components().any(|c| {
+        // Case-insensitive filesystems, and Windows' trailing dots and
+        // spaces, would let `.GIT` or `.git.` reach the same file.
+        let name = c.as_os_str().to_string_lossy().to_ascii_lowercase();
+        matches!(name.trim_end_matches(['.', ' ']), ".git" | ".gitattributes")
+    });
+    if protected {
+        return Err(AppError::Message(
+            "Writing to .git or .gitattributes is not allowed".to_string(),
+        ));
+    }
+    Ok(rel)
+}
+
 pub fn ensure_within_root(root: &Path, candidate: &Path) -> Result<(), AppError> {
     if !candidate.starts_with(root) {
         return Err(AppError::Message("Path is outside workspace".to_string()));
@@ -84,6 +103,12 @@ pub fn resolve_existing_path(root: &Path, rel: &Path) -> Result<PathBuf, AppErro
     if meta.file_type().is_symlink() {
         return Err(AppError::Message("Symlinks are not allowed".to_string()));
     }
+    // FIFOs, sockets and device files can block or misbehave when opened.
+    if !meta.is_file() && !meta.is_dir() {
+        return Err(AppError::Message(
+            "Not a regular file or directory".to_string(),
+        ));
+    }
     let canonical = candidate
         .canonicalize()
         .map_err(|e| AppError::Message(e.to_string()))?;
@@ -114,6 +139,39 @@ pub fn resolve_write_path(root: &Path, rel: &Path) -> Result<PathBuf, AppError>
     Ok(full)
 }
 
+/// An existing entry in `rel`'s directory whose name differs from `rel`'s
+/// only in letter case, e.g. `Foo.txt` when writing `foo.txt`. On
+/// case-insensitive filesystems (the macOS and Windows defaults) both names
+/// are the same file; elsewhere the pair breaks checkouts on those systems.
+pub fn case_collision(root: &Path, rel: &Path) -> Result<Option<PathBuf>, AppError> {
+    let Some(file_name) = rel.file_name() else {
+        return Ok(None);
+    };
+    let parent_rel = rel.parent().unwrap_or_else(|| Path::new(""));
+    let Ok(entries) = std::fs::read_dir(root.join(parent_rel)) else {
+        return Ok(None);
+    };
+    let wanted = file_name.to_string_lossy().to_lowercase();
+    for entry in entries {
+        let name = entry
+            .map_err(|e| AppError::Message(e.to_string()))?
+            .file_name();
+        if name != file_name && name.to_string_lossy().to_lowercase() == wanted {
+            return Ok(Some(parent_rel.join(name)));
+        }
+    }
+    Ok(None)
+}
+
+/// How much of a file's start `looks_binary` inspects.
+pub const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// Whether `head`, the start of a file, looks binary: a NUL byte within the
+/// first `BINARY_SNIFF_BYTES`.
+pub fn looks_binary(head: &[u8]) -> bool {
+    head.iter().take(BINARY_SNIFF_BYTES).any(|&b| b == 0)
+}
+
 pub fn read_to_string_limited(path: &Path, max_bytes: u64) -> Result<(String, bool), AppError> {
     use std::io::Read;
 
@@ -176,6 +234,23 @@ mod tests {
         assert!(validate_relative_path("/etc/passwd").is_err());
     }
 
+    #[test]
+    fn validate_writable_path_refuses_git_files() {
+        for path in [
+            ".git/config",
+            "sub/.git/hooks/pre-commit",
+            ".gitattributes",
+            "src/.GitAttributes",
+            ".GIT/config",
+            ".git./config",
+            ".git",
+        ] {
+            assert!(validate_writable_path(path).is_err(), "{path}");
+        }
+        assert!(validate_writable_path(".gitignore").is_ok());
+        assert!(validate_writable_path("docs/.github/workflow.yml").is_ok());
+    }
+
     #[test]
     fn ensure_within_root_accepts_child_rejects_outside() {
         let (_d, root) = tmp_root();
@@ -193,6 +268,30 @@ mod tests {
         assert_eq!(full.file_name().unwrap(), "file.txt");
     }
 
+    #[test]
+    fn case_collision_finds_entries_differing_only_in_case() {
+        let (_d, root) = tmp_root();
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("docs/Readme.md"), "x").unwrap();
+
+        assert_eq!(
+            case_collision(&root, Path::new("docs/README.md")).unwrap(),
+            Some(PathBuf::from("docs/Readme.md"))
+        );
+        assert_eq!(
+            case_collision(&root, Path::new("docs/Readme.md")).unwrap(),
+            None
+        );
+        assert_eq!(
+            case_collision(&root, Path::new("docs/other.md")).unwrap(),
+            None
+        );
+        assert_eq!(
+            case_collision(&root, Path::new("missing/a.md")).unwrap(),
+            None
+        );
+    }
+
     #[test]
     fn resolve_existing_path_resolves_real_file() {
         let (_d, root) = tmp_root();
@@ -220,6 +319,20 @@ mod tests {
         assert!(err.to_string().to_lowercase().contains("symlink"));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn resolve_existing_path_rejects_fifo() {
+        let (_d, root) = tmp_root();
+        let fifo = root.join("pipe");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let err = resolve_existing_path(&root, Path::new("pipe")).unwrap_err();
+        assert!(err.to_string().contains("Not a regular file"));
+    }
+
     #[cfg(unix)]
     #[test]
     fn ensure_safe_dir_rejects_symlinked_component() {
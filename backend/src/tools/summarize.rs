@@ -0,0 +1,304 @@
+use std::time::Instant;
+
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::llm::provider::{LLMProvider, Message, MessageRole};
+use crate::tools::definition::{ToolCall, ToolResult};
+use crate::tools::executor::ToolExecutor;
+
+/// Bounds for one `summarize_file` call.
+#[derive(Debug, Clone)]
+pub struct SummarizeLimits {
+    pub chunk_bytes: u64,
+    pub max_chunks: usize,
+    /// Stop reading further chunks once this many tokens have been spent.
+    pub max_total_tokens: u32,
+    pub max_summary_tokens: u32,
+}
+
+impl Default for SummarizeLimits {
+    fn default() -> Self {
+        Self {
+            chunk_bytes: 12_000,
+            max_chunks: 16,
+            max_total_tokens: 60_000,
+            max_summary_tokens: 512,
+        }
+    }
+}
+
+/// Run a `summarize_file` call: read the file chunk by chunk through the
+/// executor's `read_file`, summarize each chunk with `provider`, then roll the
+/// chunk summaries up into one. Returns the tool result and the tokens spent.
+pub async fn execute(
+    provider: &dyn LLMProvider,
+    executor: &ToolExecutor,
+    call: ToolCall,
+    limits: &SummarizeLimits,
+) -> (ToolResult, u32, u32) {
+    let started = Instant::now();
+    let mut usage = (0u32, 0u32);
+    let output = match call.arguments.get("path").and_then(|v| v.as_str()) {
+        Some(path) => summarize(provider, executor, path, limits, &mut usage).await,
+        None => Err(AppError::Message("Missing path".to_string())),
+    };
+    let duration_ms = started.elapsed().as_millis().min(u128::from(u64::MAX)) as u64;
+    let (ok, output, error) = match output {
+        Ok(v) => (true, v, None),
+        Err(e) => (false, serde_json::json!({}), Some(e.to_string())),
+    };
+    let result = ToolResult {
+        tool_call_id: call.id,
+        name: call.name,
+        ok,
+        output,
+        error,
+        duration_ms: Some(duration_ms),
+        denied: false,
+    };
+    (result, usage.0, usage.1)
+}
+
+async fn summarize(
+    provider: &dyn LLMProvider,
+    executor: &ToolExecutor,
+    path: &str,
+    limits: &SummarizeLimits,
+    usage: &mut (u32, u32),
+) -> Result<Value, AppError> {
+    let mut summaries = Vec::new();
+    let mut offset: u64 = 0;
+    let mut complete = false;
+
+    while summaries.len() < limits.max_chunks
+        && usage.0.saturating_add(usage.1) < limits.max_total_tokens
+    {
+        let read = executor
+            .execute(ToolCall {
+                id: format!("summarize-{offset}"),
+                name: "read_file".to_string(),
+                arguments: serde_json::json!({
+                    "path": path,
+                    "offset": offset,
+                    "limit": limits.chunk_bytes
+                }),
+            })
+            .await;
+        if !read.ok {
+            return Err(AppError::Message(read.error.unwrap_or_default()));
+        }
+        let text = read.output["content"].as_str().unwrap_or_default();
+        let more = read.output["truncated"].as_bool().unwrap_or(false);
+        // Cut at the last newline so lines aren't split across chunks.
+        // Failing that, drop a character the read split, which decodes as
+        // U+FFFD: `offset` then stays a byte offset into the file and the
+        // next read starts on a character boundary.
+        let chunk = match text.rfind('\n') {
+            Some(idx) if more => &text[..=idx],
+            _ if more => match text.trim_end_matches(char::REPLACEMENT_CHARACTER) {
+                "" => text,
+                whole_chars => whole_chars,
+            },
+            _ => text,
+        };
+        if chunk.is_empty() {
+            complete = true;
+            break;
+        }
+
+        let prompt = format!(
+            "Summarize part {} of the file `{path}` concisely, keeping names, numbers and decisions.\n\n{chunk}",
+            summaries.len() + 1
+        );
+        summaries.push(ask(provider, prompt, limits.max_summary_tokens, usage).await?);
+        offset += chunk.len() as u64;
+        if !more {
+            complete = true;
+            break;
+        }
+    }
+
+    let summary = match summaries.len() {
+        0 => String::new(),
+        1 => summaries[0].clone(),
+        _ => {
+            let parts = summaries
+                .iter()
+                .enumerate()
+                .map(|(i, s)| format!("Part {}:\n{s}", i + 1))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let prompt = format!(
+                "Combine these summaries of consecutive parts of `{path}` into one summary of the whole file.\n\n{parts}"
+            );
+            ask(provider, prompt, limits.max_summary_tokens, usage).await?
+        }
+    };
+
+    Ok(serde_json::json!({
+        "path": path,
+        "chunks": summaries.len(),
+        "bytes_summarized": offset,
+        "complete": complete,
+        "summary": summary
+    }))
+}
+
+async fn ask(
+    provider: &dyn LLMProvider,
+    prompt: String,
+    max_tokens: u32,
+    usage: &mut (u32, u32),
+) -> Result<String, AppError> {
+    let messages = vec![Message {
+        role: MessageRole::User,
+        content: Some(prompt),
+        name: None,
+        tool_call_id: None,
+        tool_calls: None,
+        images: Vec::new(),
+    }];
+    let resp = provider.chat(messages, 0.2, max_tokens).await?;
+    usage.0 = usage.0.saturating_add(resp.usage.input_tokens);
+    usage.1 = usage.1.saturating_add(resp.usage.output_tokens);
+    Ok(resp.content.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock;
+    use crate::llm::provider::LLMResponse;
+    use async_trait::async_trait;
+
+    /// Replies with every `section-N` word found in the prompt.
+    struct SectionEcho;
+
+    #[async_trait]
+    impl LLMProvider for SectionEcho {
+        fn provider_name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn model_id(&self) -> &str {
+            "echo"
+        }
+
+        async fn chat(
+            &self,
+            messages: Vec<Message>,
+            _temperature: f64,
+            _max_tokens: u32,
+        ) -> Result<LLMResponse, AppError> {
+            let prompt = messages[0].content.clone().unwrap_or_default();
+            let mut sections = prompt
+                .split(|c: char| c.is_whitespace() || c == ':')
+                .filter(|w| w.starts_with("section-"))
+                .collect::<Vec<_>>();
+            sections.dedup();
+            Ok(mock::response(&sections.join(" "), Vec::new()))
+        }
+    }
+
+    fn workspace(lines: usize) -> (tempfile::TempDir, ToolExecutor) {
+        let dir = tempfile::tempdir().unwrap();
+        let text = (0..lines)
+            .map(|i| format!("section-{i}: some notes about this part of the file"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        std::fs::write(dir.path().join("big.md"), text).unwrap();
+        let executor = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        (dir, executor)
+    }
+
+    fn call() -> ToolCall {
+        ToolCall {
+            id: "s1".to_string(),
+            name: "summarize_file".to_string(),
+            arguments: serde_json::json!({ "path": "big.md" }),
+        }
+    }
+
+    #[tokio::test]
+    async fn rollup_incorporates_every_chunk() {
+        let (_dir, executor) = workspace(40);
+        let limits = SummarizeLimits {
+            chunk_bytes: 300,
+            ..Default::default()
+        };
+        let (result, input, output) = execute(&SectionEcho, &executor, call(), &limits).await;
+
+        assert!(result.ok, "{:?}", result.error);
+        assert!(result.output["chunks"].as_u64().unwrap() > 1);
+        assert_eq!(result.output["complete"], true);
+        let summary = result.output["summary"].as_str().unwrap();
+        for i in 0..40 {
+            assert!(
+                summary.split(' ').any(|w| w == format!("section-{i}")),
+                "missing section-{i}"
+            );
+        }
+        assert!(input > 0 && output > 0);
+    }
+
+    #[tokio::test]
+    async fn chunk_count_is_bounded() {
+        let (_dir, executor) = workspace(40);
+        let limits = SummarizeLimits {
+            chunk_bytes: 300,
+            max_chunks: 2,
+            ..Default::default()
+        };
+        let (result, _, _) = execute(&SectionEcho, &executor, call(), &limits).await;
+
+        assert!(result.ok);
+        assert_eq!(result.output["chunks"], 2);
+        assert_eq!(result.output["complete"], false);
+        assert!(!result.output["summary"]
+            .as_str()
+            .unwrap()
+            .contains("section-39"));
+    }
+
+    #[tokio::test]
+    async fn chunks_split_inside_a_character_stay_aligned_with_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        // One long line of two-byte characters, read in odd-sized chunks so
+        // every read ends halfway through one.
+        let text = "é".repeat(500);
+        std::fs::write(dir.path().join("accents.txt"), &text).unwrap();
+        let executor = ToolExecutor::new(dir.path().to_path_buf()).unwrap();
+        let provider = mock::MockProvider::new("ok");
+        let mut call = call();
+        call.arguments = serde_json::json!({ "path": "accents.txt" });
+        let limits = SummarizeLimits {
+            chunk_bytes: 301,
+            ..Default::default()
+        };
+        let (result, _, _) = execute(provider.as_ref(), &executor, call, &limits).await;
+
+        assert!(result.ok, "{:?}", result.error);
+        assert_eq!(result.output["complete"], true);
+        assert_eq!(result.output["chunks"], 4);
+        assert_eq!(result.output["bytes_summarized"], 1_000);
+        let chunks = (0..4)
+            .map(|i| provider.user_content(i))
+            .map(|prompt| prompt.chars().filter(|c| *c == 'é').count())
+            .collect::<Vec<_>>();
+        assert_eq!(chunks, vec![150, 150, 150, 50]);
+        assert!(!provider
+            .user_content(0)
+            .contains(char::REPLACEMENT_CHARACTER));
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_a_tool_error() {
+        let (_dir, executor) = workspace(1);
+        let mut call = call();
+        call.arguments = serde_json::json!({ "path": "nope.md" });
+        let (result, _, _) =
+            execute(&SectionEcho, &executor, call, &SummarizeLimits::default()).await;
+        assert!(!result.ok);
+    }
+}
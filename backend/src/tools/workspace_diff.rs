@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::tools::builtin::hash::{digest_file, HashAlgorithm};
+use crate::tools::builtin::search;
+use crate::tools::security;
+
+/// Most files a snapshot covers; the rest of a larger workspace is ignored.
+pub const MAX_SNAPSHOT_FILES: usize = 2_000;
+
+/// Relative path → blake3 digest for every regular file under a workspace.
+pub type WorkspaceSnapshot = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    /// One of `added`, `deleted`, `modified` or `renamed`.
+    pub status: String,
+    /// For renames, whether the content also changed.
+    pub modified: bool,
+}
+
+pub fn snapshot(root: &Path, max_files: usize) -> Result<WorkspaceSnapshot, AppError> {
+    let root = security::canonicalize_root(root)?;
+    let mut out = WorkspaceSnapshot::new();
+    for file in search::walk_files(&root, &PathBuf::from(""), max_files)? {
+        let rel = file
+            .strip_prefix(&root)
+            .unwrap_or(&file)
+            .to_string_lossy()
+            .replace('\\', "/");
+        out.insert(rel, digest_file(&file, HashAlgorithm::Blake3)?);
+    }
+    Ok(out)
+}
+
+/// `(old_path, new_path)` for every `rename_file` call that succeeded, read
+/// from the `tool_call` / `tool_result` payloads an execution stored (see
+/// `orchestration::tool_events`).
+pub fn renames_from_events<'a>(
+    events: impl IntoIterator<Item = &'a Value>,
+) -> Vec<(String, String)> {
+    let events = events.into_iter().collect::<Vec<_>>();
+    let succeeded = events
+        .iter()
+        .filter(|e| e["phase"] == "tool_result" && e["ok"] == true)
+        .filter_map(|e| e["tool_call_id"].as_str())
+        .collect::<HashSet<_>>();
+    events
+        .iter()
+        .filter(|e| e["phase"] == "tool_call" && e["tool_name"] == "rename_file")
+        .filter(|e| {
+            e["tool_call_id"]
+                .as_str()
+                .is_some_and(|id| succeeded.contains(id))
+        })
+        .filter_map(|e| {
+            let old = e["arguments"]["old_path"].as_str()?;
+            let new = e["arguments"]["new_path"].as_str()?;
+            Some((normalize(old), normalize(new)))
+        })
+        .collect()
+}
+
+/// A tool path as a snapshot key: forward slashes, no leading `./`.
+fn normalize(path: &str) -> String {
+    let path = path.trim().replace('\\', "/");
+    path.strip_prefix("./").unwrap_or(&path).to_string()
+}
+
+/// Compare two snapshots. A deleted/added pair is reported as a single
+/// `renamed` change when it was recorded by the rename tool, or when the two
+/// files have identical content.
+pub fn diff_snapshots(
+    before: &WorkspaceSnapshot,
+    after: &WorkspaceSnapshot,
+    renames: &[(String, String)],
+) -> Vec<FileChange> {
+    let mut deleted: BTreeSet<&String> =
+        before.keys().filter(|p| !after.contains_key(*p)).collect();
+    let mut added: BTreeSet<&String> = after.keys().filter(|p| !before.contains_key(*p)).collect();
+    let mut changes = Vec::new();
+
+    for (old, new) in renames {
+        if deleted.contains(old) && added.contains(new) {
+            deleted.remove(old);
+            added.remove(new);
+            changes.push(FileChange {
+                path: new.clone(),
+                old_path: Some(old.clone()),
+                status: "renamed".to_string(),
+                modified: before[old] != after[new],
+            });
+        }
+    }
+
+    for old in deleted.clone() {
+        let hash = &before[old];
+        if let Some(new) = added.iter().copied().find(|p| &after[*p] == hash) {
+            deleted.remove(old);
+            added.remove(new);
+            changes.push(FileChange {
+                path: new.clone(),
+                old_path: Some(old.clone()),
+                status: "renamed".to_string(),
+                modified: false,
+            });
+        }
+    }
+
+    for path in deleted {
+        changes.push(FileChange {
+            path: path.clone(),
+            old_path: None,
+            status: "deleted".to_string(),
+            modified: false,
+        });
+    }
+    for path in added {
+        changes.push(FileChange {
+            path: path.clone(),
+            old_path: None,
+            status: "added".to_string(),
+            modified: false,
+        });
+    }
+    for (path, hash) in before {
+        if after.get(path).is_some_and(|h| h != hash) {
+            changes.push(FileChange {
+                path: path.clone(),
+                old_path: None,
+                status: "modified".to_string(),
+                modified: true,
+            });
+        }
+    }
+
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn tmp_root() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn pure_rename_is_detected_by_content() {
+        let (_d, root) = tmp_root();
+        fs::write(root.join("old.txt"), "same content").unwrap();
+        fs::write(root.join("keep.txt"), "untouched").unwrap();
+        let before = snapshot(&root, 100).unwrap();
+
+        fs::rename(root.join("old.txt"), root.join("new.txt")).unwrap();
+        let after = snapshot(&root, 100).unwrap();
+
+        let changes = diff_snapshots(&before, &after, &[]);
+        assert_eq!(
+            changes,
+            vec![FileChange {
+                path: "new.txt".to_string(),
+                old_path: Some("old.txt".to_string()),
+                status: "renamed".to_string(),
+                modified: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn tracked_rename_with_edit_is_renamed_and_modified() {
+        let (_d, root) = tmp_root();
+        fs::write(root.join("a.rs"), "fn a() {}").unwrap();
+        let before = snapshot(&root, 100).unwrap();
+
+        fs::create_dir(root.join("src")).unwrap();
+        fs::rename(root.join("a.rs"), root.join("src/b.rs")).unwrap();
+        fs::write(root.join("src/b.rs"), "fn b() {}").unwrap();
+        let after = snapshot(&root, 100).unwrap();
+
+        let renames = vec![("a.rs".to_string(), "src/b.rs".to_string())];
+        let changes = diff_snapshots(&before, &after, &renames);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].status, "renamed");
+        assert_eq!(changes[0].old_path.as_deref(), Some("a.rs"));
+        assert_eq!(changes[0].path, "src/b.rs");
+        assert!(changes[0].modified);
+
+        // Without the tracked rename the edit looks like a delete + add.
+        let statuses = diff_snapshots(&before, &after, &[])
+            .into_iter()
+            .map(|c| c.status)
+            .collect::<Vec<_>>();
+        assert_eq!(statuses, vec!["deleted", "added"]);
+    }
+
+    #[test]
+    fn reports_added_deleted_and_modified() {
+        let snapshot = |entries: [(&str, &str); 2]| {
+            entries
+                .iter()
+                .map(|(p, h)| (p.to_string(), h.to_string()))
+                .collect::<WorkspaceSnapshot>()
+        };
+        let before = snapshot([("a", "1"), ("b", "2")]);
+        let after = snapshot([("a", "9"), ("c", "3")]);
+        let changes = diff_snapshots(&before, &after, &[]);
+        let summary = changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.status.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![("a", "modified"), ("b", "deleted"), ("c", "added")]
+        );
+    }
+
+    #[test]
+    fn renames_come_from_successful_rename_calls() {
+        let call = |id: &str, name: &str| {
+            serde_json::json!({
+                "phase": "tool_call",
+                "tool_name": name,
+                "tool_call_id": id,
+                "arguments": { "old_path": "./x", "new_path": "y" }
+            })
+        };
+        let result = |id: &str, ok: bool| serde_json::json!({ "phase": "tool_result", "tool_call_id": id, "ok": ok });
+        let events = [
+            call("1", "rename_file"),
+            result("1", true),
+            call("2", "rename_file"),
+            result("2", false),
+            call("3", "read_file"),
+            result("3", true),
+        ];
+        assert_eq!(
+            renames_from_events(&events),
+            vec![("x".to_string(), "y".to_string())]
+        );
+    }
+}
@@ -40,12 +40,12 @@ export default function AgentForm({ agent, onClose }: Props) {
         ...form,
         model_id: form.model_id ? form.model_id : undefined,
       }
-      if (isEdit && agent) {
-        await updateAgent.mutateAsync({ id: agent.id, data: payload })
-        toast('success', 'Agent 已更新')
-      } else {
-        await createAgent.mutateAsync(payload)
-        toast('success', 'Agent 已创建')
+      const saved = isEdit && agent
+        ? await updateAgent.mutateAsync({ id: agent.id, data: payload })
+        : await createAgent.mutateAsync(payload)
+      toast('success', isEdit ? 'Agent 已更新' : 'Agent 已创建')
+      if (saved.warning) {
+        toast('warning', `系统提示词过长：${saved.warning}`)
       }
       onClose()
     } catch (err) {
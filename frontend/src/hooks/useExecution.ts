@@ -124,6 +124,28 @@ export function useExecutionSocket(executionId: string | null) {
     }
 
     if (data.event_type === 'error') {
+      // Warnings (e.g. one agent failed to reply) don't end the run.
+      if (data.data.severity === 'warning') {
+        const msg: ExecutionMessage = {
+          id: `${executionId}-${data.sequence}`,
+          sequence: data.sequence,
+          round: (data.data.round as number) || 0,
+          phase: (data.data.phase as string) || 'warning',
+          sender_type: 'system',
+          sender_id: undefined,
+          sender_name: 'system',
+          content: data.data.message as string,
+          content_type: 'text',
+          wants_to_continue: true,
+          input_tokens: 0,
+          output_tokens: 0,
+          tokens_estimated: false,
+          metadata: { severity: 'warning', code: data.data.code },
+          created_at: new Date().toISOString(),
+        }
+        setMessages((prev) => [...prev, msg])
+        return
+      }
       setError(data.data.message as string)
       setStatus('error')
       return
@@ -189,9 +211,12 @@ export function useExecutionSocket(executionId: string | null) {
         return false
       }
       const toolName = isTool ? String(data.data.tool_name || 'tool') : null
-      const inputTokens = pickNumber(data.data.input_tokens, metadata.input_tokens)
-      const outputTokens = pickNumber(data.data.output_tokens, metadata.output_tokens)
-      const tokensEstimated = pickBoolean(data.data.tokens_estimated, metadata.tokens_estimated)
+      const tokens = (metadata.tokens && typeof metadata.tokens === 'object'
+        ? metadata.tokens
+        : {}) as Record<string, unknown>
+      const inputTokens = pickNumber(data.data.input_tokens, tokens.input)
+      const outputTokens = pickNumber(data.data.output_tokens, tokens.output)
+      const tokensEstimated = pickBoolean(data.data.tokens_estimated, tokens.estimated)
       const msg: ExecutionMessage = {
         id: (data.data.message_id as string) || `${executionId}-${data.sequence}`,
         sequence: (data.data.message_sequence as number) || data.sequence,
@@ -1,11 +1,12 @@
 import axios from 'axios'
 import type {
-  Agent, AgentCreate, AgentListItem,
+  Agent, AgentCreate, AgentListItem, SavedAgent,
   Team, TeamCreate, TeamListItem,
-  Execution, ExecutionCreate,
-  FileEntry,
+  Execution, ExecutionCreate, ExecutionSummary, ActiveExecution, CostBreakdown,
+  ExecutionLLMConfig, BenchmarkResult,
+  FileEntry, FileChange,
   ModelConfig, ModelConfigCreate, ModelConfigUpdate, TestModelResponse,
-  PaginatedResponse
+  Ownership, PaginatedResponse, ToolInfo, ToolPolicy, ToolResult
 } from '@/types'
 import { isTauriApp, tauriInvoke } from '@/services/tauri'
 
@@ -26,6 +27,7 @@ export const agentApi = {
     search?: string
     tags?: string
     is_template?: boolean
+    ownership?: Ownership
   }): Promise<PaginatedResponse<AgentListItem>> => {
     if (isTauriApp()) {
       return tauriInvoke('list_agents', params as Record<string, unknown>)
@@ -42,7 +44,7 @@ export const agentApi = {
     return data
   },
 
-  create: async (agent: AgentCreate): Promise<Agent> => {
+  create: async (agent: AgentCreate): Promise<SavedAgent> => {
     if (isTauriApp()) {
       return tauriInvoke('create_agent', { agent })
     }
@@ -50,9 +52,17 @@ export const agentApi = {
     return data
   },
 
-  update: async (id: string, agent: Partial<AgentCreate>): Promise<Agent> => {
+  update: async (
+    id: string,
+    agent: Partial<AgentCreate>,
+    expectedRevision?: number
+  ): Promise<SavedAgent> => {
     if (isTauriApp()) {
-      return tauriInvoke('update_agent', { id, update: agent })
+      return tauriInvoke('update_agent', {
+        id,
+        update: agent,
+        expected_revision: expectedRevision ?? null,
+      })
     }
     const { data } = await api.put(`/agents/${id}`, agent)
     return data
@@ -80,6 +90,13 @@ export const agentApi = {
     })
     return data
   },
+  /** Id to edit: a template is first copied into a user-owned agent. */
+  cloneForEdit: async (id: string): Promise<string> => {
+    if (isTauriApp()) {
+      return tauriInvoke('clone_for_edit', { kind: 'agent', id })
+    }
+    throw new Error('Copy-on-edit is only available in the Tauri app')
+  },
 }
 
 // Team API
@@ -90,6 +107,7 @@ export const teamApi = {
     search?: string
     collaboration_mode?: string
     is_template?: boolean
+    ownership?: Ownership
   }): Promise<PaginatedResponse<TeamListItem>> => {
     if (isTauriApp()) {
       return tauriInvoke('list_teams', params as Record<string, unknown>)
@@ -114,9 +132,17 @@ export const teamApi = {
     return data
   },
 
-  update: async (id: string, team: Partial<TeamCreate>): Promise<Team> => {
+  update: async (
+    id: string,
+    team: Partial<TeamCreate>,
+    expectedRevision?: number
+  ): Promise<Team> => {
     if (isTauriApp()) {
-      return tauriInvoke('update_team', { id, update: team })
+      return tauriInvoke('update_team', {
+        id,
+        update: team,
+        expected_revision: expectedRevision ?? null,
+      })
     }
     const { data } = await api.put(`/teams/${id}`, team)
     return data
@@ -145,6 +171,34 @@ export const teamApi = {
     }
     await api.delete(`/teams/${teamId}/members/${agentId}`)
   },
+  /** Id to edit: a template is first copied into a user-owned team. */
+  cloneForEdit: async (id: string): Promise<string> => {
+    if (isTauriApp()) {
+      return tauriInvoke('clone_for_edit', { kind: 'team', id })
+    }
+    throw new Error('Copy-on-edit is only available in the Tauri app')
+  },
+
+  /** Run the same input once per model, each as its own execution. */
+  benchmark: async (
+    id: string,
+    input: string,
+    modelIds: string[],
+    llm?: ExecutionLLMConfig
+  ): Promise<BenchmarkResult[]> => {
+    if (isTauriApp()) {
+      return tauriInvoke('benchmark_team', { team_id: id, input, model_ids: modelIds, llm })
+    }
+    throw new Error('Benchmarking is only available in the Tauri app')
+  },
+
+  /** Restore deleted default agents and teams; returns how many were written. */
+  reseedDefaults: async (overwrite = false): Promise<number> => {
+    if (isTauriApp()) {
+      return tauriInvoke('reseed_defaults', { overwrite })
+    }
+    throw new Error('Restoring defaults is only available in the Tauri app')
+  },
 }
 
 // Execution API
@@ -154,6 +208,8 @@ export const executionApi = {
     page_size?: number
     team_id?: string
     status_filter?: string
+    used_tools?: boolean
+    search?: string
   }): Promise<PaginatedResponse<Execution>> => {
     if (isTauriApp()) {
       return tauriInvoke('list_executions', params as Record<string, unknown>)
@@ -162,6 +218,14 @@ export const executionApi = {
     return data
   },
 
+  /** Executions currently running, longest-running first. */
+  listActive: async (): Promise<ActiveExecution[]> => {
+    if (isTauriApp()) {
+      return tauriInvoke('list_active_executions')
+    }
+    throw new Error('Active executions are only listed in the Tauri app')
+  },
+
   get: async (id: string): Promise<Execution> => {
     if (isTauriApp()) {
       return tauriInvoke('get_execution', { id })
@@ -170,6 +234,15 @@ export const executionApi = {
     return data
   },
 
+  /** Record fields, final output and message counts, without message bodies. */
+  getSummary: async (id: string): Promise<ExecutionSummary> => {
+    if (isTauriApp()) {
+      return tauriInvoke('get_execution_summary', { execution_id: id })
+    }
+    const { data } = await api.get(`/executions/${id}/summary`)
+    return data
+  },
+
   create: async (execution: ExecutionCreate): Promise<Execution> => {
     if (isTauriApp()) {
       return tauriInvoke('create_execution', { execution })
@@ -178,9 +251,17 @@ export const executionApi = {
     return data
   },
 
-  setWorkspace: async (id: string, workspacePath: string | null): Promise<Execution> => {
+  setWorkspace: async (
+    id: string,
+    workspacePath: string | null,
+    workspaceRoots?: Record<string, string>
+  ): Promise<Execution> => {
     if (isTauriApp()) {
-      return tauriInvoke('set_execution_workspace', { id, workspace_path: workspacePath })
+      return tauriInvoke('set_execution_workspace', {
+        id,
+        workspace_path: workspacePath,
+        workspace_roots: workspaceRoots ?? null,
+      })
     }
     throw new Error('Workspace is only supported in the Tauri app')
   },
@@ -207,6 +288,13 @@ export const executionApi = {
     throw new Error('File operations are only supported in the Tauri app')
   },
 
+  getWorkspaceDiff: async (executionId: string): Promise<FileChange[]> => {
+    if (isTauriApp()) {
+      return tauriInvoke('get_workspace_diff', { execution_id: executionId })
+    }
+    throw new Error('File operations are only supported in the Tauri app')
+  },
+
   control: async (id: string, action: string, params?: Record<string, unknown>): Promise<void> => {
     if (isTauriApp()) {
       await tauriInvoke('control_execution', { id, action, params })
@@ -215,6 +303,67 @@ export const executionApi = {
     await api.post(`/executions/${id}/control`, { action, params })
   },
 
+  /** Regex-replace across stored history; resolves to the number of messages changed. */
+  redact: async (id: string, pattern: string, replacement: string): Promise<number> => {
+    if (isTauriApp()) {
+      return tauriInvoke('redact_execution', { execution_id: id, pattern, replacement })
+    }
+    throw new Error('Redaction is only supported in the Tauri app')
+  },
+
+  /** Re-run the verdict/summary of a completed execution over its stored opinions. */
+  regenerateSummary: async (id: string): Promise<Execution> => {
+    if (isTauriApp()) {
+      return tauriInvoke('regenerate_summary', { execution_id: id })
+    }
+    throw new Error('Regenerating summaries is only supported in the Tauri app')
+  },
+
+  /** Cost of a completed execution by agent, round and phase. */
+  getCostBreakdown: async (id: string): Promise<CostBreakdown> => {
+    if (isTauriApp()) {
+      return tauriInvoke('get_execution_cost_breakdown', { id })
+    }
+    throw new Error('Cost breakdowns are only supported in the Tauri app')
+  },
+
+  /** Delete messages whose execution no longer exists; resolves to the count removed. */
+  pruneOrphanMessages: async (): Promise<number> => {
+    if (isTauriApp()) {
+      return tauriInvoke('prune_orphan_messages')
+    }
+    throw new Error('Pruning messages is only supported in the Tauri app')
+  },
+
+  /** Every builtin tool with its schema, category and whether it modifies files. */
+  listTools: async (): Promise<ToolInfo[]> => {
+    if (isTauriApp()) {
+      return tauriInvoke('list_tools')
+    }
+    throw new Error('Tools are only supported in the Tauri app')
+  },
+
+  /**
+   * Run one tool against a workspace without an execution, e.g. to try tool
+   * arguments. Like an execution, only read and search tools run by default.
+   */
+  previewTool: async (
+    workspacePath: string,
+    toolName: string,
+    args: Record<string, unknown>,
+    toolPolicy?: ToolPolicy
+  ): Promise<ToolResult> => {
+    if (isTauriApp()) {
+      return tauriInvoke('preview_tool', {
+        workspace_path: workspacePath,
+        tool_name: toolName,
+        arguments: args,
+        tool_policy: toolPolicy ?? null,
+      })
+    }
+    throw new Error('Tools are only supported in the Tauri app')
+  },
+
   delete: async (id: string): Promise<void> => {
     if (isTauriApp()) {
       await tauriInvoke('delete_execution', { id })
@@ -22,7 +22,10 @@ export interface Agent {
   collaboration_style: 'dominant' | 'supportive' | 'critical'
   speaking_priority: number
   interaction_rules: InteractionRules
+  /** Bumped only by changes to how the agent replies. */
   version: number
+  /** Bumped on every edit; send back as `expectedRevision` when updating. */
+  revision: number
   is_template: boolean
   is_public: boolean
   parent_id?: string
@@ -33,6 +36,11 @@ export interface Agent {
   updated_at: string
 }
 
+// Returned by create/update: the agent plus any warning about its prompt
+export interface SavedAgent extends Agent {
+  warning?: string
+}
+
 // Agent list item (backend AgentListResponse)
 export interface AgentListItem {
   id: string
@@ -94,8 +102,23 @@ export interface Team {
   rating: number
   rating_count: number
   members: TeamMember[]
+  /** Bumped on every change; send back as `expectedRevision` when updating. */
+  revision: number
   created_at: string
   updated_at: string
+  // Only returned by get_team
+  readiness?: TeamReadiness
+}
+
+export interface TeamIssue {
+  severity: 'warning' | 'blocker'
+  message: string
+}
+
+export interface TeamReadiness {
+  status: 'ready' | 'warnings' | 'blocked'
+  score: number
+  issues: TeamIssue[]
 }
 
 // Team list item (backend TeamListResponse)
@@ -171,10 +194,14 @@ export interface Execution {
   team_id?: string
   title?: string
   initial_input: string
+  /** The team's current mode; 'unknown' once the team is deleted. */
+  collaboration_mode?: CollaborationMode | 'unknown'
   status: ExecutionStatus
   current_stage?: string
   current_round: number
   shared_state: Record<string, unknown>
+  phase: string
+  round: number
   agent_states: Record<string, AgentState>
   final_output?: string
   structured_output?: Record<string, unknown>
@@ -182,11 +209,25 @@ export interface Execution {
   tokens_budget: number
   cost: number
   cost_budget: number
+  eta_ms?: number | null
+  /** Dry-run estimate from the last plan, if one was made. */
+  estimate?: { tokens: number; cost: number } | null
+  /** Actual vs. estimate in percent; null until usage is recorded. */
+  estimate_variance_pct?: number | null
   started_at?: string
   completed_at?: string
   error_message?: string
+  /** Tool calls executed across all rounds. */
+  tool_calls?: number
+  used_tools?: boolean
+  /** False when created without an `llm` config; such executions cannot start. */
+  llm_configured?: boolean
   recent_messages: ExecutionMessage[]
   workspace_path?: string
+  /** Extra roots by name; tools address them as `name:relative/path`. */
+  workspace_roots?: Record<string, string>
+  tool_policy?: ToolPolicy
+  stream?: boolean
   created_at: string
   updated_at: string
 }
@@ -215,10 +256,35 @@ export interface ExecutionMessage {
   input_tokens: number
   output_tokens: number
   tokens_estimated: boolean
-  metadata: Record<string, unknown>
+  /** Agent opinions carry `MessageMetadata`; older messages may not. */
+  metadata: Partial<MessageMetadata> & Record<string, unknown>
   created_at: string
 }
 
+/** Stable metadata of an agent opinion message. */
+export interface MessageMetadata {
+  model: string
+  finish_reason?: 'stop' | 'length' | 'tool_calls' | 'content_filter' | 'other'
+  raw_finish_reason?: string
+  tokens: { input: number; output: number; estimated: boolean }
+  tool_calls: { id: string; name: string; ok: boolean }[]
+  reasoning?: number
+  cached?: { read: number; write: number }
+  rate_limit?: RateLimitInfo
+  truncated: boolean
+  original_chars: number
+}
+
+/** Budgets a provider reported in its rate-limit headers. */
+export interface RateLimitInfo {
+  requests_limit?: number
+  requests_remaining?: number
+  tokens_limit?: number
+  tokens_remaining?: number
+  requests_reset?: string
+  tokens_reset?: string
+}
+
 export interface ExecutionCreate {
   team_id: string
   input: string
@@ -226,6 +292,19 @@ export interface ExecutionCreate {
   budget?: BudgetConfig
   llm?: ExecutionLLMConfig
   workspace_path?: string
+  workspace_roots?: Record<string, string>
+  /** Tool categories agents may use; read and search only when omitted. */
+  tool_policy?: ToolPolicy
+  /** Stream replies where the provider supports it; defaults to true. */
+  stream?: boolean
+  /** Start from this execution's output; `input`, if any, is appended. */
+  seed_from_execution_id?: string
+}
+
+export type ToolCategory = 'read' | 'search' | 'write' | 'exec' | 'network'
+
+export interface ToolPolicy {
+  allow: ToolCategory[]
 }
 
 export interface BudgetConfig {
@@ -245,6 +324,96 @@ export interface LLMRuntimeConfig {
   supports_vision?: boolean
   input_price_per_1k?: number
   output_price_per_1k?: number
+  /** Sampling controls; omitted from requests when unset. */
+  top_p?: number
+  /** Anthropic only. */
+  top_k?: number
+  /** OpenAI-compatible only. */
+  frequency_penalty?: number
+  /** OpenAI-compatible only. */
+  presence_penalty?: number
+}
+
+/** Exact tokens are provider-reported; estimated ones were counted locally. */
+export interface CostLine {
+  key: string
+  label?: string
+  exact_tokens: number
+  estimated_tokens: number
+  exact_cost: number
+  estimated_cost: number
+  cost: number
+}
+
+export interface CostBreakdown {
+  execution_id: string
+  pricing: { input_per_1k: number; output_per_1k: number }
+  total: CostLine
+  by_agent: CostLine[]
+  by_round: CostLine[]
+  by_phase: CostLine[]
+}
+
+/** Execution preview without message bodies, from `get_execution_summary`. */
+export interface ExecutionSummary {
+  id: string
+  team_id: string
+  title?: string
+  status: string
+  current_round: number
+  current_stage?: string
+  tokens_used: number
+  cost: number
+  tool_calls: number
+  used_tools: boolean
+  llm_configured: boolean
+  started_at?: string
+  completed_at?: string
+  created_at: string
+  final_output?: string
+  error_message?: string
+  message_count: number
+  round_count: number
+  phase_counts: Record<string, number>
+}
+
+/** One model's run in a team benchmark. */
+export interface BenchmarkResult {
+  model_id: string
+  execution_id: string
+  status: string
+  final_output?: string
+  error_message?: string
+  tokens_used: number
+  cost: number
+  latency_ms: number
+}
+
+export interface ActiveExecution {
+  id: string
+  team_id: string
+  title?: string
+  phase: string
+  round: number
+  started_at?: string
+  elapsed_ms: number
+}
+
+export interface ToolResult {
+  tool_call_id: string
+  name: string
+  ok: boolean
+  output: unknown
+  error?: string
+  duration_ms?: number
+}
+
+export interface ToolInfo {
+  name: string
+  description: string
+  parameters: Record<string, unknown>
+  category: ToolCategory
+  mutating: boolean
 }
 
 export interface FileEntry {
@@ -253,6 +422,16 @@ export interface FileEntry {
   size?: number | null
 }
 
+// A workspace file changed since the execution's first round
+export interface FileChange {
+  path: string
+  /** Set for renames. */
+  old_path?: string
+  status: 'added' | 'deleted' | 'modified' | 'renamed'
+  /** For renames, whether the content also changed. */
+  modified: boolean
+}
+
 export interface ExecutionLLMConfig {
   default: LLMRuntimeConfig
   models: Record<string, LLMRuntimeConfig>
@@ -304,6 +483,9 @@ export interface PaginatedResponse<T> {
   total_pages: number
 }
 
+/** Which agents/teams a list returns: owned, public, or both (default). */
+export type Ownership = 'mine' | 'public' | 'all'
+
 export interface SuccessResponse {
   success: boolean
   message: string